@@ -0,0 +1,81 @@
+//! Compares random access into a solid block using a single [`BlockDecoder`] that skips forward
+//! with creating a new [`BlockDecoder`] for every entry.
+
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use sevenz_rust2::{Archive, ArchiveEntry, ArchiveWriter, BlockDecoder, Password, SourceReader};
+
+const FILE_COUNT: usize = 32;
+const FILE_SIZE: usize = 32 * 1024;
+
+fn create_archive() -> Vec<u8> {
+    let mut state = 0x2545_F491u32;
+    let files: Vec<Vec<u8>> = (0..FILE_COUNT)
+        .map(|_| {
+            (0..FILE_SIZE)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    b'a' + (state % 16) as u8
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entries(
+            (0..FILE_COUNT)
+                .map(|i| ArchiveEntry::new_file(&format!("file_{i}.txt")))
+                .collect(),
+            files
+                .iter()
+                .map(|data| SourceReader::new(data.as_slice()))
+                .collect(),
+        )
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn block_decoder_skip(c: &mut Criterion) {
+    let data = create_archive();
+    let mut source = Cursor::new(data.as_slice());
+    let archive = Archive::read(&mut source, &Password::empty()).unwrap();
+    let password = Password::empty();
+
+    let mut group = c.benchmark_group("block_decoder_skip");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes((FILE_COUNT * FILE_SIZE) as u64));
+
+    group.bench_function("skip_to_entry", |b| {
+        b.iter(|| {
+            let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut source);
+            for file_index in 0..FILE_COUNT {
+                black_box(decoder.decode_entry_to_vec(file_index).unwrap());
+            }
+        });
+    });
+
+    group.bench_function("new_decoder_per_entry", |b| {
+        b.iter(|| {
+            for file_index in 0..FILE_COUNT {
+                let decoder = BlockDecoder::new(1, 0, &archive, &password, &mut source);
+                decoder
+                    .for_each_entries(&mut |entry, reader| {
+                        let mut data = Vec::new();
+                        reader.read_to_end(&mut data)?;
+                        black_box(data);
+                        Ok(entry.name() != format!("file_{file_index}.txt"))
+                    })
+                    .unwrap();
+            }
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, block_decoder_skip);
+criterion_main!(benches);
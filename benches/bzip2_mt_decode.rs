@@ -0,0 +1,78 @@
+//! Measures how decoding of BZIP2 archives with multiple streams scales with the thread count.
+//!
+//! The archive is encoded with a new BZIP2 stream every megabyte, so that the streams can be
+//! decoded using multiple threads.
+
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use sevenz_rust2::{ArchiveEntry, ArchiveReader, ArchiveWriter, Password, encoder_options::*};
+
+const DATA_SIZE: usize = 16 * 1024 * 1024;
+const STREAM_SIZE: u64 = 1024 * 1024;
+
+fn create_data() -> Vec<u8> {
+    let words = [
+        "lorem",
+        "ipsum",
+        "dolor",
+        "sit",
+        "amet",
+        "consectetur",
+        "adipiscing",
+        "elit",
+    ];
+    let mut data = Vec::with_capacity(DATA_SIZE);
+    let mut state = 0x2545_F491u32;
+    while data.len() < DATA_SIZE {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        data.extend_from_slice(words[state as usize % words.len()].as_bytes());
+        data.extend_from_slice(&state.to_le_bytes()[..(state % 3) as usize]);
+        data.push(b' ');
+    }
+    data.truncate(DATA_SIZE);
+    data
+}
+
+fn create_archive(data: &[u8]) -> Vec<u8> {
+    let options = Bzip2Options::from_level(9).with_stream_size(STREAM_SIZE);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![options.into()]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(data))
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn mt_decode(c: &mut Criterion) {
+    let data = create_data();
+    let archive = create_archive(&data);
+
+    let mut group = c.benchmark_group("bzip2_mt_decode");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    for threads in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter(|| {
+                    let mut reader =
+                        ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty())
+                            .unwrap();
+                    reader.set_thread_count(threads);
+                    let decoded = reader.read_file("data.bin").unwrap();
+                    assert_eq!(decoded.len(), data.len());
+                    black_box(decoded);
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, mt_decode);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+//! Compares the compression and decompression speed of the codecs on a small corpus of text,
+//! binary and already compressed data. The compression ratios are printed to stderr.
+//!
+//! The ratios are also checked against recorded baselines by `tests/codec_ratio_tests.rs`.
+
+mod common;
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+
+fn codec_corpus(c: &mut Criterion) {
+    let corpus = common::corpus();
+    let configurations = common::configurations();
+
+    let mut group = c.benchmark_group("compress");
+    group.sample_size(10);
+    for file in &corpus {
+        group.throughput(Throughput::Bytes(file.data.len() as u64));
+        for (name, methods) in &configurations {
+            let archive = common::compress(&file.data, methods);
+            eprintln!(
+                "{name}/{}: ratio {:.4}",
+                file.name,
+                common::ratio(&file.data, &archive)
+            );
+            group.bench_with_input(BenchmarkId::new(*name, file.name), methods, |b, methods| {
+                b.iter(|| black_box(common::compress(&file.data, methods)));
+            });
+        }
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("decompress");
+    group.sample_size(10);
+    for file in &corpus {
+        group.throughput(Throughput::Bytes(file.data.len() as u64));
+        for (name, methods) in &configurations {
+            let archive = common::compress(&file.data, methods);
+            group.bench_with_input(
+                BenchmarkId::new(*name, file.name),
+                &archive,
+                |b, archive| {
+                    b.iter(|| black_box(common::decompress(archive)));
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, codec_corpus);
+criterion_main!(benches);
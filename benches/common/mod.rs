@@ -0,0 +1,100 @@
+//! Corpus and codec configurations shared by the `codec_corpus` benchmark and the compression
+//! ratio regression test in `tests/codec_ratio_tests.rs`.
+
+use std::io::Cursor;
+
+use sevenz_rust2::{
+    ArchiveEntry, ArchiveReader, ArchiveWriter, EncoderConfiguration, EncoderMethod, Password,
+};
+
+/// A file of the corpus.
+pub struct CorpusFile {
+    /// Short name, used in benchmark IDs and baseline keys.
+    pub name: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// Returns the corpus: text, an executable and already compressed data.
+pub fn corpus() -> Vec<CorpusFile> {
+    let resources = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/resources/");
+    [
+        ("text", "apache2.txt"),
+        ("binary", "decompress_x86.exe"),
+        ("compressed", "decompress_example_lzma2_bcj_x86.7z"),
+    ]
+    .into_iter()
+    .map(|(name, file)| CorpusFile {
+        name,
+        data: std::fs::read(format!("{resources}{file}")).unwrap(),
+    })
+    .collect()
+}
+
+/// Returns the codec configurations to compare, with their default options. Codecs behind
+/// disabled features are left out.
+pub fn configurations() -> Vec<(&'static str, Vec<EncoderConfiguration>)> {
+    let mut configurations = vec![
+        ("COPY", vec![EncoderMethod::COPY.into()]),
+        ("LZMA", vec![EncoderMethod::LZMA.into()]),
+        ("LZMA2", vec![EncoderMethod::LZMA2.into()]),
+        (
+            "LZMA2+BCJ_X86",
+            vec![
+                EncoderMethod::LZMA2.into(),
+                EncoderMethod::BCJ_X86_FILTER.into(),
+            ],
+        ),
+    ];
+    #[cfg(feature = "ppmd")]
+    configurations.push(("PPMD", vec![EncoderMethod::PPMD.into()]));
+    #[cfg(feature = "bzip2")]
+    configurations.push(("BZIP2", vec![EncoderMethod::BZIP2.into()]));
+    #[cfg(feature = "deflate")]
+    configurations.push(("DEFLATE", vec![EncoderMethod::DEFLATE.into()]));
+    #[cfg(feature = "zstd")]
+    configurations.push(("ZSTD", vec![EncoderMethod::ZSTD.into()]));
+    #[cfg(feature = "zstd")]
+    configurations.push((
+        "ZSTD_FRAMES",
+        vec![
+            sevenz_rust2::encoder_options::ZstandardOptions::default()
+                .with_frame_size(64 * 1024)
+                .into(),
+        ],
+    ));
+    #[cfg(feature = "brotli")]
+    configurations.push(("BROTLI", vec![EncoderMethod::BROTLI.into()]));
+    #[cfg(feature = "lz4")]
+    configurations.push(("LZ4", vec![EncoderMethod::LZ4.into()]));
+    #[cfg(feature = "snappy")]
+    configurations.push(("SNAPPY", vec![EncoderMethod::SNAPPY.into()]));
+    configurations
+}
+
+/// Compresses `data` into an in-memory archive with a single entry.
+pub fn compress(data: &[u8], methods: &[EncoderConfiguration]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(methods.to_vec());
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data"), Some(data))
+        .unwrap();
+    writer.finish_into_bytes().unwrap()
+}
+
+/// Decompresses the single entry of an archive created by [`compress`].
+pub fn decompress(archive: &[u8]) -> Vec<u8> {
+    let mut reader = ArchiveReader::new(Cursor::new(archive), Password::empty()).unwrap();
+    let mut data = Vec::new();
+    reader
+        .for_each_entries(|_, entry_reader| {
+            entry_reader.read_to_end(&mut data)?;
+            Ok(true)
+        })
+        .unwrap();
+    data
+}
+
+/// Returns the size of the archive relative to the size of the data.
+pub fn ratio(data: &[u8], archive: &[u8]) -> f64 {
+    archive.len() as f64 / data.len() as f64
+}
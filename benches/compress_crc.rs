@@ -0,0 +1,57 @@
+//! Measures the compression throughput with and without computing CRCs.
+//!
+//! The data is stored with the COPY method, so that the time spent computing CRCs isn't hidden
+//! by the time spent compressing.
+
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use sevenz_rust2::{ArchiveEntry, ArchiveWriter, EncoderMethod};
+
+const DATA_SIZE: usize = 100 * 1024 * 1024;
+
+fn create_data() -> Vec<u8> {
+    let mut data = Vec::with_capacity(DATA_SIZE);
+    let mut state = 0x2545_F491u32;
+    while data.len() < DATA_SIZE {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data
+}
+
+fn compress_crc(c: &mut Criterion) {
+    let data = create_data();
+
+    let mut group = c.benchmark_group("compress_crc");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    for compute_crcs in [true, false] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(if compute_crcs { "crc" } else { "no_crc" }),
+            &compute_crcs,
+            |b, &compute_crcs| {
+                b.iter(|| {
+                    let mut writer =
+                        ArchiveWriter::new(Cursor::new(Vec::with_capacity(DATA_SIZE + 1024)))
+                            .unwrap();
+                    writer.set_compute_crcs(compute_crcs);
+                    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+                    writer
+                        .push_archive_entry(
+                            ArchiveEntry::new_file("data.bin"),
+                            Some(data.as_slice()),
+                        )
+                        .unwrap();
+                    black_box(writer.finish().unwrap());
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, compress_crc);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Measures the LZMA2 encoding throughput for different levels, thread counts and stream sizes.
+//!
+//! The stream size is the size of the independent chunks the data is split into, which the
+//! multi threaded encoder compresses in parallel. The dictionary is limited to the stream size,
+//! since larger dictionaries can't be used by the chunks. The compressed size of every
+//! configuration is printed once, and the archive is decoded to verify its CRC32.
+
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use sevenz_rust2::{ArchiveEntry, ArchiveReader, ArchiveWriter, Password, encoder_options::*};
+
+const DATA_SIZE: usize = 10 * 1024 * 1024;
+
+fn create_data() -> Vec<u8> {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut data = Vec::with_capacity(DATA_SIZE);
+    let mut state = 0x2545_F491u32;
+    while data.len() < DATA_SIZE {
+        // Mix text with random bytes, so that the data neither compresses perfectly nor not at all.
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let start = state as usize % (text.len() - 256);
+        data.extend_from_slice(&text[start..start + 64 + (state % 192) as usize]);
+        data.extend_from_slice(&state.to_le_bytes());
+    }
+    data.truncate(DATA_SIZE);
+    data
+}
+
+fn options(level: u32, threads: u32, stream_size: u64) -> Lzma2Options {
+    let mut options = Lzma2Options::from_level_mt(level, threads, stream_size);
+    options.set_dictionary_size(options.dictionary_size().min(stream_size as u32));
+    options.set_independent_chunk_size(stream_size);
+    options
+}
+
+fn compress(data: &[u8], options: &Lzma2Options) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![options.clone().into()]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(data))
+        .unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn verify(archive: Vec<u8>, crc: u32) {
+    let mut reader = ArchiveReader::new(Cursor::new(archive), Password::empty()).unwrap();
+    let data = reader.read_file("data.bin").unwrap();
+    assert_eq!(crc32fast::hash(&data), crc);
+}
+
+fn lzma2_encode(c: &mut Criterion) {
+    let data = create_data();
+    let crc = crc32fast::hash(&data);
+
+    let mut group = c.benchmark_group("lzma2_encode");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(DATA_SIZE as u64));
+    for level in [1, 3, 6, 9] {
+        for threads in [1, 2, 4] {
+            for stream_size in [256 * 1024, 1024 * 1024, 4 * 1024 * 1024] {
+                let options = options(level, threads, stream_size);
+                let archive = compress(&data, &options);
+                let id = format!(
+                    "level {level}/{threads} threads/{} KiB streams",
+                    stream_size / 1024
+                );
+                println!("{id}: {} bytes", archive.len());
+                verify(archive, crc);
+
+                group.bench_with_input(BenchmarkId::from_parameter(id), &options, |b, options| {
+                    b.iter(|| black_box(compress(&data, options)));
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lzma2_encode);
+criterion_main!(benches);
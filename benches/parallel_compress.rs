@@ -0,0 +1,68 @@
+//! Measures the compression of many small files into a non-solid archive, one entry after
+//! another and with multiple threads.
+
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use sevenz_rust2::{ArchiveEntry, ArchiveWriter};
+
+const FILE_COUNT: usize = 64;
+const FILE_SIZE: usize = 64 * 1024;
+
+fn create_files() -> Vec<Vec<u8>> {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut state = 0x2545_F491u32;
+    (0..FILE_COUNT)
+        .map(|_| {
+            // Mix text with random words, so that every file compresses differently.
+            let mut data = Vec::with_capacity(FILE_SIZE);
+            while data.len() < FILE_SIZE {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                let start = state as usize % (text.len() - 64);
+                data.extend_from_slice(&text[start..start + 64]);
+                data.extend_from_slice(&state.to_le_bytes());
+            }
+            data.truncate(FILE_SIZE);
+            data
+        })
+        .collect()
+}
+
+fn parallel_compress(c: &mut Criterion) {
+    let files = create_files();
+
+    let mut group = c.benchmark_group("parallel_compress");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes((FILE_COUNT * FILE_SIZE) as u64));
+    for thread_count in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(thread_count),
+            &thread_count,
+            |b, &thread_count| {
+                b.iter(|| {
+                    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+                    let entries = files
+                        .iter()
+                        .enumerate()
+                        .map(|(i, data)| {
+                            (
+                                ArchiveEntry::new_file(&format!("file{i}.txt")),
+                                Some(data.as_slice()),
+                            )
+                        })
+                        .collect();
+                    writer
+                        .push_archive_entries_parallel(entries, thread_count)
+                        .unwrap();
+                    black_box(writer.finish().unwrap());
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parallel_compress);
+criterion_main!(benches);
@@ -14,7 +14,6 @@ fn main() {
 
         if !forder_dec
             .entries()
-            .iter()
             .any(|entry| entry.name() == my_file_name)
         {
             // skip the folder if it does not contain the file we want
@@ -0,0 +1,22 @@
+//! Parses arbitrary bytes as archive header.
+//!
+//! Run with `cargo fuzz run fuzz_archive_read fuzz/seeds`.
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use sevenz_rust2::{Archive, ParseLimits, Password};
+
+fuzz_target!(|data: &[u8]| {
+    // Small limits, so that a header can't allocate more memory than the fuzzer allows.
+    let limits = ParseLimits {
+        max_entries: 64 * 1024,
+        max_blocks: 64 * 1024,
+        max_coders_per_block: 64,
+        max_header_size: 16 * 1024 * 1024,
+        max_name_bytes: 16 * 1024 * 1024,
+    };
+    let _ = Archive::read_with_limits(&mut Cursor::new(data), &Password::empty(), &limits);
+});
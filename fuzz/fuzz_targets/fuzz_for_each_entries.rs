@@ -0,0 +1,43 @@
+//! Parses arbitrary bytes as archive and decodes all of its entries.
+//!
+//! Run with `cargo fuzz run fuzz_for_each_entries fuzz/seeds`.
+
+#![no_main]
+
+use std::{
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use libfuzzer_sys::fuzz_target;
+use sevenz_rust2::{Archive, ArchiveReader, ParseLimits, Password};
+
+/// Time after which decoding stops. The declared sizes of the entries can be huge, so without a
+/// deadline valid inputs would time out the fuzzer.
+const DECODE_TIME: Duration = Duration::from_millis(10);
+
+fuzz_target!(|data: &[u8]| {
+    let limits = ParseLimits {
+        max_entries: 64 * 1024,
+        max_blocks: 64 * 1024,
+        max_coders_per_block: 64,
+        max_header_size: 16 * 1024 * 1024,
+        max_name_bytes: 16 * 1024 * 1024,
+    };
+    let mut source = Cursor::new(data);
+    let Ok(archive) = Archive::read_with_limits(&mut source, &Password::empty(), &limits) else {
+        return;
+    };
+
+    let deadline = Instant::now() + DECODE_TIME;
+    let mut reader = ArchiveReader::from_archive(archive, source, Password::empty());
+    let mut buf = [0; 64 * 1024];
+    let _ = reader.for_each_entries(|_, entry_reader| {
+        while Instant::now() < deadline {
+            if entry_reader.read(&mut buf)? == 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    });
+});
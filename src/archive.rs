@@ -1,6 +1,12 @@
+use std::{
+    borrow::Cow,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+    path::{Path, PathBuf},
+};
+
 #[cfg(feature = "compress")]
 use crate::encoder_options::EncoderOptions;
-use crate::{NtTime, bitset::BitSet, block::*};
+use crate::{Error, NtTime, bitset::BitSet, block::*};
 
 /// Size of the 7z signature header in bytes (32 bytes).
 /// This is needed for calculating absolute byte offsets within the archive.
@@ -30,19 +36,44 @@ pub(crate) const K_A_TIME: u8 = 0x13;
 pub(crate) const K_M_TIME: u8 = 0x14;
 pub(crate) const K_WIN_ATTRIBUTES: u8 = 0x15;
 
-/// TODO: Implement reading & writing comments
-#[allow(unused)]
+/// Comment of the archive, stored as a UTF-16LE string in the archive properties.
 pub(crate) const K_COMMENT: u8 = 0x16;
 pub(crate) const K_ENCODED_HEADER: u8 = 0x17;
 pub(crate) const K_START_POS: u8 = 0x18;
 pub(crate) const K_DUMMY: u8 = 0x19;
+/// Comments of the individual files, stored like [`K_NAME`].
+pub(crate) const K_FILE_COMMENT: u8 = 0x1A;
+/// SHA-256 digests of the file data. This is a private property of this crate.
+///
+/// The layout follows [`K_WIN_ATTRIBUTES`]: a bit vector of the files with a digest (preceded
+/// by an "all defined" byte), an `external = 0` byte and the 32 byte digest of every file with
+/// a digest.
+pub(crate) const K_SHA256: u8 = 0x7E;
+/// BLAKE3 hashes of the file data. Like [`K_SHA256`], this is a private property of this crate
+/// with the same layout.
+pub(crate) const K_BLAKE3_HASH: u8 = 0x1B;
 
 /// Represents a parsed 7z archive structure.
 ///
 /// Contains metadata about the archive including files, compression blocks,
 /// and internal structure information necessary for decompression.
+/// With the `serde` feature, the archive can be serialized to cache the parsed header, and
+/// deserialized again to read the archive with [`ArchiveReader::from_archive`]. Archives that
+/// were serialized by an incompatible version of this crate are rejected. Deserialized archives
+/// are checked like parsed headers: the blocks are validated, the default [`ParseLimits`] are
+/// applied and the stream map is rebuilt from the blocks and files.
+///
+/// [`ArchiveReader::from_archive`]: crate::ArchiveReader::from_archive
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerdeArchive")
+)]
 pub struct Archive {
+    /// Version of the serialized form, checked when the archive is deserialized.
+    #[cfg(feature = "serde")]
+    format_version: SerdeFormatVersion,
     /// Offset from beginning of file + SIGNATURE_HEADER_SIZE to packed streams.
     pub(crate) pack_pos: u64,
     pub(crate) pack_sizes: Vec<u64>,
@@ -57,34 +88,588 @@ pub struct Archive {
     pub stream_map: StreamMap,
     /// Whether this is a solid archive (better compression, slower random access).
     pub is_solid: bool,
+    /// Blocks of the additional streams section (`kAdditionalStreamsInfo`), if present.
+    ///
+    /// Additional streams carry auxiliary data such as externally stored file properties and
+    /// are not mapped to any entry.
+    pub additional_streams: Option<Vec<Block>>,
+    pub(crate) additional_pack_pos: u64,
+    pub(crate) additional_pack_sizes: Vec<u64>,
+    /// Offset of the 7z signature in the file, which is not 0 for self-extracting archives.
+    pub(crate) base_offset: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) comment: Option<String>,
 }
 
 impl Archive {
     /// Returns the offset from beginning of file + SIGNATURE_HEADER_SIZE to packed streams.
     /// Used for calculating byte offsets when streaming uncompressed (COPY) content.
+    ///
+    /// For self-extracting archives, the offset is relative to the 7z signature, see
+    /// [`Archive::base_offset`].
     pub fn pack_pos(&self) -> u64 {
         self.pack_pos
     }
 
+    /// Returns the offset of the 7z signature in the file.
+    ///
+    /// This is 0, unless the archive was read with [`Archive::read_sfx`] and data, like the
+    /// executable stub of a self-extracting archive, precedes the signature.
+    pub fn base_offset(&self) -> u64 {
+        self.base_offset
+    }
+
+    /// Returns the comment of the archive, if it has one.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the absolute offset of the packed streams in the file.
+    pub(crate) fn pack_start(&self) -> u64 {
+        self.base_offset + SIGNATURE_HEADER_SIZE + self.pack_pos
+    }
+
+    /// Returns the block at `block_index`, or [`Error::BlockIndexOutOfBounds`] if the archive
+    /// has fewer blocks.
+    pub(crate) fn checked_block(&self, block_index: usize) -> Result<&Block, Error> {
+        self.blocks
+            .get(block_index)
+            .ok_or(Error::BlockIndexOutOfBounds {
+                index: block_index,
+                block_count: self.blocks.len(),
+            })
+    }
+
     /// Returns the sizes of each packed stream in bytes.
     /// Used for calculating byte ranges when streaming.
     pub fn pack_sizes(&self) -> &[u64] {
         &self.pack_sizes
     }
+
+    /// Returns the CRC32 of the packed stream at `index`, if the archive defines one.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the packed stream
+    pub fn pack_crc(&self, index: usize) -> Option<u32> {
+        if self.pack_crcs_defined.contains(index) {
+            self.pack_crcs.get(index).map(|&crc| crc as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the absolute byte range `(offset, length)` of every packed stream.
+    ///
+    /// Offsets are relative to the beginning of the archive file, so they can be used
+    /// directly for seeking or HTTP range requests.
+    pub fn pack_stream_ranges(&self) -> Vec<(u64, u64)> {
+        self.pack_sizes
+            .iter()
+            .zip(self.stream_map.pack_stream_offsets.iter())
+            .map(|(&size, &offset)| (self.pack_start() + offset, size))
+            .collect()
+    }
+
+    /// Returns the largest [`Block::decoder_memory_estimate`] of all blocks, which is the memory
+    /// needed to decode the archive one block at a time. Returns 0 if there are no blocks.
+    pub fn max_block_memory_estimate(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(Block::decoder_memory_estimate)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Checks the consistency of the archive structure without decompressing anything.
+    ///
+    /// This is a quick sanity check for potentially damaged archives. It verifies that the
+    /// indices between files, blocks and packed streams are in range and that the files of every
+    /// block add up to the block. An empty result doesn't guarantee that the data can be
+    /// decompressed.
+    pub fn verify_structure(&self) -> Vec<StructureWarning> {
+        let mut warnings = Vec::new();
+        let mut block_file_counts = vec![0usize; self.blocks.len()];
+        let mut block_file_sizes = vec![0u64; self.blocks.len()];
+
+        for (file_index, file) in self.files.iter().enumerate() {
+            if !file.has_stream {
+                continue;
+            }
+            match self.stream_map.file_block_index.get(file_index).copied() {
+                Some(Some(block_index)) if block_index < self.blocks.len() => {
+                    block_file_counts[block_index] += 1;
+                    block_file_sizes[block_index] =
+                        block_file_sizes[block_index].saturating_add(file.size);
+                }
+                Some(Some(block_index)) => warnings.push(StructureWarning::OutOfBoundIndex {
+                    field: "file block index",
+                    index: block_index,
+                    len: self.blocks.len(),
+                }),
+                _ => warnings.push(StructureWarning::OrphanedFile { file_index }),
+            }
+            if file.has_crc && file.crc == 0 && file.size > 0 {
+                warnings.push(StructureWarning::ZeroCrc { file_index });
+            }
+        }
+
+        for (block_index, block) in self.blocks.iter().enumerate() {
+            match self
+                .stream_map
+                .block_first_pack_stream_index
+                .get(block_index)
+            {
+                Some(&first)
+                    if first.saturating_add(block.packed_streams.len()) > self.pack_sizes.len() =>
+                {
+                    warnings.push(StructureWarning::OutOfBoundIndex {
+                        field: "pack stream index",
+                        index: (first + block.packed_streams.len()).saturating_sub(1),
+                        len: self.pack_sizes.len(),
+                    })
+                }
+                Some(_) => {}
+                None => warnings.push(StructureWarning::OutOfBoundIndex {
+                    field: "block index of pack streams",
+                    index: block_index,
+                    len: self.stream_map.block_first_pack_stream_index.len(),
+                }),
+            }
+
+            if block.num_unpack_sub_streams != block_file_counts[block_index] {
+                warnings.push(StructureWarning::SubStreamCountMismatch {
+                    block_index,
+                    expected: block.num_unpack_sub_streams,
+                    actual: block_file_counts[block_index],
+                });
+            }
+            if block.unpack_sizes.len() < block.total_output_streams {
+                warnings.push(StructureWarning::OutOfBoundIndex {
+                    field: "unpack size index",
+                    index: block.total_output_streams - 1,
+                    len: block.unpack_sizes.len(),
+                });
+            } else if block.get_unpack_size() != block_file_sizes[block_index]
+                // Blocks without sub streams, like alignment padding, belong to no file.
+                && block.num_unpack_sub_streams > 0
+            {
+                warnings.push(StructureWarning::UnpackSizeMismatch {
+                    block_index,
+                    expected: block.get_unpack_size(),
+                    actual: block_file_sizes[block_index],
+                });
+            }
+        }
+        warnings
+    }
+}
+
+/// Version of the serialized form of [`Archive`]. It is increased whenever the serialized
+/// structure changes, so that stale caches fail to deserialize.
+#[cfg(feature = "serde")]
+#[derive(Debug, Default, Clone, Copy)]
+struct SerdeFormatVersion;
+
+#[cfg(feature = "serde")]
+impl SerdeFormatVersion {
+    const CURRENT: u32 = 1;
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SerdeFormatVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(Self::CURRENT)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SerdeFormatVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = u32::deserialize(deserializer)?;
+        if version != Self::CURRENT {
+            return Err(serde::de::Error::custom(format!(
+                "Unsupported format version {version} of the serialized archive, expected {}",
+                Self::CURRENT
+            )));
+        }
+        Ok(Self)
+    }
+}
+
+/// Deserialized form of an [`Archive`], which is validated before it is converted.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerdeArchive {
+    format_version: SerdeFormatVersion,
+    pack_pos: u64,
+    pack_sizes: Vec<u64>,
+    pack_crcs_defined: BitSet,
+    pack_crcs: Vec<u64>,
+    sub_streams_info: Option<SubStreamsInfo>,
+    blocks: Vec<Block>,
+    files: Vec<ArchiveEntry>,
+    stream_map: StreamMap,
+    is_solid: bool,
+    additional_streams: Option<Vec<Block>>,
+    additional_pack_pos: u64,
+    additional_pack_sizes: Vec<u64>,
+    base_offset: u64,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeArchive> for Archive {
+    type Error = Error;
+
+    fn try_from(archive: SerdeArchive) -> Result<Self, Self::Error> {
+        let limits = ParseLimits::default();
+        Error::check_limit(
+            "entries",
+            archive.files.len() as u64,
+            limits.max_entries as u64,
+        )?;
+        let name_bytes = archive
+            .files
+            .iter()
+            .map(|file| file.name.len() as u64)
+            .sum();
+        Error::check_limit("name bytes", name_bytes, limits.max_name_bytes as u64)?;
+        let additional_blocks = archive.additional_streams.iter().flatten();
+        for (block_index, block) in archive.blocks.iter().chain(additional_blocks).enumerate() {
+            Error::check_limit("blocks", block_index as u64 + 1, limits.max_blocks as u64)?;
+            Error::check_limit(
+                "coders",
+                block.coders.len() as u64,
+                limits.max_coders_per_block as u64,
+            )?;
+            block.validate_at(block_index)?;
+        }
+        let sub_stream_crcs_too_long = archive
+            .sub_streams_info
+            .as_ref()
+            .is_some_and(|info| info.has_crc.bit_count() > info.unpack_sizes.len());
+        if archive.pack_crcs_defined.bit_count() > archive.pack_sizes.len()
+            || sub_stream_crcs_too_long
+        {
+            return Err(Error::corrupt_header(
+                "serialized archive",
+                "A bit set is longer than its values",
+            ));
+        }
+
+        let mut archive = Archive {
+            format_version: archive.format_version,
+            pack_pos: archive.pack_pos,
+            pack_sizes: archive.pack_sizes,
+            pack_crcs_defined: archive.pack_crcs_defined,
+            pack_crcs: archive.pack_crcs,
+            sub_streams_info: archive.sub_streams_info,
+            blocks: archive.blocks,
+            files: archive.files,
+            stream_map: archive.stream_map,
+            is_solid: archive.is_solid,
+            additional_streams: archive.additional_streams,
+            additional_pack_pos: archive.additional_pack_pos,
+            additional_pack_sizes: archive.additional_pack_sizes,
+            base_offset: archive.base_offset,
+            comment: archive.comment,
+        };
+        // The stream map is derived from the blocks and files, so it is rebuilt instead of
+        // trusted.
+        Archive::calculate_stream_map(&mut archive)?;
+        Ok(archive)
+    }
+}
+
+/// A structural problem of an archive, found by [`Archive::verify_structure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureWarning {
+    /// A file with data is not assigned to any block.
+    OrphanedFile {
+        /// Index of the file.
+        file_index: usize,
+    },
+    /// An index points past the end of the list it refers to.
+    OutOfBoundIndex {
+        /// The kind of index.
+        field: &'static str,
+        /// The invalid index.
+        index: usize,
+        /// The length of the list.
+        len: usize,
+    },
+    /// A file with data has a CRC of 0, which usually means the CRC wasn't stored correctly.
+    ZeroCrc {
+        /// Index of the file.
+        file_index: usize,
+    },
+    /// The number of files assigned to a block differs from its number of sub streams.
+    SubStreamCountMismatch {
+        /// Index of the block.
+        block_index: usize,
+        /// The number of sub streams of the block.
+        expected: usize,
+        /// The number of files assigned to the block.
+        actual: usize,
+    },
+    /// The sizes of the files in a block don't add up to the unpack size of the block.
+    UnpackSizeMismatch {
+        /// Index of the block.
+        block_index: usize,
+        /// The unpack size of the block.
+        expected: u64,
+        /// The sum of the sizes of the files in the block.
+        actual: u64,
+    },
+}
+
+/// Limits applied while parsing the header of an archive.
+///
+/// The header of a 7z archive contains many counts (entries, blocks, coders, ...) which are
+/// used to size allocations and loops. These limits protect against crafted archives that
+/// would otherwise cause huge allocations or extremely long parsing times. Exceeding a limit
+/// results in [`Error::LimitExceeded`].
+///
+/// The default limits are generous enough for all practical archives. Use
+/// [`ParseLimits::unlimited`] to disable them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Maximal number of entries (files, directories and data streams) in the archive.
+    pub max_entries: usize,
+    /// Maximal number of blocks in the archive.
+    pub max_blocks: usize,
+    /// Maximal number of coders, as well as coder input and output streams, of a block.
+    pub max_coders_per_block: usize,
+    /// Maximal size of the (decoded) header in bytes.
+    pub max_header_size: u64,
+    /// Maximal size of all entry names together in bytes.
+    pub max_name_bytes: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 1024 * 1024,
+            max_blocks: 4 * 1024 * 1024,
+            max_coders_per_block: 64,
+            max_header_size: 1024 * 1024 * 1024,
+            max_name_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// Returns limits that don't restrict the parser at all.
+    pub fn unlimited() -> Self {
+        Self {
+            max_entries: usize::MAX,
+            max_blocks: usize::MAX,
+            max_coders_per_block: usize::MAX,
+            max_header_size: u64::MAX,
+            max_name_bytes: usize::MAX,
+        }
+    }
+
+    /// Sets the maximal number of entries.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets the maximal number of blocks.
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+        self
+    }
+
+    /// Sets the maximal number of coders and coder streams per block.
+    pub fn with_max_coders_per_block(mut self, max_coders_per_block: usize) -> Self {
+        self.max_coders_per_block = max_coders_per_block;
+        self
+    }
+
+    /// Sets the maximal header size in bytes.
+    pub fn with_max_header_size(mut self, max_header_size: u64) -> Self {
+        self.max_header_size = max_header_size;
+        self
+    }
+
+    /// Sets the maximal size of all entry names in bytes.
+    pub fn with_max_name_bytes(mut self, max_name_bytes: usize) -> Self {
+        self.max_name_bytes = max_name_bytes;
+        self
+    }
+}
+
+/// Options for reading the header of an archive with [`Archive::read_with_options`] or
+/// [`ArchiveReader::with_options`].
+///
+/// [`ArchiveReader::with_options`]: crate::ArchiveReader::with_options
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveReadOptions {
+    /// The limits applied while parsing the header.
+    pub limits: ParseLimits,
+    /// Whether entry names and comments that aren't valid UTF-16 are rejected with
+    /// [`Error::CorruptHeader`]. Otherwise they are decoded lossily and the original code units
+    /// are kept, see [`ArchiveEntry::raw_name`].
+    pub strict_names: bool,
+    /// Whether [`ArchiveReader::with_options`] searches for a self-extracting archive with
+    /// [`Archive::read_sfx`] if the source doesn't start with the 7z signature. This reads and
+    /// scans up to the first 4 MiB of the source, so it is disabled by default.
+    ///
+    /// [`ArchiveReader::with_options`]: crate::ArchiveReader::with_options
+    pub search_sfx: bool,
+}
+
+impl ArchiveReadOptions {
+    /// Sets the limits applied while parsing the header.
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets whether names that aren't valid UTF-16 are rejected instead of decoded lossily.
+    pub fn with_strict_names(mut self, strict_names: bool) -> Self {
+        self.strict_names = strict_names;
+        self
+    }
+
+    /// Sets whether self-extracting archives with data before the signature are searched for.
+    pub fn with_search_sfx(mut self, search_sfx: bool) -> Self {
+        self.search_sfx = search_sfx;
+        self
+    }
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct SubStreamsInfo {
     pub(crate) unpack_sizes: Vec<u64>,
     pub(crate) has_crc: BitSet,
     pub(crate) crcs: Vec<u64>,
 }
 
+/// Windows file attributes of an archive entry.
+///
+/// The constants mirror the `FILE_ATTRIBUTE_*` values of the Windows API and can be combined
+/// with `|`.
+///
+/// # Example
+/// ```
+/// use sevenz_rust2::WindowsAttributes;
+///
+/// let attributes = WindowsAttributes::READONLY | WindowsAttributes::HIDDEN;
+/// assert!(attributes.contains(WindowsAttributes::HIDDEN));
+/// assert_eq!(attributes.bits(), 0x3);
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WindowsAttributes(u32);
+
+impl WindowsAttributes {
+    /// The file is read-only.
+    pub const READONLY: Self = Self(0x1);
+    /// The file is hidden.
+    pub const HIDDEN: Self = Self(0x2);
+    /// The file is used by the operating system.
+    pub const SYSTEM: Self = Self(0x4);
+    /// The entry is a directory.
+    pub const DIRECTORY: Self = Self(0x10);
+    /// The file is marked for backup or removal.
+    pub const ARCHIVE: Self = Self(0x20);
+    /// Reserved for system use.
+    pub const DEVICE: Self = Self(0x40);
+    /// The file has no other attributes set.
+    pub const NORMAL: Self = Self(0x80);
+    /// The file is used for temporary storage.
+    pub const TEMPORARY: Self = Self(0x100);
+    /// The file is a sparse file.
+    pub const SPARSE_FILE: Self = Self(0x200);
+    /// The file is a reparse point, like a symbolic link.
+    pub const REPARSE_POINT: Self = Self(0x400);
+    /// The file is compressed by the file system.
+    pub const COMPRESSED: Self = Self(0x800);
+    /// The data of the file is not immediately available.
+    pub const OFFLINE: Self = Self(0x1000);
+    /// The file is not indexed by the content indexing service.
+    pub const NOT_CONTENT_INDEXED: Self = Self(0x2000);
+    /// The file is encrypted by the file system.
+    pub const ENCRYPTED: Self = Self(0x4000);
+    /// The high 16 bits contain unix permission bits (an extension by p7zip).
+    pub const UNIX_EXTENSION: Self = Self(0x8000);
+
+    /// Creates an empty set of attributes.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw attribute bitmask.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether no attribute is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether all attributes of `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl From<u32> for WindowsAttributes {
+    fn from(value: u32) -> Self {
+        Self(value)
+    }
+}
+
+impl From<WindowsAttributes> for u32 {
+    fn from(value: WindowsAttributes) -> Self {
+        value.0
+    }
+}
+
+impl BitOr for WindowsAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for WindowsAttributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for WindowsAttributes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for WindowsAttributes {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
 /// Represents a single file or directory entry within a 7z archive.
 ///
 /// Contains metadata about the entry including name, timestamps, attributes,
 /// and size information.
+///
+/// Entries can't be built with struct literals outside of this crate, since the original code
+/// units of names that aren't valid UTF-16 are kept in a private field. Use the constructors
+/// like [`ArchiveEntry::new_file`] and the `with_*` methods, or change the public fields of an
+/// existing entry.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArchiveEntry {
     /// Name/path of the entry within the archive.
     pub name: String,
@@ -114,12 +699,32 @@ pub struct ArchiveEntry {
     pub has_crc: bool,
     /// CRC32 checksum of uncompressed data.
     pub crc: u64,
-    /// CRC32 checksum of compressed data.
+    /// CRC32 checksum of the packed stream of the block.
+    ///
+    /// Like [`ArchiveEntry::compressed_size`], it is only set for the first entry of a block
+    /// and only if the archive stores the CRC of the packed stream.
     pub compressed_crc: u64,
     /// Uncompressed size in bytes.
     pub size: u64,
-    /// Compressed size in bytes.
+    /// Size of the packed stream of the block in bytes.
+    ///
+    /// Codecs don't compress the entries of a solid block separately, so the packed size of a
+    /// block is attributed to its first entry. All other entries of the block report 0.
     pub compressed_size: u64,
+    /// Comment of this entry.
+    ///
+    /// 7-Zip doesn't read file comments, see the compatibility section of `ArchiveWriter`.
+    pub comment: Option<String>,
+    /// SHA-256 digest of the uncompressed data, if it was stored by the writer, see
+    /// `ArchiveWriter::set_store_sha256`.
+    pub sha256: Option<[u8; 32]>,
+    /// BLAKE3 hash of the uncompressed data, if it was stored by the writer, see
+    /// `ArchiveWriter::set_store_blake3_hash`. It is verified while the entry is extracted with
+    /// the `blake3-checksum` feature.
+    pub blake3_hash: Option<[u8; 32]>,
+    /// The UTF-16 code units of the name as stored in the archive, if they aren't valid UTF-16
+    /// and [`ArchiveEntry::name`] replaced the invalid parts with U+FFFD.
+    pub(crate) raw_name: Option<Vec<u16>>,
 }
 
 impl ArchiveEntry {
@@ -141,6 +746,18 @@ impl ArchiveEntry {
         }
     }
 
+    /// Creates a new archive entry representing a file with a comment.
+    ///
+    /// # Arguments
+    /// * `entry_name` - The name/path of the file within the archive
+    /// * `comment` - The comment stored for the file
+    pub fn new_file_with_comment(entry_name: &str, comment: &str) -> Self {
+        Self {
+            comment: Some(comment.to_string()),
+            ..Self::new_file(entry_name)
+        }
+    }
+
     /// Creates a new archive entry representing a directory.
     ///
     /// # Arguments
@@ -154,6 +771,65 @@ impl ArchiveEntry {
         }
     }
 
+    /// Creates a new anti-item, which marks a file as deleted.
+    ///
+    /// Anti-items are used by update archives: when extracting the update archive on top of an
+    /// existing extraction, the file with the given name should be removed.
+    ///
+    /// # Arguments
+    /// * `entry_name` - The name/path of the deleted file within the archive
+    pub fn new_anti_item(entry_name: &str) -> Self {
+        Self {
+            name: entry_name.to_string(),
+            has_stream: false,
+            is_directory: false,
+            is_anti_item: true,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the name/path of this entry within the archive.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self.raw_name = None;
+        self
+    }
+
+    /// Sets the last modified date of this entry and marks it as present.
+    pub fn with_last_modified_date(mut self, date: NtTime) -> Self {
+        self.last_modified_date = date;
+        self.has_last_modified_date = true;
+        self
+    }
+
+    /// Sets the creation date of this entry and marks it as present.
+    pub fn with_creation_date(mut self, date: NtTime) -> Self {
+        self.creation_date = date;
+        self.has_creation_date = true;
+        self
+    }
+
+    /// Sets the last access date of this entry and marks it as present.
+    pub fn with_access_date(mut self, date: NtTime) -> Self {
+        self.access_date = date;
+        self.has_access_date = true;
+        self
+    }
+
+    /// Sets the Windows file attributes of this entry and marks them as present.
+    pub fn with_windows_attributes(mut self, attributes: u32) -> Self {
+        self.windows_attributes = attributes;
+        self.has_windows_attributes = true;
+        self
+    }
+
+    /// Sets the uncompressed size of this entry. The writer replaces it with the size of the
+    /// data that was actually written, so it is only a hint until then.
+    pub fn with_size_hint(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
     /// Creates a new archive entry from a filesystem path.
     ///
     /// Automatically extracts metadata like timestamps and attributes from the filesystem.
@@ -200,6 +876,12 @@ impl ArchiveEntry {
                     entry.has_access_date = entry.access_date.0 > 0;
                 }
             }
+            #[cfg(target_os = "windows")]
+            {
+                use std::os::windows::fs::MetadataExt;
+
+                entry.set_windows_attributes_typed(meta.file_attributes().into());
+            }
         }
         entry
     }
@@ -209,6 +891,76 @@ impl ArchiveEntry {
         self.name.as_ref()
     }
 
+    /// Returns the UTF-16 code units of the name. For names that weren't valid UTF-16 in the
+    /// archive, these are the original code units, while [`ArchiveEntry::name`] holds the name
+    /// with the invalid parts replaced by U+FFFD.
+    pub fn raw_name(&self) -> Cow<'_, [u16]> {
+        match &self.raw_name {
+            Some(raw_name) => Cow::Borrowed(raw_name),
+            None => Cow::Owned(self.name.encode_utf16().collect()),
+        }
+    }
+
+    /// Returns the name of this entry as a [`Path`], without converting the `/` separators.
+    pub fn as_path(&self) -> &Path {
+        Path::new(self.name.as_str())
+    }
+
+    /// Returns the name of this entry as a path with the separators of the current platform.
+    ///
+    /// The `/` separators of the archive are replaced with `\` on Windows and kept elsewhere.
+    pub fn to_platform_path(&self) -> PathBuf {
+        PathBuf::from(with_separator(&self.name, std::path::MAIN_SEPARATOR))
+    }
+
+    /// Returns the name of the directory that contains this entry, everything before the last
+    /// `/` of the name, or `None` for entries at the top level of the archive. A trailing `/` of
+    /// a directory is ignored, like [`Path::parent`] does.
+    pub fn parent_name(&self) -> Option<&str> {
+        self.name
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .map(|(parent, _)| parent)
+    }
+
+    /// Returns the name of the directory that contains this entry, like
+    /// [`ArchiveEntry::parent_name`].
+    pub fn parent(&self) -> Option<&str> {
+        self.parent_name()
+    }
+
+    /// Returns the last component of the name, the part after the last `/`.
+    pub fn file_name(&self) -> &str {
+        let name = self.name.trim_end_matches('/');
+        name.rsplit_once('/')
+            .map_or(name, |(_, file_name)| file_name)
+    }
+
+    /// Returns the part of [`ArchiveEntry::file_name`] after the last `.`, or `None` if there is
+    /// no `.` or the name only starts with one, like [`Path::extension`].
+    pub fn extension(&self) -> Option<&str> {
+        match self.file_name().rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => Some(extension),
+            _ => None,
+        }
+    }
+
+    /// Returns the comment of this entry, if it has one.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the SHA-256 digest of the uncompressed data of this entry, if the archive
+    /// stores one.
+    pub fn sha256(&self) -> Option<[u8; 32]> {
+        self.sha256
+    }
+
+    /// Returns whether the archive stores a BLAKE3 hash of the uncompressed data of this entry.
+    pub fn has_blake3_hash(&self) -> bool {
+        self.blake3_hash.is_some()
+    }
+
     /// Returns whether this entry is a directory.
     pub fn is_directory(&self) -> bool {
         self.is_directory
@@ -239,6 +991,20 @@ impl ArchiveEntry {
         self.windows_attributes
     }
 
+    /// Returns the Windows file attributes of this entry as [`WindowsAttributes`].
+    pub fn windows_attributes_typed(&self) -> WindowsAttributes {
+        WindowsAttributes(self.windows_attributes)
+    }
+
+    /// Sets the Windows file attributes of this entry and marks them as present.
+    ///
+    /// # Arguments
+    /// * `attributes` - The attributes to store for this entry
+    pub fn set_windows_attributes_typed(&mut self, attributes: WindowsAttributes) {
+        self.has_windows_attributes = true;
+        self.windows_attributes = attributes.bits();
+    }
+
     /// Returns the last access date of this entry.
     pub fn access_date(&self) -> NtTime {
         self.access_date
@@ -248,6 +1014,115 @@ impl ArchiveEntry {
     pub fn is_anti_item(&self) -> bool {
         self.is_anti_item
     }
+
+    /// Returns whether this entry is a file with a data stream.
+    ///
+    /// Files without any data are stored without a stream, see
+    /// [`ArchiveEntry::is_empty_file`].
+    pub fn is_file(&self) -> bool {
+        self.has_stream && !self.is_directory
+    }
+
+    /// Returns whether this entry is a file without a data stream, which is extracted as an
+    /// empty file. Anti-items are not files, but mark files for deletion.
+    pub fn is_empty_file(&self) -> bool {
+        !self.is_directory && !self.has_stream && !self.is_anti_item
+    }
+
+    /// Returns whether this entry is a file with at least one byte of data.
+    pub fn has_content(&self) -> bool {
+        self.is_file() && self.size > 0
+    }
+
+    /// Returns whether this entry is an anti-item that marks a directory for deletion.
+    pub fn is_anti_directory(&self) -> bool {
+        self.is_directory && self.is_anti_item
+    }
+}
+
+/// Replaces the `/` separators of an entry name with `separator`.
+fn with_separator(name: &str, separator: char) -> String {
+    name.replace('/', &separator.to_string())
+}
+
+impl TryFrom<&std::fs::DirEntry> for ArchiveEntry {
+    type Error = Error;
+
+    /// Creates an archive entry from a directory entry, using its file name as the entry name.
+    ///
+    /// Use [`ArchiveEntryBuilder`] to keep the path relative to a base directory instead.
+    fn try_from(entry: &std::fs::DirEntry) -> Result<Self, Self::Error> {
+        entry.metadata().map_err(|e| {
+            Error::io_msg(e, format!("Failed to read metadata of {:?}", entry.path()))
+        })?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        Ok(ArchiveEntry::from_path(entry.path(), name))
+    }
+}
+
+/// Creates [`ArchiveEntry`] instances from directory entries, naming them relative to a
+/// base directory.
+///
+/// # Example
+/// ```no_run
+/// use std::path::Path;
+///
+/// use sevenz_rust2::ArchiveEntryBuilder;
+///
+/// let base = Path::new("path/to/dir");
+/// let builder = ArchiveEntryBuilder::with_base(base);
+/// for dir_entry in std::fs::read_dir(base).unwrap() {
+///     let entry = builder.build(&dir_entry.unwrap()).unwrap();
+///     println!("{}", entry.name());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArchiveEntryBuilder {
+    base: PathBuf,
+}
+
+impl ArchiveEntryBuilder {
+    /// Creates a builder that names entries by their path relative to `base`.
+    ///
+    /// # Arguments
+    /// * `base` - The directory entry names are relative to
+    pub fn with_base(base: &Path) -> Self {
+        Self {
+            base: base.to_path_buf(),
+        }
+    }
+
+    /// Creates an archive entry for `entry`.
+    ///
+    /// Returns an error if the metadata of the entry can't be read or if the entry is not
+    /// located below the base directory.
+    ///
+    /// # Arguments
+    /// * `entry` - The directory entry to create an archive entry for
+    pub fn build(&self, entry: &std::fs::DirEntry) -> Result<ArchiveEntry, Error> {
+        self.build_from_path(&entry.path())
+    }
+
+    /// Creates an archive entry for the file or directory at `path`.
+    ///
+    /// # Arguments
+    /// * `path` - Path of a file or directory below the base directory
+    pub fn build_from_path(&self, path: &Path) -> Result<ArchiveEntry, Error> {
+        path.metadata()
+            .map_err(|e| Error::io_msg(e, format!("Failed to read metadata of {path:?}")))?;
+        let relative = path.strip_prefix(&self.base).map_err(|_| {
+            Error::other(format!(
+                "Path {path:?} is not located below base directory {:?}",
+                self.base
+            ))
+        })?;
+        let name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok(ArchiveEntry::from_path(path, name))
+    }
 }
 
 /// Configuration for encoding methods when compressing data.
@@ -300,6 +1175,148 @@ impl EncoderConfiguration {
         self.options = Some(options);
         self
     }
+
+    /// Returns an encoder chain that applies the BCJ filter for `arch` to the data before it is
+    /// compressed with this configuration.
+    ///
+    /// The filter follows the compressor in the chain, since the last method of a chain receives
+    /// the entry data.
+    ///
+    /// # Arguments
+    /// * `arch` - The architecture of the executable code in the data
+    pub fn with_bcj_filter(self, arch: BcjArch) -> Vec<EncoderConfiguration> {
+        vec![self, EncoderConfiguration::new(arch.method())]
+    }
+
+    /// Returns the configuration that 7-Zip uses for the compression level `level` of the 7z
+    /// format, like `7z a -mx=level`.
+    ///
+    /// Level 0 stores the data with [`EncoderMethod::COPY`]. Levels 1 to 9 compress with LZMA2,
+    /// from a 64 KiB dictionary in fast mode at level 1, over a 16 MiB dictionary at level 5, to
+    /// a 64 MiB dictionary with the BT4 match finder at level 9. Levels above 9 are treated as 9.
+    /// The data is encoded using a single thread.
+    ///
+    /// # Arguments
+    /// * `level` - Compression level (0-9)
+    pub fn preset(level: u32) -> Self {
+        if level == 0 {
+            return Self::new(EncoderMethod::COPY);
+        }
+        crate::encoder_options::Lzma2Options::from_7zip_level(level).into()
+    }
+
+    /// Maximum number of coders in an encoder chain that 7-Zip supports.
+    pub const MAX_CHAIN_LENGTH: usize = 4;
+
+    /// Validates an encoder chain as passed to [`crate::ArchiveWriter::set_content_methods`].
+    ///
+    /// The first method of the chain writes to the archive and the last method receives the
+    /// entry data. A valid chain therefore has the form `[encryption?, compressor?, filters...]`.
+    ///
+    /// The following rules are checked:
+    /// * The chain is not empty and contains at most [`Self::MAX_CHAIN_LENGTH`] methods.
+    /// * All methods are supported for encoding with the enabled crate features.
+    /// * AES encryption has options with a non-empty password and is the first method.
+    /// * There is at most one compression codec.
+    /// * Filters (Delta/BCJ) are applied before the compressor, so they follow it in the chain.
+    ///
+    /// # Arguments
+    /// * `methods` - The encoder chain to validate
+    pub fn validate(methods: &[EncoderConfiguration]) -> Result<(), Error> {
+        if methods.is_empty() {
+            return Err(Error::other("Encoder chain is empty"));
+        }
+        if methods.len() > Self::MAX_CHAIN_LENGTH {
+            return Err(Error::unsupported(format!(
+                "Encoder chain has {} methods, but at most {} are supported",
+                methods.len(),
+                Self::MAX_CHAIN_LENGTH
+            )));
+        }
+
+        let mut compressor: Option<EncoderMethod> = None;
+        for (index, conf) in methods.iter().enumerate() {
+            let method = conf.method;
+            if method.id() == EncoderMethod::ID_BCJ2 {
+                return Err(Error::unsupported("BCJ2 is not supported for encoding"));
+            }
+            if !method.is_supported_for_encoding() {
+                return Err(Error::UnsupportedCompressionMethod(
+                    method.name().to_string(),
+                ));
+            }
+
+            if method.id() == EncoderMethod::ID_AES256_SHA256 {
+                #[cfg(feature = "aes256")]
+                match conf.options.as_ref() {
+                    Some(EncoderOptions::Aes(options)) if !options.password.is_empty() => {}
+                    _ => return Err(Error::PasswordRequired),
+                }
+                if index != 0 {
+                    return Err(Error::other(
+                        "AES encryption must be the first method of the encoder chain",
+                    ));
+                }
+            } else if method.is_filter() {
+                if compressor.is_none() && methods[index..].iter().any(|c| c.method.is_compressor())
+                {
+                    return Err(Error::other(format!(
+                        "Filter {} must be placed after the compression method in the encoder chain",
+                        method.name()
+                    )));
+                }
+            } else if let Some(previous) = compressor {
+                return Err(Error::other(format!(
+                    "Encoder chain contains more than one compression method: {} and {}",
+                    previous.name(),
+                    method.name()
+                )));
+            } else {
+                compressor = Some(method);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Architecture of the executable code that a BCJ filter prepares for compression.
+#[cfg(feature = "compress")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BcjArch {
+    /// x86 and x86-64.
+    X86,
+    /// ARM.
+    Arm,
+    /// ARM64.
+    Arm64,
+    /// ARM Thumb.
+    ArmThumb,
+    /// Itanium.
+    IA64,
+    /// SPARC.
+    Sparc,
+    /// PowerPC.
+    Ppc,
+    /// RISC-V.
+    RiscV,
+}
+
+#[cfg(feature = "compress")]
+impl BcjArch {
+    /// Returns the BCJ filter method for this architecture.
+    pub fn method(self) -> EncoderMethod {
+        match self {
+            BcjArch::X86 => EncoderMethod::BCJ_X86_FILTER,
+            BcjArch::Arm => EncoderMethod::BCJ_ARM_FILTER,
+            BcjArch::Arm64 => EncoderMethod::BCJ_ARM64_FILTER,
+            BcjArch::ArmThumb => EncoderMethod::BCJ_ARM_THUMB_FILTER,
+            BcjArch::IA64 => EncoderMethod::BCJ_IA64_FILTER,
+            BcjArch::Sparc => EncoderMethod::BCJ_SPARC_FILTER,
+            BcjArch::Ppc => EncoderMethod::BCJ_PPC_FILTER,
+            BcjArch::RiscV => EncoderMethod::BCJ_RISCV_FILTER,
+        }
+    }
 }
 
 /// Encoder method that can be chained (filter, compression and encryption).
@@ -349,12 +1366,21 @@ impl EncoderMethod {
     pub const ID_LZS: &'static [u8] = &[0x04, 0xF7, 0x11, 0x05];
     /// Method ID for Lizard compression.
     pub const ID_LIZARD: &'static [u8] = &[0x04, 0xF7, 0x11, 0x06];
+    /// Method ID for Snappy compression.
+    ///
+    /// This ID is unofficial and not registered with 7-Zip, so archives using it can only be
+    /// read by implementations that chose the same ID.
+    pub const ID_SNAPPY: &'static [u8] = &[0x04, 0xF7, 0x11, 0x07];
     /// Method ID for Deflate compression.
     pub const ID_DEFLATE: &'static [u8] = &[0x04, 0x01, 0x08];
     /// Method ID for Deflate64 compression.
     pub const ID_DEFLATE64: &'static [u8] = &[0x04, 0x01, 0x09];
     /// Method ID for AES256-SHA256 encryption.
     pub const ID_AES256_SHA256: &'static [u8] = &[0x06, 0xF1, 0x07, 0x01];
+    /// Method ID for the AES-128 encryption of RAR 2.9, with a SHA-1 based key derivation.
+    pub const ID_RAR29_AES: &'static [u8] = &[0x06, 0xF1, 0x03, 0x03];
+    /// Method ID for AES-256-CBC encryption without key derivation.
+    pub const ID_AES256_CBC: &'static [u8] = &[0x06, 0xF0, 0x01, 0x81];
 
     /// COPY method (no compression).
     pub const COPY: Self = Self("COPY", Self::ID_COPY);
@@ -376,12 +1402,19 @@ impl EncoderMethod {
     pub const LZS: Self = Self("LZS", Self::ID_LZS);
     /// Lizard compression method.
     pub const LIZARD: Self = Self("LIZARD", Self::ID_LIZARD);
+    /// Snappy compression method. Uses the unofficial method ID [`EncoderMethod::ID_SNAPPY`].
+    pub const SNAPPY: Self = Self("SNAPPY", Self::ID_SNAPPY);
     /// Deflate compression method.
     pub const DEFLATE: Self = Self("DEFLATE", Self::ID_DEFLATE);
     /// Deflate64 compression method.
     pub const DEFLATE64: Self = Self("DEFLATE64", Self::ID_DEFLATE64);
     /// AES256-SHA256 encryption method.
     pub const AES256_SHA256: Self = Self("AES256_SHA256", Self::ID_AES256_SHA256);
+    /// RAR 2.9 AES-128 encryption method. Only supported for decoding.
+    pub const RAR29_AES: Self = Self("RAR29_AES", Self::ID_RAR29_AES);
+    /// AES-256-CBC encryption method without key derivation. Not supported, since the key can't
+    /// be derived from a password.
+    pub const AES256_CBC: Self = Self("AES256_CBC", Self::ID_AES256_CBC);
 
     /// BCJ x86 filter method.
     pub const BCJ_X86_FILTER: Self = Self("BCJ_X86", Self::ID_BCJ_X86);
@@ -415,9 +1448,12 @@ impl EncoderMethod {
         &Self::LZ4,
         &Self::LZS,
         &Self::LIZARD,
+        &Self::SNAPPY,
         &Self::DEFLATE,
         &Self::DEFLATE64,
         &Self::AES256_SHA256,
+        &Self::RAR29_AES,
+        &Self::AES256_CBC,
         &Self::BCJ_X86_FILTER,
         &Self::BCJ_PPC_FILTER,
         &Self::BCJ_IA64_FILTER,
@@ -442,6 +1478,64 @@ impl EncoderMethod {
         self.1
     }
 
+    /// Returns `true` if this method is a filter (Delta or one of the BCJ filters).
+    pub fn is_filter(&self) -> bool {
+        matches!(
+            self.id(),
+            Self::ID_DELTA
+                | Self::ID_BCJ_X86
+                | Self::ID_BCJ2
+                | Self::ID_BCJ_PPC
+                | Self::ID_BCJ_IA64
+                | Self::ID_BCJ_ARM
+                | Self::ID_BCJ_ARM64
+                | Self::ID_BCJ_ARM_THUMB
+                | Self::ID_BCJ_SPARC
+                | Self::ID_BCJ_RISCV
+        )
+    }
+
+    /// Returns `true` if this method is an encryption method.
+    pub fn is_encryption(&self) -> bool {
+        matches!(
+            self.id(),
+            Self::ID_AES256_SHA256 | Self::ID_RAR29_AES | Self::ID_AES256_CBC
+        )
+    }
+
+    /// Returns `true` if this method is a compression codec (including COPY).
+    pub fn is_compressor(&self) -> bool {
+        !self.is_filter() && !self.is_encryption()
+    }
+
+    /// Returns `true` if this method can be used for encoding with the enabled crate features.
+    #[cfg(feature = "compress")]
+    pub fn is_supported_for_encoding(&self) -> bool {
+        let id = self.id();
+        matches!(
+            id,
+            Self::ID_COPY
+                | Self::ID_LZMA
+                | Self::ID_LZMA2
+                | Self::ID_DELTA
+                | Self::ID_BCJ_X86
+                | Self::ID_BCJ_PPC
+                | Self::ID_BCJ_IA64
+                | Self::ID_BCJ_ARM
+                | Self::ID_BCJ_ARM64
+                | Self::ID_BCJ_ARM_THUMB
+                | Self::ID_BCJ_SPARC
+                | Self::ID_BCJ_RISCV
+        ) || (cfg!(feature = "ppmd") && id == Self::ID_PPMD)
+            || (cfg!(feature = "bzip2") && id == Self::ID_BZIP2)
+            || (cfg!(feature = "zstd") && id == Self::ID_ZSTD)
+            || (cfg!(feature = "brotli") && id == Self::ID_BROTLI)
+            || (cfg!(feature = "lz4") && id == Self::ID_LZ4)
+            || (cfg!(feature = "deflate") && id == Self::ID_DEFLATE)
+            || (cfg!(feature = "snappy") && id == Self::ID_SNAPPY)
+            || (cfg!(feature = "aes256") && id == Self::ID_AES256_SHA256)
+    }
+
     #[inline]
     /// Finds an encoder method by its binary ID.
     ///
@@ -454,6 +1548,18 @@ impl EncoderMethod {
             .cloned()
             .cloned()
     }
+
+    /// Finds an encoder method by its name, ignoring ASCII case.
+    ///
+    /// # Arguments
+    /// * `name` - The method name to search for, as returned by [`EncoderMethod::name`]
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::ENCODING_METHODS
+            .iter()
+            .find(|item| item.name().eq_ignore_ascii_case(name))
+            .cloned()
+            .cloned()
+    }
 }
 
 /// Mapping structure that correlates files, blocks, and pack streams within an archive.
@@ -461,6 +1567,7 @@ impl EncoderMethod {
 /// This structure maintains the relationships between archive entries and their
 /// corresponding compression blocks and packed data streams.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StreamMap {
     pub(crate) block_first_pack_stream_index: Vec<usize>,
     pub(crate) pack_stream_offsets: Vec<u64>,
@@ -468,6 +1575,7 @@ pub struct StreamMap {
     pub block_first_file_index: Vec<usize>,
     /// Block index for each file (None if file has no data).
     pub file_block_index: Vec<Option<usize>>,
+    pub(crate) file_offset_in_block: Vec<u64>,
 }
 
 impl StreamMap {
@@ -478,10 +1586,43 @@ impl StreamMap {
     }
 
     /// Returns byte offsets of each pack stream within the packed data region.
-    /// Combined with pack_pos and SIGNATURE_HEADER_SIZE to get absolute offsets.
+    /// Combined with pack_pos, SIGNATURE_HEADER_SIZE and the base offset to get absolute offsets.
     pub fn pack_stream_offsets(&self) -> &[u64] {
         &self.pack_stream_offsets
     }
+
+    /// Returns the offset of the data of the file at `file_index` in the decoded data of its
+    /// block, which is the sum of the sizes of the files in front of it in the same block.
+    ///
+    /// Returns 0 for files without data and indices out of bounds.
+    pub fn entry_offset_in_block(&self, file_index: usize) -> u64 {
+        self.file_offset_in_block
+            .get(file_index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the indices of the files whose data is stored in the block `block_index`, in the
+    /// order they appear in the block.
+    ///
+    /// Files without data, like directories and empty files, belong to no block, even if they
+    /// are listed between the files of a block.
+    pub fn block_entry_indices(&self, block_index: usize) -> impl Iterator<Item = usize> + '_ {
+        let start = self
+            .block_first_file_index
+            .get(block_index)
+            .copied()
+            .unwrap_or(self.file_block_index.len());
+        self.file_block_index
+            .get(start..)
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            // The files of a block are contiguous, apart from files without data.
+            .take_while(move |(_, index)| index.is_none_or(|index| index == block_index))
+            .filter(|(_, index)| index.is_some())
+            .map(move |(i, _)| start + i)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -490,3 +1631,133 @@ pub(crate) struct StartHeader {
     pub(crate) next_header_size: u64,
     pub(crate) next_header_crc: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_kind_predicates() {
+        for bits in 0..16u8 {
+            let entry = ArchiveEntry {
+                is_directory: bits & 1 != 0,
+                has_stream: bits & 2 != 0,
+                is_anti_item: bits & 4 != 0,
+                size: u64::from(bits & 8 != 0),
+                ..Default::default()
+            };
+            // Directories and anti-items never have a stream.
+            if entry.has_stream && (entry.is_directory || entry.is_anti_item) {
+                continue;
+            }
+
+            assert!(!(entry.is_file() && entry.is_empty_file()), "{entry:?}");
+            assert!(!entry.has_content() || entry.is_file(), "{entry:?}");
+            assert!(
+                !entry.is_anti_directory() || entry.is_directory(),
+                "{entry:?}"
+            );
+            let kinds = [entry.is_directory(), entry.is_file(), entry.is_empty_file()];
+            let expected_kinds = match entry.is_anti_item && !entry.is_directory {
+                // An anti-item for a file is none of the kinds.
+                true => 0,
+                false => 1,
+            };
+            assert_eq!(
+                kinds.iter().filter(|&&kind| kind).count(),
+                expected_kinds,
+                "{entry:?}"
+            );
+            assert_eq!(
+                entry.is_anti_directory(),
+                entry.is_directory && entry.is_anti_item
+            );
+            assert_eq!(entry.has_content(), entry.is_file() && entry.size > 0);
+        }
+    }
+
+    #[test]
+    fn test_entry_paths() {
+        let entry = ArchiveEntry::new_file("dir/sub dir/file.txt");
+        assert_eq!(entry.as_path(), Path::new("dir/sub dir/file.txt"));
+        assert_eq!(entry.parent_name(), Some("dir/sub dir"));
+        assert_eq!(with_separator(&entry.name, '/'), "dir/sub dir/file.txt");
+        assert_eq!(with_separator(&entry.name, '\\'), "dir\\sub dir\\file.txt");
+        #[cfg(windows)]
+        assert_eq!(
+            entry.to_platform_path(),
+            PathBuf::from("dir\\sub dir\\file.txt")
+        );
+        #[cfg(not(windows))]
+        assert_eq!(
+            entry.to_platform_path(),
+            PathBuf::from("dir/sub dir/file.txt")
+        );
+        assert_eq!(
+            entry.to_platform_path().components().count(),
+            3,
+            "{:?}",
+            entry.to_platform_path()
+        );
+
+        assert_eq!(
+            ArchiveEntry::new_directory("dir/sub/").parent_name(),
+            Some("dir")
+        );
+        assert_eq!(ArchiveEntry::new_file("file.txt").parent_name(), None);
+    }
+
+    #[test]
+    fn test_entry_file_name_and_extension() {
+        let entry = ArchiveEntry::new_file("dir/sub/file.tar.gz");
+        assert_eq!(entry.file_name(), "file.tar.gz");
+        assert_eq!(entry.extension(), Some("gz"));
+        assert_eq!(entry.parent_name(), Some("dir/sub"));
+        assert_eq!(entry.parent(), Some("dir/sub"));
+
+        let entry = ArchiveEntry::new_file("noext");
+        assert_eq!(entry.file_name(), "noext");
+        assert_eq!(entry.extension(), None);
+        assert_eq!(entry.parent_name(), None);
+        assert_eq!(entry.parent(), None);
+
+        let entry = ArchiveEntry::new_file(".hidden");
+        assert_eq!(entry.file_name(), ".hidden");
+        assert_eq!(entry.extension(), None);
+
+        let entry = ArchiveEntry::new_file("dir/.config.toml");
+        assert_eq!(entry.extension(), Some("toml"));
+        assert_eq!(ArchiveEntry::new_file("dir.d/file").extension(), None);
+
+        let entry = ArchiveEntry::new_directory("dir/sub/");
+        assert_eq!(entry.file_name(), "sub");
+        assert_eq!(entry.parent(), Some("dir"));
+    }
+
+    #[test]
+    fn test_entry_builder_methods() {
+        let now = NtTime::now();
+        let entry = ArchiveEntry::new_file("x.txt")
+            .with_last_modified_date(now)
+            .with_windows_attributes(0x20);
+        assert_eq!(entry.name(), "x.txt");
+        assert!(entry.has_stream());
+        assert!(entry.has_last_modified_date);
+        assert_eq!(entry.last_modified_date(), now);
+        assert!(entry.has_windows_attributes);
+        assert_eq!(entry.windows_attributes(), 0x20);
+        assert!(!entry.has_creation_date && !entry.has_access_date);
+
+        let date = NtTime::UNIX_EPOCH;
+        let entry = entry
+            .with_name("dir/y.txt")
+            .with_creation_date(date)
+            .with_access_date(date)
+            .with_size_hint(42);
+        assert_eq!(entry.name(), "dir/y.txt");
+        assert!(entry.has_creation_date && entry.has_access_date);
+        assert_eq!(entry.creation_date(), date);
+        assert_eq!(entry.access_date(), date);
+        assert_eq!(entry.size(), 42);
+    }
+}
@@ -4,6 +4,11 @@ use std::io::Write;
 
 /// A set of usize values represented as a bit vector.
 #[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerdeBitSet", into = "SerdeBitSet")
+)]
 pub(crate) struct BitSet {
     /// We use a Vec<usize> to store the bits, where each usize represents usize::BITS.
     bits: Vec<usize>,
@@ -27,6 +32,12 @@ impl BitSet {
         Self { bits, bit_count }
     }
 
+    /// Returns the number of bits in the set, not the number of set bits.
+    #[cfg(feature = "serde")]
+    pub(crate) fn bit_count(&self) -> usize {
+        self.bit_count
+    }
+
     /// Returns the number of set bits in this set.
     pub(crate) fn len(&self) -> usize {
         self.bits
@@ -62,6 +73,10 @@ impl BitSet {
         }
 
         let (block_idx, bit_idx) = self.bit_indices(value);
+        // Deserialized sets only store the blocks up to the highest value.
+        if block_idx >= self.bits.len() {
+            self.bits.resize(block_idx + 1, 0);
+        }
         self.bits[block_idx] |= 1 << bit_idx;
 
         true
@@ -129,6 +144,50 @@ impl BitSet {
     }
 }
 
+/// Serialized form of a [`BitSet`], which doesn't depend on the width of `usize`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdeBitSet {
+    bit_count: usize,
+    /// The values in the set, in ascending order.
+    values: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeBitSet> for BitSet {
+    type Error = String;
+
+    fn try_from(set: SerdeBitSet) -> Result<Self, Self::Error> {
+        // Only the blocks up to the highest value are allocated, so that a corrupt bit count
+        // doesn't cause a huge allocation.
+        let mut bit_set = Self::new();
+        for value in set.values {
+            if value >= set.bit_count {
+                return Err(format!(
+                    "Bit set value {value} is out of range, the set has {} bits",
+                    set.bit_count
+                ));
+            }
+            bit_set.insert(value);
+        }
+        bit_set.bit_count = set.bit_count;
+        Ok(bit_set)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<BitSet> for SerdeBitSet {
+    fn from(set: BitSet) -> Self {
+        let stored_bits = set.bits.len() * usize::BITS as usize;
+        Self {
+            values: (0..stored_bits.min(set.bit_count))
+                .filter(|&value| set.contains(value))
+                .collect(),
+            bit_count: set.bit_count,
+        }
+    }
+}
+
 impl Default for BitSet {
     fn default() -> Self {
         Self::new()
@@ -265,4 +324,23 @@ mod tests {
         }
         Ok(bits)
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bitset_from_serde() {
+        let set = BitSet::try_from(SerdeBitSet {
+            values: vec![1, 70],
+            bit_count: usize::MAX,
+        })
+        .unwrap();
+        assert_eq!(set.bits.len(), 2);
+        assert!(set.contains(1) && set.contains(70));
+        assert_eq!(SerdeBitSet::from(set).values, [1, 70]);
+
+        let out_of_range = SerdeBitSet {
+            values: vec![8],
+            bit_count: 8,
+        };
+        assert!(BitSet::try_from(out_of_range).is_err());
+    }
 }
@@ -1,8 +1,21 @@
+use lzma_rust2::{lzma_get_memory_usage_by_props, lzma2_get_memory_usage};
+
+use crate::{Error, archive::EncoderMethod, codec};
+
+/// Largest BZIP2 block size, which is not stored in the coder properties.
+const BZIP2_MAX_BLOCK_SIZE: u64 = 900_000;
+
+/// Window log of the ZSTD compression levels 1 to 22 for inputs of unknown size.
+const ZSTD_WINDOW_LOG: [u8; 22] = [
+    19, 20, 21, 21, 21, 21, 21, 21, 22, 22, 22, 22, 22, 22, 22, 22, 23, 23, 23, 25, 26, 27,
+];
+
 /// Represents a compression block.
 ///
 /// A block contains one or more coders (compression/filter methods) that are chained
 /// together to process data.
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Block {
     /// Coders (compression/filter methods) in this block.
     pub coders: Vec<Coder>,
@@ -68,6 +81,154 @@ impl Block {
     pub fn ordered_coder_iter(&self) -> OrderedCoderIter<'_> {
         OrderedCoderIter::new(self)
     }
+
+    /// Returns whether the data of this block is encrypted with AES.
+    pub fn is_encrypted(&self) -> bool {
+        self.coders.iter().any(|coder| {
+            EncoderMethod::by_id(coder.encoder_method_id()).is_some_and(|m| m.is_encryption())
+        })
+    }
+
+    /// Returns the coder input stream indices that are fed directly from packed streams.
+    ///
+    /// The n-th index belongs to the n-th packed stream of this block, starting at
+    /// [`crate::StreamMap::block_first_pack_stream_index`].
+    pub fn packed_stream_indices(&self) -> &[u64] {
+        &self.packed_streams
+    }
+
+    /// Returns an estimate of the memory in bytes needed to decode this block with a single
+    /// thread. It is computed from the coder properties only, without decoding any data.
+    ///
+    /// The estimate covers the dictionaries of LZMA and LZMA2, the model of PPMD, the block of
+    /// BZIP2 and the window of ZSTD. BZIP2 doesn't store its block size in the properties, so the
+    /// largest block size is assumed, and the ZSTD window is derived from the compression level.
+    /// Filters, encryption and other codecs only use small buffers and are not counted.
+    pub fn decoder_memory_estimate(&self) -> u64 {
+        self.coders.iter().map(Coder::decoder_memory_estimate).sum()
+    }
+
+    /// Checks that the coders, bind pairs, packed streams and unpack sizes of this block are
+    /// consistent with each other, so that the block can be decoded without indexing out of
+    /// bounds or following a cycle of coders.
+    ///
+    /// Archives are validated when their header is read, so this only needs to be called for
+    /// blocks that were modified afterwards.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.check_consistency()
+            .map_err(|detail| Error::corrupt_header("block", detail))
+    }
+
+    /// Like [`Block::validate`], but names the index of the block in the error.
+    pub(crate) fn validate_at(&self, block_index: usize) -> Result<(), Error> {
+        self.check_consistency().map_err(|detail| {
+            Error::corrupt_header("block", format!("Block {block_index}: {detail}"))
+        })
+    }
+
+    fn check_consistency(&self) -> Result<(), String> {
+        let (num_in_streams, num_out_streams) = self.coders.iter().fold((0u64, 0u64), |acc, c| {
+            (
+                acc.0.saturating_add(c.num_in_streams),
+                acc.1.saturating_add(c.num_out_streams),
+            )
+        });
+        if num_in_streams != self.total_input_streams as u64
+            || num_out_streams != self.total_output_streams as u64
+        {
+            return Err(format!(
+                "The coders have {num_in_streams} input and {num_out_streams} output streams, \
+                 but the block has {} and {}",
+                self.total_input_streams, self.total_output_streams
+            ));
+        }
+        if self.total_output_streams == 0 {
+            return Err("Total output streams can't be 0".into());
+        }
+        if let Some(index) = self.coders.iter().position(|c| c.num_out_streams != 1) {
+            return Err(format!(
+                "Coder {index} has {} output streams, only one is supported",
+                self.coders[index].num_out_streams
+            ));
+        }
+        if self.bind_pairs.len() != self.total_output_streams - 1 {
+            return Err(format!(
+                "Expected {} bind pairs, got {}",
+                self.total_output_streams - 1,
+                self.bind_pairs.len()
+            ));
+        }
+        if self.bind_pairs.len() + self.packed_streams.len() != self.total_input_streams {
+            return Err(format!(
+                "{} bind pairs and {} packed streams don't cover {} input streams",
+                self.bind_pairs.len(),
+                self.packed_streams.len(),
+                self.total_input_streams
+            ));
+        }
+        if self.unpack_sizes.len() != self.total_output_streams {
+            return Err(format!(
+                "Expected {} unpack sizes, got {}",
+                self.total_output_streams,
+                self.unpack_sizes.len()
+            ));
+        }
+
+        let mut in_bound = vec![false; self.total_input_streams];
+        let mut out_bound = vec![false; self.total_output_streams];
+        for bp in self.bind_pairs.iter() {
+            let in_used = in_bound
+                .get_mut(bp.in_index as usize)
+                .ok_or_else(|| format!("Bind pair input stream {} is out of range", bp.in_index))?;
+            if std::mem::replace(in_used, true) {
+                return Err(format!("Input stream {} is bound twice", bp.in_index));
+            }
+            let out_used = out_bound.get_mut(bp.out_index as usize).ok_or_else(|| {
+                format!("Bind pair output stream {} is out of range", bp.out_index)
+            })?;
+            if std::mem::replace(out_used, true) {
+                return Err(format!("Output stream {} is bound twice", bp.out_index));
+            }
+        }
+        for &index in self.packed_streams.iter() {
+            let in_used = in_bound
+                .get_mut(index as usize)
+                .ok_or_else(|| format!("Packed stream {index} is out of range"))?;
+            if std::mem::replace(in_used, true) {
+                return Err(format!(
+                    "Input stream {index} is used by more than one bind pair or packed stream"
+                ));
+            }
+        }
+
+        // Every coder has a single output stream, so the index of an output stream is the index
+        // of its coder. Starting at the coder of the unbound output stream, every coder has to be
+        // reached exactly once by following the bind pairs of its input streams.
+        let main_coder = out_bound.iter().position(|&bound| !bound).unwrap_or(0);
+        let mut visited = vec![false; self.coders.len()];
+        let mut pending = vec![main_coder];
+        while let Some(coder_index) = pending.pop() {
+            if std::mem::replace(&mut visited[coder_index], true) {
+                return Err(format!("Coder {coder_index} is part of a cycle"));
+            }
+            let start: u64 = self.coders[..coder_index]
+                .iter()
+                .map(|coder| coder.num_in_streams)
+                .sum();
+            let end = start + self.coders[coder_index].num_in_streams;
+            pending.extend(
+                (start..end)
+                    .filter_map(|in_index| self.find_bind_pair_for_in_stream(in_index))
+                    .map(|bp| bp.out_index as usize),
+            );
+        }
+        if let Some(coder_index) = visited.iter().position(|&visited| !visited) {
+            return Err(format!(
+                "Coder {coder_index} is not connected to the output"
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Represents a single coder within a compression block.
@@ -75,6 +236,11 @@ impl Block {
 /// A coder defines a specific compression method, filter, or encryption method
 /// used to process data within a block.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SerdeCoder", into = "SerdeCoder")
+)]
 pub struct Coder {
     encoder_method_id: [u8; 0xF],
     pub(crate) id_size: usize,
@@ -92,12 +258,127 @@ impl Coder {
         &self.encoder_method_id[0..self.id_size]
     }
 
+    /// Creates a coder with one input and one output stream.
+    ///
+    /// This is an advanced API to synthesize the coders of a block, for example to decode the
+    /// streams of a damaged archive with [`codec::decode_raw_stream`].
+    ///
+    /// # Arguments
+    /// * `method` - The method of the coder
+    /// * `properties` - The coder properties as stored in the archive
+    pub fn with_method(method: EncoderMethod, properties: &[u8]) -> Self {
+        let mut coder = Self {
+            id_size: method.id().len(),
+            num_in_streams: 1,
+            num_out_streams: 1,
+            properties: properties.to_vec(),
+            ..Default::default()
+        };
+        coder
+            .decompression_method_id_mut()
+            .copy_from_slice(method.id());
+        coder
+    }
+
+    /// Returns the coder properties as stored in the archive, like the dictionary size of LZMA.
+    pub fn properties(&self) -> &[u8] {
+        &self.properties
+    }
+
     pub(crate) fn decompression_method_id_mut(&mut self) -> &mut [u8] {
         &mut self.encoder_method_id[0..self.id_size]
     }
+
+    /// Returns the memory needed to decode this coder, or 0 if the properties are invalid.
+    fn decoder_memory_estimate(&self) -> u64 {
+        let properties = self.properties.as_slice();
+        match self.encoder_method_id() {
+            EncoderMethod::ID_LZMA => match properties {
+                [props, dict_size @ ..] if dict_size.len() >= 4 => {
+                    let dict_size = u32::from_le_bytes(dict_size[..4].try_into().unwrap());
+                    lzma_get_memory_usage_by_props(dict_size, *props)
+                        .map_or(0, |kb| u64::from(kb) * 1024)
+                }
+                _ => 0,
+            },
+            EncoderMethod::ID_LZMA2 => codec::lzma2::dict_size_from_properties(properties)
+                .map_or(0, |dict_size| {
+                    u64::from(lzma2_get_memory_usage(dict_size)) * 1024
+                }),
+            EncoderMethod::ID_PPMD => properties.get(1..5).map_or(0, |memory_size| {
+                u64::from(u32::from_le_bytes(memory_size.try_into().unwrap()))
+            }),
+            // The decoder keeps the block and an index of 4 bytes per byte of the block.
+            EncoderMethod::ID_BZIP2 => 100_000 + 4 * BZIP2_MAX_BLOCK_SIZE,
+            EncoderMethod::ID_ZSTD => {
+                let window_log = match properties.get(2) {
+                    // Level 0 selects the default level 3.
+                    None | Some(0) => ZSTD_WINDOW_LOG[2],
+                    Some(&level @ 1..=22) => ZSTD_WINDOW_LOG[level as usize - 1],
+                    // Negative levels, stored as their two's complement.
+                    Some(_) => ZSTD_WINDOW_LOG[0],
+                };
+                1 << window_log
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Serialized form of a [`Coder`], with the method ID only as long as it is.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdeCoder {
+    method_id: Vec<u8>,
+    num_in_streams: u64,
+    num_out_streams: u64,
+    properties: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SerdeCoder> for Coder {
+    type Error = String;
+
+    fn try_from(coder: SerdeCoder) -> Result<Self, Self::Error> {
+        let mut encoder_method_id = [0; 0xF];
+        encoder_method_id
+            .get_mut(..coder.method_id.len())
+            .ok_or_else(|| format!("Coder ID of {} bytes is too long", coder.method_id.len()))?
+            .copy_from_slice(&coder.method_id);
+        Ok(Self {
+            encoder_method_id,
+            id_size: coder.method_id.len(),
+            num_in_streams: coder.num_in_streams,
+            num_out_streams: coder.num_out_streams,
+            properties: coder.properties,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Coder> for SerdeCoder {
+    fn from(coder: Coder) -> Self {
+        Self {
+            method_id: coder.encoder_method_id().to_vec(),
+            num_in_streams: coder.num_in_streams,
+            num_out_streams: coder.num_out_streams,
+            properties: coder.properties,
+        }
+    }
+}
+
+/// The method and properties of a coder, as used to encode the data of an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecInfo {
+    /// The compression method, filter, or encryption method of the coder.
+    pub method: EncoderMethod,
+    /// The coder properties, like the model order and memory size of PPMD or the dictionary
+    /// size of LZMA2.
+    pub properties: Vec<u8>,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) struct BindPair {
     pub(crate) in_index: u64,
     pub(crate) out_index: u64,
@@ -1,4 +1,231 @@
+//! Low level access to the codecs bundled with this crate.
+//!
+//! The functions in the submodules create readers and writers for raw codec streams, independent
+//! of the 7z container. They are parameterized the same way as the coders of a 7z block: readers
+//! take the coder properties as stored in the archive, writers take the encoder options and
+//! report the properties that need to be stored to decode the stream again.
+//!
+//! The module is only public with the `codec` feature.
+
+pub(crate) mod bcj;
 #[cfg(feature = "brotli")]
-pub mod brotli;
+pub(crate) mod brotli;
+#[cfg(feature = "bzip2")]
+pub(crate) mod bzip2;
+pub mod delta;
 #[cfg(feature = "lz4")]
-pub mod lz4;
+pub(crate) mod lz4;
+#[cfg(feature = "codec")]
+pub mod lzma;
+pub mod lzma2;
+#[cfg(feature = "ppmd")]
+pub mod ppmd;
+#[cfg(feature = "snappy")]
+pub(crate) mod snappy;
+#[cfg(feature = "zstd")]
+pub(crate) mod zstd;
+
+#[cfg(any(
+    feature = "codec",
+    all(feature = "compress", feature = "util", target_arch = "wasm32")
+))]
+use std::io;
+#[cfg(all(
+    feature = "compress",
+    any(feature = "codec", all(feature = "util", target_arch = "wasm32"))
+))]
+use std::io::Write;
+#[cfg(feature = "codec")]
+use std::io::{Read, Take};
+
+#[cfg(all(feature = "codec", feature = "aes256"))]
+use crate::encryption::AesDecoder;
+#[cfg(any(
+    feature = "codec",
+    all(feature = "compress", feature = "util", target_arch = "wasm32")
+))]
+use crate::error::Error;
+#[cfg(feature = "codec")]
+use crate::{
+    Password,
+    archive::EncoderMethod,
+    block::Coder,
+    decoder::{self, Decoder},
+};
+#[cfg(all(
+    feature = "compress",
+    any(feature = "codec", all(feature = "util", target_arch = "wasm32"))
+))]
+use crate::{
+    archive::EncoderConfiguration,
+    encoder::{self, Encoder},
+    writer::CountingWriter,
+};
+
+#[cfg(feature = "codec")]
+const MAX_MEM_LIMIT_KB: usize = usize::MAX / 1024;
+
+/// Reader that decodes a raw codec stream.
+#[cfg(feature = "codec")]
+pub struct CodecReader<R: Read> {
+    inner: Take<Decoder<R>>,
+}
+
+#[cfg(feature = "codec")]
+impl<R: Read> CodecReader<R> {
+    fn new(
+        input: R,
+        method: EncoderMethod,
+        properties: &[u8],
+        unpacked_size: Option<u64>,
+    ) -> Result<Self, Error> {
+        let coder = Coder::with_method(method, properties);
+        Self::with_coder(input, &coder, unpacked_size, &Password::empty())
+    }
+
+    fn with_coder(
+        input: R,
+        coder: &Coder,
+        unpacked_size: Option<u64>,
+        password: &Password,
+    ) -> Result<Self, Error> {
+        // Without a known size, the decoders read until their end marker or the end of the input.
+        let unpacked_size = unpacked_size.unwrap_or(u64::MAX);
+        let unpacked_len = usize::try_from(unpacked_size).unwrap_or(usize::MAX);
+        let decoder = decoder::add_decoder(
+            input,
+            unpacked_len,
+            coder,
+            password,
+            MAX_MEM_LIMIT_KB,
+            1,
+            decoder::DEFAULT_MAX_KEY_DERIVATION_POWER,
+        )?;
+
+        Ok(Self {
+            inner: decoder.take(unpacked_size),
+        })
+    }
+}
+
+#[cfg(feature = "codec")]
+impl<R: Read> Read for CodecReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Creates a reader that decodes the raw stream of a single coder.
+///
+/// This is an advanced API for tools that recover data from damaged archives: the stream can be
+/// any byte range of a file, decoded with the method ID and coder properties given by the
+/// caller, for example taken from [`Block::coders`](crate::Block::coders) of a similar archive.
+/// Nothing is validated against the archive structure, and the output of a wrong guess is
+/// garbage or an error.
+///
+/// # Arguments
+/// * `input` - The reader providing the packed stream
+/// * `method_id` - The method ID of the coder, see [`EncoderMethod::id`]
+/// * `properties` - The coder properties as stored in the archive
+/// * `unpacked_size` - The size of the decoded data, or `None` to read until the stream ends
+/// * `password` - The password for AES encrypted streams
+#[cfg(feature = "codec")]
+pub fn decode_raw_stream<R: Read>(
+    input: R,
+    method_id: &[u8],
+    properties: &[u8],
+    unpacked_size: Option<u64>,
+    password: Option<&Password>,
+) -> Result<CodecReader<R>, Error> {
+    let method = EncoderMethod::by_id(method_id).ok_or_else(|| Error::UnsupportedCoder {
+        id: method_id.to_vec(),
+    })?;
+    let coder = Coder::with_method(method, properties);
+    CodecReader::with_coder(
+        input,
+        &coder,
+        unpacked_size,
+        password.unwrap_or(&Password::empty()),
+    )
+}
+
+/// Creates a reader that decrypts a raw AES-256 stream with an already derived key.
+///
+/// Like [`decode_raw_stream`], this is an advanced API for recovery tools. It skips the key
+/// derivation from the password and the coder properties, so data can still be decrypted when
+/// the properties are lost or the key was recovered otherwise. The decrypted data is padded to
+/// the AES block size, so pass the `unpacked_size` to drop the padding when it is known.
+///
+/// # Arguments
+/// * `input` - The reader providing the encrypted stream
+/// * `aes_key` - The AES-256 key
+/// * `iv` - The initialization vector
+/// * `unpacked_size` - The size of the decrypted data, or `None` to read until the stream ends
+#[cfg(all(feature = "codec", feature = "aes256"))]
+pub fn decode_raw_aes256_stream<R: Read>(
+    input: R,
+    aes_key: &[u8; 32],
+    iv: &[u8; 16],
+    unpacked_size: Option<u64>,
+) -> CodecReader<R> {
+    let decoder = Decoder::Aes(Box::new(AesDecoder::with_key(input, aes_key, iv)));
+    CodecReader {
+        inner: decoder.take(unpacked_size.unwrap_or(u64::MAX)),
+    }
+}
+
+/// Writer that encodes data into a raw codec stream.
+///
+/// The stream must be completed with [`CodecWriter::finish`]. To keep access to the underlying
+/// writer, pass a mutable reference to it.
+#[cfg(all(
+    feature = "compress",
+    any(feature = "codec", all(feature = "util", target_arch = "wasm32"))
+))]
+pub struct CodecWriter<W: Write> {
+    inner: Encoder<W>,
+    properties: Vec<u8>,
+}
+
+#[cfg(all(
+    feature = "compress",
+    any(feature = "codec", all(feature = "util", target_arch = "wasm32"))
+))]
+impl<W: Write> CodecWriter<W> {
+    pub(crate) fn new(output: W, config: EncoderConfiguration) -> Result<Self, Error> {
+        let mut properties = [0u8; 256];
+        let properties = encoder::get_options_as_properties(
+            config.method,
+            config.options.as_ref(),
+            &mut properties,
+        )
+        .to_vec();
+        let inner = encoder::add_encoder(CountingWriter::new(output), &config)?;
+        Ok(Self { inner, properties })
+    }
+
+    /// Returns the coder properties that are needed to decode the stream.
+    pub fn properties(&self) -> &[u8] {
+        &self.properties
+    }
+
+    /// Finishes the stream and flushes all remaining data into the underlying writer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.inner.finish()?.flush()
+    }
+}
+
+#[cfg(all(
+    feature = "compress",
+    any(feature = "codec", all(feature = "util", target_arch = "wasm32"))
+))]
+impl<W: Write> Write for CodecWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
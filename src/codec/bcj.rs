@@ -0,0 +1,27 @@
+//! The BCJ filters, which convert relative addresses of branch instructions into absolute
+//! addresses, so that repeated calls of the same function compress better.
+
+use crate::error::Error;
+
+/// Returns the coder properties that store `start_offset`. Like 7-Zip, no properties are stored
+/// for the default offset of 0.
+#[cfg(feature = "compress")]
+pub(crate) fn start_offset_to_properties(start_offset: u32, out: &mut [u8]) -> &[u8] {
+    if start_offset == 0 {
+        return &[];
+    }
+    out[..4].copy_from_slice(&start_offset.to_le_bytes());
+    &out[..4]
+}
+
+/// Reads the start offset from the coder properties. Missing properties mean an offset of 0.
+pub(crate) fn start_offset_from_properties(properties: &[u8]) -> Result<u32, Error> {
+    match properties {
+        [] => Ok(0),
+        [a, b, c, d] => Ok(u32::from_le_bytes([*a, *b, *c, *d])),
+        _ => Err(Error::corrupt_header(
+            "coder properties",
+            format!("BCJ properties have {} bytes", properties.len()),
+        )),
+    }
+}
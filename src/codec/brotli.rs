@@ -267,6 +267,25 @@ impl<W: Write> BrotliEncoder<W> {
         })
     }
 
+    /// Finishes the Brotli stream, or the last frame, and returns the underlying writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        match self.inner {
+            InnerWriter::Standard(compressor) => {
+                // Consuming the compressor writes the end of the stream.
+                let mut writer = compressor.into_inner();
+                writer.flush()?;
+                Ok(writer)
+            }
+            InnerWriter::Framed { .. } => {
+                self.flush()?;
+                match self.inner {
+                    InnerWriter::Framed { writer, .. } => Ok(writer),
+                    InnerWriter::Standard(_) => unreachable!(),
+                }
+            }
+        }
+    }
+
     #[cfg(feature = "compress")]
     fn write_frame(
         writer: &mut W,
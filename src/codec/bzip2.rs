@@ -0,0 +1,268 @@
+#[cfg(feature = "compress")]
+use std::io::Write;
+use std::io::{self, Chain, Cursor, Read};
+
+use bzip2::read::{BzDecoder, MultiBzDecoder};
+#[cfg(feature = "compress")]
+use bzip2::{Compression, write::BzEncoder};
+
+/// Size of the stream header: "BZh", the block size and the magic of the first block.
+const STREAM_HEADER_SIZE: usize = 10;
+/// Magic of a compressed block (BCD encoded pi).
+const BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+/// Magic of the end of stream marker (BCD encoded sqrt(pi)), which directly follows the header of
+/// an empty stream.
+const END_OF_STREAM_MAGIC: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+/// Streams larger than this are not buffered for parallel decoding, but decoded serially.
+const MAX_PARALLEL_STREAM_SIZE: usize = 32 * 1024 * 1024;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns whether a BZIP2 stream starts at the beginning of `data`.
+fn is_stream_start(data: &[u8]) -> bool {
+    data.len() >= STREAM_HEADER_SIZE
+        && data.starts_with(b"BZh")
+        && (b'1'..=b'9').contains(&data[3])
+        && (data[4..10] == BLOCK_MAGIC || data[4..10] == END_OF_STREAM_MAGIC)
+}
+
+/// Decoder for concatenated BZIP2 streams, as written by pbzip2, which decodes multiple streams
+/// in parallel.
+///
+/// Every stream is byte aligned, so the packed data is split at stream headers. Since a stream
+/// header could also appear by chance inside of compressed data, the decoder falls back to
+/// decoding serially if decoding a split stream fails.
+pub(crate) struct Bzip2DecoderMt<R: Read> {
+    input: Option<R>,
+    input_finished: bool,
+    threads: usize,
+    /// Packed data that was read, starting at a stream header.
+    buffer: Vec<u8>,
+    /// Position from which to search for the next stream header in `buffer`.
+    search_pos: usize,
+    output: Vec<u8>,
+    output_pos: usize,
+    serial: Option<MultiBzDecoder<Chain<Cursor<Vec<u8>>, R>>>,
+}
+
+impl<R: Read> Bzip2DecoderMt<R> {
+    pub(crate) fn new(input: R, threads: u32) -> Self {
+        Self {
+            input: Some(input),
+            input_finished: false,
+            threads: threads.max(1) as usize,
+            buffer: Vec::new(),
+            search_pos: 1,
+            output: Vec::new(),
+            output_pos: 0,
+            serial: None,
+        }
+    }
+
+    /// Reads packed data until `threads` complete streams are buffered or the input ends. Returns
+    /// the end offsets of the streams in the buffer, or `None` if the streams are too large to
+    /// be decoded in parallel.
+    fn collect_streams(&mut self) -> io::Result<Option<Vec<usize>>> {
+        let mut ends = Vec::with_capacity(self.threads);
+        loop {
+            while self.search_pos + STREAM_HEADER_SIZE <= self.buffer.len() {
+                let candidates =
+                    &self.buffer[self.search_pos..=self.buffer.len() - STREAM_HEADER_SIZE];
+                let Some(offset) = candidates.iter().position(|&b| b == b'B') else {
+                    self.search_pos += candidates.len();
+                    break;
+                };
+                self.search_pos += offset;
+                if is_stream_start(&self.buffer[self.search_pos..]) {
+                    ends.push(self.search_pos);
+                    if ends.len() == self.threads {
+                        self.search_pos += 1;
+                        return Ok(Some(ends));
+                    }
+                }
+                self.search_pos += 1;
+            }
+
+            let stream_start = ends.last().copied().unwrap_or_default();
+            if self.buffer.len() - stream_start > MAX_PARALLEL_STREAM_SIZE {
+                return Ok(None);
+            }
+
+            if self.input_finished {
+                if self.buffer.len() > stream_start {
+                    ends.push(self.buffer.len());
+                }
+                return Ok(Some(ends));
+            }
+            let Some(input) = self.input.as_mut() else {
+                return Err(io::Error::other("Input of BZIP2 decoder is missing"));
+            };
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_CHUNK_SIZE, 0);
+            let read = input.read(&mut self.buffer[len..]);
+            self.buffer
+                .truncate(len + read.as_ref().map_or(0, |&read| read));
+            if read? == 0 {
+                self.input_finished = true;
+            }
+        }
+    }
+
+    fn switch_to_serial(&mut self) -> io::Result<()> {
+        let Some(input) = self.input.take() else {
+            return Err(io::Error::other("Input of BZIP2 decoder is missing"));
+        };
+        let buffer = std::mem::take(&mut self.buffer);
+        self.serial = Some(MultiBzDecoder::new(Cursor::new(buffer).chain(input)));
+        Ok(())
+    }
+
+    /// Decodes the next batch of streams into `output`. Returns `false` at the end of the data.
+    fn decode_next(&mut self) -> io::Result<bool> {
+        let Some(ends) = self.collect_streams()? else {
+            self.switch_to_serial()?;
+            return Ok(true);
+        };
+        let Some(&last_end) = ends.last() else {
+            return Ok(false);
+        };
+
+        let buffer = &self.buffer;
+        let decoded: Vec<io::Result<Vec<u8>>> = std::thread::scope(|scope| {
+            let mut start = 0;
+            let handles: Vec<_> = ends
+                .iter()
+                .map(|&end| {
+                    let stream = &buffer[start..end];
+                    start = end;
+                    scope.spawn(move || {
+                        let mut decoder = BzDecoder::new(stream);
+                        let mut data = Vec::new();
+                        decoder.read_to_end(&mut data)?;
+                        if decoder.total_in() != stream.len() as u64 {
+                            return Err(io::Error::other("Trailing data after BZIP2 stream"));
+                        }
+                        Ok(data)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("BZIP2 decoder thread panicked")))
+                })
+                .collect()
+        });
+
+        if decoded.iter().any(|data| data.is_err()) {
+            self.switch_to_serial()?;
+            return Ok(true);
+        }
+
+        self.output.clear();
+        self.output_pos = 0;
+        for data in decoded.into_iter().flatten() {
+            self.output.extend_from_slice(&data);
+        }
+        self.buffer.drain(..last_end);
+        self.search_pos = self.search_pos.saturating_sub(last_end).max(1);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for Bzip2DecoderMt<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.output_pos < self.output.len() {
+                let len = buf.len().min(self.output.len() - self.output_pos);
+                buf[..len].copy_from_slice(&self.output[self.output_pos..self.output_pos + len]);
+                self.output_pos += len;
+                return Ok(len);
+            }
+            if let Some(serial) = self.serial.as_mut() {
+                return serial.read(buf);
+            }
+            if !self.decode_next()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Encoder that starts a new, independent BZIP2 stream every `stream_size` bytes of input, so
+/// that the streams can be decoded in parallel.
+#[cfg(feature = "compress")]
+pub(crate) struct Bzip2Encoder<W: Write> {
+    encoder: Option<BzEncoder<W>>,
+    /// The writer between two streams.
+    writer: Option<W>,
+    level: Compression,
+    stream_size: u64,
+    remaining: u64,
+    wrote_stream: bool,
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Bzip2Encoder<W> {
+    pub(crate) fn new(writer: W, level: u32, stream_size: u64) -> Self {
+        Self {
+            encoder: None,
+            writer: Some(writer),
+            level: Compression::new(level),
+            stream_size,
+            remaining: stream_size,
+            wrote_stream: false,
+        }
+    }
+
+    fn encoder(&mut self) -> io::Result<&mut BzEncoder<W>> {
+        if self.encoder.is_none() {
+            let Some(writer) = self.writer.take() else {
+                return Err(io::Error::other("BZIP2 encoder is already finished"));
+            };
+            self.encoder = Some(BzEncoder::new(writer, self.level));
+            self.wrote_stream = true;
+        }
+        Ok(self.encoder.as_mut().unwrap())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        if !self.wrote_stream {
+            // Write an empty stream, so that the packed data is a valid BZIP2 file.
+            self.encoder()?;
+        }
+        match (self.encoder.take(), self.writer.take()) {
+            (Some(encoder), _) => encoder.finish(),
+            (None, Some(writer)) => Ok(writer),
+            (None, None) => Err(io::Error::other("BZIP2 encoder is already finished")),
+        }
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Write for Bzip2Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.stream_size == 0 {
+            return self.encoder()?.write(buf);
+        }
+
+        let len = buf.len().min(self.remaining as usize);
+        let written = self.encoder()?.write(&buf[..len])?;
+        self.remaining -= written as u64;
+        if self.remaining == 0 {
+            let encoder = self.encoder.take().unwrap();
+            self.writer = Some(encoder.finish()?);
+            self.remaining = self.stream_size;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (self.encoder.as_mut(), self.writer.as_mut()) {
+            (Some(encoder), _) => encoder.flush(),
+            (None, Some(writer)) => writer.flush(),
+            (None, None) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! The delta filter, which stores the difference of every byte to the byte `distance` bytes
+//! before it.
+
+#[cfg(feature = "codec")]
+use std::io::Read;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use std::io::Write;
+
+#[cfg(feature = "codec")]
+use super::CodecReader;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use super::CodecWriter;
+#[cfg(feature = "codec")]
+use crate::archive::EncoderMethod;
+use crate::error::Error;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use crate::{archive::EncoderConfiguration, encoder_options::DeltaOptions};
+
+/// Largest distance that the delta filter supports.
+#[cfg(any(feature = "codec", feature = "compress"))]
+pub(crate) const MAX_DISTANCE: u32 = 256;
+
+/// Returns the coder properties that store `distance`, which must be between 1 and
+/// [`MAX_DISTANCE`].
+#[cfg(any(feature = "codec", feature = "compress"))]
+pub(crate) fn distance_to_properties(distance: u32) -> [u8; 1] {
+    debug_assert!((1..=MAX_DISTANCE).contains(&distance));
+    [(distance - 1) as u8]
+}
+
+/// Reads the distance from the coder properties. Missing properties mean a distance of 1.
+pub(crate) fn distance_from_properties(properties: &[u8]) -> Result<u32, Error> {
+    match properties {
+        [] => Ok(1),
+        [property] => Ok(u32::from(*property) + 1),
+        _ => Err(Error::corrupt_header(
+            "coder properties",
+            format!("Delta properties have {} bytes", properties.len()),
+        )),
+    }
+}
+
+/// Creates a reader that reverts the delta filter.
+///
+/// # Arguments
+/// * `input` - The filtered stream
+/// * `distance` - Delta distance (1-256)
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+///
+/// use sevenz_rust2::codec::delta;
+///
+/// let mut filtered = Vec::new();
+/// let mut writer = delta::writer(&mut filtered, 2).unwrap();
+/// writer.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+/// writer.finish().unwrap();
+///
+/// let mut reader = delta::reader(filtered.as_slice(), 2).unwrap();
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, [1, 2, 3, 4, 5, 6]);
+/// ```
+#[cfg(feature = "codec")]
+pub fn reader<R: Read>(input: R, distance: u32) -> Result<CodecReader<R>, Error> {
+    if !(1..=MAX_DISTANCE).contains(&distance) {
+        return Err(Error::other(format!(
+            "Delta distance must be between 1 and {MAX_DISTANCE}"
+        )));
+    }
+    let properties = distance_to_properties(distance);
+    CodecReader::new(input, EncoderMethod::DELTA_FILTER, &properties, None)
+}
+
+/// Creates a writer that applies the delta filter.
+///
+/// # Arguments
+/// * `output` - The writer to write the filtered stream to
+/// * `distance` - Delta distance (1-256, clamped to this range, 0 becomes 1)
+#[cfg(all(feature = "codec", feature = "compress"))]
+pub fn writer<W: Write>(output: W, distance: u32) -> Result<CodecWriter<W>, Error> {
+    CodecWriter::new(
+        output,
+        EncoderConfiguration::new(EncoderMethod::DELTA_FILTER)
+            .with_options(DeltaOptions::from_distance(distance).into()),
+    )
+}
+
+#[cfg(all(test, any(feature = "codec", feature = "compress")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_properties() {
+        for distance in [1, 2, 4, 16, 255, 256] {
+            let properties = distance_to_properties(distance);
+            assert_eq!(distance_from_properties(&properties).unwrap(), distance);
+        }
+        assert_eq!(distance_to_properties(256), [255]);
+        assert_eq!(distance_from_properties(&[]).unwrap(), 1);
+        assert!(distance_from_properties(&[0, 0]).is_err());
+    }
+}
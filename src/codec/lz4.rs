@@ -206,6 +206,7 @@ enum InnerWriter<W: Write> {
     Standard(FrameEncoder<W>),
     Framed {
         writer: W,
+        frame_info: FrameInfo,
         frame_size: usize,
         compressed_data: Vec<u8>,
         uncompressed_data: Vec<u8>,
@@ -215,13 +216,14 @@ enum InnerWriter<W: Write> {
 
 #[cfg(feature = "compress")]
 impl<W: Write> Lz4Encoder<W> {
-    pub(crate) fn new(writer: W, frame_size: usize) -> Result<Self, Error> {
+    pub(crate) fn new(writer: W, frame_size: usize, frame_info: FrameInfo) -> Result<Self, Error> {
         let inner = if frame_size == 0 {
-            let encoder = FrameEncoder::new(writer);
+            let encoder = FrameEncoder::with_frame_info(frame_info, writer);
             InnerWriter::Standard(encoder)
         } else {
             InnerWriter::Framed {
                 writer,
+                frame_info,
                 frame_size,
                 compressed_data: Vec::with_capacity(frame_size),
                 uncompressed_data: vec![0; frame_size],
@@ -234,6 +236,7 @@ impl<W: Write> Lz4Encoder<W> {
 
     fn write_frame(
         writer: &mut W,
+        frame_info: &FrameInfo,
         compressed_data: &mut Vec<u8>,
         uncompressed_data: &[u8],
     ) -> std::io::Result<()> {
@@ -243,7 +246,9 @@ impl<W: Write> Lz4Encoder<W> {
         compressed_data.clear();
 
         // zstdmt expects that the content size is set when using skippable frames with LZ4.
-        let frame_info = FrameInfo::default().content_size(Some(uncompressed_data.len() as u64));
+        let frame_info = frame_info
+            .clone()
+            .content_size(Some(uncompressed_data.len() as u64));
         let mut frame_encoder = FrameEncoder::with_frame_info(frame_info, compressed_data);
         frame_encoder.write_all(uncompressed_data)?;
         let compressed_data = frame_encoder.finish()?;
@@ -265,6 +270,7 @@ impl<W: Write> Lz4Encoder<W> {
             InnerWriter::Standard(encoder) => Ok(encoder.finish()?),
             InnerWriter::Framed {
                 mut writer,
+                frame_info,
                 mut compressed_data,
                 uncompressed_data,
                 uncompressed_data_size,
@@ -272,6 +278,7 @@ impl<W: Write> Lz4Encoder<W> {
             } => {
                 Self::write_frame(
                     &mut writer,
+                    &frame_info,
                     &mut compressed_data,
                     &uncompressed_data[..uncompressed_data_size],
                 )?;
@@ -288,6 +295,7 @@ impl<W: Write> Write for Lz4Encoder<W> {
             InnerWriter::Standard(encoder) => encoder.write(buf),
             InnerWriter::Framed {
                 writer,
+                frame_info,
                 frame_size,
                 compressed_data,
                 uncompressed_data,
@@ -311,6 +319,7 @@ impl<W: Write> Write for Lz4Encoder<W> {
                     if *uncompressed_data_size >= *frame_size {
                         Self::write_frame(
                             writer,
+                            frame_info,
                             compressed_data,
                             &uncompressed_data[..*uncompressed_data_size],
                         )?;
@@ -328,6 +337,7 @@ impl<W: Write> Write for Lz4Encoder<W> {
             InnerWriter::Standard(encoder) => encoder.flush(),
             InnerWriter::Framed {
                 writer,
+                frame_info,
                 compressed_data,
                 uncompressed_data,
                 uncompressed_data_size,
@@ -335,6 +345,7 @@ impl<W: Write> Write for Lz4Encoder<W> {
             } => {
                 Self::write_frame(
                     writer,
+                    frame_info,
                     compressed_data,
                     &uncompressed_data[..*uncompressed_data_size],
                 )?;
@@ -0,0 +1,64 @@
+//! Raw LZMA streams without a header, as stored in 7z archives.
+
+use std::io::Read;
+#[cfg(feature = "compress")]
+use std::io::Write;
+
+use super::CodecReader;
+#[cfg(feature = "compress")]
+use super::CodecWriter;
+#[cfg(feature = "compress")]
+use crate::{archive::EncoderConfiguration, encoder_options::LzmaOptions};
+use crate::{archive::EncoderMethod, error::Error};
+
+/// Creates a reader that decodes a raw LZMA stream.
+///
+/// # Arguments
+/// * `input` - The compressed stream
+/// * `unpacked_size` - The size of the decompressed data
+/// * `properties` - The 5 bytes of coder properties: the `lc`/`lp`/`pb` byte followed by the
+///   dictionary size as little-endian `u32`
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+///
+/// use sevenz_rust2::{codec::lzma, encoder_options::LzmaOptions};
+///
+/// let mut compressed = Vec::new();
+/// let mut writer = lzma::writer(&mut compressed, LzmaOptions::from_level(6)).unwrap();
+/// writer.write_all(b"hello world").unwrap();
+/// let properties = writer.properties().to_vec();
+/// writer.finish().unwrap();
+///
+/// let mut reader = lzma::reader(compressed.as_slice(), 11, &properties).unwrap();
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"hello world");
+/// ```
+pub fn reader<R: Read>(
+    input: R,
+    unpacked_size: u64,
+    properties: &[u8],
+) -> Result<CodecReader<R>, Error> {
+    if properties.len() < 5 {
+        return Err(Error::other("LZMA properties too short"));
+    }
+    CodecReader::new(input, EncoderMethod::LZMA, properties, Some(unpacked_size))
+}
+
+/// Creates a writer that encodes a raw LZMA stream without end marker.
+///
+/// The size of the uncompressed data needs to be stored next to the stream, since it is needed to
+/// decode the stream again.
+///
+/// # Arguments
+/// * `output` - The writer to write the compressed stream to
+/// * `options` - The LZMA encoder options
+#[cfg(feature = "compress")]
+pub fn writer<W: Write>(output: W, options: LzmaOptions) -> Result<CodecWriter<W>, Error> {
+    CodecWriter::new(
+        output,
+        EncoderConfiguration::new(EncoderMethod::LZMA).with_options(options.into()),
+    )
+}
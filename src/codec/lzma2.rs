@@ -0,0 +1,174 @@
+//! Raw LZMA2 streams, as stored in 7z archives.
+
+#[cfg(feature = "codec")]
+use std::io::Read;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use std::io::Write;
+
+#[cfg(feature = "codec")]
+use super::CodecReader;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use super::CodecWriter;
+#[cfg(feature = "codec")]
+use crate::archive::EncoderMethod;
+use crate::error::Error;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use crate::{archive::EncoderConfiguration, encoder_options::Lzma2Options};
+
+/// Largest value of the property byte, which stands for a dictionary of `u32::MAX` bytes.
+const MAX_PROPERTY: u8 = 40;
+
+/// Returns the dictionary size that the property byte `property` stands for.
+fn property_to_dict_size(property: u8) -> u32 {
+    if property >= MAX_PROPERTY {
+        return u32::MAX;
+    }
+    let property = u32::from(property);
+    (2 | (property & 1)) << (property / 2 + 11)
+}
+
+/// Returns the coder properties for `dict_size`.
+///
+/// The property byte can only store dictionary sizes of the form `2^n` or `3 * 2^n`, so the
+/// dictionary size is rounded up to the next such value, like 7-Zip and xz-utils do.
+#[cfg(feature = "compress")]
+pub(crate) fn dict_size_to_properties(dict_size: u32) -> [u8; 1] {
+    let property = (0..MAX_PROPERTY)
+        .find(|&property| property_to_dict_size(property) >= dict_size)
+        .unwrap_or(MAX_PROPERTY);
+    [property]
+}
+
+/// Returns the smallest dictionary size that is at least `dict_size` and can be stored in the
+/// coder properties.
+#[cfg(feature = "compress")]
+pub(crate) fn round_dict_size(dict_size: u32) -> u32 {
+    property_to_dict_size(dict_size_to_properties(dict_size)[0])
+}
+
+/// Reads the dictionary size from the coder properties.
+pub(crate) fn dict_size_from_properties(properties: &[u8]) -> Result<u32, Error> {
+    let Some(&property) = properties.first() else {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "LZMA2 properties too short",
+        ));
+    };
+    if property & !0x3F != 0 {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "Unsupported LZMA2 property bits",
+        ));
+    }
+    if property > MAX_PROPERTY {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "Dictionary larger than 4GiB maximum size",
+        ));
+    }
+    Ok(property_to_dict_size(property))
+}
+
+/// Creates a reader that decodes a raw LZMA2 stream.
+///
+/// # Arguments
+/// * `input` - The compressed stream
+/// * `unpacked_size` - The size of the decompressed data
+/// * `properties` - The coder property byte that encodes the dictionary size
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+///
+/// use sevenz_rust2::{codec::lzma2, encoder_options::Lzma2Options};
+///
+/// let mut compressed = Vec::new();
+/// let mut writer = lzma2::writer(&mut compressed, Lzma2Options::from_level(6)).unwrap();
+/// writer.write_all(b"hello world").unwrap();
+/// let properties = writer.properties().to_vec();
+/// writer.finish().unwrap();
+///
+/// let mut reader = lzma2::reader(compressed.as_slice(), 11, &properties).unwrap();
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"hello world");
+/// ```
+#[cfg(feature = "codec")]
+pub fn reader<R: Read>(
+    input: R,
+    unpacked_size: u64,
+    properties: &[u8],
+) -> Result<CodecReader<R>, Error> {
+    CodecReader::new(input, EncoderMethod::LZMA2, properties, Some(unpacked_size))
+}
+
+/// Creates a writer that encodes a raw LZMA2 stream.
+///
+/// # Arguments
+/// * `output` - The writer to write the compressed stream to
+/// * `options` - The LZMA2 encoder options
+#[cfg(all(feature = "codec", feature = "compress"))]
+pub fn writer<W: Write>(output: W, options: Lzma2Options) -> Result<CodecWriter<W>, Error> {
+    CodecWriter::new(
+        output,
+        EncoderConfiguration::new(EncoderMethod::LZMA2).with_options(options.into()),
+    )
+}
+
+#[cfg(all(test, feature = "compress"))]
+mod tests {
+    use super::*;
+
+    /// Property encoding of `lzma_lzma2_props_encode` in xz-utils, which 7-Zip matches.
+    fn xz_properties(dict_size: u32) -> u8 {
+        let mut d = dict_size.max(4096) - 1;
+        d |= d >> 2;
+        d |= d >> 3;
+        d |= d >> 4;
+        d |= d >> 8;
+        d |= d >> 16;
+        if d == u32::MAX {
+            40
+        } else {
+            // Equals `get_dist_slot(d + 1) - 24` of xz-utils.
+            let n = d + 1;
+            let bits = 31 - n.leading_zeros();
+            (bits * 2 + ((n >> (bits - 1)) & 1)) as u8 - 24
+        }
+    }
+
+    #[test]
+    fn test_dict_size_properties() {
+        let mut sizes = vec![
+            lzma_rust2::DICT_SIZE_MIN,
+            lzma_rust2::DICT_SIZE_MAX,
+            u32::MAX,
+        ];
+        for shift in 12..32 {
+            for base in [1u32 << shift, 3u32.saturating_mul(1 << (shift - 1))] {
+                sizes.extend([base - 1, base, base.saturating_add(1)]);
+            }
+        }
+        sizes.extend((4096..=lzma_rust2::DICT_SIZE_MAX).step_by(999_983));
+
+        for dict_size in sizes {
+            let dict_size = dict_size.clamp(lzma_rust2::DICT_SIZE_MIN, u32::MAX);
+            let properties = dict_size_to_properties(dict_size);
+            assert_eq!(properties[0], xz_properties(dict_size), "{dict_size}");
+
+            let rounded = dict_size_from_properties(&properties).unwrap();
+            assert!(rounded >= dict_size, "{dict_size}");
+            assert_eq!(rounded, round_dict_size(dict_size));
+            assert_eq!(dict_size_to_properties(rounded), properties);
+        }
+
+        assert_eq!(dict_size_to_properties(4096), [0]);
+        assert_eq!(dict_size_to_properties(5_000_000), [21]);
+        assert_eq!(round_dict_size(5_000_000), 6 << 20);
+        assert_eq!(dict_size_to_properties(lzma_rust2::DICT_SIZE_MAX), [40]);
+        assert_eq!(round_dict_size(lzma_rust2::DICT_SIZE_MAX), u32::MAX);
+        assert!(dict_size_from_properties(&[]).is_err());
+        assert!(dict_size_from_properties(&[41]).is_err());
+        assert!(dict_size_from_properties(&[0x40]).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Raw PPMd variant H streams with the range coder of 7-Zip, as stored in 7z archives.
+
+#[cfg(feature = "codec")]
+use std::io::Read;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use std::io::Write;
+
+#[cfg(feature = "codec")]
+use super::CodecReader;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use super::CodecWriter;
+#[cfg(all(feature = "codec", feature = "compress"))]
+use crate::{archive::EncoderConfiguration, encoder_options::PpmdOptions};
+use ppmd_rust::{PPMD7_MAX_MEM_SIZE, PPMD7_MAX_ORDER, PPMD7_MIN_MEM_SIZE, PPMD7_MIN_ORDER};
+
+#[cfg(feature = "codec")]
+use crate::archive::EncoderMethod;
+use crate::error::Error;
+
+/// Reads the model order and memory size from the coder properties.
+pub(crate) fn order_memory_size_from_properties(properties: &[u8]) -> Result<(u32, u32), Error> {
+    if properties.len() < 5 {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "PPMD properties too short",
+        ));
+    }
+    let order = properties[0] as u32;
+    let memory_size =
+        u32::from_le_bytes([properties[1], properties[2], properties[3], properties[4]]);
+
+    if order < PPMD7_MIN_ORDER {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "PPMD order smaller than PPMD7_MIN_ORDER",
+        ));
+    }
+
+    if order > PPMD7_MAX_ORDER {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "PPMD order larger than PPMD7_MAX_ORDER",
+        ));
+    }
+
+    if memory_size < PPMD7_MIN_MEM_SIZE {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "PPMD memory size smaller than PPMD7_MIN_MEM_SIZE",
+        ));
+    }
+
+    if memory_size > PPMD7_MAX_MEM_SIZE {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "PPMD memory size larger than PPMD7_MAX_MEM_SIZE",
+        ));
+    }
+
+    Ok((order, memory_size))
+}
+
+/// Creates a reader that decodes a raw PPMD stream.
+///
+/// # Arguments
+/// * `input` - The compressed stream
+/// * `unpacked_size` - The size of the decompressed data
+/// * `properties` - The 5 bytes of coder properties: the model order followed by the memory
+///   size as little-endian `u32`
+///
+/// # Examples
+/// ```
+/// use std::io::{Read, Write};
+///
+/// use sevenz_rust2::codec::ppmd;
+///
+/// let mut compressed = Vec::new();
+/// let mut writer = ppmd::writer(&mut compressed, 6, 16 << 20).unwrap();
+/// writer.write_all(b"hello world").unwrap();
+/// let properties = writer.properties().to_vec();
+/// writer.finish().unwrap();
+///
+/// let mut reader = ppmd::reader(compressed.as_slice(), 11, &properties).unwrap();
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"hello world");
+/// ```
+#[cfg(feature = "codec")]
+pub fn reader<R: Read>(
+    input: R,
+    unpacked_size: u64,
+    properties: &[u8],
+) -> Result<CodecReader<R>, Error> {
+    CodecReader::new(input, EncoderMethod::PPMD, properties, Some(unpacked_size))
+}
+
+/// Creates a writer that encodes a raw PPMD stream.
+///
+/// # Arguments
+/// * `output` - The writer to write the compressed stream to
+/// * `order` - Model order (clamped to valid PPMD range)
+/// * `memory_size` - Memory size in bytes (clamped to valid PPMD range)
+#[cfg(all(feature = "codec", feature = "compress"))]
+pub fn writer<W: Write>(output: W, order: u32, memory_size: u32) -> Result<CodecWriter<W>, Error> {
+    let options = PpmdOptions::from_order_memory_size(order, memory_size);
+    CodecWriter::new(
+        output,
+        EncoderConfiguration::new(EncoderMethod::PPMD).with_options(options.into()),
+    )
+}
@@ -0,0 +1,56 @@
+use std::io::Read;
+#[cfg(feature = "compress")]
+use std::io::Write;
+
+use snap::read::FrameDecoder;
+#[cfg(feature = "compress")]
+use snap::write::FrameEncoder;
+
+/// Decoder for Snappy data in the framing format, which consists of checksummed chunks.
+pub(crate) struct SnappyDecoder<R: Read> {
+    inner: FrameDecoder<R>,
+}
+
+impl<R: Read> SnappyDecoder<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            inner: FrameDecoder::new(input),
+        }
+    }
+}
+
+impl<R: Read> Read for SnappyDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Encoder for Snappy data in the framing format.
+#[cfg(feature = "compress")]
+pub(crate) struct SnappyEncoder<W: Write> {
+    inner: FrameEncoder<W>,
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> SnappyEncoder<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            inner: FrameEncoder::new(writer),
+        }
+    }
+
+    pub(crate) fn finish(self) -> std::io::Result<W> {
+        self.inner.into_inner().map_err(|err| err.into_error())
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Write for SnappyEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
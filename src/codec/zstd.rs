@@ -0,0 +1,469 @@
+#[cfg(feature = "compress")]
+use std::io::Write;
+use std::io::{self, BufReader, Chain, Cursor, Read, Seek, SeekFrom};
+
+#[cfg(feature = "compress")]
+use crate::ByteWriter;
+use crate::{ByteReader, Error};
+
+/// Magic bytes of the skippable frame that contains the seek table.
+const SEEK_TABLE_FRAME_MAGIC: u32 = 0x184D2A5E;
+/// Magic bytes at the very end of the seek table footer.
+const SEEKABLE_MAGIC: u32 = 0x8F92EAB1;
+/// Size of the seek table footer: number of frames, descriptor and magic.
+const SEEK_TABLE_FOOTER_SIZE: u64 = 9;
+/// Size of the skippable frame header: magic and frame size.
+const SKIPPABLE_FRAME_HEADER_SIZE: u64 = 8;
+/// Set in the seek table descriptor if every entry carries a checksum.
+const CHECKSUM_FLAG: u8 = 0x80;
+
+/// Frames larger than this, compressed or decompressed, are not decoded in parallel but serially.
+const MAX_PARALLEL_FRAME_SIZE: usize = 32 * 1024 * 1024;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Seek table of a stream written in the ZSTD seekable format.
+///
+/// The format is a concatenation of independent ZSTD frames, followed by a skippable frame that
+/// stores the compressed and decompressed size of every frame.
+pub(crate) struct SeekTable {
+    /// Compressed and decompressed start offsets of every frame.
+    frames: Vec<(u64, u64)>,
+    /// Size of all frames, excluding the seek table itself.
+    compressed_size: u64,
+}
+
+impl SeekTable {
+    /// Reads the seek table of the stream that starts at `stream_offset` and is `stream_size` bytes
+    /// long. Returns `None` if the stream doesn't end with a seek table footer.
+    pub(crate) fn read_from<R: Read + Seek>(
+        reader: &mut R,
+        stream_offset: u64,
+        stream_size: u64,
+    ) -> Result<Option<Self>, Error> {
+        if stream_size < SKIPPABLE_FRAME_HEADER_SIZE + SEEK_TABLE_FOOTER_SIZE {
+            return Ok(None);
+        }
+
+        reader.seek(SeekFrom::Start(
+            stream_offset + stream_size - SEEK_TABLE_FOOTER_SIZE,
+        ))?;
+        let frame_count = reader.read_u32()? as u64;
+        let descriptor = reader.read_u8()?;
+        if reader.read_u32()? != SEEKABLE_MAGIC {
+            return Ok(None);
+        }
+        if descriptor & 0x7C != 0 {
+            return Err(Error::other("Invalid zstd seek table descriptor"));
+        }
+
+        let entry_size = if descriptor & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let table_size = frame_count
+            .checked_mul(entry_size)
+            .and_then(|size| size.checked_add(SEEK_TABLE_FOOTER_SIZE))
+            .filter(|&size| size + SKIPPABLE_FRAME_HEADER_SIZE <= stream_size)
+            .ok_or_else(|| Error::other("Invalid zstd seek table size"))?;
+
+        let compressed_size = stream_size - table_size - SKIPPABLE_FRAME_HEADER_SIZE;
+        reader.seek(SeekFrom::Start(stream_offset + compressed_size))?;
+        if reader.read_u32()? != SEEK_TABLE_FRAME_MAGIC || reader.read_u32()? as u64 != table_size {
+            return Err(Error::other("Invalid zstd seek table frame"));
+        }
+
+        let mut frames = Vec::with_capacity(frame_count as usize);
+        let mut compressed_offset = 0u64;
+        let mut decompressed_offset = 0u64;
+        for _ in 0..frame_count {
+            frames.push((compressed_offset, decompressed_offset));
+            compressed_offset += reader.read_u32()? as u64;
+            decompressed_offset += reader.read_u32()? as u64;
+            if entry_size == 12 {
+                let _checksum = reader.read_u32()?;
+            }
+        }
+
+        if compressed_offset != compressed_size {
+            return Err(Error::other(
+                "Zstd seek table doesn't match the stream size",
+            ));
+        }
+
+        Ok(Some(Self {
+            frames,
+            compressed_size,
+        }))
+    }
+
+    /// Returns the size of all frames, excluding the seek table.
+    pub(crate) fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+
+    /// Finds the frame that contains the given decompressed offset and returns its compressed and
+    /// decompressed start offsets.
+    pub(crate) fn locate(&self, decompressed_offset: u64) -> (u64, u64) {
+        let index = self
+            .frames
+            .partition_point(|&(_, start)| start <= decompressed_offset);
+        match index {
+            0 => (0, 0),
+            index => self.frames[index - 1],
+        }
+    }
+}
+
+/// Encoder that writes the ZSTD seekable format: every `frame_size` bytes of input are compressed
+/// into an independent frame and a seek table is appended when finishing.
+#[cfg(feature = "compress")]
+pub(crate) struct ZstdSeekableEncoder<W: Write> {
+    frames: ZstdFrameEncoder<W>,
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> ZstdSeekableEncoder<W> {
+    pub(crate) fn new(writer: W, level: i32, frame_size: usize) -> Self {
+        Self {
+            frames: ZstdFrameEncoder::new(writer, level, frame_size),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.frames.write_frame()?;
+        let ZstdFrameEncoder {
+            mut writer, frames, ..
+        } = self.frames;
+
+        let table_size = frames.len() * 8 + SEEK_TABLE_FOOTER_SIZE as usize;
+        let table_size = u32::try_from(table_size)
+            .map_err(|_| io::Error::other("Too many frames for a zstd seek table"))?;
+
+        writer.write_u32(SEEK_TABLE_FRAME_MAGIC)?;
+        writer.write_u32(table_size)?;
+        for &(compressed_size, decompressed_size) in frames.iter() {
+            writer.write_u32(compressed_size)?;
+            writer.write_u32(decompressed_size)?;
+        }
+        writer.write_u32(frames.len() as u32)?;
+        writer.write_u8(0)?;
+        writer.write_u32(SEEKABLE_MAGIC)?;
+
+        Ok(writer)
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Write for ZstdSeekableEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.frames.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.frames.flush()
+    }
+}
+
+/// Encoder that compresses every `frame_size` bytes of input into an independent ZSTD frame, so
+/// that the frames can be decoded in parallel.
+///
+/// The input of a frame is buffered, so that its size is stored in the frame header. This lets
+/// the decoder know how much memory a frame needs before decoding it.
+#[cfg(feature = "compress")]
+pub(crate) struct ZstdFrameEncoder<W: Write> {
+    writer: W,
+    level: i32,
+    frame_size: usize,
+    uncompressed_data: Vec<u8>,
+    compressed_data: Vec<u8>,
+    /// Compressed and decompressed size of every written frame.
+    frames: Vec<(u32, u32)>,
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> ZstdFrameEncoder<W> {
+    pub(crate) fn new(writer: W, level: i32, frame_size: usize) -> Self {
+        Self {
+            writer,
+            level,
+            frame_size,
+            uncompressed_data: Vec::new(),
+            compressed_data: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if self.uncompressed_data.is_empty() {
+            return Ok(());
+        }
+
+        let mut encoder =
+            zstd::Encoder::new(std::mem::take(&mut self.compressed_data), self.level)?;
+        encoder.set_pledged_src_size(Some(self.uncompressed_data.len() as u64))?;
+        encoder.include_contentsize(true)?;
+        encoder.write_all(&self.uncompressed_data)?;
+        let mut compressed_data = encoder.finish()?;
+
+        self.writer.write_all(&compressed_data)?;
+        self.frames.push((
+            compressed_data.len() as u32,
+            self.uncompressed_data.len() as u32,
+        ));
+
+        compressed_data.clear();
+        self.compressed_data = compressed_data;
+        self.uncompressed_data.clear();
+
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.write_frame()?;
+        if self.frames.is_empty() {
+            // Write an empty frame, so that the packed data is a valid ZSTD stream.
+            let mut encoder = zstd::Encoder::new(self.writer, self.level)?;
+            encoder.set_pledged_src_size(Some(0))?;
+            encoder.include_contentsize(true)?;
+            return encoder.finish();
+        }
+        Ok(self.writer)
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: Write> Write for ZstdFrameEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes_consumed = 0;
+
+        while bytes_consumed < buf.len() {
+            let available_space = self.frame_size - self.uncompressed_data.len();
+            let bytes_to_copy = usize::min(buf.len() - bytes_consumed, available_space);
+
+            self.uncompressed_data
+                .extend_from_slice(&buf[bytes_consumed..bytes_consumed + bytes_to_copy]);
+            bytes_consumed += bytes_to_copy;
+
+            if self.uncompressed_data.len() >= self.frame_size {
+                self.write_frame()?;
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_frame()?;
+        self.writer.flush()
+    }
+}
+
+type SerialDecoder<R> = zstd::Decoder<'static, BufReader<Chain<Cursor<Vec<u8>>, R>>>;
+
+/// Decoder for concatenated ZSTD frames, which decodes multiple frames in parallel.
+///
+/// The size of a frame is known from its block headers without decoding it, so the packed data
+/// is split at frame boundaries. Frames are only decoded in parallel if their header stores
+/// their decompressed size, so that the memory for the output is known and bounded by the
+/// remaining unpack size. Skippable frames, like the seek table of the seekable format, decode
+/// to nothing. If a frame is too large, has no content size or can't be parsed, or if there is
+/// only a single frame left, the decoder falls back to decoding serially.
+pub(crate) struct ZstdDecoderMt<R: Read> {
+    input: Option<R>,
+    input_finished: bool,
+    threads: usize,
+    /// Number of bytes the remaining frames may decode to.
+    remaining_size: u64,
+    /// Packed data that was read, starting at a frame.
+    buffer: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    serial: Option<SerialDecoder<R>>,
+}
+
+impl<R: Read> ZstdDecoderMt<R> {
+    pub(crate) fn new(input: R, threads: u32, unpack_size: u64) -> Self {
+        Self {
+            input: Some(input),
+            input_finished: false,
+            threads: threads.max(1) as usize,
+            remaining_size: unpack_size,
+            buffer: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            serial: None,
+        }
+    }
+
+    /// Reads packed data until `threads` complete frames are buffered or the input ends. Returns
+    /// the end offsets and decompressed sizes of the frames in the buffer, or `None` if the
+    /// frames are too large or invalid and need to be decoded serially.
+    fn collect_frames(&mut self) -> io::Result<Option<Vec<(usize, usize)>>> {
+        let mut frames: Vec<(usize, usize)> = Vec::with_capacity(self.threads);
+        let mut remaining_size = self.remaining_size;
+        loop {
+            let mut frame_start = frames.last().map_or(0, |&(end, _)| end);
+            while frames.len() < self.threads {
+                let frame = &self.buffer[frame_start..];
+                let size = match zstd::zstd_safe::find_frame_compressed_size(frame) {
+                    Ok(size) if size > 0 => size,
+                    _ => break,
+                };
+                let content_size = match zstd::zstd_safe::get_frame_content_size(frame) {
+                    Ok(Some(content_size))
+                        if content_size <= remaining_size
+                            && content_size <= MAX_PARALLEL_FRAME_SIZE as u64 =>
+                    {
+                        content_size
+                    }
+                    _ => return Ok(None),
+                };
+                remaining_size -= content_size;
+                frame_start += size;
+                frames.push((frame_start, content_size as usize));
+            }
+            if frames.len() == self.threads {
+                return Ok(Some(frames));
+            }
+
+            if self.buffer.len() - frame_start > MAX_PARALLEL_FRAME_SIZE {
+                return Ok(None);
+            }
+
+            if self.input_finished {
+                // Trailing data that isn't a complete frame is left to the serial decoder, which
+                // reports the error.
+                return Ok((self.buffer.len() == frame_start).then_some(frames));
+            }
+            let Some(input) = self.input.as_mut() else {
+                return Err(io::Error::other("Input of ZSTD decoder is missing"));
+            };
+            let len = self.buffer.len();
+            self.buffer.resize(len + READ_CHUNK_SIZE, 0);
+            let read = input.read(&mut self.buffer[len..]);
+            self.buffer
+                .truncate(len + read.as_ref().map_or(0, |&read| read));
+            if read? == 0 {
+                self.input_finished = true;
+            }
+        }
+    }
+
+    fn switch_to_serial(&mut self) -> io::Result<()> {
+        let Some(input) = self.input.take() else {
+            return Err(io::Error::other("Input of ZSTD decoder is missing"));
+        };
+        let buffer = std::mem::take(&mut self.buffer);
+        self.serial = Some(zstd::Decoder::new(Cursor::new(buffer).chain(input))?);
+        Ok(())
+    }
+
+    /// Decodes the next batch of frames into `output`. Returns `false` at the end of the data.
+    fn decode_next(&mut self) -> io::Result<bool> {
+        let Some(frames) = self.collect_frames()? else {
+            self.switch_to_serial()?;
+            return Ok(true);
+        };
+        let Some(&(last_end, _)) = frames.last() else {
+            return Ok(false);
+        };
+        if frames.len() == 1 {
+            // Nothing to decode in parallel.
+            self.switch_to_serial()?;
+            return Ok(true);
+        }
+
+        let buffer = &self.buffer;
+        let decoded: Vec<io::Result<Vec<u8>>> = std::thread::scope(|scope| {
+            let mut start = 0;
+            let handles: Vec<_> = frames
+                .iter()
+                .map(|&(end, content_size)| {
+                    let frame = &buffer[start..end];
+                    start = end;
+                    // Fails if the frame decodes to more than its header claims.
+                    scope.spawn(move || zstd::bulk::decompress(frame, content_size))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|_| Err(io::Error::other("ZSTD decoder thread panicked")))
+                })
+                .collect()
+        });
+
+        self.output.clear();
+        self.output_pos = 0;
+        for data in decoded {
+            let data = data?;
+            self.remaining_size -= data.len() as u64;
+            self.output.extend_from_slice(&data);
+        }
+        self.buffer.drain(..last_end);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for ZstdDecoderMt<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.output_pos < self.output.len() {
+                let len = buf.len().min(self.output.len() - self.output_pos);
+                buf[..len].copy_from_slice(&self.output[self.output_pos..self.output_pos + len]);
+                self.output_pos += len;
+                return Ok(len);
+            }
+            if let Some(serial) = self.serial.as_mut() {
+                return serial.read(buf);
+            }
+            if !self.decode_next()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(packed: &[u8], threads: u32, unpack_size: u64) -> (Vec<u8>, bool) {
+        let mut decoder = ZstdDecoderMt::new(packed, threads, unpack_size);
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        (data, decoder.serial.is_some())
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_frames_with_content_size_are_decoded_in_parallel() {
+        let content: Vec<u8> = (0..4 * 1000u32).map(|i| (i % 251) as u8).collect();
+        let mut encoder = ZstdFrameEncoder::new(Vec::new(), 3, 1000);
+        encoder.write_all(&content).unwrap();
+        let packed = encoder.finish().unwrap();
+
+        let (data, serial) = decode(&packed, 2, content.len() as u64);
+        assert_eq!(data, content);
+        assert!(!serial);
+
+        // Frames that exceed the unpack size are not decoded in parallel.
+        let (data, serial) = decode(&packed, 2, 1500);
+        assert_eq!(data, content);
+        assert!(serial);
+    }
+
+    #[test]
+    fn test_frames_without_content_size_are_decoded_serially() {
+        let content = vec![0u8; 4 * 1024 * 1024];
+        let mut packed = zstd::stream::encode_all(content.as_slice(), 3).unwrap();
+        packed.extend(zstd::stream::encode_all(content.as_slice(), 3).unwrap());
+
+        let (data, serial) = decode(&packed, 4, 2 * content.len() as u64);
+        assert_eq!(data, content.repeat(2));
+        assert!(serial);
+    }
+}
@@ -1,7 +1,7 @@
-use std::{io, io::Read};
+use std::io::Read;
 
 #[cfg(feature = "bzip2")]
-use bzip2::read::BzDecoder;
+use bzip2::read::MultiBzDecoder;
 #[cfg(feature = "deflate")]
 use flate2::bufread::DeflateDecoder;
 use lzma_rust2::{
@@ -10,17 +10,27 @@ use lzma_rust2::{
     lzma2_get_memory_usage,
 };
 #[cfg(feature = "ppmd")]
-use ppmd_rust::{
-    PPMD7_MAX_MEM_SIZE, PPMD7_MAX_ORDER, PPMD7_MIN_MEM_SIZE, PPMD7_MIN_ORDER, Ppmd7Decoder,
-};
+use ppmd_rust::Ppmd7Decoder;
 
 #[cfg(feature = "brotli")]
 use crate::codec::brotli::BrotliDecoder;
+#[cfg(feature = "bzip2")]
+use crate::codec::bzip2::Bzip2DecoderMt;
 #[cfg(feature = "lz4")]
 use crate::codec::lz4::Lz4Decoder;
+#[cfg(feature = "snappy")]
+use crate::codec::snappy::SnappyDecoder;
+#[cfg(feature = "zstd")]
+use crate::codec::zstd::ZstdDecoderMt;
 #[cfg(feature = "aes256")]
-use crate::encryption::Aes256Sha256Decoder;
-use crate::{ByteReader, Password, archive::EncoderMethod, block::Coder, error::Error};
+use crate::encryption::AesDecoder;
+use crate::{ByteReader, Password, archive::EncoderMethod, block::Coder, codec, error::Error};
+
+/// Default for the maximal AES key derivation power, 2^25 SHA-256 iterations.
+pub(crate) const DEFAULT_MAX_KEY_DERIVATION_POWER: u8 = 25;
+
+/// Upper bound of the maximal AES key derivation power, 2^31 SHA-256 iterations.
+pub(crate) const MAX_KEY_DERIVATION_POWER: u8 = 31;
 
 pub enum Decoder<R: Read> {
     Copy(R),
@@ -34,15 +44,21 @@ pub enum Decoder<R: Read> {
     #[cfg(feature = "brotli")]
     Brotli(Box<BrotliDecoder<R>>),
     #[cfg(feature = "bzip2")]
-    Bzip2(BzDecoder<R>),
+    Bzip2(MultiBzDecoder<R>),
+    #[cfg(feature = "bzip2")]
+    Bzip2Mt(Box<Bzip2DecoderMt<R>>),
     #[cfg(feature = "deflate")]
     Deflate(DeflateDecoder<std::io::BufReader<R>>),
     #[cfg(feature = "lz4")]
     Lz4(Lz4Decoder<R>),
+    #[cfg(feature = "snappy")]
+    Snappy(SnappyDecoder<R>),
     #[cfg(feature = "zstd")]
     Zstd(zstd::Decoder<'static, std::io::BufReader<R>>),
+    #[cfg(feature = "zstd")]
+    ZstdMt(Box<ZstdDecoderMt<R>>),
     #[cfg(feature = "aes256")]
-    Aes256Sha256(Box<Aes256Sha256Decoder<R>>),
+    Aes(Box<AesDecoder<R>>),
 }
 
 impl<R: Read> Read for Decoder<R> {
@@ -60,14 +76,20 @@ impl<R: Read> Read for Decoder<R> {
             Decoder::Brotli(r) => r.read(buf),
             #[cfg(feature = "bzip2")]
             Decoder::Bzip2(r) => r.read(buf),
+            #[cfg(feature = "bzip2")]
+            Decoder::Bzip2Mt(r) => r.read(buf),
             #[cfg(feature = "deflate")]
             Decoder::Deflate(r) => r.read(buf),
             #[cfg(feature = "lz4")]
             Decoder::Lz4(r) => r.read(buf),
+            #[cfg(feature = "snappy")]
+            Decoder::Snappy(r) => r.read(buf),
             #[cfg(feature = "zstd")]
             Decoder::Zstd(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            Decoder::ZstdMt(r) => r.read(buf),
             #[cfg(feature = "aes256")]
-            Decoder::Aes256Sha256(r) => r.read(buf),
+            Decoder::Aes(r) => r.read(buf),
         }
     }
 }
@@ -79,23 +101,20 @@ pub fn add_decoder<I: Read>(
     #[allow(unused)] password: &Password,
     max_mem_limit_kb: usize,
     threads: u32,
+    #[allow(unused)] max_key_derivation_power: u8,
 ) -> Result<Decoder<I>, Error> {
     let method = EncoderMethod::by_id(coder.encoder_method_id());
     let method = if let Some(m) = method {
         m
     } else {
-        return Err(Error::UnsupportedCompressionMethod(format!(
-            "{:?}",
-            coder.encoder_method_id()
-        )));
+        return Err(Error::UnsupportedCoder {
+            id: coder.encoder_method_id().to_vec(),
+        });
     };
     match method.id() {
         EncoderMethod::ID_COPY => Ok(Decoder::Copy(input)),
         EncoderMethod::ID_LZMA => {
             let dict_size = get_lzma_dic_size(coder)?;
-            if coder.properties.is_empty() {
-                return Err(Error::Other("LZMA properties too short".into()));
-            }
             let props = coder.properties[0];
             let lz =
                 LzmaReader::new_with_props(input, uncompressed_len as _, props, dict_size, None)
@@ -103,7 +122,7 @@ pub fn add_decoder<I: Read>(
             Ok(Decoder::Lzma(Box::new(lz)))
         }
         EncoderMethod::ID_LZMA2 => {
-            let dic_size = get_lzma2_dic_size(coder)?;
+            let dic_size = codec::lzma2::dict_size_from_properties(&coder.properties)?;
             let mem_size = lzma2_get_memory_usage(dic_size) as usize;
             if mem_size > max_mem_limit_kb {
                 return Err(Error::MaxMemLimited {
@@ -124,7 +143,7 @@ pub fn add_decoder<I: Read>(
         EncoderMethod::ID_PPMD => {
             let (order, memory_size) = get_ppmd_order_memory_size(coder, max_mem_limit_kb)?;
             let ppmd = Ppmd7Decoder::new(input, order, memory_size)
-                .map_err(|err| Error::other(err.to_string()))?;
+                .map_err(|err| Error::corrupt_header("coder properties", err.to_string()))?;
             Ok(Decoder::Ppmd(Box::new(ppmd)))
         }
         #[cfg(feature = "brotli")]
@@ -134,8 +153,13 @@ pub fn add_decoder<I: Read>(
         }
         #[cfg(feature = "bzip2")]
         EncoderMethod::ID_BZIP2 => {
-            let de = BzDecoder::new(input);
-            Ok(Decoder::Bzip2(de))
+            // The streams of multi-stream data, like written by pbzip2, are decoded in parallel.
+            let de = if threads < 2 {
+                Decoder::Bzip2(MultiBzDecoder::new(input))
+            } else {
+                Decoder::Bzip2Mt(Box::new(Bzip2DecoderMt::new(input, threads)))
+            };
+            Ok(de)
         }
         #[cfg(feature = "deflate")]
         EncoderMethod::ID_DEFLATE => {
@@ -148,50 +172,60 @@ pub fn add_decoder<I: Read>(
             let de = Lz4Decoder::new(input)?;
             Ok(Decoder::Lz4(de))
         }
+        #[cfg(feature = "snappy")]
+        EncoderMethod::ID_SNAPPY => {
+            let de = SnappyDecoder::new(input);
+            Ok(Decoder::Snappy(de))
+        }
         #[cfg(feature = "zstd")]
         EncoderMethod::ID_ZSTD => {
-            let zs = zstd::Decoder::new(input)?;
-            Ok(Decoder::Zstd(zs))
+            // Data split into multiple frames with a known size is decoded in parallel.
+            let zs = if threads < 2 {
+                Decoder::Zstd(zstd::Decoder::new(input)?)
+            } else {
+                Decoder::ZstdMt(Box::new(ZstdDecoderMt::new(
+                    input,
+                    threads,
+                    uncompressed_len as u64,
+                )))
+            };
+            Ok(zs)
         }
         EncoderMethod::ID_BCJ_X86 => {
-            let de = BcjReader::new_x86(input, 0);
+            let de = BcjReader::new_x86(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_ARM => {
-            let de = BcjReader::new_arm(input, 0);
+            let de = BcjReader::new_arm(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_ARM64 => {
-            let de = BcjReader::new_arm64(input, 0);
+            let de = BcjReader::new_arm64(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_ARM_THUMB => {
-            let de = BcjReader::new_arm_thumb(input, 0);
+            let de = BcjReader::new_arm_thumb(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_PPC => {
-            let de = BcjReader::new_ppc(input, 0);
+            let de = BcjReader::new_ppc(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_IA64 => {
-            let de = BcjReader::new_ia64(input, 0);
+            let de = BcjReader::new_ia64(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_SPARC => {
-            let de = BcjReader::new_sparc(input, 0);
+            let de = BcjReader::new_sparc(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_BCJ_RISCV => {
-            let de = BcjReader::new_riscv(input, 0);
+            let de = BcjReader::new_riscv(input, get_bcj_start_offset(coder)?);
             Ok(Decoder::Bcj(de))
         }
         EncoderMethod::ID_DELTA => {
-            let d = if coder.properties.is_empty() {
-                1
-            } else {
-                coder.properties[0].wrapping_add(1)
-            };
-            let de = DeltaReader::new(input, d as usize);
+            let distance = codec::delta::distance_from_properties(&coder.properties)?;
+            let de = DeltaReader::new(input, distance as usize);
             Ok(Decoder::Delta(de))
         }
         #[cfg(feature = "aes256")]
@@ -199,9 +233,23 @@ pub fn add_decoder<I: Read>(
             if password.is_empty() {
                 return Err(Error::PasswordRequired);
             }
-            let de = Aes256Sha256Decoder::new(input, &coder.properties, password)?;
-            Ok(Decoder::Aes256Sha256(Box::new(de)))
+            let de = AesDecoder::new(input, &coder.properties, password, max_key_derivation_power)?;
+            Ok(Decoder::Aes(Box::new(de)))
         }
+        #[cfg(feature = "aes256")]
+        EncoderMethod::ID_RAR29_AES => {
+            if password.is_empty() {
+                return Err(Error::PasswordRequired);
+            }
+            let de = AesDecoder::new_rar29(input, &coder.properties, password)?;
+            Ok(Decoder::Aes(Box::new(de)))
+        }
+        // LZS and Lizard are known by ID, so that they are reported by name, but there are no
+        // decoders for them yet. Plain AES-256-CBC takes the raw key, which is neither stored in
+        // the archive nor derived from the password.
+        EncoderMethod::ID_LZS | EncoderMethod::ID_LIZARD | EncoderMethod::ID_AES256_CBC => Err(
+            Error::UnsupportedCompressionMethod(method.name().to_string()),
+        ),
         _ => Err(Error::UnsupportedCompressionMethod(
             method.name().to_string(),
         )),
@@ -210,36 +258,7 @@ pub fn add_decoder<I: Read>(
 
 #[cfg(feature = "ppmd")]
 fn get_ppmd_order_memory_size(coder: &Coder, max_mem_limit_kb: usize) -> Result<(u32, u32), Error> {
-    if coder.properties.len() < 5 {
-        return Err(Error::other("PPMD properties too short"));
-    }
-    let order = coder.properties[0] as u32;
-    let memory_size = u32::from_le_bytes([
-        coder.properties[1],
-        coder.properties[2],
-        coder.properties[3],
-        coder.properties[4],
-    ]);
-
-    if order < PPMD7_MIN_ORDER {
-        return Err(Error::other("PPMD order smaller than PPMD7_MIN_ORDER"));
-    }
-
-    if order > PPMD7_MAX_ORDER {
-        return Err(Error::other("PPMD order larger than PPMD7_MAX_ORDER"));
-    }
-
-    if memory_size < PPMD7_MIN_MEM_SIZE {
-        return Err(Error::other(
-            "PPMD memory size smaller than PPMD7_MIN_MEM_SIZE",
-        ));
-    }
-
-    if memory_size > PPMD7_MAX_MEM_SIZE {
-        return Err(Error::other(
-            "PPMD memory size larger than PPMD7_MAX_MEM_SIZE",
-        ));
-    }
+    let (order, memory_size) = codec::ppmd::order_memory_size_from_properties(&coder.properties)?;
 
     if memory_size as usize > max_mem_limit_kb {
         return Err(Error::MaxMemLimited {
@@ -251,25 +270,17 @@ fn get_ppmd_order_memory_size(coder: &Coder, max_mem_limit_kb: usize) -> Result<
     Ok((order, memory_size))
 }
 
-fn get_lzma2_dic_size(coder: &Coder) -> Result<u32, Error> {
-    if coder.properties.is_empty() {
-        return Err(Error::other("LZMA2 properties too short"));
-    }
-    let dict_size_bits = 0xFF & coder.properties[0] as u32;
-    if (dict_size_bits & (!0x3F)) != 0 {
-        return Err(Error::other("Unsupported LZMA2 property bits"));
-    }
-    if dict_size_bits > 40 {
-        return Err(Error::other("Dictionary larger than 4GiB maximum size"));
-    }
-    if dict_size_bits == 40 {
-        return Ok(0xFFFFFFFF);
-    }
-    let size = (2 | (dict_size_bits & 0x1)) << (dict_size_bits / 2 + 11);
-    Ok(size)
+fn get_bcj_start_offset(coder: &Coder) -> Result<usize, Error> {
+    Ok(codec::bcj::start_offset_from_properties(&coder.properties)? as usize)
 }
 
-fn get_lzma_dic_size(coder: &Coder) -> io::Result<u32> {
+fn get_lzma_dic_size(coder: &Coder) -> Result<u32, Error> {
+    if coder.properties.len() < 5 {
+        return Err(Error::corrupt_header(
+            "coder properties",
+            "LZMA properties too short",
+        ));
+    }
     let mut props = &coder.properties[1..5];
-    props.read_u32()
+    Ok(props.read_u32()?)
 }
@@ -7,8 +7,14 @@ use lzma_rust2::{
 
 #[cfg(feature = "brotli")]
 use crate::codec::brotli::BrotliEncoder;
+#[cfg(feature = "bzip2")]
+use crate::codec::bzip2::Bzip2Encoder;
 #[cfg(feature = "lz4")]
 use crate::codec::lz4::Lz4Encoder;
+#[cfg(feature = "snappy")]
+use crate::codec::snappy::SnappyEncoder;
+#[cfg(feature = "zstd")]
+use crate::codec::zstd::{ZstdFrameEncoder, ZstdSeekableEncoder};
 #[cfg(feature = "brotli")]
 use crate::encoder_options::BrotliOptions;
 #[cfg(feature = "bzip2")]
@@ -26,133 +32,124 @@ use crate::encryption::Aes256Sha256Encoder;
 use crate::{
     Error,
     archive::{EncoderConfiguration, EncoderMethod},
+    codec,
     encoder_options::{DeltaOptions, EncoderOptions, Lzma2Options, LzmaOptions},
     writer::CountingWriter,
 };
 
+/// A writer at the end of which the encoded stream must be finished explicitly.
+///
+/// Encoders are chained behind trait objects, so finishing can't consume the concrete encoder
+/// types. Finishing an encoder also finishes the writers it wraps, down to the output.
+pub(crate) trait FinishableWrite: Write {
+    /// Writes the end of the stream and finishes the wrapped writers.
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+/// A borrowed output ends the chain of encoders. It's only flushed, since its owner continues
+/// to use it.
+impl<W: Write + ?Sized> FinishableWrite for &mut W {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        (*self).flush()
+    }
+}
+
 pub(crate) enum Encoder<W: Write> {
     Copy(CountingWriter<W>),
-    Bcj(Option<BcjWriter<CountingWriter<W>>>),
+    Bcj(BcjWriter<CountingWriter<W>>),
     Delta(DeltaWriter<CountingWriter<W>>),
-    Lzma(Option<LzmaWriter<CountingWriter<W>>>),
-    Lzma2(Option<Lzma2Writer<CountingWriter<W>>>),
-    Lzma2Mt(Option<Lzma2WriterMt<CountingWriter<W>>>),
+    Lzma(LzmaWriter<CountingWriter<W>>),
+    Lzma2(Lzma2Writer<CountingWriter<W>>, ChunkBoundary),
+    Lzma2Mt(Lzma2WriterMt<CountingWriter<W>>),
     #[cfg(feature = "ppmd")]
-    Ppmd(Option<Box<ppmd_rust::Ppmd7Encoder<CountingWriter<W>>>>),
+    Ppmd(Box<ppmd_rust::Ppmd7Encoder<CountingWriter<W>>>),
     #[cfg(feature = "brotli")]
     Brotli(BrotliEncoder<CountingWriter<W>>),
     #[cfg(feature = "bzip2")]
-    Bzip2(Option<bzip2::write::BzEncoder<CountingWriter<W>>>),
+    Bzip2(Bzip2Encoder<CountingWriter<W>>),
     #[cfg(feature = "deflate")]
-    Deflate(Option<flate2::write::DeflateEncoder<CountingWriter<W>>>),
+    Deflate(flate2::write::DeflateEncoder<CountingWriter<W>>),
     #[cfg(feature = "lz4")]
-    Lz4(Option<Lz4Encoder<CountingWriter<W>>>),
+    Lz4(Lz4Encoder<CountingWriter<W>>),
+    #[cfg(feature = "snappy")]
+    Snappy(SnappyEncoder<CountingWriter<W>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, CountingWriter<W>>),
+    #[cfg(feature = "zstd")]
+    ZstdSeekable(ZstdSeekableEncoder<CountingWriter<W>>),
     #[cfg(feature = "zstd")]
-    Zstd(Option<zstd::Encoder<'static, CountingWriter<W>>>),
+    ZstdFrames(ZstdFrameEncoder<CountingWriter<W>>),
     #[cfg(feature = "aes256")]
     Aes(Aes256Sha256Encoder<CountingWriter<W>>),
 }
 
+impl<W: Write> Encoder<W> {
+    /// Writes the end of the encoded stream and returns the writer it was written to.
+    pub(crate) fn finish(self) -> std::io::Result<CountingWriter<W>> {
+        match self {
+            Encoder::Copy(w) => Ok(w),
+            Encoder::Bcj(w) => w.finish(),
+            Encoder::Delta(w) => Ok(w.into_inner()),
+            Encoder::Lzma(w) => w.finish(),
+            Encoder::Lzma2(w, _) => w.finish(),
+            Encoder::Lzma2Mt(w) => w.finish(),
+            #[cfg(feature = "ppmd")]
+            Encoder::Ppmd(w) => w.finish(false),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(w) => w.finish(),
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(w) => w.finish(),
+            #[cfg(feature = "deflate")]
+            Encoder::Deflate(w) => w.finish(),
+            #[cfg(feature = "lz4")]
+            Encoder::Lz4(w) => w.finish(),
+            #[cfg(feature = "snappy")]
+            Encoder::Snappy(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdFrames(w) => w.finish(),
+            #[cfg(feature = "aes256")]
+            Encoder::Aes(w) => w.finish(),
+        }
+    }
+}
+
+impl<'a> FinishableWrite for Encoder<Box<dyn FinishableWrite + 'a>> {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        Encoder::finish(*self)?.into_inner().finish()
+    }
+}
+
 impl<W: Write> Write for Encoder<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        // Some encoder need to finish the encoding process. Because of lifetime limitations on
-        // dynamic dispatch, we need to implement an implicit contract, where empty writes with
-        // "&[]" trigger the call to "finish()". We need to also make sure to propagate the empty
-        // write into the inner writer, so that the whole chain of encoders can properly finish
-        // their data stream. Not a great way to do it, but I couldn't get a proper dynamic
-        // dispatch based approach to work.
         match self {
             Encoder::Copy(w) => w.write(buf),
+            Encoder::Bcj(w) => w.write(buf),
             Encoder::Delta(w) => w.write(buf),
-            Encoder::Bcj(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    inner.write(buf)?;
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
-            Encoder::Lzma(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
-            Encoder::Lzma2(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
-            Encoder::Lzma2Mt(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
+            Encoder::Lzma(w) => w.write(buf),
+            Encoder::Lzma2(w, chunks) => chunks.write(w, buf),
+            Encoder::Lzma2Mt(w) => w.write(buf),
             #[cfg(feature = "ppmd")]
-            Encoder::Ppmd(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish(false)?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
-            // TODO: Also add a proper "finish" method here.
+            Encoder::Ppmd(w) => w.write(buf),
             #[cfg(feature = "brotli")]
             Encoder::Brotli(w) => w.write(buf),
             #[cfg(feature = "bzip2")]
-            Encoder::Bzip2(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
+            Encoder::Bzip2(w) => w.write(buf),
             #[cfg(feature = "deflate")]
-            Encoder::Deflate(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
+            Encoder::Deflate(w) => w.write(buf),
             #[cfg(feature = "lz4")]
-            Encoder::Lz4(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
+            Encoder::Lz4(w) => w.write(buf),
+            #[cfg(feature = "snappy")]
+            Encoder::Snappy(w) => w.write(buf),
             #[cfg(feature = "zstd")]
-            Encoder::Zstd(w) => match buf.is_empty() {
-                true => {
-                    let writer = w.take().unwrap();
-                    let mut inner = writer.finish()?;
-                    let _ = inner.write(buf);
-                    Ok(0)
-                }
-                false => w.as_mut().unwrap().write(buf),
-            },
+            Encoder::Zstd(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdFrames(w) => w.write(buf),
             #[cfg(feature = "aes256")]
             Encoder::Aes(w) => w.write(buf),
         }
@@ -161,23 +158,29 @@ impl<W: Write> Write for Encoder<W> {
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             Encoder::Copy(w) => w.flush(),
-            Encoder::Bcj(w) => w.as_mut().unwrap().flush(),
+            Encoder::Bcj(w) => w.flush(),
             Encoder::Delta(w) => w.flush(),
-            Encoder::Lzma(w) => w.as_mut().unwrap().flush(),
-            Encoder::Lzma2(w) => w.as_mut().unwrap().flush(),
-            Encoder::Lzma2Mt(w) => w.as_mut().unwrap().flush(),
+            Encoder::Lzma(w) => w.flush(),
+            Encoder::Lzma2(w, _) => w.flush(),
+            Encoder::Lzma2Mt(w) => w.flush(),
             #[cfg(feature = "brotli")]
             Encoder::Brotli(w) => w.flush(),
             #[cfg(feature = "ppmd")]
-            Encoder::Ppmd(w) => w.as_mut().unwrap().flush(),
+            Encoder::Ppmd(w) => w.flush(),
             #[cfg(feature = "bzip2")]
-            Encoder::Bzip2(w) => w.as_mut().unwrap().flush(),
+            Encoder::Bzip2(w) => w.flush(),
             #[cfg(feature = "deflate")]
-            Encoder::Deflate(w) => w.as_mut().unwrap().flush(),
+            Encoder::Deflate(w) => w.flush(),
             #[cfg(feature = "lz4")]
-            Encoder::Lz4(w) => w.as_mut().unwrap().flush(),
+            Encoder::Lz4(w) => w.flush(),
+            #[cfg(feature = "snappy")]
+            Encoder::Snappy(w) => w.flush(),
             #[cfg(feature = "zstd")]
-            Encoder::Zstd(w) => w.as_mut().unwrap().flush(),
+            Encoder::Zstd(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdSeekable(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::ZstdFrames(w) => w.flush(),
             #[cfg(feature = "aes256")]
             Encoder::Aes(w) => w.flush(),
         }
@@ -200,23 +203,34 @@ pub(crate) fn add_encoder<W: Write>(
             let dw = DeltaWriter::new(input, options.0 as usize);
             Ok(Encoder::Delta(dw))
         }
-        EncoderMethod::ID_BCJ_X86 => Ok(Encoder::Bcj(Some(BcjWriter::new_x86(input, 0)))),
-        EncoderMethod::ID_BCJ_ARM => Ok(Encoder::Bcj(Some(BcjWriter::new_arm(input, 0)))),
-        EncoderMethod::ID_BCJ_ARM_THUMB => {
-            Ok(Encoder::Bcj(Some(BcjWriter::new_arm_thumb(input, 0))))
+        EncoderMethod::ID_BCJ_X86
+        | EncoderMethod::ID_BCJ_ARM
+        | EncoderMethod::ID_BCJ_ARM_THUMB
+        | EncoderMethod::ID_BCJ_ARM64
+        | EncoderMethod::ID_BCJ_IA64
+        | EncoderMethod::ID_BCJ_SPARC
+        | EncoderMethod::ID_BCJ_PPC
+        | EncoderMethod::ID_BCJ_RISCV => {
+            let start_offset = bcj_start_offset(method, method_config.options.as_ref()) as usize;
+            let bcj = match method.id() {
+                EncoderMethod::ID_BCJ_X86 => BcjWriter::new_x86(input, start_offset),
+                EncoderMethod::ID_BCJ_ARM => BcjWriter::new_arm(input, start_offset),
+                EncoderMethod::ID_BCJ_ARM_THUMB => BcjWriter::new_arm_thumb(input, start_offset),
+                EncoderMethod::ID_BCJ_ARM64 => BcjWriter::new_arm64(input, start_offset),
+                EncoderMethod::ID_BCJ_IA64 => BcjWriter::new_ia64(input, start_offset),
+                EncoderMethod::ID_BCJ_SPARC => BcjWriter::new_sparc(input, start_offset),
+                EncoderMethod::ID_BCJ_PPC => BcjWriter::new_ppc(input, start_offset),
+                _ => BcjWriter::new_riscv(input, start_offset),
+            };
+            Ok(Encoder::Bcj(bcj))
         }
-        EncoderMethod::ID_BCJ_ARM64 => Ok(Encoder::Bcj(Some(BcjWriter::new_arm64(input, 0)))),
-        EncoderMethod::ID_BCJ_IA64 => Ok(Encoder::Bcj(Some(BcjWriter::new_ia64(input, 0)))),
-        EncoderMethod::ID_BCJ_SPARC => Ok(Encoder::Bcj(Some(BcjWriter::new_sparc(input, 0)))),
-        EncoderMethod::ID_BCJ_PPC => Ok(Encoder::Bcj(Some(BcjWriter::new_ppc(input, 0)))),
-        EncoderMethod::ID_BCJ_RISCV => Ok(Encoder::Bcj(Some(BcjWriter::new_riscv(input, 0)))),
         EncoderMethod::ID_LZMA => {
             let options = match &method_config.options {
                 Some(EncoderOptions::Lzma(options)) => options.clone(),
                 _ => LzmaOptions::default(),
             };
             let lz = LzmaWriter::new_no_header(input, &options.0, false)?;
-            Ok(Encoder::Lzma(Some(lz)))
+            Ok(Encoder::Lzma(lz))
         }
         EncoderMethod::ID_LZMA2 => {
             let lzma2_options = match &method_config.options {
@@ -225,14 +239,13 @@ pub(crate) fn add_encoder<W: Write>(
             };
 
             let encoder = match lzma2_options.threads {
-                0 | 1 => Encoder::Lzma2(Some(Lzma2Writer::new(input, lzma2_options.options))),
+                0 | 1 => {
+                    let chunks = ChunkBoundary::new(lzma2_options.independent_chunk_size());
+                    Encoder::Lzma2(Lzma2Writer::new(input, lzma2_options.options), chunks)
+                }
                 _ => {
                     let threads = lzma2_options.threads;
-                    Encoder::Lzma2Mt(Some(Lzma2WriterMt::new(
-                        input,
-                        lzma2_options.options,
-                        threads,
-                    )?))
+                    Encoder::Lzma2Mt(Lzma2WriterMt::new(input, lzma2_options.options, threads)?)
                 }
             };
 
@@ -249,7 +262,7 @@ pub(crate) fn add_encoder<W: Write>(
                 ppmd_rust::Ppmd7Encoder::new(input, options.order, options.memory_size)
                     .map_err(|err| Error::other(err.to_string()))?;
 
-            Ok(Encoder::Ppmd(Some(Box::new(ppmd_encoder))))
+            Ok(Encoder::Ppmd(Box::new(ppmd_encoder)))
         }
         #[cfg(feature = "brotli")]
         EncoderMethod::ID_BROTLI => {
@@ -274,10 +287,9 @@ pub(crate) fn add_encoder<W: Write>(
                 _ => Bzip2Options::default(),
             };
 
-            let bzip2_encoder =
-                bzip2::write::BzEncoder::new(input, bzip2::Compression::new(options.0));
+            let bzip2_encoder = Bzip2Encoder::new(input, options.level, options.stream_size);
 
-            Ok(Encoder::Bzip2(Some(bzip2_encoder)))
+            Ok(Encoder::Bzip2(bzip2_encoder))
         }
         #[cfg(feature = "deflate")]
         EncoderMethod::ID_DEFLATE => {
@@ -288,7 +300,7 @@ pub(crate) fn add_encoder<W: Write>(
 
             let deflate_encoder =
                 flate2::write::DeflateEncoder::new(input, flate2::Compression::new(options.0));
-            Ok(Encoder::Deflate(Some(deflate_encoder)))
+            Ok(Encoder::Deflate(deflate_encoder))
         }
         #[cfg(feature = "lz4")]
         EncoderMethod::ID_LZ4 => {
@@ -297,9 +309,19 @@ pub(crate) fn add_encoder<W: Write>(
                 _ => Lz4Options::default(),
             };
 
-            let lz4_encoder = Lz4Encoder::new(input, options.skippable_frame_size as usize)?;
+            let lz4_encoder = Lz4Encoder::new(
+                input,
+                options.skippable_frame_size as usize,
+                options.frame_info(),
+            )?;
 
-            Ok(Encoder::Lz4(Some(lz4_encoder)))
+            Ok(Encoder::Lz4(lz4_encoder))
+        }
+        #[cfg(feature = "snappy")]
+        EncoderMethod::ID_SNAPPY => {
+            // Snappy has no parameters, so the options are not needed.
+            let snappy_encoder = SnappyEncoder::new(input);
+            Ok(Encoder::Snappy(snappy_encoder))
         }
         #[cfg(feature = "zstd")]
         EncoderMethod::ID_ZSTD => {
@@ -308,9 +330,24 @@ pub(crate) fn add_encoder<W: Write>(
                 _ => ZstandardOptions::default(),
             };
 
-            let zstd_encoder = zstd::Encoder::new(input, options.0 as i32)?;
+            if options.seekable_frame_size > 0 {
+                let zstd_encoder = ZstdSeekableEncoder::new(
+                    input,
+                    options.level as i32,
+                    options.seekable_frame_size,
+                );
+                return Ok(Encoder::ZstdSeekable(zstd_encoder));
+            }
 
-            Ok(Encoder::Zstd(Some(zstd_encoder)))
+            if options.frame_size > 0 {
+                let zstd_encoder =
+                    ZstdFrameEncoder::new(input, options.level as i32, options.frame_size as usize);
+                return Ok(Encoder::ZstdFrames(zstd_encoder));
+            }
+
+            let zstd_encoder = zstd::Encoder::new(input, options.level as i32)?;
+
+            Ok(Encoder::Zstd(zstd_encoder))
         }
         #[cfg(feature = "aes256")]
         EncoderMethod::ID_AES256_SHA256 => {
@@ -338,19 +375,26 @@ pub(crate) fn get_options_as_properties<'a>(
                 _ => DeltaOptions::default(),
             };
 
-            out[0] = options.0.saturating_sub(1) as u8;
+            out[..1].copy_from_slice(&codec::delta::distance_to_properties(options.0));
             &out[0..1]
         }
+        EncoderMethod::ID_BCJ_X86
+        | EncoderMethod::ID_BCJ_ARM
+        | EncoderMethod::ID_BCJ_ARM_THUMB
+        | EncoderMethod::ID_BCJ_ARM64
+        | EncoderMethod::ID_BCJ_IA64
+        | EncoderMethod::ID_BCJ_SPARC
+        | EncoderMethod::ID_BCJ_PPC
+        | EncoderMethod::ID_BCJ_RISCV => {
+            codec::bcj::start_offset_to_properties(bcj_start_offset(method, options), out)
+        }
         EncoderMethod::ID_LZMA2 => {
             let options = match options {
                 Some(EncoderOptions::Lzma2(options)) => options,
                 _ => &Lzma2Options::default(),
             };
             let dict_size = options.options.lzma_options.dict_size;
-            let lead = dict_size.leading_zeros();
-            let second_bit = (dict_size >> (30u32.wrapping_sub(lead))).wrapping_sub(2);
-            let prop = (19u32.wrapping_sub(lead) * 2 + second_bit) as u8;
-            out[0] = prop;
+            out[..1].copy_from_slice(&codec::lzma2::dict_size_to_properties(dict_size));
             &out[0..1]
         }
         EncoderMethod::ID_LZMA => {
@@ -408,7 +452,7 @@ pub(crate) fn get_options_as_properties<'a>(
 
             out[0] = version_major as u8;
             out[1] = version_minor as u8;
-            out[2] = options.0 as u8;
+            out[2] = options.level as u8;
             &out[0..3]
         }
         #[cfg(feature = "aes256")]
@@ -423,3 +467,63 @@ pub(crate) fn get_options_as_properties<'a>(
         _ => &[],
     }
 }
+
+/// Returns the start offset of the BCJ filter `method`, or 0 if the options are missing or
+/// belong to a different filter.
+fn bcj_start_offset(method: EncoderMethod, options: Option<&EncoderOptions>) -> u32 {
+    match (method.id(), options) {
+        (EncoderMethod::ID_BCJ_X86, Some(EncoderOptions::BcjX86(options))) => options.start_offset,
+        (EncoderMethod::ID_BCJ_ARM, Some(EncoderOptions::BcjArm(options))) => options.start_offset,
+        (EncoderMethod::ID_BCJ_ARM64, Some(EncoderOptions::BcjArm64(options))) => {
+            options.start_offset
+        }
+        (EncoderMethod::ID_BCJ_ARM_THUMB, Some(EncoderOptions::BcjArmThumb(options))) => {
+            options.start_offset
+        }
+        (EncoderMethod::ID_BCJ_IA64, Some(EncoderOptions::BcjIa64(options))) => {
+            options.start_offset
+        }
+        (EncoderMethod::ID_BCJ_SPARC, Some(EncoderOptions::BcjSparc(options))) => {
+            options.start_offset
+        }
+        (EncoderMethod::ID_BCJ_PPC, Some(EncoderOptions::BcjPpc(options))) => options.start_offset,
+        (EncoderMethod::ID_BCJ_RISCV, Some(EncoderOptions::BcjRiscV(options))) => {
+            options.start_offset
+        }
+        _ => 0,
+    }
+}
+
+/// Splits the input of the single threaded LZMA2 encoder at exact chunk boundaries.
+///
+/// The LZMA2 encoder only checks whether it needs to start a new independent chunk after it
+/// wrote an LZMA2 chunk, which can hold up to 2 MiB of uncompressed data. Flushing the encoder
+/// at every boundary makes the independent chunks exactly `chunk_size` big, like the multi
+/// threaded encoder does.
+pub(crate) struct ChunkBoundary {
+    chunk_size: Option<u64>,
+    remaining: u64,
+}
+
+impl ChunkBoundary {
+    fn new(chunk_size: Option<u64>) -> Self {
+        Self {
+            chunk_size,
+            remaining: chunk_size.unwrap_or_default(),
+        }
+    }
+
+    fn write<W: Write>(&mut self, writer: &mut W, buf: &[u8]) -> std::io::Result<usize> {
+        let Some(chunk_size) = self.chunk_size else {
+            return writer.write(buf);
+        };
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        let written = writer.write(&buf[..len])?;
+        self.remaining -= written as u64;
+        if self.remaining == 0 {
+            writer.flush()?;
+            self.remaining = chunk_size;
+        }
+        Ok(written)
+    }
+}
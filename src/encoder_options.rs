@@ -79,19 +79,103 @@ impl Lzma2Options {
         Self { options, threads }
     }
 
+    /// Creates LZMA2 options with the settings that 7-Zip uses for the compression level
+    /// `level` of the 7z format (`-mx=level`), see [`EncoderConfiguration::preset`].
+    ///
+    /// | Level | Dictionary | Mode   | Match finder | Nice length |
+    /// |-------|------------|--------|--------------|-------------|
+    /// | 1, 2  | 64 KiB     | fast   | HC4          | 32          |
+    /// | 3, 4  | 1 MiB      | fast   | HC4          | 32          |
+    /// | 5, 6  | 16 MiB     | normal | BT4          | 32          |
+    /// | 7, 8  | 32 MiB     | normal | BT4          | 64          |
+    /// | 9     | 64 MiB     | normal | BT4          | 64          |
+    ///
+    /// Level 0 is treated as level 1 and levels above 9 as level 9.
+    pub(crate) fn from_7zip_level(level: u32) -> Self {
+        let level = level.clamp(1, 9);
+        let (dict_size, nice_len) = match level {
+            1 | 2 => (64 << 10, 32),
+            3 | 4 => (1 << 20, 32),
+            5 | 6 => (16 << 20, 32),
+            7 | 8 => (32 << 20, 64),
+            _ => (64 << 20, 64),
+        };
+        let fast = level < 5;
+        let lzma_options = lzma_rust2::LzmaOptions {
+            dict_size,
+            lc: lzma_rust2::LzmaOptions::LC_DEFAULT,
+            lp: lzma_rust2::LzmaOptions::LP_DEFAULT,
+            pb: lzma_rust2::LzmaOptions::PB_DEFAULT,
+            mode: if fast {
+                lzma_rust2::EncodeMode::Fast
+            } else {
+                lzma_rust2::EncodeMode::Normal
+            },
+            nice_len,
+            mf: if fast {
+                lzma_rust2::MfType::Hc4
+            } else {
+                lzma_rust2::MfType::Bt4
+            },
+            depth_limit: 0,
+            preset_dict: None,
+        };
+        Self {
+            options: lzma_rust2::Lzma2Options {
+                lzma_options,
+                ..Default::default()
+            },
+            threads: 1,
+        }
+    }
+
     /// Sets the dictionary size used when encoding.
     ///
-    /// Will be clamped between 4096..=4294967280.
+    /// Will be clamped between 4096..=4294967280. The coder properties of LZMA2 can only store
+    /// dictionary sizes of the form `2^n` or `3 * 2^n`, so the size is rounded up to the next such
+    /// value, see [`Lzma2Options::dictionary_size`].
     pub fn set_dictionary_size(&mut self, dict_size: u32) {
+        let dict_size = dict_size.clamp(lzma_rust2::DICT_SIZE_MIN, lzma_rust2::DICT_SIZE_MAX);
         self.options.lzma_options.dict_size =
-            dict_size.clamp(lzma_rust2::DICT_SIZE_MIN, lzma_rust2::DICT_SIZE_MAX);
+            crate::codec::lzma2::round_dict_size(dict_size).min(lzma_rust2::DICT_SIZE_MAX);
+    }
+
+    /// Sets the size of independent chunks of uncompressed data.
+    ///
+    /// The encoder resets its dictionary and state after every `chunk_size` bytes, even when
+    /// encoding with a single thread. Such streams can be decoded using multiple threads
+    /// (see [`crate::ArchiveReader::set_thread_count`]). Smaller chunks allow more parallelism
+    /// when decoding, but worsen the compression ratio. The value will be clamped to have at
+    /// least the size of the dictionary. A value of `0` disables independent chunks.
+    ///
+    /// # Arguments
+    /// * `chunk_size` - Size of each independent chunk of uncompressed data
+    pub fn set_independent_chunk_size(&mut self, chunk_size: u64) {
+        let chunk_size = NonZeroU64::new(chunk_size)
+            .map(|size| size.max(NonZeroU64::new(self.dictionary_size() as u64).unwrap()));
+        self.options.set_chunk_size(chunk_size);
+    }
+
+    /// Returns the size of independent chunks, if set.
+    pub fn independent_chunk_size(&self) -> Option<u64> {
+        self.options
+            .chunk_size
+            .map(|size| size.get().max(self.dictionary_size() as u64))
+    }
+
+    /// Returns the dictionary size used when encoding.
+    pub fn dictionary_size(&self) -> u32 {
+        self.options.lzma_options.dict_size
     }
 }
 
 #[cfg(feature = "bzip2")]
 #[derive(Debug, Copy, Clone)]
 /// Options for BZIP2 compression.
-pub struct Bzip2Options(pub(crate) u32);
+pub struct Bzip2Options {
+    pub(crate) level: u32,
+    pub(crate) stream_size: u64,
+}
 
 #[cfg(feature = "bzip2")]
 impl Bzip2Options {
@@ -100,14 +184,27 @@ impl Bzip2Options {
     /// # Arguments
     /// * `level` - Compression level (typically 1-9)
     pub const fn from_level(level: u32) -> Self {
-        Self(level)
+        Self {
+            level,
+            stream_size: 0,
+        }
+    }
+
+    /// Sets the size of uncompressed data after which a new, independent BZIP2 stream is
+    /// started, like pbzip2 does. The streams can then be decoded in parallel, see
+    /// [`ArchiveReader::set_thread_count`](crate::ArchiveReader::set_thread_count).
+    ///
+    /// A value of 0 writes a single stream. The default value is 0.
+    pub const fn with_stream_size(mut self, stream_size: u64) -> Self {
+        self.stream_size = stream_size;
+        self
     }
 }
 
 #[cfg(feature = "bzip2")]
 impl Default for Bzip2Options {
     fn default() -> Self {
-        Self(6)
+        Self::from_level(6)
     }
 }
 
@@ -186,8 +283,8 @@ impl DeltaOptions {
     pub const fn from_distance(distance: u32) -> Self {
         let distance = if distance == 0 {
             1
-        } else if distance > 256 {
-            256
+        } else if distance > crate::codec::delta::MAX_DISTANCE {
+            crate::codec::delta::MAX_DISTANCE
         } else {
             distance
         };
@@ -202,6 +299,82 @@ impl Default for DeltaOptions {
     }
 }
 
+macro_rules! bcj_options {
+    ($($(#[$doc:meta])* $name:ident => $variant:ident, $method:ident;)*) => {$(
+        #[cfg(feature = "compress")]
+        #[derive(Debug, Default, Copy, Clone)]
+        $(#[$doc])*
+        ///
+        /// The start offset is the address that the first byte of the data is loaded at, like the
+        /// virtual address of a code section that is compressed on its own. A non-zero offset is
+        /// stored in the coder properties, so the data is decoded with the same offset.
+        pub struct $name {
+            /// Address of the first byte of the data.
+            pub start_offset: u32,
+        }
+
+        #[cfg(feature = "compress")]
+        impl $name {
+            /// Creates options with the specified start offset.
+            ///
+            /// # Arguments
+            /// * `start_offset` - Address of the first byte of the data
+            pub const fn from_start_offset(start_offset: u32) -> Self {
+                Self { start_offset }
+            }
+        }
+
+        #[cfg(feature = "compress")]
+        impl From<$name> for EncoderOptions {
+            fn from(options: $name) -> Self {
+                Self::$variant(options)
+            }
+        }
+
+        #[cfg(feature = "compress")]
+        impl From<$name> for EncoderConfiguration {
+            fn from(options: $name) -> Self {
+                Self::new(crate::EncoderMethod::$method).with_options(EncoderOptions::$variant(options))
+            }
+        }
+    )*};
+}
+
+bcj_options! {
+    /// Options for the BCJ x86 filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjX86Options => BcjX86, BCJ_X86_FILTER;
+    /// Options for the BCJ ARM filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjArmOptions => BcjArm, BCJ_ARM_FILTER;
+    /// Options for the BCJ ARM64 filter.
+    ///
+    /// 7-Zip supports the start offset of this filter.
+    BcjArm64Options => BcjArm64, BCJ_ARM64_FILTER;
+    /// Options for the BCJ ARM Thumb filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjArmThumbOptions => BcjArmThumb, BCJ_ARM_THUMB_FILTER;
+    /// Options for the BCJ IA64 filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjIa64Options => BcjIa64, BCJ_IA64_FILTER;
+    /// Options for the BCJ SPARC filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjSparcOptions => BcjSparc, BCJ_SPARC_FILTER;
+    /// Options for the BCJ PowerPC filter.
+    ///
+    /// 7-Zip can't decode this filter with a non-zero start offset.
+    BcjPpcOptions => BcjPpc, BCJ_PPC_FILTER;
+    /// Options for the BCJ RISC-V filter.
+    ///
+    /// 7-Zip supports the start offset of this filter.
+    BcjRiscVOptions => BcjRiscV, BCJ_RISCV_FILTER;
+}
+
 #[cfg(feature = "deflate")]
 #[derive(Debug, Copy, Clone)]
 /// Options for Deflate compression.
@@ -227,14 +400,57 @@ impl Default for DeflateOptions {
 }
 
 #[cfg(feature = "lz4")]
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone)]
 /// Options for LZ4 compression.
 pub struct Lz4Options {
     pub(crate) skippable_frame_size: u32,
+    pub(crate) content_checksum: bool,
+    pub(crate) block_independence: bool,
+}
+
+#[cfg(feature = "lz4")]
+impl Default for Lz4Options {
+    fn default() -> Self {
+        Self {
+            skippable_frame_size: 0,
+            content_checksum: false,
+            block_independence: true,
+        }
+    }
 }
 
 #[cfg(feature = "lz4")]
 impl Lz4Options {
+    /// Sets whether a checksum of the uncompressed content is appended to each LZ4 frame.
+    /// The checksum is verified while decoding.
+    ///
+    /// Defaults to `false`.
+    pub fn with_content_checksum(mut self, content_checksum: bool) -> Self {
+        self.content_checksum = content_checksum;
+        self
+    }
+
+    /// Sets whether the blocks of an LZ4 frame are compressed independently of each other.
+    /// Independent blocks can be decompressed in parallel, while linked blocks can reference
+    /// data of previous blocks and compress slightly better.
+    ///
+    /// Defaults to `true`.
+    pub fn with_block_independence(mut self, block_independence: bool) -> Self {
+        self.block_independence = block_independence;
+        self
+    }
+
+    pub(crate) fn frame_info(&self) -> lz4_flex::frame::FrameInfo {
+        let block_mode = if self.block_independence {
+            lz4_flex::frame::BlockMode::Independent
+        } else {
+            lz4_flex::frame::BlockMode::Linked
+        };
+        lz4_flex::frame::FrameInfo::default()
+            .content_checksum(self.content_checksum)
+            .block_mode(block_mode)
+    }
+
     /// Set's the skippable frame size. The size is defined as the size of uncompressed data a frame
     /// contains. A value of 0 deactivates skippable frames and uses the native LZ4 bitstream.
     /// If a value is set, then the similar skippable frame format is used.
@@ -257,6 +473,15 @@ impl Lz4Options {
     }
 }
 
+#[cfg(feature = "snappy")]
+#[derive(Debug, Default, Copy, Clone)]
+/// Options for Snappy compression.
+///
+/// Snappy has no compression level or other parameters. The type exists so that Snappy can be
+/// configured like the other codecs. Snappy uses the unofficial method ID
+/// [`EncoderMethod::ID_SNAPPY`](crate::EncoderMethod::ID_SNAPPY).
+pub struct SnappyOptions;
+
 #[cfg(feature = "ppmd")]
 #[derive(Debug, Copy, Clone)]
 /// Options for PPMD compression.
@@ -303,6 +528,28 @@ impl PpmdOptions {
         };
         Self { order, memory_size }
     }
+
+    /// Creates PPMD options from the coder properties stored in an archive, for example the
+    /// properties of a [`CodecInfo`](crate::CodecInfo).
+    ///
+    /// # Arguments
+    /// * `properties` - The 5 bytes of coder properties: the model order followed by the memory
+    ///   size as little-endian `u32`
+    pub fn from_properties(properties: &[u8]) -> Result<Self, crate::Error> {
+        let (order, memory_size) =
+            crate::codec::ppmd::order_memory_size_from_properties(properties)?;
+        Ok(Self { order, memory_size })
+    }
+
+    /// Returns the model order.
+    pub const fn order(&self) -> u32 {
+        self.order
+    }
+
+    /// Returns the memory size in bytes.
+    pub const fn memory_size(&self) -> u32 {
+        self.memory_size
+    }
 }
 
 #[cfg(feature = "ppmd")]
@@ -312,10 +559,18 @@ impl Default for PpmdOptions {
     }
 }
 
+#[cfg(feature = "zstd")]
+/// Largest amount of uncompressed data a seekable ZSTD frame may contain.
+const MAXIMAL_SEEKABLE_FRAME_SIZE: usize = 1024 * 1024 * 1024;
+
 #[cfg(feature = "zstd")]
 #[derive(Debug, Copy, Clone)]
 /// Options for Zstandard compression.
-pub struct ZstandardOptions(pub(crate) u32);
+pub struct ZstandardOptions {
+    pub(crate) level: u32,
+    pub(crate) seekable_frame_size: usize,
+    pub(crate) frame_size: u32,
+}
 
 #[cfg(feature = "zstd")]
 impl ZstandardOptions {
@@ -325,14 +580,42 @@ impl ZstandardOptions {
     /// * `level` - Compression level (typically 1-22)
     pub const fn from_level(level: u32) -> Self {
         let level = if level > 22 { 22 } else { level };
-        Self(level)
+        Self {
+            level,
+            seekable_frame_size: 0,
+            frame_size: 0,
+        }
+    }
+
+    /// Set's the seekable frame size. The size is defined as the size of uncompressed data a frame
+    /// contains. A value of 0 deactivates the seekable format and writes a single ZSTD frame.
+    /// If a value is set, the data is split into independent frames followed by a seek table, as
+    /// defined by the ZSTD seekable format. This allows random access into the compressed stream.
+    ///
+    /// Values larger than 1 GiB will be set to 1 GiB. The default value is 0.
+    pub fn with_seekable_frame_size(mut self, seekable_frame_size: usize) -> Self {
+        self.seekable_frame_size = usize::min(seekable_frame_size, MAXIMAL_SEEKABLE_FRAME_SIZE);
+        self
+    }
+
+    /// Set's the frame size. The size is defined as the size of uncompressed data a frame
+    /// contains. A value of 0 writes a single ZSTD frame. If a value is set, the data is split
+    /// into independent frames of `frame_size` bytes. Frames of up to 32 MiB are decoded in
+    /// parallel. Any ZSTD decoder reads such concatenated frames.
+    ///
+    /// The data of a frame is buffered in memory, so that its size can be stored in the frame
+    /// header. Unlike the seekable format, no seek table is written. If a seekable frame size is
+    /// set as well, the seekable format is used. The default value is 0.
+    pub fn with_frame_size(mut self, frame_size: u32) -> Self {
+        self.frame_size = frame_size;
+        self
     }
 }
 
 #[cfg(feature = "zstd")]
 impl Default for ZstandardOptions {
     fn default() -> Self {
-        Self(3)
+        Self::from_level(3)
     }
 }
 
@@ -396,6 +679,30 @@ pub enum EncoderOptions {
     /// Delta filter options.
     Delta(DeltaOptions),
     #[cfg(feature = "compress")]
+    /// BCJ x86 filter options.
+    BcjX86(BcjX86Options),
+    #[cfg(feature = "compress")]
+    /// BCJ ARM filter options.
+    BcjArm(BcjArmOptions),
+    #[cfg(feature = "compress")]
+    /// BCJ ARM64 filter options.
+    BcjArm64(BcjArm64Options),
+    #[cfg(feature = "compress")]
+    /// BCJ ARM Thumb filter options.
+    BcjArmThumb(BcjArmThumbOptions),
+    #[cfg(feature = "compress")]
+    /// BCJ IA64 filter options.
+    BcjIa64(BcjIa64Options),
+    #[cfg(feature = "compress")]
+    /// BCJ SPARC filter options.
+    BcjSparc(BcjSparcOptions),
+    #[cfg(feature = "compress")]
+    /// BCJ PowerPC filter options.
+    BcjPpc(BcjPpcOptions),
+    #[cfg(feature = "compress")]
+    /// BCJ RISC-V filter options.
+    BcjRiscV(BcjRiscVOptions),
+    #[cfg(feature = "compress")]
     /// LZMA compression options.
     Lzma(LzmaOptions),
     #[cfg(feature = "compress")]
@@ -416,6 +723,9 @@ pub enum EncoderOptions {
     #[cfg(feature = "ppmd")]
     /// PPMD compression options.
     Ppmd(PpmdOptions),
+    #[cfg(feature = "snappy")]
+    /// Snappy compression options.
+    Snappy(SnappyOptions),
     #[cfg(feature = "zstd")]
     /// Zstandard compression options.
     Zstd(ZstandardOptions),
@@ -487,6 +797,13 @@ impl From<PpmdOptions> for EncoderConfiguration {
     }
 }
 
+#[cfg(feature = "snappy")]
+impl From<SnappyOptions> for EncoderConfiguration {
+    fn from(options: SnappyOptions) -> Self {
+        Self::new(crate::EncoderMethod::SNAPPY).with_options(EncoderOptions::Snappy(options))
+    }
+}
+
 #[cfg(feature = "zstd")]
 impl From<ZstandardOptions> for EncoderConfiguration {
     fn from(options: ZstandardOptions) -> Self {
@@ -501,6 +818,13 @@ impl From<DeltaOptions> for EncoderOptions {
     }
 }
 
+#[cfg(feature = "compress")]
+impl From<LzmaOptions> for EncoderOptions {
+    fn from(o: LzmaOptions) -> Self {
+        Self::Lzma(o)
+    }
+}
+
 #[cfg(feature = "compress")]
 impl From<Lzma2Options> for EncoderOptions {
     fn from(o: Lzma2Options) -> Self {
@@ -543,6 +867,13 @@ impl From<PpmdOptions> for EncoderOptions {
     }
 }
 
+#[cfg(feature = "snappy")]
+impl From<SnappyOptions> for EncoderOptions {
+    fn from(o: SnappyOptions) -> Self {
+        Self::Snappy(o)
+    }
+}
+
 #[cfg(feature = "zstd")]
 impl From<ZstandardOptions> for EncoderOptions {
     fn from(o: ZstandardOptions) -> Self {
@@ -565,3 +896,31 @@ impl EncoderOptions {
         }
     }
 }
+
+#[cfg(all(test, feature = "compress"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lzma2_7zip_levels_match_explicit_options() {
+        // 7-Zip uses the match finders of the xz presets, but a nice length of 32 and no depth
+        // limit for the fast levels.
+        for (level, preset, dict_size, nice_len) in [
+            (1, 1, 64 << 10, 32),
+            (3, 3, 1 << 20, 32),
+            (5, 5, 16 << 20, 32),
+            (7, 6, 32 << 20, 64),
+            (9, 6, 64 << 20, 64),
+        ] {
+            let mut explicit = lzma_rust2::Lzma2Options::with_preset(preset);
+            explicit.lzma_options.dict_size = dict_size;
+            explicit.lzma_options.nice_len = nice_len;
+            explicit.lzma_options.depth_limit = 0;
+            assert_eq!(
+                format!("{:?}", Lzma2Options::from_7zip_level(level).options),
+                format!("{explicit:?}"),
+                "level {level}"
+            );
+        }
+    }
+}
@@ -1,17 +1,17 @@
-//! AES256-SHA256 encryption/decryption for 7z archives
+//! AES256-SHA256 encryption/decryption for 7z archives and RAR 2.9 AES decryption
 //! Vendored dependency - suppress deprecation warnings for generic-array usage
 
 #![allow(deprecated)]
 
 use std::{
     borrow::Cow,
-    io::{Read, Seek, Write},
+    io::{Read, Seek, SeekFrom, Write},
 };
 
 #[cfg(feature = "compress")]
 use aes::cipher::BlockEncryptMut;
 use aes::{
-    Aes256,
+    Aes128, Aes256,
     cipher::{BlockDecryptMut, KeyIvInit, generic_array::GenericArray},
 };
 use sha2::Digest;
@@ -20,12 +20,14 @@ use crate::Password;
 #[cfg(feature = "compress")]
 use crate::encoder_options::AesEncoderOptions;
 
+type Aes128CbcDec = cbc::Decryptor<Aes128>;
 type Aes256CbcDec = cbc::Decryptor<Aes256>;
 
 #[cfg(feature = "compress")]
 type Aes256CbcEnc = cbc::Encryptor<Aes256>;
 
-pub(crate) struct Aes256Sha256Decoder<R> {
+/// Decrypts the AES-CBC encrypted data of the 7z AES-256 and the RAR 2.9 AES-128 coders.
+pub(crate) struct AesDecoder<R> {
     cipher: Cipher,
     input: R,
     done: bool,
@@ -35,14 +37,35 @@ pub(crate) struct Aes256Sha256Decoder<R> {
     pos: usize,
 }
 
-impl<R: Read> Aes256Sha256Decoder<R> {
+impl<R: Read> AesDecoder<R> {
     pub(crate) fn new(
         input: R,
         properties: &[u8],
         password: &Password,
+        max_key_derivation_power: u8,
     ) -> Result<Self, crate::Error> {
-        let cipher = Cipher::from_properties(properties, password.as_slice())?;
-        Ok(Self {
+        let (aes_key, iv) = get_aes_key(properties, password.as_slice(), max_key_derivation_power)?;
+        Ok(Self::with_key(input, &aes_key, &iv))
+    }
+
+    /// Creates a decoder from the derived AES key and IV, skipping the key derivation.
+    pub(crate) fn with_key(input: R, aes_key: &[u8; 32], iv: &[u8; 16]) -> Self {
+        Self::with_cipher(input, Cipher::new(aes_key, iv))
+    }
+
+    /// Creates a decoder for the RAR 2.9 AES-128 coder, which derives the key from the password
+    /// and the salt in the `properties` with SHA-1.
+    pub(crate) fn new_rar29(
+        input: R,
+        properties: &[u8],
+        password: &Password,
+    ) -> Result<Self, crate::Error> {
+        let (aes_key, iv) = get_rar29_aes_key(properties, password.as_slice())?;
+        Ok(Self::with_cipher(input, Cipher::new_aes128(&aes_key, &iv)))
+    }
+
+    fn with_cipher(input: R, cipher: Cipher) -> Self {
+        Self {
             input,
             cipher,
             done: false,
@@ -50,7 +73,7 @@ impl<R: Read> Aes256Sha256Decoder<R> {
             ostart: 0,
             ofinish: 0,
             pos: 0,
-        })
+        }
     }
 
     fn get_more_data(&mut self) -> std::io::Result<usize> {
@@ -77,7 +100,7 @@ impl<R: Read> Aes256Sha256Decoder<R> {
     }
 }
 
-impl<R: Read> Read for Aes256Sha256Decoder<R> {
+impl<R: Read> Read for AesDecoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if self.ostart >= self.ofinish {
             let mut n: usize;
@@ -102,38 +125,45 @@ impl<R: Read> Read for Aes256Sha256Decoder<R> {
     }
 }
 
-impl<R: Read + Seek> Seek for Aes256Sha256Decoder<R> {
-    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        let len = self.ofinish - self.ostart;
-        match pos {
-            std::io::SeekFrom::Start(p) => {
-                let n = (p as i64 - self.pos as i64).min(len as i64);
-
-                if n < 0 {
-                    Ok(0)
-                } else {
-                    self.ostart += n as usize;
-                    Ok(p)
-                }
+/// Seeking is only supported forward, by decrypting and discarding the data up to the new
+/// position, since the CBC state can't be restored for earlier positions.
+impl<R: Read> Seek for AesDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let current = self.pos as u64;
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::Current(n) => current.checked_add_signed(n),
+            SeekFrom::End(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "Aes256 decoder doesn't support seeking from the end",
+                ));
             }
-            std::io::SeekFrom::End(_) => Err(std::io::Error::new(
+        };
+        let Some(target) = target.filter(|&target| target >= current) else {
+            return Err(std::io::Error::new(
                 std::io::ErrorKind::Unsupported,
-                "Aes256 decoder unsupport seek from end",
-            )),
-            std::io::SeekFrom::Current(n) => {
-                let n = n.min(len as i64);
-                if n < 0 {
-                    Ok(0)
-                } else {
-                    self.ostart += n as usize;
-                    Ok(self.pos as u64 + n as u64)
-                }
-            }
+                "Aes256 decoder doesn't support seeking backwards",
+            ));
+        };
+
+        let skip = target - current;
+        let skipped = std::io::copy(&mut self.by_ref().take(skip), &mut std::io::sink())?;
+        if skipped < skip {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Aes256 decoder can't seek past the end of the data",
+            ));
         }
+        Ok(target)
     }
 }
 
-fn get_aes_key(properties: &[u8], password: &[u8]) -> Result<([u8; 32], [u8; 16]), crate::Error> {
+fn get_aes_key(
+    properties: &[u8],
+    password: &[u8],
+    max_key_derivation_power: u8,
+) -> Result<([u8; 32], [u8; 16]), crate::Error> {
     let properties = match properties.len() {
         0 => {
             return Err(crate::Error::other("AES256 properties too short"));
@@ -160,19 +190,23 @@ fn get_aes_key(properties: &[u8], password: &[u8]) -> Result<([u8; 32], [u8; 16]
     salt.copy_from_slice(&properties[2..(2 + salt_size)]);
     let mut iv = [0u8; 16];
     iv[0..iv_size].copy_from_slice(&properties[(2 + salt_size)..(2 + salt_size + iv_size)]);
+    // The power comes from the archive, 2^62 hash iterations would never finish.
+    if num_cycles_power != 0x3F && num_cycles_power > max_key_derivation_power {
+        return Err(crate::Error::other("key derivation cost too high"));
+    }
     if password.is_empty() {
         return Err(crate::Error::PasswordRequired);
     }
     let aes_key = if num_cycles_power == 0x3F {
         let mut aes_key = [0u8; 32];
-        aes_key.copy_from_slice(&salt[..salt_size]);
+        aes_key[..salt_size].copy_from_slice(&salt);
         let n = password.len().min(aes_key.len() - salt_size);
         aes_key[salt_size..n + salt_size].copy_from_slice(&password[0..n]);
         aes_key
     } else {
         let mut sha = sha2::Sha256::default();
         let mut extra = [0u8; 8];
-        for _ in 0..(1u32 << num_cycles_power) {
+        for _ in 0..(1u64 << num_cycles_power) {
             sha.update(&salt);
             sha.update(password);
             sha.update(extra);
@@ -188,18 +222,112 @@ fn get_aes_key(properties: &[u8], password: &[u8]) -> Result<([u8; 32], [u8; 16]
     Ok((aes_key, iv))
 }
 
+/// Derives the AES-128 key and IV of the RAR 2.9 AES coder from the UTF-16LE `password`
+/// and the optional 8 byte salt stored in the `properties`.
+fn get_rar29_aes_key(
+    properties: &[u8],
+    password: &[u8],
+) -> Result<([u8; 16], [u8; 16]), crate::Error> {
+    /// RAR limits passwords to 127 characters.
+    const MAX_PASSWORD_LEN: usize = 127 * 2;
+    const NUM_ROUNDS: u32 = 1 << 18;
+
+    let salt = match properties.len() {
+        0 | 8 => properties,
+        len => {
+            return Err(crate::Error::other(format!(
+                "Invalid RAR AES properties size {len}"
+            )));
+        }
+    };
+    if password.is_empty() {
+        return Err(crate::Error::PasswordRequired);
+    }
+    let password = &password[..password.len().min(MAX_PASSWORD_LEN)];
+    let mut data = [password, salt].concat();
+
+    let mut sha = sha1::Sha1::new();
+    let mut hashed_len = 0;
+    let mut iv = [0u8; 16];
+    for i in 0..NUM_ROUNDS {
+        sha.update(&data);
+        rar_sha1_write_back(&mut data, hashed_len);
+        hashed_len += data.len();
+        // The counter never completes two blocks, so it is never written back.
+        sha.update(&i.to_le_bytes()[..3]);
+        hashed_len += 3;
+        // Every 16th of the rounds, the last byte of the intermediate digest is a byte of the IV.
+        if i % (NUM_ROUNDS / 16) == 0 {
+            let digest = sha.clone().finalize();
+            iv[(i / (NUM_ROUNDS / 16)) as usize] = digest[19];
+        }
+    }
+    let digest = sha.finalize();
+    // The key is the first four words of the digest, each with its bytes reversed.
+    let mut aes_key = [0u8; 16];
+    for (key, word) in aes_key.chunks_mut(4).zip(digest.chunks(4)) {
+        key.copy_from_slice(word);
+        key.reverse();
+    }
+    Ok((aes_key, iv))
+}
+
+/// Emulates the in-place SHA-1 of RAR 3, which writes the expanded message of every block but
+/// the first one completed by an update back to the hashed `data`, when `hashed_len` bytes were
+/// hashed before. Later rounds of the key derivation hash the modified data, which only makes
+/// a difference for passwords and salt longer than 64 bytes.
+fn rar_sha1_write_back(data: &mut [u8], hashed_len: usize) {
+    let first_block_end = 64 - hashed_len % 64;
+    let Some(rest) = data.get_mut(first_block_end..) else {
+        return;
+    };
+    for block in rest.chunks_exact_mut(64) {
+        let mut w = [0u32; 16];
+        for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        // After the 80 rounds, the 16 word schedule holds the words 64 to 79.
+        for t in 16..80 {
+            w[t % 16] =
+                (w[(t + 13) % 16] ^ w[(t + 8) % 16] ^ w[(t + 2) % 16] ^ w[t % 16]).rotate_left(1);
+        }
+        for (bytes, word) in block.chunks_exact_mut(4).zip(w) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+}
+
+enum CbcDecryptor {
+    Aes128(Box<Aes128CbcDec>),
+    Aes256(Box<Aes256CbcDec>),
+}
+
 struct Cipher {
-    dec: Aes256CbcDec,
+    dec: CbcDecryptor,
     buf: Vec<u8>,
 }
 
 impl Cipher {
-    fn from_properties(properties: &[u8], password: &[u8]) -> Result<Self, crate::Error> {
-        let (aes_key, iv) = get_aes_key(properties, password)?;
-        Ok(Self {
-            dec: Aes256CbcDec::new(&GenericArray::from(aes_key), &iv.into()),
+    fn new(aes_key: &[u8; 32], iv: &[u8; 16]) -> Self {
+        Self {
+            dec: CbcDecryptor::Aes256(Box::new(Aes256CbcDec::new(aes_key.into(), iv.into()))),
             buf: Default::default(),
-        })
+        }
+    }
+
+    fn new_aes128(aes_key: &[u8; 16], iv: &[u8; 16]) -> Self {
+        Self {
+            dec: CbcDecryptor::Aes128(Box::new(Aes128CbcDec::new(aes_key.into(), iv.into()))),
+            buf: Default::default(),
+        }
+    }
+
+    fn decrypt_block(&mut self, block: &mut [u8]) {
+        let block = GenericArray::from_mut_slice(block);
+        match &mut self.dec {
+            CbcDecryptor::Aes128(dec) => dec.decrypt_block_mut(block),
+            CbcDecryptor::Aes256(dec) => dec.decrypt_block_mut(block),
+        }
     }
 
     fn update<W: Write>(&mut self, mut data: &mut [u8], mut output: W) -> std::io::Result<usize> {
@@ -209,12 +337,12 @@ impl Cipher {
             let end = 16 - self.buf.len();
             self.buf.extend_from_slice(&data[..end]);
             data = &mut data[end..];
-            let block = GenericArray::from_mut_slice(&mut self.buf);
-            self.dec.decrypt_block_mut(block);
-            let out = block.as_slice();
-            output.write_all(out)?;
-            n += out.len();
-            self.buf.clear();
+            let mut block = std::mem::take(&mut self.buf);
+            self.decrypt_block(&mut block);
+            output.write_all(&block)?;
+            n += block.len();
+            block.clear();
+            self.buf = block;
         }
 
         for a in data.chunks_mut(16) {
@@ -222,11 +350,9 @@ impl Cipher {
                 self.buf.extend_from_slice(a);
                 break;
             }
-            let block = GenericArray::from_mut_slice(a);
-            self.dec.decrypt_block_mut(block);
-            let out = block.as_slice();
-            output.write_all(out)?;
-            n += out.len();
+            self.decrypt_block(a);
+            output.write_all(a)?;
+            n += a.len();
         }
         Ok(n)
     }
@@ -249,7 +375,6 @@ pub(crate) struct Aes256Sha256Encoder<W> {
     output: W,
     enc: Aes256CbcEnc,
     buffer: Vec<u8>,
-    finished: bool,
     write_size: u32,
 }
 
@@ -259,13 +384,13 @@ impl<W> Aes256Sha256Encoder<W> {
         let (key, iv) = crate::encryption::aes::get_aes_key(
             &options.properties(),
             options.password.as_slice(),
+            u8::MAX,
         )?;
 
         Ok(Self {
             output,
             enc: Aes256CbcEnc::new(&GenericArray::from(key), &iv.into()),
             buffer: Default::default(),
-            finished: false,
             write_size: 0,
         })
     }
@@ -281,19 +406,25 @@ impl<W> Aes256Sha256Encoder<W> {
         self.write_size += block.len() as u32;
         Ok(())
     }
+
+    /// Pads and encrypts the remaining data and returns the underlying writer.
+    pub(crate) fn finish(mut self) -> std::io::Result<W>
+    where
+        W: Write,
+    {
+        if !self.buffer.is_empty() {
+            assert!(self.buffer.len() < 16);
+            let mut block = [0u8; 16];
+            block[..self.buffer.len()].copy_from_slice(&self.buffer);
+            self.write_block(&mut block)?;
+        }
+        Ok(self.output)
+    }
 }
 
 #[cfg(feature = "compress")]
 impl<W: Write> Write for Aes256Sha256Encoder<W> {
     fn write(&mut self, mut buf: &[u8]) -> std::io::Result<usize> {
-        if self.finished && !buf.is_empty() {
-            return Ok(0);
-        }
-        if buf.is_empty() {
-            self.finished = true;
-            self.flush()?;
-            return self.output.write(buf);
-        }
         let len = buf.len();
         if !self.buffer.is_empty() {
             assert!(self.buffer.len() < 16);
@@ -327,13 +458,7 @@ impl<W: Write> Write for Aes256Sha256Encoder<W> {
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        if !self.buffer.is_empty() && self.finished {
-            assert!(self.buffer.len() < 16);
-            let mut block = [0u8; 16];
-            block[..self.buffer.len()].copy_from_slice(&self.buffer);
-            self.write_block(&mut block)?;
-            self.buffer.clear();
-        }
+        // Only whole blocks can be encrypted, the rest is padded and written by `finish`.
         Ok(())
     }
 }
@@ -353,14 +478,103 @@ mod tests {
         let mut enc = Aes256Sha256Encoder::new(writer, &options).unwrap();
         let original = include_bytes!("aes.rs");
         enc.write_all(original).expect("encode data");
-        let _ = enc.write(&[]).unwrap();
+        enc.finish().unwrap();
 
         let mut encoded_data = &encoded[..];
         let mut dec =
-            Aes256Sha256Decoder::new(&mut encoded_data, &options.properties(), &password).unwrap();
+            AesDecoder::new(&mut encoded_data, &options.properties(), &password, 8).unwrap();
 
         let mut decoded = vec![];
         let _ = std::io::copy(&mut dec, &mut decoded).unwrap();
         assert_eq!(&decoded[..original.len()], &original[..]);
     }
+
+    fn encode(data: &[u8], options: &AesEncoderOptions) -> Vec<u8> {
+        let mut encoded = vec![];
+        let mut enc = Aes256Sha256Encoder::new(&mut encoded, options).unwrap();
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap();
+        encoded
+    }
+
+    #[test]
+    fn test_rar29_aes_key() {
+        // The expected keys were computed with a Python port of the in-place SHA-1 and the key
+        // derivation of unrar 3.x (`hash_process` and `SetCryptKeys`), as no RAR tools were
+        // available. The long password spans more than one SHA-1 block, where the expanded
+        // message is written back to the password.
+        let salt = [1, 2, 3, 4, 5, 6, 7, 8];
+        for (password, key, iv) in [
+            (
+                "password",
+                "413960312dec09cdfb250251fe1be37c",
+                "e32ca60bca0ab1c28908804ee237a3a8",
+            ),
+            (
+                "This RAR password is longer than one SHA-1 block",
+                "cb366d4bcd13285ccaa09b526dc89382",
+                "de61bcd19f25047125b60d78e4e82564",
+            ),
+        ] {
+            let password = Password::from(password);
+            let (aes_key, aes_iv) = get_rar29_aes_key(&salt, password.as_slice()).unwrap();
+            let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+            assert_eq!(hex(&aes_key), key);
+            assert_eq!(hex(&aes_iv), iv);
+        }
+    }
+
+    #[test]
+    fn test_aes_rejects_key_derivation_power_above_limit() {
+        let password: Password = "1234".into();
+        let mut options = AesEncoderOptions::new(password.clone());
+        options.num_cycles_power = 40;
+        let props = options.properties();
+        let result = AesDecoder::new(
+            &[][..],
+            &props,
+            &password,
+            crate::decoder::MAX_KEY_DERIVATION_POWER,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_aes_decoder_seek() {
+        let password: Password = "1234".into();
+        let options = AesEncoderOptions::new(password.clone());
+        let original: Vec<u8> = (0..4096u32).map(|i| (i * 7 % 251) as u8).collect();
+        let encoded = encode(&original, &options);
+        let mut dec =
+            AesDecoder::new(encoded.as_slice(), &options.properties(), &password, 8).unwrap();
+
+        let mut buf = [0u8; 10];
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, original[..10]);
+
+        // Within the decrypted buffer.
+        assert_eq!(dec.seek(SeekFrom::Current(20)).unwrap(), 30);
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, original[30..40]);
+
+        // Beyond the decrypted buffer.
+        assert_eq!(dec.seek(SeekFrom::Start(3000)).unwrap(), 3000);
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, original[3000..3010]);
+        assert_eq!(dec.stream_position().unwrap(), 3010);
+
+        // Backwards.
+        let err = dec.seek(SeekFrom::Start(100)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        let err = dec.seek(SeekFrom::Current(-1)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        assert!(dec.seek(SeekFrom::End(0)).is_err());
+        // Failed seeks don't move the position.
+        dec.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, original[3010..3020]);
+
+        // Past the end.
+        let err = dec.seek(SeekFrom::Start(5000)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
 }
@@ -12,10 +12,15 @@ pub enum Error {
         /// Minor version number.
         minor: u8,
     },
-    /// Checksum verification failed during decompression.
+    /// The CRC of decoded data doesn't match the CRC stored in the archive.
     ChecksumVerificationFailed,
-    /// Next header CRC mismatch.
-    NextHeaderCrcMismatch,
+    /// The CRC of the start header or of the next header doesn't match the stored CRC.
+    HeaderCrcMismatch {
+        /// The CRC stored in the archive.
+        expected: u32,
+        /// The CRC of the header data.
+        actual: u32,
+    },
     /// IO error with optional context message.
     Io(std::io::Error, Cow<'static, str>),
     /// Error opening file.
@@ -32,7 +37,7 @@ pub enum Error {
     BadTerminatedSubStreamsInfo,
     /// Bad terminated header.
     BadTerminatedHeader(u8),
-    /// External compression method not supported.
+    /// Externally stored block descriptions are not supported.
     ExternalUnsupported,
     /// Unsupported compression method.
     UnsupportedCompressionMethod(String),
@@ -51,6 +56,77 @@ pub enum Error {
     MaybeBadPassword(std::io::Error),
     /// File not found.
     FileNotFound,
+    /// A value read from the archive header exceeds the configured [`crate::ParseLimits`].
+    LimitExceeded {
+        /// Name of the exceeded limit.
+        limit: &'static str,
+        /// The value found in the archive.
+        value: u64,
+        /// The maximal allowed value.
+        max: u64,
+    },
+    /// Entry names that would be extracted outside of the destination directory.
+    UnsafeEntryNames(Vec<String>),
+    /// A coder with a method ID that is not known.
+    UnsupportedCoder {
+        /// The method ID of the coder.
+        id: Vec<u8>,
+    },
+    /// The archive header is malformed.
+    CorruptHeader {
+        /// The part of the header that is malformed, like `"files info"`.
+        section: &'static str,
+        /// Description of the problem.
+        detail: String,
+    },
+    /// The password is wrong. Returned if decrypting the header of an archive with an encrypted
+    /// header fails, since the header is protected by a CRC.
+    WrongPassword,
+    /// A block index is not smaller than the number of blocks in the archive.
+    BlockIndexOutOfBounds {
+        /// The requested block index.
+        index: usize,
+        /// The number of blocks in the archive.
+        block_count: usize,
+    },
+    /// A block refers to a packed stream that the archive doesn't contain.
+    PackStreamIndexOutOfBounds {
+        /// The index of the missing packed stream.
+        index: usize,
+    },
+    /// The header contains a property that is not supported.
+    UnsupportedProperty {
+        /// The ID of the property.
+        nid: u8,
+    },
+    /// The file names property has a length that can't hold UTF-16 names.
+    InvalidFileNamesLength {
+        /// The length of the property in bytes.
+        length: usize,
+    },
+    /// An entry with the same name was already added to the archive. Returned by the archive
+    /// writer if duplicate entries are rejected.
+    DuplicateEntry(String),
+    /// An entry that was passed to a block decoder is not stored in its block.
+    EntryNotInBlock {
+        /// The index of the entry in the archive.
+        file_index: usize,
+        /// The index of the block.
+        block_index: usize,
+    },
+    /// A checkpoint passed to a block decoder doesn't match the position of an entry in its
+    /// block.
+    CheckpointMismatch {
+        /// The index of the entry that should be resumed.
+        file_index: usize,
+        /// The index of the block.
+        block_index: usize,
+    },
+    /// A block ended before the data of all of its entries was decoded.
+    UnexpectedEndOfBlock {
+        /// The index of the block.
+        block_index: usize,
+    },
 }
 
 impl From<std::io::Error> for Error {
@@ -70,11 +146,27 @@ impl Error {
         Self::Unsupported(s.into())
     }
 
+    #[inline]
+    pub(crate) fn corrupt_header(section: &'static str, detail: impl Into<String>) -> Self {
+        Self::CorruptHeader {
+            section,
+            detail: detail.into(),
+        }
+    }
+
     #[inline]
     pub(crate) fn io_msg(e: std::io::Error, msg: impl Into<Cow<'static, str>>) -> Self {
         Self::Io(e, msg.into())
     }
 
+    #[inline]
+    pub(crate) fn check_limit(limit: &'static str, value: u64, max: u64) -> Result<(), Self> {
+        if value > max {
+            return Err(Self::LimitExceeded { limit, value, max });
+        }
+        Ok(())
+    }
+
     pub(crate) fn bad_password(e: std::io::Error, encryped: bool) -> Self {
         if encryped {
             Self::MaybeBadPassword(e)
@@ -100,10 +192,75 @@ impl Error {
     }
 }
 
+impl Error {
+    /// Returns whether the error is caused by damaged or malformed archive data, like a wrong
+    /// signature, a checksum mismatch or a corrupt header.
+    pub fn is_corruption(&self) -> bool {
+        matches!(
+            self,
+            Self::BadSignature(_)
+                | Self::ChecksumVerificationFailed
+                | Self::HeaderCrcMismatch { .. }
+                | Self::BadTerminatedStreamsInfo(_)
+                | Self::BadTerminatedUnpackInfo
+                | Self::BadTerminatedPackInfo(_)
+                | Self::BadTerminatedSubStreamsInfo
+                | Self::BadTerminatedHeader(_)
+                | Self::CorruptHeader { .. }
+                | Self::PackStreamIndexOutOfBounds { .. }
+                | Self::InvalidFileNamesLength { .. }
+                | Self::UnexpectedEndOfBlock { .. }
+        )
+    }
+
+    /// Returns whether the error is caused by a feature of the archive that this crate doesn't
+    /// support, like an unknown coder.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(
+            self,
+            Self::UnsupportedVersion { .. }
+                | Self::ExternalUnsupported
+                | Self::UnsupportedCompressionMethod(_)
+                | Self::Unsupported(_)
+                | Self::UnsupportedCoder { .. }
+                | Self::UnsupportedProperty { .. }
+        )
+    }
+
+    /// Returns whether the error was returned by the underlying reader or writer.
+    pub fn is_io(&self) -> bool {
+        matches!(self, Self::Io(..) | Self::FileOpen(..))
+    }
+
+    /// Returns whether retrying the operation could succeed, because the underlying reader or
+    /// writer failed with an error like [`std::io::ErrorKind::Interrupted`] or
+    /// [`std::io::ErrorKind::TimedOut`].
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Io(e, _) | Self::FileOpen(e, _) => matches!(
+                e.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::WouldBlock
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            ),
+            _ => false,
+        }
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Debug::fmt(&self, f)
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e, _) | Self::FileOpen(e, _) | Self::MaybeBadPassword(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -15,10 +15,15 @@
 //! | DEFLATE (*)    | ✓             | ✓           |
 //! | PPMD           | ✓             | ✓           |
 //! | LZ4 (*)        | ✓             | ✓           |
+//! | SNAPPY (**)    | ✓             | ✓           |
 //! | ZSTD (*)       | ✓             | ✓           |
 //!
 //! (*) Require optional cargo feature.
 //!
+//! (**) Requires the optional `snappy` cargo feature. Snappy has no official 7z method ID, so this
+//! crate uses the unofficial ID `04 F7 11 07`. Other 7z implementations will most likely not be
+//! able to read these archives.
+//!
 //! | Filter        | Decompression | Compression |
 //! |---------------|---------------|-------------|
 //! | BCJ X86       | ✓             | ✓           |
@@ -52,12 +57,17 @@ mod writer;
 pub(crate) mod archive;
 pub(crate) mod bitset;
 pub(crate) mod block;
-mod codec;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(not(feature = "codec"))]
+pub(crate) mod codec;
 pub(crate) mod decoder;
 
 mod time;
 #[cfg(feature = "util")]
 mod util;
+#[cfg(not(target_arch = "wasm32"))]
+mod volume;
 
 use std::{
     io::{Read, Write},
@@ -68,14 +78,22 @@ pub use archive::*;
 pub use block::*;
 pub use encryption::Password;
 pub use error::Error;
-pub use reader::{ArchiveReader, BlockDecoder};
-pub use time::NtTime;
+pub use reader::{
+    ArchiveReader, BlockDecoder, DecoderCheckpoint, EntryHandle, SharedArchiveReader,
+};
+pub use time::{NtTime, NtTimeError};
 #[cfg(all(feature = "compress", feature = "util", not(target_arch = "wasm32")))]
 pub use util::compress::*;
 #[cfg(all(feature = "util", not(target_arch = "wasm32")))]
+pub use util::convert::*;
+#[cfg(all(feature = "util", not(target_arch = "wasm32")))]
 pub use util::decompress::*;
+#[cfg(all(feature = "compress", feature = "util", not(target_arch = "wasm32")))]
+pub use util::merge::*;
 #[cfg(all(feature = "util", target_arch = "wasm32"))]
 pub use util::wasm::*;
+#[cfg(not(target_arch = "wasm32"))]
+pub use volume::*;
 #[cfg(feature = "compress")]
 pub use writer::*;
 
@@ -161,18 +179,38 @@ impl<T: Write> ByteWriter for T {
 
 /// A trait for writers that finishes the stream on drop.
 trait AutoFinish {
-    /// Finish writing the stream without error handling.
-    fn finish_ignore_error(self);
+    /// Finish writing the stream.
+    fn finish_on_drop(self) -> std::io::Result<()>;
+}
+
+thread_local! {
+    static AUTO_FINISH_ERROR: std::cell::RefCell<Option<std::io::Error>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Returns the error of the last [`AutoFinisher`] on this thread that failed to finish its
+/// stream when it was dropped, and clears it.
+///
+/// Errors can't be returned from a drop, so call this after the finisher went out of scope to
+/// check that the stream is complete.
+pub fn take_auto_finish_error() -> Option<std::io::Error> {
+    AUTO_FINISH_ERROR.with(|error| error.borrow_mut().take())
 }
 
 /// A wrapper around a writer that finishes the stream on drop.
+///
+/// The stream is also finished if the thread panics, so that the data written so far is kept.
+/// Since errors can't be returned from a drop, they are stored and can be retrieved with
+/// [`take_auto_finish_error`].
 #[allow(private_bounds)]
 pub struct AutoFinisher<T: AutoFinish>(Option<T>);
 
 impl<T: AutoFinish> Drop for AutoFinisher<T> {
     fn drop(&mut self) {
         if let Some(writer) = self.0.take() {
-            writer.finish_ignore_error();
+            if let Err(e) = writer.finish_on_drop() {
+                AUTO_FINISH_ERROR.with(|error| *error.borrow_mut() = Some(e));
+            }
         }
     }
 }
@@ -3,19 +3,28 @@ use std::{
     collections::HashMap,
     fs::File,
     io,
-    io::{Read, Seek, SeekFrom},
+    io::{Cursor, Read, Seek, SeekFrom, Write},
     num::NonZeroUsize,
+    ops::{ControlFlow, Deref},
     rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
 };
 
 use crc32fast::Hasher;
 use lzma_rust2::filter::bcj2::Bcj2Reader;
 
 use crate::{
-    ByteReader, Password, archive::*, bitset::BitSet, block::*, decoder::add_decoder, error::Error,
+    ByteReader, Password,
+    archive::*,
+    bitset::BitSet,
+    block::*,
+    decoder::{DEFAULT_MAX_KEY_DERIVATION_POWER, MAX_KEY_DERIVATION_POWER, add_decoder},
+    error::Error,
 };
 
 const MAX_MEM_LIMIT_KB: usize = usize::MAX / 1024;
+/// Maximal size of the data in front of the signature of a self-extracting archive.
+const MAX_SFX_STUB_SIZE: u64 = 4 * 1024 * 1024;
 
 pub struct BoundedReader<R: Read> {
     inner: R,
@@ -43,16 +52,45 @@ impl<R: Read> Read for BoundedReader<R> {
     }
 }
 
+/// Reader that hides the first `base` bytes of its inner reader, so that an archive preceded by
+/// other data appears to start at position 0.
+struct OffsetReader<R> {
+    inner: R,
+    base: u64,
+}
+
+impl<R: Read> Read for OffsetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for OffsetReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => SeekFrom::Start(self.base.saturating_add(pos)),
+            pos => pos,
+        };
+        let position = self.inner.seek(pos)?;
+        position.checked_sub(self.base).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a position before the archive",
+            )
+        })
+    }
+}
+
 /// A special reader that shares it's inner reader with other instances and
 /// needs to re-seek every read operation.
 #[derive(Debug)]
-pub(crate) struct SharedBoundedReader<'a, R> {
-    inner: Rc<RefCell<&'a mut R>>,
+pub(crate) struct SharedBoundedReader<R> {
+    inner: Rc<RefCell<R>>,
     cur: u64,
     bounds: (u64, u64),
 }
 
-impl<'a, R> Clone for SharedBoundedReader<'a, R> {
+impl<R> Clone for SharedBoundedReader<R> {
     fn clone(&self) -> Self {
         Self {
             inner: Rc::clone(&self.inner),
@@ -62,7 +100,7 @@ impl<'a, R> Clone for SharedBoundedReader<'a, R> {
     }
 }
 
-impl<'a, R: Read + Seek> Seek for SharedBoundedReader<'a, R> {
+impl<R: Read + Seek> Seek for SharedBoundedReader<R> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         let new_pos = match pos {
             SeekFrom::Start(pos) => self.bounds.0 as i64 + pos as i64,
@@ -77,25 +115,23 @@ impl<'a, R: Read + Seek> Seek for SharedBoundedReader<'a, R> {
     }
 }
 
-impl<'a, R: Read + Seek> Read for SharedBoundedReader<'a, R> {
+impl<R: Read + Seek> Read for SharedBoundedReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if self.cur >= self.bounds.1 {
             return Ok(0);
         }
 
+        let bound = (buf.len() as u64).min(self.bounds.1 - self.cur) as usize;
         let mut inner = self.inner.borrow_mut();
-
         inner.seek(SeekFrom::Start(self.cur))?;
-
-        let bound = buf.len().min((self.bounds.1 - self.cur) as usize);
         let size = inner.read(&mut buf[..bound])?;
         self.cur += size as u64;
         Ok(size)
     }
 }
 
-impl<'a, R: Read + Seek> SharedBoundedReader<'a, R> {
-    fn new(inner: Rc<RefCell<&'a mut R>>, bounds: (u64, u64)) -> Self {
+impl<R: Read + Seek> SharedBoundedReader<R> {
+    fn new(inner: Rc<RefCell<R>>, bounds: (u64, u64)) -> Self {
         Self {
             inner,
             cur: bounds.0,
@@ -142,6 +178,44 @@ impl<R: Read> Read for Crc32VerifyingReader<R> {
     }
 }
 
+/// Verifies the BLAKE3 hash of an entry once all of its data was read, like
+/// [`Crc32VerifyingReader`].
+#[cfg(feature = "blake3-checksum")]
+struct Blake3VerifyingReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+    expected_value: [u8; 32],
+    remaining: usize,
+}
+
+#[cfg(feature = "blake3-checksum")]
+impl<R: Read> Blake3VerifyingReader<R> {
+    fn new(inner: R, remaining: usize, expected_value: [u8; 32]) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+            expected_value,
+            remaining,
+        }
+    }
+}
+
+#[cfg(feature = "blake3-checksum")]
+impl<R: Read> Read for Blake3VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let size = self.inner.read(buf)?;
+        self.hasher.update(&buf[..size]);
+        self.remaining = self.remaining.saturating_sub(size);
+        if self.remaining == 0 && *self.hasher.finalize().as_bytes() != self.expected_value {
+            return Err(std::io::Error::other(Error::ChecksumVerificationFailed));
+        }
+        Ok(size)
+    }
+}
+
 impl Archive {
     /// Open 7z file under specified `path`.
     #[inline]
@@ -189,9 +263,191 @@ impl Archive {
     /// }
     /// ```
     pub fn read<R: Read + Seek>(reader: &mut R, password: &Password) -> Result<Archive, Error> {
+        Self::read_with_limits(reader, password, &ParseLimits::default())
+    }
+
+    /// Same as [`Archive::read`], but applies the given [`ParseLimits`] instead of the default
+    /// limits while parsing the header.
+    ///
+    /// # Parameters
+    /// - `reader`   - the reader of the 7z file archive
+    /// - `password` - archive password encoded in utf16 little endian
+    /// - `limits`   - the limits to apply while parsing the header
+    pub fn read_with_limits<R: Read + Seek>(
+        reader: &mut R,
+        password: &Password,
+        limits: &ParseLimits,
+    ) -> Result<Archive, Error> {
+        let options = ArchiveReadOptions::default().with_limits(*limits);
+        Self::read_with_options(reader, password, &options)
+    }
+
+    /// Same as [`Archive::read`], but reads the header with the given [`ArchiveReadOptions`].
+    ///
+    /// # Parameters
+    /// - `reader`   - the reader of the 7z file archive
+    /// - `password` - archive password encoded in utf16 little endian
+    /// - `options`  - the options to read the header with
+    pub fn read_with_options<R: Read + Seek>(
+        reader: &mut R,
+        password: &Password,
+        options: &ArchiveReadOptions,
+    ) -> Result<Archive, Error> {
         let reader_len = reader.seek(SeekFrom::End(0))?;
         reader.seek(SeekFrom::Start(0))?;
+        let start_header_crc = Self::read_signature(reader)?;
+        let start_header_pos = reader.stream_position()?;
+
+        let header_valid = if start_header_crc == 0 {
+            let mut buf = [0; 20];
+            reader.read_exact(&mut buf)?;
+            reader.seek(SeekFrom::Start(start_header_pos))?;
+            buf.iter().any(|a| *a != 0)
+        } else {
+            true
+        };
+        if header_valid {
+            let start_header = Self::read_start_header(reader, start_header_crc)?;
+            if start_header.next_header_size == 0 {
+                if reader_len <= SIGNATURE_HEADER_SIZE {
+                    return Ok(Archive::default());
+                }
+                // The writer points to an empty header until the archive is finished, so
+                // there is data, but the archive wasn't finished.
+                reader.seek(SeekFrom::Start(start_header_pos))?;
+                return Self::try_to_locale_end_header(reader, reader_len, password, 1, options);
+            }
+            Self::init_archive(reader, start_header, password, true, 1, options)
+        } else {
+            Self::try_to_locale_end_header(reader, reader_len, password, 1, options)
+        }
+    }
+
+    /// Same as [`Archive::read`], but also reads archives that are preceded by other data, like
+    /// the executable stub of a self-extracting archive.
+    ///
+    /// The first 4 MiB of the reader are searched for the 7z signature followed by a valid start
+    /// header. The position of the signature is available from [`Archive::base_offset`].
+    ///
+    /// # Parameters
+    /// - `reader`   - the reader of the 7z file archive
+    /// - `password` - archive password encoded in utf16 little endian
+    pub fn read_sfx<R: Read + Seek>(reader: &mut R, password: &Password) -> Result<Archive, Error> {
+        Self::read_sfx_with_options(reader, password, &ArchiveReadOptions::default())
+    }
+
+    fn read_sfx_with_options<R: Read + Seek>(
+        reader: &mut R,
+        password: &Password,
+        options: &ArchiveReadOptions,
+    ) -> Result<Archive, Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let mut prefix = Vec::new();
+        reader
+            .take(MAX_SFX_STUB_SIZE + SIGNATURE_HEADER_SIZE)
+            .read_to_end(&mut prefix)?;
+
+        let signature_len = SEVEN_Z_SIGNATURE.len();
+        let base_offset =
+            prefix
+                .windows(SIGNATURE_HEADER_SIZE as usize)
+                .position(|signature_header| {
+                    signature_header.starts_with(SEVEN_Z_SIGNATURE) && {
+                        let crc = u32::from_le_bytes(
+                            signature_header[signature_len + 2..signature_len + 6]
+                                .try_into()
+                                .unwrap(),
+                        );
+                        crc != 0 && crc == crc32fast::hash(&signature_header[signature_len + 6..])
+                    }
+                });
+        let Some(base_offset) = base_offset else {
+            let mut signature = [0; 6];
+            let len = prefix.len().min(signature.len());
+            signature[..len].copy_from_slice(&prefix[..len]);
+            return Err(Error::BadSignature(signature));
+        };
+
+        let base_offset = base_offset as u64;
+        let mut reader = OffsetReader {
+            inner: reader,
+            base: base_offset,
+        };
+        let mut archive = Self::read_with_options(&mut reader, password, options)?;
+        archive.base_offset = base_offset;
+        Ok(archive)
+    }
+
+    /// Verifies the CRC32 of every packed stream that has a CRC defined in the archive.
+    ///
+    /// Only the raw compressed data is read, nothing is decompressed. Returns `false` if any
+    /// packed stream doesn't match its CRC.
+    ///
+    /// # Parameters
+    /// - `source` - the reader of the 7z file archive
+    pub fn verify_pack_crcs<R: Read + Seek>(&self, source: &mut R) -> Result<bool, Error> {
+        Ok(self.verify_pack_streams(source)?.is_empty())
+    }
+
+    /// Verifies the CRC32 of every packed stream that has a CRC defined in the archive and
+    /// returns the indices of the packed streams that don't match.
+    ///
+    /// Only the raw compressed data is read, nothing is decompressed. Packed streams without a
+    /// CRC are not checked.
+    ///
+    /// # Parameters
+    /// - `source` - the reader of the 7z file archive
+    pub fn verify_pack_streams<R: Read + Seek>(&self, source: &mut R) -> Result<Vec<usize>, Error> {
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut mismatches = Vec::new();
+        for (index, (offset, length)) in self.pack_stream_ranges().into_iter().enumerate() {
+            let Some(expected_crc) = self.pack_crc(index) else {
+                continue;
+            };
+            source.seek(SeekFrom::Start(offset))?;
+            let mut hasher = crc32fast::Hasher::new();
+            let mut remaining = length;
+            while remaining > 0 {
+                let n = remaining.min(buf.len() as u64) as usize;
+                source.read_exact(&mut buf[..n])?;
+                hasher.update(&buf[..n]);
+                remaining -= n as u64;
+            }
+            if hasher.finalize() != expected_crc {
+                mismatches.push(index);
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Returns whether the header of the archive is encrypted, so that the entries can't be
+    /// listed without the password.
+    ///
+    /// No password is needed: the header is encrypted if it is encoded and the coders of the
+    /// encoded header contain AES. Archives whose header is readable may still contain encrypted
+    /// entries, see [`ArchiveReader::entry_is_encrypted`].
+    ///
+    /// # Parameters
+    /// - `reader` - the reader of the 7z file archive
+    pub fn header_encrypted<R: Read + Seek>(reader: &mut R) -> Result<bool, Error> {
+        reader.seek(SeekFrom::Start(0))?;
+        let start_header_crc = Self::read_signature(reader)?;
+        let start_header = Self::read_start_header(reader, start_header_crc)?;
+        let limits = ParseLimits::default();
+        let buf = Self::read_next_header(reader, &start_header, true, &limits)?;
 
+        let mut header = buf.as_slice();
+        if header.read_u8()? != K_ENCODED_HEADER {
+            return Ok(false);
+        }
+        let mut archive = Archive::default();
+        Self::read_streams_info(&mut header, &mut archive, &limits)?;
+        Ok(archive.blocks.iter().any(Block::is_encrypted))
+    }
+
+    /// Reads the signature and the version of the archive and returns the CRC of the start
+    /// header.
+    fn read_signature<R: Read>(reader: &mut R) -> Result<u32, Error> {
         let mut signature = [0; 6];
         reader.read_exact(&mut signature)?;
         if signature != SEVEN_Z_SIGNATURE {
@@ -208,23 +464,7 @@ impl Archive {
             });
         }
 
-        let start_header_crc = reader.read_u32()?;
-
-        let header_valid = if start_header_crc == 0 {
-            let current_position = reader.stream_position()?;
-            let mut buf = [0; 20];
-            reader.read_exact(&mut buf)?;
-            reader.seek(SeekFrom::Start(current_position))?;
-            buf.iter().any(|a| *a != 0)
-        } else {
-            true
-        };
-        if header_valid {
-            let start_header = Self::read_start_header(reader, start_header_crc)?;
-            Self::init_archive(reader, start_header, password, true, 1)
-        } else {
-            Self::try_to_locale_end_header(reader, reader_len, password, 1)
-        }
+        Ok(reader.read_u32()?)
     }
 
     fn read_start_header<R: Read>(
@@ -235,7 +475,10 @@ impl Archive {
         reader.read_exact(&mut buf)?;
         let crc32 = crc32fast::hash(&buf);
         if crc32 != start_header_crc {
-            return Err(Error::ChecksumVerificationFailed);
+            return Err(Error::HeaderCrcMismatch {
+                expected: start_header_crc,
+                actual: crc32,
+            });
         }
         let mut buf_read = buf.as_slice();
         let offset = buf_read.read_u64()?;
@@ -249,22 +492,40 @@ impl Archive {
         })
     }
 
-    fn read_header<R: Read + Seek>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_header<R: Read + Seek, RI: Read + Seek>(
+        header: &mut R,
+        reader: &mut RI,
+        archive: &mut Archive,
+        password: &Password,
+        thread_count: u32,
+        options: &ArchiveReadOptions,
+    ) -> Result<(), Error> {
+        let limits = &options.limits;
         let mut nid = header.read_u8()?;
         if nid == K_ARCHIVE_PROPERTIES {
-            Self::read_archive_properties(header)?;
+            Self::read_archive_properties(header, archive, options)?;
             nid = header.read_u8()?;
         }
 
+        let mut additional_data = Vec::new();
         if nid == K_ADDITIONAL_STREAMS_INFO {
-            return Err(Error::other("Additional streams unsupported"));
+            // The additional streams use their own pack info and blocks, so they are parsed
+            // separately to not mix them up with the main streams of the entries.
+            let mut additional = Archive::default();
+            Self::read_streams_info(header, &mut additional, limits)?;
+            additional_data =
+                Self::read_additional_streams(reader, &additional, password, thread_count, limits)?;
+            archive.additional_pack_pos = additional.pack_pos;
+            archive.additional_pack_sizes = additional.pack_sizes;
+            archive.additional_streams = Some(additional.blocks);
+            nid = header.read_u8()?;
         }
         if nid == K_MAIN_STREAMS_INFO {
-            Self::read_streams_info(header, archive)?;
+            Self::read_streams_info(header, archive, limits)?;
             nid = header.read_u8()?;
         }
         if nid == K_FILES_INFO {
-            Self::read_files_info(header, archive)?;
+            Self::read_files_info(header, archive, &additional_data, options)?;
             nid = header.read_u8()?;
         }
         if nid != K_END {
@@ -274,11 +535,73 @@ impl Archive {
         Ok(())
     }
 
-    fn read_archive_properties<R: Read + Seek>(header: &mut R) -> Result<(), Error> {
+    /// Decodes the blocks of the additional streams, which hold the data of externally stored
+    /// file properties.
+    fn read_additional_streams<RI: Read + Seek>(
+        reader: &mut RI,
+        additional: &Archive,
+        password: &Password,
+        thread_count: u32,
+        limits: &ParseLimits,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let mut total_size = 0u64;
+        let mut additional_data = Vec::with_capacity(additional.blocks.len());
+        for (block_index, block) in additional.blocks.iter().enumerate() {
+            let unpack_size = block.get_unpack_size();
+            total_size = total_size.saturating_add(unpack_size);
+            Error::check_limit("header size", total_size, limits.max_header_size)?;
+            let mut decoder = Self::encoded_header_block_decoder(
+                reader,
+                additional,
+                block_index,
+                password,
+                thread_count,
+            )?;
+            let mut data = vec![0; assert_usize(unpack_size, "additional stream size")?];
+            decoder
+                .read_exact(&mut data)
+                .map_err(|e| Error::bad_password(e, !password.is_empty()))?;
+            additional_data.push(data);
+        }
+        Ok(additional_data)
+    }
+
+    fn read_archive_properties<R: Read + Seek>(
+        header: &mut R,
+        archive: &mut Archive,
+        options: &ArchiveReadOptions,
+    ) -> Result<(), Error> {
         let mut nid = header.read_u8()?;
         while nid != K_END {
             let property_size = read_variable_usize(header, "propertySize")?;
-            header.seek(SeekFrom::Current(property_size as i64))?;
+            if nid == K_COMMENT {
+                if property_size & 1 != 0 {
+                    return Err(Error::corrupt_header(
+                        "archive comment",
+                        format!("Invalid length {property_size}"),
+                    ));
+                }
+                Error::check_limit(
+                    "comment bytes",
+                    property_size as u64,
+                    options.limits.max_name_bytes as u64,
+                )?;
+                let mut data = vec![0; property_size];
+                header.read_exact(&mut data)?;
+                let comment = NamesReader::new(
+                    &mut data.as_slice(),
+                    property_size,
+                    "archive comment",
+                    options.strict_names,
+                )
+                .next()
+                .transpose()?;
+                archive.comment = comment
+                    .map(|(comment, _)| comment)
+                    .filter(|comment| !comment.is_empty());
+            } else {
+                header.seek(SeekFrom::Current(property_size as i64))?;
+            }
             nid = header.read_u8()?;
         }
         Ok(())
@@ -289,6 +612,7 @@ impl Archive {
         reader_len: u64,
         password: &Password,
         thread_count: u32,
+        options: &ArchiveReadOptions,
     ) -> Result<Self, Error> {
         let search_limit = 1024 * 1024;
         let prev_data_size = reader.stream_position()? + 20;
@@ -310,34 +634,39 @@ impl Archive {
                     next_header_size: reader_len - pos,
                     next_header_crc: 0,
                 };
-                let result =
-                    Self::init_archive(reader, start_header, password, false, thread_count)?;
+                let result = Self::init_archive(
+                    reader,
+                    start_header,
+                    password,
+                    false,
+                    thread_count,
+                    options,
+                )?;
 
                 if !result.files.is_empty() {
                     return Ok(result);
                 }
             }
         }
-        Err(Error::other(
+        Err(Error::corrupt_header(
+            "start header",
             "Start header corrupt and unable to guess end header",
         ))
     }
 
-    fn init_archive<R: Read + Seek>(
+    /// Reads the (possibly encoded) header that the start header points to.
+    fn read_next_header<R: Read + Seek>(
         reader: &mut R,
-        start_header: StartHeader,
-        password: &Password,
+        start_header: &StartHeader,
         verify_crc: bool,
-        thread_count: u32,
-    ) -> Result<Self, Error> {
-        if start_header.next_header_size > usize::MAX as u64 {
-            return Err(Error::other(format!(
-                "Cannot handle next_header_size {}",
-                start_header.next_header_size
-            )));
-        }
-
-        let next_header_size_int = start_header.next_header_size as usize;
+        limits: &ParseLimits,
+    ) -> Result<Vec<u8>, Error> {
+        Error::check_limit(
+            "header size",
+            start_header.next_header_size,
+            limits.max_header_size,
+        )?;
+        let next_header_size_int = assert_usize(start_header.next_header_size, "next_header_size")?;
 
         reader.seek(SeekFrom::Start(
             SIGNATURE_HEADER_SIZE + start_header.next_header_offset,
@@ -345,26 +674,58 @@ impl Archive {
 
         let mut buf = vec![0; next_header_size_int];
         reader.read_exact(&mut buf)?;
-        if verify_crc && crc32fast::hash(&buf) as u64 != start_header.next_header_crc {
-            return Err(Error::NextHeaderCrcMismatch);
+        if verify_crc {
+            let crc32 = crc32fast::hash(&buf);
+            if crc32 as u64 != start_header.next_header_crc {
+                return Err(Error::HeaderCrcMismatch {
+                    expected: start_header.next_header_crc as u32,
+                    actual: crc32,
+                });
+            }
         }
+        Ok(buf)
+    }
+
+    fn init_archive<R: Read + Seek>(
+        reader: &mut R,
+        start_header: StartHeader,
+        password: &Password,
+        verify_crc: bool,
+        thread_count: u32,
+        options: &ArchiveReadOptions,
+    ) -> Result<Self, Error> {
+        let limits = &options.limits;
+        let mut buf = Self::read_next_header(reader, &start_header, verify_crc, limits)?;
 
         let mut archive = Archive::default();
         let mut buf_reader = buf.as_slice();
         let mut nid = buf_reader.read_u8()?;
         let mut header = if nid == K_ENCODED_HEADER {
-            let (mut out_reader, buf_size) = Self::read_encoded_header(
+            let decoded = Self::read_encoded_header(
                 &mut buf_reader,
                 reader,
                 &mut archive,
                 password,
                 thread_count,
-            )?;
-            buf.clear();
-            buf.resize(buf_size, 0);
-            out_reader
-                .read_exact(&mut buf)
-                .map_err(|e| Error::bad_password(e, !password.is_empty()))?;
+                limits,
+            )
+            .and_then(|(mut out_reader, buf_size)| {
+                Error::check_limit("header size", buf_size as u64, limits.max_header_size)?;
+                buf.clear();
+                buf.resize(buf_size, 0);
+                out_reader
+                    .read_exact(&mut buf)
+                    .map_err(|e| Error::bad_password(e, !password.is_empty()))
+            });
+            if let Err(e) = decoded {
+                let encrypted = archive.blocks.iter().any(Block::is_encrypted);
+                // The encoded header is protected by a CRC, so decoding it only fails with a
+                // wrong password, unless the archive is corrupt.
+                return Err(match e {
+                    Error::MaybeBadPassword(_) if encrypted => Error::WrongPassword,
+                    e => e,
+                });
+            }
             archive = Archive::default();
             buf_reader = buf.as_slice();
             nid = buf_reader.read_u8()?;
@@ -372,11 +733,21 @@ impl Archive {
         } else {
             buf_reader
         };
-        let mut header = std::io::Cursor::new(&mut header);
+        let mut header = Cursor::new(&mut header);
         if nid == K_HEADER {
-            Self::read_header(&mut header, &mut archive)?;
+            Self::read_header(
+                &mut header,
+                reader,
+                &mut archive,
+                password,
+                thread_count,
+                options,
+            )?;
         } else {
-            return Err(Error::other("Broken or unsupported archive: no Header"));
+            return Err(Error::corrupt_header(
+                "header",
+                format!("Expected kHeader, got {nid}"),
+            ));
         }
 
         archive.is_solid = archive
@@ -393,67 +764,104 @@ impl Archive {
         archive: &mut Archive,
         password: &Password,
         thread_count: u32,
+        limits: &ParseLimits,
     ) -> Result<(Box<dyn Read + 'r>, usize), Error> {
-        Self::read_streams_info(header, archive)?;
-        let block = archive
-            .blocks
-            .first()
-            .ok_or(Error::other("no blocks, can't read encoded header"))?;
-        let first_pack_stream_index = 0;
-        let block_offset = SIGNATURE_HEADER_SIZE + archive.pack_pos;
+        Self::read_streams_info(header, archive, limits)?;
+        // 7-Zip writes the header into a single block and rejects other layouts. Decoding only
+        // that block also keeps crafted headers from deriving a key or allocating a dictionary
+        // for every block.
+        if archive.blocks.len() != 1 {
+            return Err(Error::corrupt_header(
+                "encoded header",
+                format!("Expected one block, found {}", archive.blocks.len()),
+            ));
+        }
+        if password.is_empty() && archive.blocks[0].is_encrypted() {
+            return Err(Error::PasswordRequired);
+        }
         if archive.pack_sizes.is_empty() {
-            return Err(Error::other("no packed streams, can't read encoded header"));
-        }
-
-        reader.seek(SeekFrom::Start(block_offset))?;
-        let coder_len = block.coders.len();
-        let unpack_size = block.get_unpack_size() as usize;
-        let pack_size = archive.pack_sizes[first_pack_stream_index] as usize;
-        let input_reader = BoundedReader::new(reader, pack_size);
-        let mut decoder: Box<dyn Read> = Box::new(input_reader);
-        let mut decoder = if coder_len > 0 {
-            for (index, coder) in block.ordered_coder_iter() {
-                if coder.num_in_streams != 1 || coder.num_out_streams != 1 {
-                    return Err(Error::other(
-                        "Multi input/output stream coders are not yet supported",
-                    ));
-                }
-                let next = add_decoder(
-                    decoder,
-                    block.get_unpack_size_at_index(index) as usize,
-                    coder,
-                    password,
-                    MAX_MEM_LIMIT_KB,
-                    thread_count,
-                )?;
-                decoder = Box::new(next);
-            }
-            decoder
-        } else {
-            decoder
+            return Err(Error::corrupt_header("encoded header", "No packed streams"));
+        }
+
+        let unpack_size = assert_usize(archive.blocks[0].get_unpack_size(), "block unpack size")?;
+        let decoder =
+            Self::encoded_header_block_decoder(reader, archive, 0, password, thread_count)?;
+        Ok((decoder, unpack_size))
+    }
+
+    /// Returns a decoder for a block of the streams info of the encoded header or the additional
+    /// streams. The packed streams of the block are located like in
+    /// [`Self::calculate_stream_map`].
+    fn encoded_header_block_decoder<'r, RI: 'r + Read + Seek>(
+        reader: &'r mut RI,
+        archive: &Archive,
+        block_index: usize,
+        password: &Password,
+        thread_count: u32,
+    ) -> Result<Box<dyn Read + 'r>, Error> {
+        let pack_stream_index: usize = archive.blocks[..block_index]
+            .iter()
+            .map(|block| block.packed_streams.len())
+            .sum();
+        let Some(&pack_size) = archive.pack_sizes.get(pack_stream_index) else {
+            return Err(Error::PackStreamIndexOutOfBounds {
+                index: pack_stream_index,
+            });
         };
+        let pack_offset = archive.pack_sizes[..pack_stream_index]
+            .iter()
+            .try_fold(archive.pack_pos, |offset, &size| offset.checked_add(size))
+            .and_then(|offset| offset.checked_add(SIGNATURE_HEADER_SIZE))
+            .ok_or_else(|| Error::corrupt_header("encoded header", "Pack sizes overflow"))?;
+
+        let block = &archive.blocks[block_index];
+        reader.seek(SeekFrom::Start(pack_offset))?;
+        let unpack_size = assert_usize(block.get_unpack_size(), "block unpack size")?;
+        let mut decoder: Box<dyn Read + 'r> = Box::new(BoundedReader::new(
+            reader,
+            assert_usize(pack_size, "pack size")?,
+        ));
+        for (index, coder) in block.ordered_coder_iter() {
+            if coder.num_in_streams != 1 || coder.num_out_streams != 1 {
+                return Err(Error::unsupported(
+                    "Multi input/output stream coders are not yet supported",
+                ));
+            }
+            decoder = Box::new(add_decoder(
+                decoder,
+                assert_usize(block.get_unpack_size_at_index(index), "coder unpack size")?,
+                coder,
+                password,
+                MAX_MEM_LIMIT_KB,
+                thread_count,
+                DEFAULT_MAX_KEY_DERIVATION_POWER,
+            )?);
+        }
         if block.has_crc {
             decoder = Box::new(Crc32VerifyingReader::new(decoder, unpack_size, block.crc));
         }
-
-        Ok((decoder, unpack_size))
+        Ok(decoder)
     }
 
-    fn read_streams_info<R: Read>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_streams_info<R: Read>(
+        header: &mut R,
+        archive: &mut Archive,
+        limits: &ParseLimits,
+    ) -> Result<(), Error> {
         let mut nid = header.read_u8()?;
         if nid == K_PACK_INFO {
-            Self::read_pack_info(header, archive)?;
+            Self::read_pack_info(header, archive, limits)?;
             nid = header.read_u8()?;
         }
 
         if nid == K_UNPACK_INFO {
-            Self::read_unpack_info(header, archive)?;
+            Self::read_unpack_info(header, archive, limits)?;
             nid = header.read_u8()?;
         } else {
             archive.blocks.clear();
         }
         if nid == K_SUB_STREAMS_INFO {
-            Self::read_sub_streams_info(header, archive)?;
+            Self::read_sub_streams_info(header, archive, limits)?;
             nid = header.read_u8()?;
         }
         if nid != K_END {
@@ -463,8 +871,30 @@ impl Archive {
         Ok(())
     }
 
-    fn read_files_info<R: Read + Seek>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_files_info<R: Read + Seek>(
+        header: &mut R,
+        archive: &mut Archive,
+        additional_data: &[Vec<u8>],
+        options: &ArchiveReadOptions,
+    ) -> Result<(), Error> {
+        let limits = &options.limits;
         let num_files = read_variable_usize(header, "num files")?;
+        Error::check_limit("entries", num_files as u64, limits.max_entries as u64)?;
+        // Every file either has a sub stream or needs a bit in kEmptyStream, so a header can't
+        // describe more files than that. This keeps a crafted count from allocating the entries.
+        let num_sub_streams = archive
+            .sub_streams_info
+            .as_ref()
+            .map_or(0, |info| info.unpack_sizes.len() as u64);
+        let position = header.stream_position()?;
+        let remaining = header.seek(SeekFrom::End(0))?.saturating_sub(position);
+        header.seek(SeekFrom::Start(position))?;
+        if num_files as u64 > num_sub_streams.saturating_add(remaining.saturating_mul(8)) {
+            return Err(Error::corrupt_header(
+                "files info",
+                format!("{num_files} files don't fit into the header"),
+            ));
+        }
         let mut files: Vec<ArchiveEntry> = vec![Default::default(); num_files];
 
         let mut is_empty_stream: Option<BitSet> = None;
@@ -484,8 +914,9 @@ impl Archive {
                     let n = if let Some(s) = &is_empty_stream {
                         s.len()
                     } else {
-                        return Err(Error::other(
-                            "Header format error: kEmptyStream must appear before kEmptyFile",
+                        return Err(Error::corrupt_header(
+                            "files info",
+                            "kEmptyStream must appear before kEmptyFile",
                         ));
                     };
                     is_empty_file = Some(read_bits(header, n)?);
@@ -494,97 +925,139 @@ impl Archive {
                     let n = if let Some(s) = is_empty_stream.as_ref() {
                         s.len()
                     } else {
-                        return Err(Error::other(
-                            "Header format error: kEmptyStream must appear before kEmptyFile",
+                        return Err(Error::corrupt_header(
+                            "files info",
+                            "kEmptyStream must appear before kAnti",
                         ));
                     };
                     is_anti = Some(read_bits(header, n)?);
                 }
                 K_NAME => {
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other("Not implemented:external != 0"));
+                    let mut data = PropertyData::from_header(header, additional_data)?;
+                    let Some(names_len) = data.data_len(size).filter(|len| len & 1 == 0) else {
+                        return Err(Error::InvalidFileNamesLength {
+                            length: usize::try_from(size).unwrap_or(usize::MAX),
+                        });
+                    };
+
+                    Error::check_limit("name bytes", names_len, limits.max_name_bytes as u64)?;
+                    let names_len = assert_usize(names_len, "file names length")?;
+                    let names_reader =
+                        NamesReader::new(&mut data, names_len, "file names", options.strict_names);
+
+                    let mut next_file = 0;
+                    for s in names_reader {
+                        let file = files.get_mut(next_file).ok_or_else(|| {
+                            Error::corrupt_header("file names", "More names than files")
+                        })?;
+                        (file.name, file.raw_name) = s?;
+                        next_file += 1;
                     }
-                    if (size - 1) & 1 != 0 {
-                        return Err(Error::other("file names length invalid"));
+
+                    if next_file != files.len() {
+                        return Err(Error::corrupt_header(
+                            "file names",
+                            "Fewer names than files",
+                        ));
                     }
+                }
+                K_FILE_COMMENT => {
+                    let mut data = PropertyData::from_header(header, additional_data)?;
+                    let Some(comments_len) = data.data_len(size).filter(|len| len & 1 == 0) else {
+                        return Err(Error::corrupt_header(
+                            "file comments",
+                            format!("Invalid length {size}"),
+                        ));
+                    };
 
-                    let size = assert_usize(size, "file names length")?;
-                    // let mut names = vec![0u8; size - 1];
-                    // header.read_exact(&mut names)?;
-                    let names_reader = NamesReader::new(header, size - 1);
+                    Error::check_limit(
+                        "comment bytes",
+                        comments_len,
+                        limits.max_name_bytes as u64,
+                    )?;
+                    let comments_len = assert_usize(comments_len, "file comments length")?;
+                    let comments_reader = NamesReader::new(
+                        &mut data,
+                        comments_len,
+                        "file comments",
+                        options.strict_names,
+                    );
 
                     let mut next_file = 0;
-                    for s in names_reader {
-                        files[next_file].name = s?;
+                    for s in comments_reader {
+                        let file = files.get_mut(next_file).ok_or_else(|| {
+                            Error::corrupt_header("file comments", "More comments than files")
+                        })?;
+                        let (comment, _) = s?;
+                        // Files without a comment are stored with an empty one.
+                        file.comment = (!comment.is_empty()).then_some(comment);
                         next_file += 1;
                     }
 
                     if next_file != files.len() {
-                        return Err(Error::other("Error parsing file names"));
+                        return Err(Error::corrupt_header(
+                            "file comments",
+                            "Fewer comments than files",
+                        ));
                     }
                 }
                 K_C_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kCTime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut data = PropertyData::from_header(header, additional_data)?;
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_creation_date = times_defined.contains(i);
                         if file.has_creation_date {
-                            file.creation_date = header.read_u64()?.into();
+                            file.creation_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_A_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kATime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut data = PropertyData::from_header(header, additional_data)?;
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_access_date = times_defined.contains(i);
                         if file.has_access_date {
-                            file.access_date = header.read_u64()?.into();
+                            file.access_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_M_TIME => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kMTime Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut data = PropertyData::from_header(header, additional_data)?;
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_last_modified_date = times_defined.contains(i);
                         if file.has_last_modified_date {
-                            file.last_modified_date = header.read_u64()?.into();
+                            file.last_modified_date = data.read_u64()?.into();
                         }
                     }
                 }
                 K_WIN_ATTRIBUTES => {
                     let times_defined = read_all_or_bits(header, num_files)?;
-                    let external = header.read_u8()?;
-                    if external != 0 {
-                        return Err(Error::other(format!(
-                            "kWinAttributes Unimplemented:external={external}"
-                        )));
-                    }
+                    let mut data = PropertyData::from_header(header, additional_data)?;
                     for (i, file) in files.iter_mut().enumerate() {
                         file.has_windows_attributes = times_defined.contains(i);
                         if file.has_windows_attributes {
-                            file.windows_attributes = header.read_u32()?;
+                            file.windows_attributes = data.read_u32()?;
+                        }
+                    }
+                }
+                K_SHA256 | K_BLAKE3_HASH => {
+                    let digests_defined = read_all_or_bits(header, num_files)?;
+                    let mut data = PropertyData::from_header(header, additional_data)?;
+                    for (i, file) in files.iter_mut().enumerate() {
+                        if digests_defined.contains(i) {
+                            let mut digest = [0; 32];
+                            data.read_exact(&mut digest)?;
+                            match prop_type {
+                                K_SHA256 => file.sha256 = Some(digest),
+                                _ => file.blake3_hash = Some(digest),
+                            }
                         }
                     }
                 }
-                K_START_POS => return Err(Error::other("kStartPos is unsupported, please report")),
+                K_START_POS => {
+                    return Err(Error::UnsupportedProperty { nid: K_START_POS });
+                }
                 K_DUMMY => {
                     header.seek(SeekFrom::Current(size as i64))?;
                 }
@@ -605,12 +1078,19 @@ impl Archive {
                 let sub_stream_info = if let Some(s) = archive.sub_streams_info.as_ref() {
                     s
                 } else {
-                    return Err(Error::other(
+                    return Err(Error::corrupt_header(
+                        "files info",
                         "Archive contains file with streams but no subStreamsInfo",
                     ));
                 };
                 file.is_directory = false;
                 file.is_anti_item = false;
+                if non_empty_file_counter >= sub_stream_info.unpack_sizes.len() {
+                    return Err(Error::corrupt_header(
+                        "files info",
+                        "Archive contains more files with streams than subStreamsInfo",
+                    ));
+                }
                 file.has_crc = sub_stream_info.has_crc.contains(non_empty_file_counter);
                 file.crc = sub_stream_info.crcs[non_empty_file_counter];
                 file.size = sub_stream_info.unpack_sizes[non_empty_file_counter];
@@ -636,7 +1116,7 @@ impl Archive {
         Ok(())
     }
 
-    fn calculate_stream_map(archive: &mut Archive) -> Result<(), Error> {
+    pub(crate) fn calculate_stream_map(archive: &mut Archive) -> Result<(), Error> {
         let mut stream_map = StreamMap::default();
 
         let mut next_block_pack_stream_index = 0;
@@ -652,16 +1132,20 @@ impl Archive {
         stream_map.pack_stream_offsets = vec![0; num_pack_sizes];
         for i in 0..num_pack_sizes {
             stream_map.pack_stream_offsets[i] = next_pack_stream_offset;
-            next_pack_stream_offset += archive.pack_sizes[i];
+            next_pack_stream_offset = next_pack_stream_offset
+                .checked_add(archive.pack_sizes[i])
+                .ok_or_else(|| Error::corrupt_header("pack info", "Pack sizes overflow"))?;
         }
 
         stream_map.block_first_file_index = vec![0; num_blocks];
         stream_map.file_block_index = vec![None; archive.files.len()];
+        stream_map.file_offset_in_block = vec![0; archive.files.len()];
         let mut next_block_index = 0;
         let mut next_block_unpack_stream_index = 0;
+        let mut next_block_offset = 0u64;
         for i in 0..archive.files.len() {
-            if !archive.files[i].has_stream && next_block_unpack_stream_index == 0 {
-                stream_map.file_block_index[i] = None;
+            // See `StreamMap::block_entry_indices`.
+            if !archive.files[i].has_stream {
                 continue;
             }
             if next_block_unpack_stream_index == 0 {
@@ -673,21 +1157,30 @@ impl Archive {
                     next_block_index += 1;
                 }
                 if next_block_index >= archive.blocks.len() {
-                    return Err(Error::other("Too few blocks in archive"));
+                    return Err(Error::corrupt_header(
+                        "stream map",
+                        "Too few blocks in archive",
+                    ));
                 }
             }
             stream_map.file_block_index[i] = Some(next_block_index);
-            if !archive.files[i].has_stream {
-                continue;
-            }
+            stream_map.file_offset_in_block[i] = next_block_offset;
+            next_block_offset = next_block_offset.saturating_add(archive.files[i].size);
 
             //set `compressed_size` of first file in block
             if stream_map.block_first_file_index[next_block_index] == i {
                 let first_pack_stream_index =
                     stream_map.block_first_pack_stream_index[next_block_index];
-                let pack_size = archive.pack_sizes[first_pack_stream_index];
+                let pack_size = *archive.pack_sizes.get(first_pack_stream_index).ok_or(
+                    Error::PackStreamIndexOutOfBounds {
+                        index: first_pack_stream_index,
+                    },
+                )?;
 
                 archive.files[i].compressed_size = pack_size;
+                if let Some(crc) = archive.pack_crc(first_pack_stream_index) {
+                    archive.files[i].compressed_crc = crc as u64;
+                }
             }
 
             next_block_unpack_stream_index += 1;
@@ -696,16 +1189,42 @@ impl Archive {
             {
                 next_block_index += 1;
                 next_block_unpack_stream_index = 0;
+                next_block_offset = 0;
             }
         }
+        // Every sub stream of the remaining blocks needs a file to be extracted to.
+        if let Some((block_index, block)) = archive
+            .blocks
+            .iter()
+            .enumerate()
+            .skip(next_block_index)
+            .find(|(_, block)| block.num_unpack_sub_streams > 0)
+        {
+            return Err(Error::corrupt_header(
+                "stream map",
+                format!(
+                    "Block {block_index} has {} sub streams, but there are too few files with data",
+                    block.num_unpack_sub_streams
+                ),
+            ));
+        }
 
         archive.stream_map = stream_map;
         Ok(())
     }
 
-    fn read_pack_info<R: Read>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_pack_info<R: Read>(
+        header: &mut R,
+        archive: &mut Archive,
+        limits: &ParseLimits,
+    ) -> Result<(), Error> {
         archive.pack_pos = read_variable_u64(header)?;
         let num_pack_streams = read_variable_usize(header, "num pack streams")?;
+        Error::check_limit(
+            "pack streams",
+            num_pack_streams as u64,
+            (limits.max_blocks as u64).saturating_mul(limits.max_coders_per_block as u64),
+        )?;
         let mut nid = header.read_u8()?;
         if nid == K_SIZE {
             archive.pack_sizes = vec![0u64; num_pack_streams];
@@ -732,36 +1251,46 @@ impl Archive {
 
         Ok(())
     }
-    fn read_unpack_info<R: Read>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_unpack_info<R: Read>(
+        header: &mut R,
+        archive: &mut Archive,
+        limits: &ParseLimits,
+    ) -> Result<(), Error> {
         let nid = header.read_u8()?;
         if nid != K_FOLDER {
-            return Err(Error::other(format!("Expected kFolder, got {nid}")));
+            return Err(Error::corrupt_header(
+                "unpack info",
+                format!("Expected kFolder, got {nid}"),
+            ));
         }
         let num_blocks = read_variable_usize(header, "num blocks")?;
+        Error::check_limit("blocks", num_blocks as u64, limits.max_blocks as u64)?;
 
-        archive.blocks.reserve_exact(num_blocks);
+        archive.blocks.clear();
         let external = header.read_u8()?;
         if external != 0 {
             return Err(Error::ExternalUnsupported);
         }
 
         for _ in 0..num_blocks {
-            archive.blocks.push(Self::read_block(header)?);
+            archive.blocks.push(Self::read_block(header, limits)?);
         }
 
         let nid = header.read_u8()?;
         if nid != K_CODERS_UNPACK_SIZE {
-            return Err(Error::other(format!(
-                "Expected kCodersUnpackSize, got {nid}"
-            )));
+            return Err(Error::corrupt_header(
+                "unpack info",
+                format!("Expected kCodersUnpackSize, got {nid}"),
+            ));
         }
 
-        for block in archive.blocks.iter_mut() {
+        for (block_index, block) in archive.blocks.iter_mut().enumerate() {
             let tos = block.total_output_streams;
-            block.unpack_sizes.reserve_exact(tos);
+            block.unpack_sizes.clear();
             for _ in 0..tos {
                 block.unpack_sizes.push(read_variable_u64(header)?);
             }
+            block.validate_at(block_index)?;
         }
 
         let mut nid = header.read_u8()?;
@@ -784,7 +1313,11 @@ impl Archive {
         Ok(())
     }
 
-    fn read_sub_streams_info<R: Read>(header: &mut R, archive: &mut Archive) -> Result<(), Error> {
+    fn read_sub_streams_info<R: Read>(
+        header: &mut R,
+        archive: &mut Archive,
+        limits: &ParseLimits,
+    ) -> Result<(), Error> {
         for block in archive.blocks.iter_mut() {
             block.num_unpack_sub_streams = 1;
         }
@@ -796,7 +1329,12 @@ impl Archive {
             for block in archive.blocks.iter_mut() {
                 let num_streams = read_variable_usize(header, "numStreams")?;
                 block.num_unpack_sub_streams = num_streams;
-                total_unpack_streams += num_streams;
+                total_unpack_streams = total_unpack_streams.saturating_add(num_streams);
+                Error::check_limit(
+                    "entries",
+                    total_unpack_streams as u64,
+                    limits.max_entries as u64,
+                )?;
             }
             nid = header.read_u8()?;
         }
@@ -821,12 +1359,18 @@ impl Archive {
                     let size = read_variable_u64(header)?;
                     sub_streams_info.unpack_sizes[next_unpack_stream] = size;
                     next_unpack_stream += 1;
-                    sum += size;
+                    sum = u64::checked_add(sum, size).ok_or_else(|| {
+                        Error::corrupt_header(
+                            "sub streams info",
+                            "Sum of unpack sizes of block exceeds total unpack size",
+                        )
+                    })?;
                 }
             }
             if sum > block.get_unpack_size() {
-                return Err(Error::other(
-                    "sum of unpack sizes of block exceeds total unpack size",
+                return Err(Error::corrupt_header(
+                    "sub streams info",
+                    "Sum of unpack sizes of block exceeds total unpack size",
                 ));
             }
             // Calculate the last size from the total minus the sum of N-1 sizes.
@@ -884,14 +1428,19 @@ impl Archive {
         Ok(())
     }
 
-    fn read_block<R: Read>(header: &mut R) -> Result<Block, Error> {
+    fn read_block<R: Read>(header: &mut R, limits: &ParseLimits) -> Result<Block, Error> {
         let mut block = Block::default();
 
         let num_coders = read_variable_usize(header, "num coders")?;
+        Error::check_limit(
+            "coders per block",
+            num_coders as u64,
+            limits.max_coders_per_block as u64,
+        )?;
         let mut coders = Vec::with_capacity(num_coders);
         let mut total_in_streams = 0;
         let mut total_out_streams = 0;
-        for _i in 0..num_coders {
+        for coder_index in 0..num_coders {
             let mut coder = Coder::default();
             let bits = header.read_u8()?;
             let id_size = bits & 0xF;
@@ -901,7 +1450,9 @@ impl Archive {
 
             coder.id_size = id_size as usize;
 
-            header.read_exact(coder.decompression_method_id_mut())?;
+            header
+                .read_exact(coder.decompression_method_id_mut())
+                .map_err(|e| Error::io_msg(e, format!("Read method id of coder {coder_index}")))?;
             if is_simple {
                 coder.num_in_streams = 1;
                 coder.num_out_streams = 1;
@@ -909,18 +1460,35 @@ impl Archive {
                 coder.num_in_streams = read_variable_u64(header)?;
                 coder.num_out_streams = read_variable_u64(header)?;
             }
-            total_in_streams += coder.num_in_streams;
-            total_out_streams += coder.num_out_streams;
+            total_in_streams = u64::saturating_add(total_in_streams, coder.num_in_streams);
+            total_out_streams = u64::saturating_add(total_out_streams, coder.num_out_streams);
+            let max_streams = limits.max_coders_per_block as u64;
+            Error::check_limit("coder streams per block", total_in_streams, max_streams)?;
+            Error::check_limit("coder streams per block", total_out_streams, max_streams)?;
             if has_attributes {
-                let properties_size = read_variable_usize(header, "properties size")?;
-                let mut props = vec![0u8; properties_size];
-                header.read_exact(&mut props)?;
+                let properties_size = read_variable_u64(header)?;
+                // Grow the buffer while reading, so that a bogus size can't force a huge
+                // allocation up front.
+                let mut props = Vec::new();
+                header
+                    .by_ref()
+                    .take(properties_size)
+                    .read_to_end(&mut props)
+                    .map_err(|e| {
+                        Error::io_msg(e, format!("Read properties of coder {coder_index}"))
+                    })?;
+                if props.len() as u64 != properties_size {
+                    return Err(Error::corrupt_header(
+                        "block",
+                        format!("Properties of coder {coder_index} are truncated"),
+                    ));
+                }
                 coder.properties = props;
             }
             coders.push(coder);
             // would need to keep looping as above:
             if more_alternative_methods {
-                return Err(Error::other(
+                return Err(Error::unsupported(
                     "Alternative methods are unsupported, please report. The reference implementation doesn't support them either.",
                 ));
             }
@@ -932,7 +1500,10 @@ impl Archive {
         block.total_output_streams = total_out_streams;
 
         if total_out_streams == 0 {
-            return Err(Error::other("Total output streams can't be 0"));
+            return Err(Error::corrupt_header(
+                "block",
+                "Total output streams can't be 0",
+            ));
         }
         let num_bind_pairs = total_out_streams - 1;
         let mut bind_pairs = Vec::with_capacity(num_bind_pairs);
@@ -946,7 +1517,8 @@ impl Archive {
         block.bind_pairs = bind_pairs;
 
         if total_in_streams < num_bind_pairs {
-            return Err(Error::other(
+            return Err(Error::corrupt_header(
+                "block",
                 "Total input streams can't be less than the number of bind pairs",
             ));
         }
@@ -961,7 +1533,10 @@ impl Archive {
                 }
             }
             if index == u64::MAX {
-                return Err(Error::other("Couldn't find stream's bind pair index"));
+                return Err(Error::corrupt_header(
+                    "block",
+                    "Couldn't find stream's bind pair index",
+                ));
             }
             packed_streams[0] = index;
         } else {
@@ -984,7 +1559,9 @@ fn read_variable_usize<R: Read>(reader: &mut R, field: &str) -> Result<usize, Er
 #[inline]
 fn assert_usize(size: u64, field: &str) -> Result<usize, Error> {
     if size > usize::MAX as u64 {
-        return Err(Error::other(format!("Cannot handle {field} {size}")));
+        return Err(Error::unsupported(format!(
+            "Cannot handle {field} {size}: size too large for this platform"
+        )));
     }
     Ok(size as usize)
 }
@@ -1034,7 +1611,54 @@ fn read_bits<R: Read>(header: &mut R, size: usize) -> io::Result<BitSet> {
     Ok(bits)
 }
 
+/// The data of a file property, which is either stored in the header or externally in one of
+/// the additional streams.
+enum PropertyData<'a, R> {
+    Header(&'a mut R),
+    External(&'a [u8]),
+}
+
+impl<'a, R: Read> PropertyData<'a, R> {
+    /// Reads the `external` flag of a property and, if it is set, the index of the additional
+    /// stream that holds the property data.
+    fn from_header(header: &'a mut R, additional_data: &'a [Vec<u8>]) -> Result<Self, Error> {
+        if header.read_u8()? == 0 {
+            return Ok(Self::Header(header));
+        }
+        let data_index = read_variable_usize(header, "data index")?;
+        let data = additional_data.get(data_index).ok_or_else(|| {
+            Error::corrupt_header(
+                "files info",
+                format!("External property refers to missing additional stream {data_index}"),
+            )
+        })?;
+        Ok(Self::External(data))
+    }
+
+    /// Returns the length of the property data, given the size of the property in the header.
+    fn data_len(&self, property_size: u64) -> Option<u64> {
+        match self {
+            // The size includes the `external` flag.
+            Self::Header(_) => property_size.checked_sub(1),
+            Self::External(data) => Some(data.len() as u64),
+        }
+    }
+}
+
+impl<R: Read> Read for PropertyData<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Header(header) => header.read(buf),
+            Self::External(data) => data.read(buf),
+        }
+    }
+}
+
 struct NamesReader<'a, R: Read> {
+    /// Header section for errors, like `"file names"`.
+    section: &'static str,
+    /// Whether invalid UTF-16 is an error instead of being replaced.
+    strict: bool,
     max_bytes: usize,
     read_bytes: usize,
     cache: Vec<u16>,
@@ -1042,8 +1666,10 @@ struct NamesReader<'a, R: Read> {
 }
 
 impl<'a, R: Read> NamesReader<'a, R> {
-    fn new(reader: &'a mut R, max_bytes: usize) -> Self {
+    fn new(reader: &'a mut R, max_bytes: usize, section: &'static str, strict: bool) -> Self {
         Self {
+            section,
+            strict,
             max_bytes,
             reader,
             read_bytes: 0,
@@ -1052,8 +1678,10 @@ impl<'a, R: Read> NamesReader<'a, R> {
     }
 }
 
+/// Yields the decoded names, together with the original code units of names that weren't
+/// valid UTF-16. The end of the section terminates the last name, even without a trailing NUL.
 impl<R: Read> Iterator for NamesReader<'_, R> {
-    type Item = Result<String, Error>;
+    type Item = Result<(String, Option<Vec<u16>>), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.max_bytes <= self.read_bytes {
@@ -1074,7 +1702,14 @@ impl<R: Read> Iterator for NamesReader<'_, R> {
             self.cache.push(u);
         }
 
-        Some(String::from_utf16(&self.cache).map_err(|e| Error::other(e.to_string())))
+        Some(match String::from_utf16(&self.cache) {
+            Ok(name) => Ok((name, None)),
+            Err(e) if self.strict => Err(Error::corrupt_header(self.section, e.to_string())),
+            Err(_) => Ok((
+                String::from_utf16_lossy(&self.cache),
+                Some(self.cache.clone()),
+            )),
+        })
     }
 }
 
@@ -1084,12 +1719,89 @@ struct IndexEntry {
     file_index: usize,
 }
 
+impl IndexEntry {
+    /// Returns the block of an entry with data.
+    fn checked_block_index(&self) -> Result<usize, Error> {
+        self.block_index.ok_or_else(|| {
+            Error::corrupt_header(
+                "stream map",
+                format!("Entry {} has data but no block", self.file_index),
+            )
+        })
+    }
+}
+
+/// An entry of an archive together with the information where its data is stored.
+///
+/// Returned by [`ArchiveReader::entry_handles`].
+#[derive(Debug, Copy, Clone)]
+pub struct EntryHandle<'a> {
+    archive: &'a Archive,
+    entry: &'a ArchiveEntry,
+    index: usize,
+    block_index: Option<usize>,
+}
+
+impl<'a> EntryHandle<'a> {
+    /// Returns the archive entry.
+    pub fn entry(&self) -> &'a ArchiveEntry {
+        self.entry
+    }
+
+    /// Returns the index of the entry in [`Archive::files`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the index of the block containing the data of the entry, or `None` if the entry
+    /// has no data (directories, empty files and anti-items).
+    pub fn block_index(&self) -> Option<usize> {
+        self.block_index
+    }
+
+    /// Returns the compressed size attributed to this entry.
+    ///
+    /// The packed size of a block is attributed to the first entry of the block, so all other
+    /// entries of a solid block report 0.
+    pub fn compressed_size(&self) -> u64 {
+        self.entry.compressed_size
+    }
+
+    /// Returns the block containing the data of the entry.
+    pub fn block(&self) -> Option<&'a Block> {
+        self.block_index
+            .and_then(|block_index| self.archive.blocks.get(block_index))
+    }
+
+    /// Returns whether the data of the entry is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.block().is_some_and(Block::is_encrypted)
+    }
+
+    /// Returns the compression methods used for the data of the entry. Empty if the entry has no
+    /// data.
+    pub fn methods(&self) -> Vec<EncoderMethod> {
+        self.block().map(block_methods).unwrap_or_default()
+    }
+}
+
+/// Returns the known methods of all coders of `block`.
+fn block_methods(block: &Block) -> Vec<EncoderMethod> {
+    block
+        .coders
+        .iter()
+        .filter_map(|coder| EncoderMethod::by_id(coder.encoder_method_id()))
+        .collect()
+}
+
 /// Reads a 7z archive file.
 pub struct ArchiveReader<R: Read + Seek> {
     source: R,
-    archive: Archive,
+    archive: Arc<Archive>,
     password: Password,
     thread_count: u32,
+    max_key_derivation_power: u8,
+    memory_limit: Option<u64>,
     index: HashMap<String, IndexEntry>,
 }
 
@@ -1104,27 +1816,52 @@ impl ArchiveReader<File> {
     }
 }
 
+impl ArchiveReader<Cursor<Vec<u8>>> {
+    /// Creates an [`ArchiveReader`] to read a 7z archive that is completely in memory.
+    #[inline]
+    pub fn from_seekable_buffer(data: Vec<u8>, password: Password) -> Result<Self, Error> {
+        Self::new(Cursor::new(data), password)
+    }
+
+    /// Creates an [`ArchiveReader`] to read a 7z archive from a reader that can't seek, like the
+    /// body of an HTTP response.
+    ///
+    /// Since the header of a 7z archive is stored at its end, the whole stream is read into
+    /// memory first. This needs as much memory as the archive is large, so prefer
+    /// [`ArchiveReader::new`] for large archives that are available as a file.
+    pub fn from_non_seekable<S: Read>(mut reader: S, password: Password) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| Error::io_msg(e, "Failed to read archive"))?;
+        Self::from_seekable_buffer(data, password)
+    }
+}
+
 impl<R: Read + Seek> ArchiveReader<R> {
     /// Creates a [`ArchiveReader`] to read a 7z archive file from the given `source` reader.
+    ///
+    /// Self-extracting archives with data before the 7z signature are only read with
+    /// [`ArchiveReadOptions::search_sfx`], see [`ArchiveReader::with_options`].
     #[inline]
-    pub fn new(mut source: R, password: Password) -> Result<Self, Error> {
-        let archive = Archive::read(&mut source, &password)?;
+    pub fn new(source: R, password: Password) -> Result<Self, Error> {
+        Self::with_options(source, password, &ArchiveReadOptions::default())
+    }
 
-        let mut reader = Self {
-            source,
-            archive,
-            password,
-            thread_count: 1,
-            index: HashMap::default(),
+    /// Same as [`ArchiveReader::new`], but reads the header with the given
+    /// [`ArchiveReadOptions`], like parse limits or strict name decoding.
+    pub fn with_options(
+        mut source: R,
+        password: Password,
+        options: &ArchiveReadOptions,
+    ) -> Result<Self, Error> {
+        let archive = match Archive::read_with_options(&mut source, &password, options) {
+            Err(Error::BadSignature(_)) if options.search_sfx => {
+                Archive::read_sfx_with_options(&mut source, &password, options)?
+            }
+            archive => archive?,
         };
-
-        reader.fill_index();
-
-        let thread_count =
-            std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap());
-        reader.set_thread_count(thread_count.get() as u32);
-
-        Ok(reader)
+        Ok(Self::from_archive(archive, source, password))
     }
 
     /// Creates an [`ArchiveReader`] from an existing [`Archive`] instance.
@@ -1140,9 +1877,11 @@ impl<R: Read + Seek> ArchiveReader<R> {
     pub fn from_archive(archive: Archive, source: R, password: Password) -> Self {
         let mut reader = Self {
             source,
-            archive,
+            archive: Arc::new(archive),
             password,
             thread_count: 1,
+            max_key_derivation_power: DEFAULT_MAX_KEY_DERIVATION_POWER,
+            memory_limit: None,
             index: HashMap::default(),
         };
 
@@ -1155,14 +1894,61 @@ impl<R: Read + Seek> ArchiveReader<R> {
         reader
     }
 
+    /// Verifies the CRC32 of all packed streams without decompressing them.
+    ///
+    /// See [`Archive::verify_pack_crcs`].
+    pub fn verify_pack_crcs(&mut self) -> Result<bool, Error> {
+        self.archive.verify_pack_crcs(&mut self.source)
+    }
+
+    /// Verifies the CRC32 of every packed stream without decompressing it and returns the
+    /// indices of the packed streams that don't match. See [`Archive::verify_pack_streams`].
+    pub fn verify_pack_streams(&mut self) -> Result<Vec<usize>, Error> {
+        self.archive.verify_pack_streams(&mut self.source)
+    }
+
     /// Sets the thread count to use when multi-threading is supported by the de-compression
-    /// (currently only LZMA2 if encoded with MT support).
+    /// (currently LZMA2 if encoded with MT support and BZIP2 if encoded as multiple streams).
     ///
     /// Defaults to `std::thread::available_parallelism()` if not set manually.
     pub fn set_thread_count(&mut self, thread_count: u32) {
         self.thread_count = thread_count.clamp(1, 256);
     }
 
+    /// Sets the maximal key derivation power of AES encrypted entries. The key of an entry is
+    /// derived with `2^power` SHA-256 iterations, with the power read from the archive, so a
+    /// crafted archive could otherwise keep the reader busy practically forever. Decoding an entry
+    /// whose power exceeds the limit returns an error.
+    ///
+    /// Defaults to 25, about 33 million iterations. 7-Zip writes archives with a power of 19.
+    /// Values above 31 are clamped to 31. An encrypted header is always decoded with the default
+    /// limit.
+    pub fn set_max_key_derivation_power(&mut self, max: u8) {
+        self.max_key_derivation_power = max.min(MAX_KEY_DERIVATION_POWER);
+    }
+
+    /// Sets the maximal memory in bytes that decoding a block may need, or `None` for no limit,
+    /// which is the default.
+    ///
+    /// Before a block is decoded, its [`Block::decoder_memory_estimate`] is compared with the
+    /// limit, and [`Error::MaxMemLimited`] is returned if it is exceeded. This keeps crafted
+    /// archives from allocating huge dictionaries. The header is always decoded without a limit.
+    pub fn set_memory_limit(&mut self, limit: Option<u64>) {
+        self.memory_limit = limit;
+    }
+
+    /// Returns the highest key derivation power of the AES encrypted blocks of the archive, or
+    /// `None` if no block is encrypted.
+    pub fn key_derivation_power(&self) -> Option<u8> {
+        self.archive
+            .blocks
+            .iter()
+            .flat_map(|block| &block.coders)
+            .filter(|coder| coder.encoder_method_id() == EncoderMethod::ID_AES256_SHA256)
+            .filter_map(|coder| coder.properties.first().map(|b0| b0 & 0x3F))
+            .max()
+    }
+
     fn fill_index(&mut self) {
         for (file_index, file) in self.archive.files.iter().enumerate() {
             let block_index = self.archive.stream_map.file_block_index[file_index];
@@ -1186,21 +1972,48 @@ impl<R: Read + Seek> ArchiveReader<R> {
         &self.archive
     }
 
-    fn build_decode_stack<'r>(
-        source: &'r mut R,
+    fn build_decode_stack<'r, S: Read + Seek + 'r>(
+        mut source: S,
         archive: &Archive,
         block_index: usize,
         password: &Password,
         thread_count: u32,
+        max_key_derivation_power: u8,
+        memory_limit: Option<u64>,
     ) -> Result<(Box<dyn Read + 'r>, usize), Error> {
-        let block = &archive.blocks[block_index];
+        let block = archive.checked_block(block_index)?;
+        if let Some(limit) = memory_limit {
+            let estimate = block.decoder_memory_estimate();
+            if estimate > limit {
+                return Err(Error::MaxMemLimited {
+                    max_kb: usize::try_from(limit / 1024).unwrap_or(usize::MAX),
+                    actaul_kb: usize::try_from(estimate.div_ceil(1024)).unwrap_or(usize::MAX),
+                });
+            }
+        }
         if block.total_input_streams > block.total_output_streams {
-            return Self::build_decode_stack2(source, archive, block_index, password, thread_count);
+            return Self::build_decode_stack2(
+                source,
+                archive,
+                block_index,
+                password,
+                thread_count,
+                max_key_derivation_power,
+            );
         }
         let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
-        let block_offset = SIGNATURE_HEADER_SIZE
-            + archive.pack_pos
-            + archive.stream_map.pack_stream_offsets[first_pack_stream_index];
+        let (Some(&pack_size), Some(&pack_offset)) = (
+            archive.pack_sizes.get(first_pack_stream_index),
+            archive
+                .stream_map
+                .pack_stream_offsets
+                .get(first_pack_stream_index),
+        ) else {
+            return Err(Error::PackStreamIndexOutOfBounds {
+                index: first_pack_stream_index,
+            });
+        };
+        let block_offset = archive.pack_start() + pack_offset;
 
         let (mut has_crc, mut crc) = (block.has_crc, block.crc);
 
@@ -1222,10 +2035,9 @@ impl<R: Read + Seek> ArchiveReader<R> {
         }
 
         source.seek(SeekFrom::Start(block_offset))?;
-        let pack_size = archive.pack_sizes[first_pack_stream_index] as usize;
+        let pack_size = assert_usize(pack_size, "pack size")?;
 
-        let mut decoder: Box<dyn Read> = Box::new(BoundedReader::new(source, pack_size));
-        let block = &archive.blocks[block_index];
+        let mut decoder: Box<dyn Read + 'r> = Box::new(BoundedReader::new(source, pack_size));
         for (index, coder) in block.ordered_coder_iter() {
             if coder.num_in_streams != 1 || coder.num_out_streams != 1 {
                 return Err(Error::unsupported(
@@ -1234,18 +2046,19 @@ impl<R: Read + Seek> ArchiveReader<R> {
             }
             let next = add_decoder(
                 decoder,
-                block.get_unpack_size_at_index(index) as usize,
+                assert_usize(block.get_unpack_size_at_index(index), "coder unpack size")?,
                 coder,
                 password,
                 MAX_MEM_LIMIT_KB,
                 thread_count,
+                max_key_derivation_power,
             )?;
             decoder = Box::new(next);
         }
         if has_crc {
             decoder = Box::new(Crc32VerifyingReader::new(
                 decoder,
-                block.get_unpack_size() as usize,
+                assert_usize(block.get_unpack_size(), "block unpack size")?,
                 crc,
             ));
         }
@@ -1253,15 +2066,16 @@ impl<R: Read + Seek> ArchiveReader<R> {
         Ok((decoder, pack_size))
     }
 
-    fn build_decode_stack2<'r>(
-        source: &'r mut R,
+    fn build_decode_stack2<'r, S: Read + Seek + 'r>(
+        source: S,
         archive: &Archive,
         block_index: usize,
         password: &Password,
         thread_count: u32,
+        max_key_derivation_power: u8,
     ) -> Result<(Box<dyn Read + 'r>, usize), Error> {
         const MAX_CODER_COUNT: usize = 32;
-        let block = &archive.blocks[block_index];
+        let block = archive.checked_block(block_index)?;
         if block.coders.len() > MAX_CODER_COUNT {
             return Err(Error::unsupported(format!(
                 "Too many coders: {}",
@@ -1272,7 +2086,15 @@ impl<R: Read + Seek> ArchiveReader<R> {
         assert!(block.total_input_streams > block.total_output_streams);
         let shared_source = Rc::new(RefCell::new(source));
         let first_pack_stream_index = archive.stream_map.block_first_pack_stream_index[block_index];
-        let start_pos = SIGNATURE_HEADER_SIZE + archive.pack_pos;
+        let pack_stream_end = first_pack_stream_index + block.packed_streams.len();
+        if pack_stream_end > archive.pack_sizes.len()
+            || pack_stream_end > archive.stream_map.pack_stream_offsets.len()
+        {
+            return Err(Error::PackStreamIndexOutOfBounds {
+                index: pack_stream_end - 1,
+            });
+        }
+        let start_pos = archive.pack_start();
         let offsets = &archive.stream_map.pack_stream_offsets[first_pack_stream_index..];
 
         let mut sources = Vec::with_capacity(block.packed_streams.len());
@@ -1314,11 +2136,11 @@ impl<R: Read + Seek> ArchiveReader<R> {
 
         let id = block.coders[main_coder_index].encoder_method_id();
         if id != EncoderMethod::ID_BCJ2 {
-            return Err(Error::unsupported(format!("Unsupported method: {id:?}")));
+            return Err(Error::UnsupportedCoder { id: id.to_vec() });
         }
 
         let num_in_streams = block.coders[main_coder_index].num_in_streams as usize;
-        let mut inputs: Vec<Box<dyn Read>> = Vec::with_capacity(num_in_streams);
+        let mut inputs: Vec<Box<dyn Read + 'r>> = Vec::with_capacity(num_in_streams);
         let start_i = coder_to_stream_map[main_coder_index];
         for i in start_i..num_in_streams + start_i {
             inputs.push(Self::get_in_stream(
@@ -1328,33 +2150,33 @@ impl<R: Read + Seek> ArchiveReader<R> {
                 password,
                 i,
                 thread_count,
+                max_key_derivation_power,
             )?);
         }
-        let mut decoder: Box<dyn Read> = Box::new(Bcj2Reader::new(inputs, block.get_unpack_size()));
+        let mut decoder: Box<dyn Read + 'r> =
+            Box::new(Bcj2Reader::new(inputs, block.get_unpack_size()));
         if block.has_crc {
             decoder = Box::new(Crc32VerifyingReader::new(
                 decoder,
-                block.get_unpack_size() as usize,
+                assert_usize(block.get_unpack_size(), "block unpack size")?,
                 block.crc,
             ));
         }
         Ok((
             decoder,
-            archive.pack_sizes[first_pack_stream_index] as usize,
+            assert_usize(archive.pack_sizes[first_pack_stream_index], "pack size")?,
         ))
     }
 
-    fn get_in_stream<'r>(
+    fn get_in_stream<'r, S: Read + Seek + 'r>(
         block: &Block,
-        sources: &[SharedBoundedReader<'r, R>],
+        sources: &[SharedBoundedReader<S>],
         coder_to_stream_map: &[usize],
         password: &Password,
         in_stream_index: usize,
         thread_count: u32,
-    ) -> Result<Box<dyn Read + 'r>, Error>
-    where
-        R: 'r,
-    {
+        max_key_derivation_power: u8,
+    ) -> Result<Box<dyn Read + 'r>, Error> {
         let index = block
             .packed_streams
             .iter()
@@ -1366,9 +2188,10 @@ impl<R: Read + Seek> ArchiveReader<R> {
         let bp = block
             .find_bind_pair_for_in_stream(in_stream_index as u64)
             .ok_or_else(|| {
-                Error::other(format!(
-                    "Couldn't find bind pair for stream {in_stream_index}"
-                ))
+                Error::corrupt_header(
+                    "block",
+                    format!("Couldn't find bind pair for stream {in_stream_index}"),
+                )
             })?;
         let index = bp.out_index as usize;
 
@@ -1379,26 +2202,29 @@ impl<R: Read + Seek> ArchiveReader<R> {
             password,
             index,
             thread_count,
+            max_key_derivation_power,
         )
     }
 
-    fn get_in_stream2<'r>(
+    fn get_in_stream2<'r, S: Read + Seek + 'r>(
         block: &Block,
-        sources: &[SharedBoundedReader<'r, R>],
+        sources: &[SharedBoundedReader<S>],
         coder_to_stream_map: &[usize],
         password: &Password,
         in_stream_index: usize,
         thread_count: u32,
-    ) -> Result<Box<dyn Read + 'r>, Error>
-    where
-        R: 'r,
-    {
+        max_key_derivation_power: u8,
+    ) -> Result<Box<dyn Read + 'r>, Error> {
         let coder = &block.coders[in_stream_index];
         let start_index = coder_to_stream_map[in_stream_index];
         if start_index == usize::MAX {
-            return Err(Error::other("in_stream_index out of range"));
+            return Err(Error::corrupt_header(
+                "block",
+                format!("Coder {in_stream_index} has no input stream"),
+            ));
         }
-        let uncompressed_len = block.unpack_sizes[in_stream_index] as usize;
+        let uncompressed_len =
+            assert_usize(block.unpack_sizes[in_stream_index], "coder unpack size")?;
         if coder.num_in_streams == 1 {
             let input = Self::get_in_stream(
                 block,
@@ -1407,6 +2233,7 @@ impl<R: Read + Seek> ArchiveReader<R> {
                 password,
                 start_index,
                 thread_count,
+                max_key_derivation_power,
             )?;
 
             let decoder = add_decoder(
@@ -1416,6 +2243,7 @@ impl<R: Read + Seek> ArchiveReader<R> {
                 password,
                 MAX_MEM_LIMIT_KB,
                 thread_count,
+                max_key_derivation_power,
             )?;
             return Ok(Box::new(decoder));
         }
@@ -1429,35 +2257,98 @@ impl<R: Read + Seek> ArchiveReader<R> {
     /// Attention about solid archive:
     /// When decoding a solid archive, the data to be decompressed depends on the data in front of it,
     /// you cannot simply skip the previous data and only decompress the data in the back.
+    ///
+    /// Data of an entry that the closure didn't read is decoded and verified after the closure
+    /// returns `Ok(true)`. Returning `Ok(false)` skips the rest of the current block.
     pub fn for_each_entries<F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error>>(
         &mut self,
         mut each: F,
+    ) -> Result<(), Error> {
+        self.for_each_entries_until(
+            |entry, reader| match each(entry, reader) {
+                Ok(true) => ControlFlow::Continue(Ok(())),
+                Ok(false) => ControlFlow::Break(()),
+                Err(e) => ControlFlow::Continue(Err(e)),
+            },
+            false,
+        )
+    }
+
+    /// Same as [`ArchiveReader::for_each_entries`], but the closure returns a [`ControlFlow`].
+    ///
+    /// `Continue(Ok(()))` continues with the next entry, `Continue(Err(e))` stops and returns
+    /// the error and `Break(())` stops decoding without an error. Unlike returning `Ok(false)`
+    /// from [`ArchiveReader::for_each_entries`], breaking also skips the remaining blocks.
+    pub fn for_each_entries_cf<
+        F: FnMut(&ArchiveEntry, &mut dyn Read) -> ControlFlow<(), Result<(), Error>>,
+    >(
+        &mut self,
+        each: F,
+    ) -> Result<(), Error> {
+        self.for_each_entries_until(each, true)
+    }
+
+    /// Decodes the entries of all blocks and then the entries without data. A `Break(())` of
+    /// the closure stops decoding the current block, and all remaining entries if
+    /// `break_all` is set. Entries without data always stop at a break.
+    fn for_each_entries_until<
+        F: FnMut(&ArchiveEntry, &mut dyn Read) -> ControlFlow<(), Result<(), Error>>,
+    >(
+        &mut self,
+        mut each: F,
+        break_all: bool,
     ) -> Result<(), Error> {
         let block_count = self.archive.blocks.len();
         for block_index in 0..block_count {
-            let forder_dec = BlockDecoder::new(
+            let mut block_decoder = BlockDecoder::new(
                 self.thread_count,
                 block_index,
                 &self.archive,
                 &self.password,
                 &mut self.source,
             );
-            forder_dec.for_each_entries(&mut each)?;
+            block_decoder.set_max_key_derivation_power(self.max_key_derivation_power);
+            block_decoder.set_memory_limit(self.memory_limit);
+            if block_decoder.for_each_entries_cf(&mut each)?.is_break() && break_all {
+                return Ok(());
+            }
         }
         // decode empty files
         for file_index in 0..self.archive.files.len() {
-            let block_index = self.archive.stream_map.file_block_index[file_index];
-            if block_index.is_none() {
+            if self.archive.stream_map.file_block_index[file_index].is_none() {
                 let file = &self.archive.files[file_index];
                 let empty_reader: &mut dyn Read = &mut ([0u8; 0].as_slice());
-                if !each(file, empty_reader)? {
-                    return Ok(());
+                match each(file, empty_reader) {
+                    ControlFlow::Continue(result) => result?,
+                    ControlFlow::Break(()) => return Ok(()),
                 }
             }
         }
         Ok(())
     }
 
+    /// Takes a closure to decode each file in the block `block_index`, see
+    /// [`BlockDecoder::for_each_entries`].
+    #[cfg(all(feature = "util", not(target_arch = "wasm32")))]
+    pub(crate) fn for_each_block_entries<
+        F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error>,
+    >(
+        &mut self,
+        block_index: usize,
+        each: &mut F,
+    ) -> Result<bool, Error> {
+        let mut block_decoder = BlockDecoder::new(
+            self.thread_count,
+            block_index,
+            &self.archive,
+            &self.password,
+            &mut self.source,
+        );
+        block_decoder.set_max_key_derivation_power(self.max_key_derivation_power);
+        block_decoder.set_memory_limit(self.memory_limit);
+        block_decoder.for_each_entries(each)
+    }
+
     /// Returns the data of a file with the given path inside the archive.
     ///
     /// # Notice
@@ -1470,25 +2361,28 @@ impl<R: Read + Seek> ArchiveReader<R> {
         if !file.has_stream {
             return Ok(Vec::new());
         }
+        // The data is read into memory, so it must be addressable.
+        let size = assert_usize(file.size, "file size")?;
 
-        let block_index = index_entry
-            .block_index
-            .ok_or_else(|| Error::other("File has no associated block"))?;
+        let block_index = index_entry.checked_block_index()?;
 
         match self.archive.is_solid {
             true => {
                 let mut result = None;
                 let target_file_ptr = file as *const _;
 
-                BlockDecoder::new(
+                let mut block_decoder = BlockDecoder::new(
                     self.thread_count,
                     block_index,
                     &self.archive,
                     &self.password,
                     &mut self.source,
-                )
-                .for_each_entries(&mut |archive_entry, reader| {
-                    let mut data = Vec::with_capacity(archive_entry.size as usize);
+                );
+                block_decoder.set_max_key_derivation_power(self.max_key_derivation_power);
+                block_decoder.set_memory_limit(self.memory_limit);
+                block_decoder.for_each_entries(&mut |archive_entry, reader| {
+                    let mut data =
+                        Vec::with_capacity(assert_usize(archive_entry.size, "file size")?);
                     reader.read_to_end(&mut data)?;
 
                     if std::ptr::eq(archive_entry, target_file_ptr) {
@@ -1504,7 +2398,7 @@ impl<R: Read + Seek> ArchiveReader<R> {
             false => {
                 let pack_index = self.archive.stream_map.block_first_pack_stream_index[block_index];
                 let pack_offset = self.archive.stream_map.pack_stream_offsets[pack_index];
-                let block_offset = SIGNATURE_HEADER_SIZE + self.archive.pack_pos + pack_offset;
+                let block_offset = self.archive.pack_start() + pack_offset;
 
                 self.source.seek(SeekFrom::Start(block_offset))?;
 
@@ -1514,18 +2408,20 @@ impl<R: Read + Seek> ArchiveReader<R> {
                     block_index,
                     &self.password,
                     self.thread_count,
+                    self.max_key_derivation_power,
+                    self.memory_limit,
                 )?;
 
-                let mut data = Vec::with_capacity(file.size as usize);
+                let mut data = Vec::with_capacity(size);
                 let mut decoder: Box<dyn Read> =
-                    Box::new(BoundedReader::new(&mut block_reader, file.size as usize));
+                    Box::new(BoundedReader::new(&mut block_reader, size));
 
                 if file.has_crc {
-                    decoder = Box::new(Crc32VerifyingReader::new(
-                        decoder,
-                        file.size as usize,
-                        file.crc,
-                    ));
+                    decoder = Box::new(Crc32VerifyingReader::new(decoder, size, file.crc));
+                }
+                #[cfg(feature = "blake3-checksum")]
+                if let Some(hash) = file.blake3_hash {
+                    decoder = Box::new(Blake3VerifyingReader::new(decoder, size, hash));
                 }
 
                 decoder.read_to_end(&mut data)?;
@@ -1535,11 +2431,269 @@ impl<R: Read + Seek> ArchiveReader<R> {
         }
     }
 
+    /// Returns a reader over the data of the entry at `file_index`. Entries in front of it in the
+    /// same block are decoded and discarded.
+    #[cfg(all(feature = "util", target_arch = "wasm32"))]
+    pub(crate) fn entry_reader(&mut self, file_index: usize) -> Result<Box<dyn Read + '_>, Error> {
+        let file = self
+            .archive
+            .files
+            .get(file_index)
+            .ok_or(Error::FileNotFound)?;
+        let Some(block_index) = self.archive.stream_map.file_block_index[file_index] else {
+            return Ok(Box::new(std::io::empty()));
+        };
+        let size = assert_usize(file.size, "file size")?;
+        let (has_crc, crc) = (file.has_crc, file.crc);
+        #[cfg(feature = "blake3-checksum")]
+        let blake3_hash = file.blake3_hash;
+        let first_file_index = self.archive.stream_map.block_first_file_index[block_index];
+        let skip: u64 = self.archive.files[first_file_index..file_index]
+            .iter()
+            .filter(|file| file.has_stream)
+            .map(|file| file.size)
+            .sum();
+
+        let pack_index = self.archive.stream_map.block_first_pack_stream_index[block_index];
+        let pack_offset = self.archive.stream_map.pack_stream_offsets[pack_index];
+        let block_offset = self.archive.pack_start() + pack_offset;
+        self.source.seek(SeekFrom::Start(block_offset))?;
+
+        let (mut block_reader, _size) = Self::build_decode_stack(
+            &mut self.source,
+            &self.archive,
+            block_index,
+            &self.password,
+            self.thread_count,
+            self.max_key_derivation_power,
+            self.memory_limit,
+        )?;
+        std::io::copy(&mut (&mut block_reader).take(skip), &mut std::io::sink())?;
+
+        let mut decoder: Box<dyn Read + '_> = Box::new(BoundedReader::new(block_reader, size));
+        if has_crc {
+            decoder = Box::new(Crc32VerifyingReader::new(decoder, size, crc));
+        }
+        #[cfg(feature = "blake3-checksum")]
+        if let Some(hash) = blake3_hash {
+            decoder = Box::new(Blake3VerifyingReader::new(decoder, size, hash));
+        }
+        Ok(decoder)
+    }
+
+    /// Returns up to `length` bytes of a file with the given path inside the archive, starting
+    /// at `offset` inside the file.
+    ///
+    /// The entry is still decompressed from its beginning, but bytes before `offset` are
+    /// discarded and decoding stops after `offset + length`. If the range reaches past the end of
+    /// the file, the returned data is shorter than `length`.
+    ///
+    /// # Notice
+    /// Like [`ArchiveReader::read_file`], this needs to decode all data in front of the file
+    /// when used with solid archives.
+    ///
+    /// # Arguments
+    /// * `name` - The path of the file inside the archive
+    /// * `offset` - The offset inside the uncompressed file data to start at
+    /// * `length` - The maximal number of bytes to return
+    pub fn read_file_range(
+        &mut self,
+        name: &str,
+        offset: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(length.min(1024 * 1024));
+        self.read_file_range_to_writer(name, offset, length, &mut data)?;
+        Ok(data)
+    }
+
+    /// Same as [`ArchiveReader::read_file_range`], but writes the data into `writer`.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Arguments
+    /// * `name` - The path of the file inside the archive
+    /// * `offset` - The offset inside the uncompressed file data to start at
+    /// * `length` - The maximal number of bytes to write
+    /// * `writer` - The destination of the data
+    pub fn read_file_range_to_writer(
+        &mut self,
+        name: &str,
+        offset: u64,
+        length: usize,
+        writer: &mut dyn Write,
+    ) -> Result<usize, Error> {
+        let index_entry = *self.index.get(name).ok_or(Error::FileNotFound)?;
+        let file = &self.archive.files[index_entry.file_index];
+
+        if !file.has_stream || offset >= file.size || length == 0 {
+            return Ok(0);
+        }
+
+        let block_index = index_entry.checked_block_index()?;
+
+        fn copy_range(
+            reader: &mut dyn Read,
+            offset: u64,
+            length: usize,
+            writer: &mut dyn Write,
+        ) -> Result<usize, Error> {
+            std::io::copy(&mut reader.take(offset), &mut std::io::sink())?;
+            let written = std::io::copy(&mut reader.take(length as u64), writer)?;
+            Ok(written as usize)
+        }
+
+        #[cfg(feature = "zstd")]
+        if let Some(written) = Self::read_zstd_seekable_range(
+            &mut self.source,
+            &self.archive,
+            block_index,
+            index_entry.file_index,
+            offset,
+            length,
+            writer,
+        )? {
+            return Ok(written);
+        }
+
+        match self.archive.is_solid {
+            true => {
+                let mut result = None;
+                let target_file_ptr = file as *const _;
+
+                let mut block_decoder = BlockDecoder::new(
+                    self.thread_count,
+                    block_index,
+                    &self.archive,
+                    &self.password,
+                    &mut self.source,
+                );
+                block_decoder.set_max_key_derivation_power(self.max_key_derivation_power);
+                block_decoder.set_memory_limit(self.memory_limit);
+                block_decoder.for_each_entries(&mut |archive_entry, reader| {
+                    if std::ptr::eq(archive_entry, target_file_ptr) {
+                        result = Some(copy_range(reader, offset, length, writer)?);
+                        Ok(false)
+                    } else {
+                        std::io::copy(reader, &mut std::io::sink())?;
+                        Ok(true)
+                    }
+                })?;
+
+                result.ok_or(Error::FileNotFound)
+            }
+            false => {
+                let pack_index = self.archive.stream_map.block_first_pack_stream_index[block_index];
+                let pack_offset = self.archive.stream_map.pack_stream_offsets[pack_index];
+                let block_offset = self.archive.pack_start() + pack_offset;
+
+                self.source.seek(SeekFrom::Start(block_offset))?;
+
+                let (mut block_reader, _size) = Self::build_decode_stack(
+                    &mut self.source,
+                    &self.archive,
+                    block_index,
+                    &self.password,
+                    self.thread_count,
+                    self.max_key_derivation_power,
+                    self.memory_limit,
+                )?;
+
+                let size = assert_usize(file.size, "file size")?;
+
+                let mut decoder: Box<dyn Read> =
+                    Box::new(BoundedReader::new(&mut block_reader, size));
+
+                if file.has_crc {
+                    decoder = Box::new(Crc32VerifyingReader::new(decoder, size, file.crc));
+                }
+                #[cfg(feature = "blake3-checksum")]
+                if let Some(hash) = file.blake3_hash {
+                    decoder = Box::new(Blake3VerifyingReader::new(decoder, size, hash));
+                }
+
+                copy_range(&mut decoder, offset, length, writer)
+            }
+        }
+    }
+
+    /// Reads a range of a file directly from the frame containing it, if the block consists of a
+    /// single ZSTD coder written in the seekable format. Returns `None` if the block doesn't
+    /// support random access.
+    #[cfg(feature = "zstd")]
+    fn read_zstd_seekable_range(
+        source: &mut R,
+        archive: &Archive,
+        block_index: usize,
+        file_index: usize,
+        offset: u64,
+        length: usize,
+        writer: &mut dyn Write,
+    ) -> Result<Option<usize>, Error> {
+        let block = &archive.blocks[block_index];
+        if block.coders.len() != 1
+            || block.packed_streams.len() != 1
+            || block.coders[0].encoder_method_id() != EncoderMethod::ID_ZSTD
+        {
+            return Ok(None);
+        }
+
+        let pack_index = archive.stream_map.block_first_pack_stream_index[block_index];
+        let pack_offset = archive.pack_start() + archive.stream_map.pack_stream_offsets[pack_index];
+        let pack_size = archive.pack_sizes[pack_index];
+
+        let Some(seek_table) =
+            crate::codec::zstd::SeekTable::read_from(source, pack_offset, pack_size)?
+        else {
+            return Ok(None);
+        };
+
+        let first_file_index = archive.stream_map.block_first_file_index[block_index];
+        let entry_offset: u64 = archive.files[first_file_index..file_index]
+            .iter()
+            .filter(|file| file.has_stream)
+            .map(|file| file.size)
+            .sum();
+        let file_size = archive.files[file_index].size;
+        let length = u64::min(length as u64, file_size - offset);
+
+        let target = entry_offset + offset;
+        let (frame_offset, frame_start) = seek_table.locate(target);
+
+        source.seek(SeekFrom::Start(pack_offset + frame_offset))?;
+        let frames = source.take(seek_table.compressed_size() - frame_offset);
+        let mut decoder = zstd::Decoder::new(frames)?;
+
+        std::io::copy(
+            &mut (&mut decoder).take(target - frame_start),
+            &mut std::io::sink(),
+        )?;
+        let written = std::io::copy(&mut decoder.take(length), writer)?;
+
+        Ok(Some(written as usize))
+    }
+
     /// Get the compression method(s) used for a specific file in the archive.
+    #[deprecated(since = "0.21.0", note = "use `ArchiveReader::file_methods` instead")]
     pub fn file_compression_methods(
         &self,
         file_name: &str,
         methods: &mut Vec<EncoderMethod>,
+    ) -> Result<(), Error> {
+        methods.extend(self.file_methods(file_name)?);
+        Ok(())
+    }
+
+    /// Appends the method and properties of every coder used for a specific file in the archive
+    /// to `infos`. Nothing is appended if the file has no data.
+    ///
+    /// Unlike [`ArchiveReader::file_methods`], this keeps the codec parameters, which can be
+    /// inspected for example with
+    /// [`PpmdOptions::from_properties`](crate::encoder_options::PpmdOptions::from_properties).
+    pub fn file_codec_info(
+        &self,
+        file_name: &str,
+        infos: &mut Vec<CodecInfo>,
     ) -> Result<(), Error> {
         let index_entry = self.index.get(file_name).ok_or(Error::FileNotFound)?;
         let file = &self.archive.files[index_entry.file_index];
@@ -1548,43 +2702,269 @@ impl<R: Read + Seek> ArchiveReader<R> {
             return Ok(());
         }
 
-        let block_index = index_entry
-            .block_index
-            .ok_or_else(|| Error::other("File has no associated block"))?;
+        let block_index = index_entry.checked_block_index()?;
 
-        let block = self
-            .archive
-            .blocks
-            .get(block_index)
-            .ok_or_else(|| Error::other("Block not found"))?;
-
-        block
-            .coders
-            .iter()
-            .filter_map(|coder| EncoderMethod::by_id(coder.encoder_method_id()))
-            .for_each(|method| {
-                methods.push(method);
-            });
+        let block = self.archive.checked_block(block_index)?;
 
+        infos.extend(block.coders.iter().filter_map(|coder| {
+            EncoderMethod::by_id(coder.encoder_method_id()).map(|method| CodecInfo {
+                method,
+                properties: coder.properties.clone(),
+            })
+        }));
         Ok(())
     }
 
+    /// Returns the compression method(s) used for a specific file in the archive. The list is
+    /// empty if the file has no data.
+    pub fn file_methods(&self, file_name: &str) -> Result<Vec<EncoderMethod>, Error> {
+        let index_entry = self.index.get(file_name).ok_or(Error::FileNotFound)?;
+        let file = &self.archive.files[index_entry.file_index];
+
+        if !file.has_stream {
+            return Ok(Vec::new());
+        }
+
+        let block_index = index_entry.checked_block_index()?;
+
+        let block = self.archive.checked_block(block_index)?;
+
+        Ok(block_methods(block))
+    }
+
+    /// Returns all entries of the archive, in the order they are stored.
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.archive.files
+    }
+
+    /// Returns the entries stored in the block with the given index, in the order they appear
+    /// in the block, see [`StreamMap::block_entry_indices`]. Yields nothing if `block_index` is
+    /// out of range.
+    pub fn entries_in_block(&self, block_index: usize) -> impl Iterator<Item = &ArchiveEntry> {
+        self.archive
+            .stream_map
+            .block_entry_indices(block_index)
+            .map(|file_index| &self.archive.files[file_index])
+    }
+
+    /// Returns an iterator over all blocks, yielding the index of every block together with the
+    /// entries stored in it. Entries without data are not part of any block.
+    pub fn blocks_iter(
+        &self,
+    ) -> impl Iterator<Item = (usize, impl Iterator<Item = &ArchiveEntry>)> {
+        (0..self.archive.blocks.len())
+            .map(|block_index| (block_index, self.entries_in_block(block_index)))
+    }
+
+    /// Returns the entry at `index` in the order the entries are stored, or `None` if the index
+    /// is out of range.
+    pub fn entry_by_index(&self, index: usize) -> Option<&ArchiveEntry> {
+        self.archive.files.get(index)
+    }
+
+    /// Returns the entry with the given name, or `None` if there is no such entry.
+    pub fn entry_by_name(&self, name: &str) -> Option<&ArchiveEntry> {
+        self.index
+            .get(name)
+            .map(|index_entry| &self.archive.files[index_entry.file_index])
+    }
+
+    /// Returns the CRC32 of the entry with the given name as stored in the archive, or `None` if
+    /// there is no such entry or no CRC is stored for it.
+    ///
+    /// This allows comparing files on disk against the archive without decoding any data.
+    pub fn entry_crc(&self, name: &str) -> Option<u32> {
+        self.entry_by_name(name)
+            .filter(|entry| entry.has_stream && entry.has_crc)
+            .map(|entry| entry.crc as u32)
+    }
+
+    /// Computes the digest `D` of the data of the file with the given path inside the archive.
+    ///
+    /// The data is streamed through the digest without collecting it in memory. In solid
+    /// archives, the entries in front of the file are decoded and discarded, but decoding stops
+    /// at the end of the file.
+    ///
+    /// # Arguments
+    /// * `name` - The path of the file inside the archive
+    #[cfg(feature = "digest")]
+    pub fn compute_entry_digest<D: digest::Digest>(
+        &mut self,
+        name: &str,
+    ) -> Result<digest::Output<D>, Error> {
+        struct DigestWriter<D>(D);
+
+        impl<D: digest::Digest> Write for DigestWriter<D> {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.update(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = DigestWriter(D::new());
+        self.read_file_range_to_writer(name, 0, usize::MAX, &mut writer)?;
+        Ok(writer.0.finalize())
+    }
+
+    /// Returns whether the data of the entry with the given name is encrypted. Returns `false`
+    /// if there is no such entry or the entry has no data.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the entry
+    pub fn entry_is_encrypted(&self, name: &str) -> bool {
+        self.index
+            .get(name)
+            .and_then(|index_entry| index_entry.block_index)
+            .and_then(|block_index| self.archive.blocks.get(block_index))
+            .is_some_and(Block::is_encrypted)
+    }
+
+    /// Returns an iterator over handles to all entries of the archive, in the order they are
+    /// stored.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::{ArchiveReader, Password};
+    ///
+    /// let reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+    /// for handle in reader.entry_handles() {
+    ///     println!(
+    ///         "{} in block {:?}, encrypted: {}",
+    ///         handle.entry().name(),
+    ///         handle.block_index(),
+    ///         handle.is_encrypted()
+    ///     );
+    /// }
+    /// ```
+    pub fn entry_handles(&self) -> impl Iterator<Item = EntryHandle<'_>> {
+        let archive = &self.archive;
+        archive
+            .files
+            .iter()
+            .enumerate()
+            .map(move |(index, entry)| EntryHandle {
+                archive,
+                entry,
+                index,
+                block_index: archive
+                    .stream_map
+                    .file_block_index
+                    .get(index)
+                    .copied()
+                    .flatten(),
+            })
+    }
+
     /// Sets the password to use for decrypting encrypted archive entries.
     pub fn set_password(&mut self, password: Password) {
         self.password = password;
     }
 }
 
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Converts this reader into a [`SharedArchiveReader`] that can be cloned and shared
+    /// between threads.
+    pub fn into_shared(self) -> SharedArchiveReader<R> {
+        SharedArchiveReader {
+            archive: Arc::clone(&self.archive),
+            inner: Arc::new(Mutex::new(self)),
+        }
+    }
+}
+
+/// A cloneable, thread-safe handle to an [`ArchiveReader`].
+///
+/// All operations that decode data acquire an internal lock, so they are serialized between
+/// threads. The archive metadata can be accessed without locking.
+///
+/// Created with [`ArchiveReader::into_shared`].
+pub struct SharedArchiveReader<R: Read + Seek> {
+    inner: Arc<Mutex<ArchiveReader<R>>>,
+    archive: Arc<Archive>,
+}
+
+impl<R: Read + Seek> Clone for SharedArchiveReader<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            archive: Arc::clone(&self.archive),
+        }
+    }
+}
+
+impl<R: Read + Seek> SharedArchiveReader<R> {
+    fn lock(&self) -> MutexGuard<'_, ArchiveReader<R>> {
+        // Every read seeks to its start position, so a reader of a panicked thread can be reused.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Returns the archive metadata.
+    pub fn archive(&self) -> impl Deref<Target = Archive> + '_ {
+        self.archive.as_ref()
+    }
+
+    /// Returns the data of a file with the given path inside the archive.
+    ///
+    /// See [`ArchiveReader::read_file`].
+    pub fn read_file(&self, name: &str) -> Result<Vec<u8>, Error> {
+        self.lock().read_file(name)
+    }
+
+    /// Takes a closure to decode each files in the archive. The lock is held until all entries
+    /// are processed.
+    ///
+    /// See [`ArchiveReader::for_each_entries`].
+    pub fn for_each_entries<F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error>>(
+        &self,
+        each: F,
+    ) -> Result<(), Error> {
+        self.lock().for_each_entries(each)
+    }
+}
+
 /// Decoder for a specific block within a 7z archive.
 ///
 /// Provides access to entries within a single compression block and allows
 /// decoding files from that block.
 pub struct BlockDecoder<'a, R: Read + Seek> {
     thread_count: u32,
+    max_key_derivation_power: u8,
+    memory_limit: Option<u64>,
     block_index: usize,
     archive: &'a Archive,
     password: &'a Password,
-    source: &'a mut R,
+    source: Rc<RefCell<&'a mut R>>,
+    stream: Option<BlockStream<'a>>,
+    skip_progress: Option<Box<dyn FnMut(u64, u64) + 'a>>,
+}
+
+/// Position of a [`BlockDecoder`] in the decoded data of its block, captured with
+/// [`BlockDecoder::checkpoint`] to resume an interrupted extraction with
+/// [`BlockDecoder::resume_from`].
+///
+/// The checkpoint only records how far the block was decoded, so resuming always decodes the
+/// block from its start and discards the data in front of the entry. It can be stored by the
+/// caller, since all fields are public.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderCheckpoint {
+    /// Index of the block in [`Archive::blocks`].
+    pub block_index: usize,
+    /// Index of the next entry to decode in [`Archive::files`].
+    pub file_index: usize,
+    /// Number of decoded bytes of the block in front of the entry.
+    pub unpacked_offset: u64,
+}
+
+/// Decoder of a block that is positioned at the start of the entry `next_file_index`.
+struct BlockStream<'a> {
+    reader: Box<dyn Read + 'a>,
+    next_file_index: usize,
 }
 
 impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
@@ -1606,37 +2986,288 @@ impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
     ) -> Self {
         Self {
             thread_count,
+            max_key_derivation_power: DEFAULT_MAX_KEY_DERIVATION_POWER,
+            memory_limit: None,
             block_index,
             archive,
             password,
-            source,
+            source: Rc::new(RefCell::new(source)),
+            stream: None,
+            skip_progress: None,
         }
     }
 
     /// Sets the thread count to use when multi-threading is supported by the de-compression
-    /// (currently only LZMA2 if encoded with MT support).
+    /// (currently LZMA2 if encoded with MT support and BZIP2 if encoded as multiple streams).
     pub fn set_thread_count(&mut self, thread_count: u32) {
         self.thread_count = thread_count.clamp(1, 256);
     }
 
-    /// Returns a slice of archive entries contained in this block.
+    /// Sets the maximal key derivation power of AES encrypted coders, see
+    /// [`ArchiveReader::set_max_key_derivation_power`].
+    pub fn set_max_key_derivation_power(&mut self, max: u8) {
+        self.max_key_derivation_power = max.min(MAX_KEY_DERIVATION_POWER);
+    }
+
+    /// Sets the maximal memory in bytes that decoding the block may need, see
+    /// [`ArchiveReader::set_memory_limit`].
+    pub fn set_memory_limit(&mut self, limit: Option<u64>) {
+        self.memory_limit = limit;
+    }
+
+    /// Sets a callback that reports the progress of skipping data with
+    /// [`BlockDecoder::skip_to_entry`] and [`BlockDecoder::resume_from`].
     ///
-    /// The entries are returned in the order they appear in the block.
-    pub fn entries(&self) -> &[ArchiveEntry] {
-        let start = self.archive.stream_map.block_first_file_index[self.block_index];
-        let file_count = self.archive.blocks[self.block_index].num_unpack_sub_streams;
-        &self.archive.files[start..(file_count + start)]
+    /// The callback is called with the number of bytes that were skipped so far and the number of
+    /// bytes to skip in total.
+    pub fn set_skip_progress(&mut self, progress: impl FnMut(u64, u64) + 'a) {
+        self.skip_progress = Some(Box::new(progress));
+    }
+
+    /// Returns the position of the decoder, or `None` if decoding didn't start yet.
+    ///
+    /// After the last entry of the block was decoded, the checkpoint points behind it, at the
+    /// index that follows the last entry, with the unpacked size of all entries as offset.
+    pub fn checkpoint(&self) -> Option<DecoderCheckpoint> {
+        let stream = self.stream.as_ref()?;
+        Some(DecoderCheckpoint {
+            block_index: self.block_index,
+            file_index: stream.next_file_index,
+            unpacked_offset: self.offset_in_block(stream.next_file_index),
+        })
+    }
+
+    /// Positions the decoder at the start of the entry with the archive file index `file_index`
+    /// to resume an interrupted extraction of this block.
+    ///
+    /// The `checkpoint` is validated against the archive, so that an archive that changed since
+    /// the checkpoint was captured is detected. It must belong to this block and not be behind
+    /// `file_index`. A checkpoint captured after the last entry of the block means that the
+    /// block was extracted completely, resuming it with its own `file_index` does nothing.
+    ///
+    /// The state of the decoders can't be restored from a checkpoint, so resuming with a new
+    /// decoder always decodes the block from its start. The data in front of the entry is decoded
+    /// and discarded like with [`BlockDecoder::skip_to_entry`], which reports its progress to the
+    /// callback of [`BlockDecoder::set_skip_progress`]. The checkpoint only saves the writes of
+    /// the entries that were already extracted.
+    ///
+    /// # Arguments
+    /// * `file_index` - Index of the entry in [`Archive::files`], which must be part of this
+    ///   block, or the `file_index` of a checkpoint after the last entry
+    /// * `checkpoint` - The checkpoint captured by [`BlockDecoder::checkpoint`]
+    pub fn resume_from(
+        &mut self,
+        file_index: usize,
+        checkpoint: Option<DecoderCheckpoint>,
+    ) -> Result<(), Error> {
+        let end_index = self.end_entry_index();
+        if let Some(checkpoint) = checkpoint {
+            let in_block = self
+                .archive
+                .stream_map
+                .file_block_index
+                .get(checkpoint.file_index)
+                == Some(&Some(self.block_index));
+            if checkpoint.block_index != self.block_index
+                || checkpoint.file_index > file_index
+                || !(in_block || Some(checkpoint.file_index) == end_index)
+                || self.offset_in_block(checkpoint.file_index) != checkpoint.unpacked_offset
+            {
+                return Err(Error::CheckpointMismatch {
+                    file_index,
+                    block_index: self.block_index,
+                });
+            }
+            if Some(file_index) == end_index {
+                // The block was already extracted completely.
+                return Ok(());
+            }
+        }
+        self.skip_to_entry(file_index)
+    }
+
+    /// Returns the index that follows the last entry of this block, which a checkpoint captured
+    /// after the last entry points to, or `None` if the block has no entries.
+    fn end_entry_index(&self) -> Option<usize> {
+        self.archive
+            .stream_map
+            .block_entry_indices(self.block_index)
+            .last()
+            .map(|last| last + 1)
+    }
+
+    /// Returns the number of decoded bytes of the block in front of the entry `file_index`, or
+    /// the unpacked size of all entries if `file_index` follows the last entry.
+    fn offset_in_block(&self, file_index: usize) -> u64 {
+        let stream_map = &self.archive.stream_map;
+        if Some(file_index) == self.end_entry_index() {
+            let last = file_index - 1;
+            stream_map.entry_offset_in_block(last) + self.archive.files[last].size
+        } else {
+            stream_map.entry_offset_in_block(file_index)
+        }
+    }
+
+    /// Returns an iterator over the archive entries contained in this block.
+    ///
+    /// The entries are returned in the order they appear in the block. Entries without data,
+    /// like directories, are not part of any block.
+    pub fn entries(&self) -> impl Iterator<Item = &'a ArchiveEntry> + use<'a, R> {
+        let files = &self.archive.files;
+        self.archive
+            .stream_map
+            .block_entry_indices(self.block_index)
+            .map(move |file_index| &files[file_index])
     }
 
     /// Returns the number of entries contained in this block.
     pub fn entry_count(&self) -> usize {
-        self.archive.blocks[self.block_index].num_unpack_sub_streams
+        self.archive
+            .stream_map
+            .block_entry_indices(self.block_index)
+            .count()
+    }
+
+    /// Returns the index of the entry of this block that follows the entry `file_index`, or the
+    /// index after it if it is the last entry.
+    fn next_entry_index(&self, file_index: usize) -> usize {
+        self.archive
+            .stream_map
+            .block_entry_indices(self.block_index)
+            .find(|&index| index > file_index)
+            .unwrap_or(file_index + 1)
+    }
+
+    /// Positions the decoder at the start of the entry with the archive file index `file_index`.
+    ///
+    /// The data of the entries in front of it is decoded and discarded, without verifying their
+    /// CRCs. Skipping forward continues from the current position, so reading the entries of a
+    /// solid block in ascending order only decodes the block once. Skipping backwards restarts
+    /// decoding at the beginning of the block.
+    ///
+    /// # Arguments
+    /// * `file_index` - Index of the entry in [`Archive::files`], which must be part of this block
+    pub fn skip_to_entry(&mut self, file_index: usize) -> Result<(), Error> {
+        let stream = self.stream_at_entry(file_index)?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    /// Skips to the entry with the archive file index `file_index` like
+    /// [`BlockDecoder::skip_to_entry`] and returns the stream positioned at its data.
+    fn stream_at_entry(&mut self, file_index: usize) -> Result<BlockStream<'a>, Error> {
+        self.archive.checked_block(self.block_index)?;
+        if self.archive.stream_map.file_block_index.get(file_index) != Some(&Some(self.block_index))
+        {
+            return Err(Error::EntryNotInBlock {
+                file_index,
+                block_index: self.block_index,
+            });
+        }
+
+        if self
+            .stream
+            .as_ref()
+            .is_some_and(|stream| stream.next_file_index > file_index)
+        {
+            self.stream = None;
+        }
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => self.new_stream()?,
+        };
+
+        let stream_map = &self.archive.stream_map;
+        let total = stream_map.entry_offset_in_block(file_index)
+            - stream_map.entry_offset_in_block(stream.next_file_index);
+        let mut skipped = 0;
+        let mut buf = vec![0; total.min(64 * 1024) as usize];
+        while skipped < total {
+            let len = (buf.len() as u64).min(total - skipped) as usize;
+            let n = match stream.reader.read(&mut buf[..len]) {
+                Ok(0) => {
+                    return Err(Error::UnexpectedEndOfBlock {
+                        block_index: self.block_index,
+                    });
+                }
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(Error::from(e).maybe_bad_password(!self.password.is_empty()));
+                }
+            };
+            skipped += n as u64;
+            if let Some(progress) = self.skip_progress.as_mut() {
+                progress(skipped, total);
+            }
+        }
+        stream.next_file_index = file_index;
+        Ok(stream)
+    }
+
+    /// Decodes the entry with the archive file index `file_index` and returns its data.
+    ///
+    /// Uses [`BlockDecoder::skip_to_entry`] to reach the entry, so decoding multiple entries of
+    /// a solid block in ascending order only decodes the block once.
+    ///
+    /// # Arguments
+    /// * `file_index` - Index of the entry in [`Archive::files`], which must be part of this block
+    pub fn decode_entry_to_vec(&mut self, file_index: usize) -> Result<Vec<u8>, Error> {
+        let mut stream = self.stream_at_entry(file_index)?;
+        let file = &self.archive.files[file_index];
+        let mut data = Vec::new();
+        if file.has_stream && file.size > 0 {
+            let size = assert_usize(file.size, "file size")?;
+            data.reserve_exact(size);
+            let mut reader: Box<dyn Read> = Box::new(BoundedReader::new(&mut stream.reader, size));
+            if file.has_crc {
+                reader = Box::new(Crc32VerifyingReader::new(reader, size, file.crc));
+            }
+            #[cfg(feature = "blake3-checksum")]
+            if let Some(hash) = file.blake3_hash {
+                reader = Box::new(Blake3VerifyingReader::new(reader, size, hash));
+            }
+            reader
+                .read_to_end(&mut data)
+                .map_err(|e| Error::from(e).maybe_bad_password(!self.password.is_empty()))?;
+            if data.len() != size {
+                return Err(Error::UnexpectedEndOfBlock {
+                    block_index: self.block_index,
+                });
+            }
+        }
+
+        stream.next_file_index = self.next_entry_index(file_index);
+        self.stream = Some(stream);
+        Ok(data)
+    }
+
+    fn new_stream(&self) -> Result<BlockStream<'a>, Error> {
+        let source = SharedBoundedReader::new(Rc::clone(&self.source), (0, i64::MAX as u64));
+        let (reader, _size) = ArchiveReader::<R>::build_decode_stack(
+            source,
+            self.archive,
+            self.block_index,
+            self.password,
+            self.thread_count,
+            self.max_key_derivation_power,
+            self.memory_limit,
+        )?;
+        Ok(BlockStream {
+            reader,
+            next_file_index: self.archive.stream_map.block_first_file_index[self.block_index],
+        })
     }
 
     /// Takes a closure to decode each files in this block.
     ///
     /// When decoding files in a block, the data to be decompressed depends on the data in front of
     /// it, you cannot simply skip the previous data and only decompress the data in the back.
+    /// If [`BlockDecoder::skip_to_entry`] was used before, decoding continues with the entry
+    /// the decoder is positioned at.
+    ///
+    /// Data of an entry that the closure didn't read is decoded and verified after the closure
+    /// returns `Ok(true)`. Return `Ok(false)` to stop decoding the block instead.
     ///
     /// Non-solid archives use one block per file and allow more effective decoding of single files.
     pub fn for_each_entries<F: FnMut(&ArchiveEntry, &mut dyn Read) -> Result<bool, Error>>(
@@ -1645,38 +3276,71 @@ impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
     ) -> Result<bool, Error> {
         let Self {
             thread_count,
+            max_key_derivation_power,
+            memory_limit,
             block_index,
             archive,
             password,
             source,
+            stream,
+            skip_progress: _,
         } = self;
-        let (mut block_reader, _size) = ArchiveReader::build_decode_stack(
-            source,
-            archive,
-            block_index,
-            password,
-            thread_count,
-        )?;
+        archive.checked_block(block_index)?;
         let start = archive.stream_map.block_first_file_index[block_index];
-        let file_count = archive.blocks[block_index].num_unpack_sub_streams;
 
-        for file_index in start..(file_count + start) {
+        let (mut block_reader, first_file_index) = match stream {
+            Some(stream) => (stream.reader, stream.next_file_index),
+            None => {
+                let (block_reader, _size) = match Rc::try_unwrap(source) {
+                    Ok(source) => ArchiveReader::<R>::build_decode_stack(
+                        source.into_inner(),
+                        archive,
+                        block_index,
+                        password,
+                        thread_count,
+                        max_key_derivation_power,
+                        memory_limit,
+                    )?,
+                    Err(source) => ArchiveReader::<R>::build_decode_stack(
+                        SharedBoundedReader::new(source, (0, i64::MAX as u64)),
+                        archive,
+                        block_index,
+                        password,
+                        thread_count,
+                        max_key_derivation_power,
+                        memory_limit,
+                    )?,
+                };
+                (block_reader, start)
+            }
+        };
+
+        let file_indices = archive
+            .stream_map
+            .block_entry_indices(block_index)
+            .skip_while(|&file_index| file_index < first_file_index);
+        for file_index in file_indices {
             let file = &archive.files[file_index];
             if file.has_stream && file.size > 0 {
+                let size = assert_usize(file.size, "file size")?;
                 let mut decoder: Box<dyn Read> =
-                    Box::new(BoundedReader::new(&mut block_reader, file.size as usize));
+                    Box::new(BoundedReader::new(&mut block_reader, size));
                 if file.has_crc {
-                    decoder = Box::new(Crc32VerifyingReader::new(
-                        decoder,
-                        file.size as usize,
-                        file.crc,
-                    ));
+                    decoder = Box::new(Crc32VerifyingReader::new(decoder, size, file.crc));
+                }
+                #[cfg(feature = "blake3-checksum")]
+                if let Some(hash) = file.blake3_hash {
+                    decoder = Box::new(Blake3VerifyingReader::new(decoder, size, hash));
                 }
                 if !each(file, &mut decoder)
-                    .map_err(|e| e.maybe_bad_password(!self.password.is_empty()))?
+                    .map_err(|e| e.maybe_bad_password(!password.is_empty()))?
                 {
                     return Ok(false);
                 }
+                // Consume what the closure didn't read, so that the next entry starts at the
+                // right position and the CRC of this entry is still verified.
+                io::copy(&mut decoder, &mut io::sink())
+                    .map_err(|e| Error::from(e).maybe_bad_password(!password.is_empty()))?;
             } else {
                 let empty_reader: &mut dyn Read = &mut ([0u8; 0].as_slice());
                 if !each(file, empty_reader)? {
@@ -1686,4 +3350,174 @@ impl<'a, R: Read + Seek> BlockDecoder<'a, R> {
         }
         Ok(true)
     }
+
+    /// Same as [`BlockDecoder::for_each_entries`], but the closure returns a [`ControlFlow`].
+    ///
+    /// `Continue(Ok(()))` continues with the next entry, `Continue(Err(e))` stops and returns
+    /// the error and `Break(())` stops decoding the block. Returns `Break(())` if the closure
+    /// stopped decoding.
+    pub fn for_each_entries_cf<
+        F: FnMut(&ArchiveEntry, &mut dyn Read) -> ControlFlow<(), Result<(), Error>>,
+    >(
+        self,
+        mut each: F,
+    ) -> Result<ControlFlow<()>, Error> {
+        let completed = self.for_each_entries(&mut |entry, reader| match each(entry, reader) {
+            ControlFlow::Continue(result) => result.map(|()| true),
+            ControlFlow::Break(()) => Ok(false),
+        })?;
+        Ok(match completed {
+            true => ControlFlow::Continue(()),
+            false => ControlFlow::Break(()),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "compress"))]
+mod tests {
+    use super::*;
+
+    /// A reader that returns at most one byte per `read` call.
+    struct OneByteReader<R>(R);
+
+    impl<R: Read> Read for OneByteReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl<R: Seek> Seek for OneByteReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_header_parsing_is_short_read_safe() {
+        use crate::{ArchiveWriter, HeaderCompression, NtTime, SourceReader};
+
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_header_compression(HeaderCompression::Never);
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+            .unwrap();
+        let mut entry = ArchiveEntry::new_file("dir/file.txt");
+        entry.has_last_modified_date = true;
+        entry.last_modified_date = NtTime::new(133_000_000_000_000_001);
+        entry.has_windows_attributes = true;
+        entry.windows_attributes = 0x20;
+        entry.comment = Some("comment".to_string());
+        writer
+            .push_archive_entry(entry, Some(b"content".as_slice()))
+            .unwrap();
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+            .unwrap();
+        let (entries, readers) = ["a.txt", "b.txt", "c.txt"]
+            .into_iter()
+            .map(|name| {
+                (
+                    ArchiveEntry::new_file(name),
+                    SourceReader::new(name.as_bytes()),
+                )
+            })
+            .unzip();
+        writer.push_archive_entries(entries, readers).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let offset = 32 + u64::from_le_bytes(data[12..20].try_into().unwrap()) as usize;
+        let size = u64::from_le_bytes(data[20..28].try_into().unwrap()) as usize;
+        let header = &data[offset..offset + size];
+        assert_eq!(header[0], K_HEADER);
+
+        let mut expected = Archive::default();
+        Archive::read_header(
+            &mut Cursor::new(&header[1..]),
+            &mut Cursor::new(&data),
+            &mut expected,
+            &Password::empty(),
+            1,
+            &ArchiveReadOptions::default(),
+        )
+        .unwrap();
+        let mut archive = Archive::default();
+        Archive::read_header(
+            &mut OneByteReader(Cursor::new(&header[1..])),
+            &mut OneByteReader(Cursor::new(&data)),
+            &mut archive,
+            &Password::empty(),
+            1,
+            &ArchiveReadOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(archive.files.len(), 6);
+        assert_eq!(archive.blocks.len(), 2);
+        assert_eq!(archive.files[1].comment(), Some("comment"));
+        assert_eq!(format!("{archive:?}"), format!("{expected:?}"));
+    }
+
+    #[test]
+    fn test_compressed_header_is_not_aligned() {
+        use crate::{ArchiveWriter, HeaderCompression};
+
+        let write = |header_compression| {
+            let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+            writer.set_pack_stream_alignment(64);
+            writer.set_header_compression(header_compression);
+            for i in 0..10 {
+                let name = format!("file{i}.txt");
+                writer
+                    .push_archive_entry(ArchiveEntry::new_file(&name), Some(name.as_bytes()))
+                    .unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        };
+        let header_of = |data: &[u8]| {
+            let offset = 32 + u64::from_le_bytes(data[12..20].try_into().unwrap()) as usize;
+            let size = u64::from_le_bytes(data[20..28].try_into().unwrap()) as usize;
+            data[offset..offset + size].to_vec()
+        };
+
+        // The header is written after the packed streams, so the packed streams of both
+        // archives are at the same positions.
+        let plain_data = write(HeaderCompression::Never);
+        let plain_header = header_of(&plain_data);
+        assert_eq!(plain_header[0], K_HEADER);
+
+        let data = write(HeaderCompression::Always);
+        let encoded_header = header_of(&data);
+        assert_eq!(encoded_header[0], K_ENCODED_HEADER);
+        let mut source = Cursor::new(&data);
+        let (mut reader, _) = Archive::read_encoded_header(
+            &mut Cursor::new(&encoded_header[1..]),
+            &mut source,
+            &mut Archive::default(),
+            &Password::empty(),
+            1,
+            &ParseLimits::default(),
+        )
+        .unwrap();
+        let mut header = Vec::new();
+        reader.read_to_end(&mut header).unwrap();
+        assert_eq!(header[0], K_HEADER);
+
+        // Only the plain header contains dummy properties to align the names and times.
+        assert!(header.len() < plain_header.len());
+        let read = |header: &[u8], data: &[u8]| {
+            let mut archive = Archive::default();
+            Archive::read_header(
+                &mut Cursor::new(&header[1..]),
+                &mut Cursor::new(data),
+                &mut archive,
+                &Password::empty(),
+                1,
+                &ArchiveReadOptions::default(),
+            )
+            .unwrap();
+            format!("{archive:?}")
+        };
+        assert_eq!(read(&header, &data), read(&plain_header, &plain_data));
+    }
 }
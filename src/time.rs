@@ -1,16 +1,34 @@
+use std::time::{Duration, SystemTime};
+
 /// An error that can be thrown when converting to [`NtTime`]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum NtTimeError {
+    /// The date is before the [`NtTime`] epoch (1601-01-01).
     Negative,
+    /// The date is after the latest representable [`NtTime`].
     Overflow,
 }
 
+impl std::fmt::Display for NtTimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NtTimeError::Negative => write!(f, "date is before the NT time epoch"),
+            NtTimeError::Overflow => write!(f, "date is after the maximum NT time"),
+        }
+    }
+}
+
+impl std::error::Error for NtTimeError {}
+
 /// A type that represents a Windows file time and is used in the 7z archive format.
 ///
 /// Can easily be converted to and from [`std::time::SystemTime`].
 ///
-/// The feature flag `nt-time` implements conversions for [`nt_time::FileTime`].
+/// The feature flag `nt-time` implements conversions for [`nt_time::FileTime`], the feature
+/// flags `chrono` and `time` implement conversions for `chrono::DateTime<Utc>` and
+/// `time::OffsetDateTime`.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NtTime(pub(crate) u64);
 
 impl Default for NtTime {
@@ -21,13 +39,20 @@ impl Default for NtTime {
 
 impl NtTime {
     const FILE_TIMES_PER_SEC: u64 = 10_000_000;
+    const NANOS_PER_FILE_TIME: i128 = 100;
+    const NANOS_PER_SEC: i128 = 1_000_000_000;
 
     /// The [`NtTime`] of the  unix epoch (1970-01-01).
     pub const UNIX_EPOCH: NtTime = NtTime::new(134774 * 86400 * Self::FILE_TIMES_PER_SEC);
 
-    /// The epoch of the [`NtTime`] (0001-01-01).
+    /// The epoch of the [`NtTime`] (1601-01-01).
     pub const NT_TIME_EPOCH: NtTime = NtTime::new(0);
 
+    /// The latest [`NtTime`] that Windows accepts as a file time (30828-09-14).
+    ///
+    /// Conversions from dates after this saturate to this value.
+    pub const MAX: NtTime = NtTime::new(i64::MAX as u64);
+
     /// Creates a new [`NtTime`] with the given file time.
     #[must_use]
     #[inline]
@@ -39,20 +64,155 @@ impl NtTime {
     #[must_use]
     #[inline]
     pub fn now() -> Self {
-        use std::time::SystemTime;
-
         SystemTime::now()
             .try_into()
             .expect("the current date and time is not a valid NtTime")
     }
 
-    fn sub(self, rhs: Self) -> std::time::Duration {
-        let duration = self.0 - rhs.0;
-        std::time::Duration::new(
-            duration / Self::FILE_TIMES_PER_SEC,
-            u32::try_from((duration % Self::FILE_TIMES_PER_SEC) * 100)
-                .expect("the number of nanoseconds is not a valid `u32`"),
-        )
+    /// Creates an [`NtTime`] from the number of nanoseconds since the unix epoch.
+    ///
+    /// The value is rounded down to the 100 nanosecond resolution of [`NtTime`]. Dates after
+    /// [`NtTime::MAX`] saturate.
+    ///
+    /// # Arguments
+    /// * `nanos` - Nanoseconds since 1970-01-01, negative values are before the unix epoch
+    pub fn from_unix_nanos(nanos: i128) -> Result<Self, NtTimeError> {
+        let file_time =
+            nanos.div_euclid(Self::NANOS_PER_FILE_TIME) + i128::from(Self::UNIX_EPOCH.0);
+        if file_time < 0 {
+            return Err(NtTimeError::Negative);
+        }
+        Ok(Self::new(
+            u64::try_from(file_time)
+                .unwrap_or(u64::MAX)
+                .min(Self::MAX.0),
+        ))
+    }
+
+    /// Creates an [`NtTime`] from the number of seconds since the unix epoch.
+    ///
+    /// Dates after [`NtTime::MAX`] saturate.
+    ///
+    /// # Arguments
+    /// * `secs` - Seconds since 1970-01-01, negative values are before the unix epoch
+    pub fn from_unix_timestamp(secs: i64) -> Result<Self, NtTimeError> {
+        Self::from_unix_nanos(i128::from(secs) * Self::NANOS_PER_SEC)
+    }
+
+    /// Returns the number of nanoseconds since the unix epoch. Negative values are before the
+    /// unix epoch.
+    #[must_use]
+    pub fn to_unix_nanos(self) -> i128 {
+        (i128::from(self.0) - i128::from(Self::UNIX_EPOCH.0)) * Self::NANOS_PER_FILE_TIME
+    }
+
+    /// Returns the number of seconds since the unix epoch, including the fractional part.
+    #[must_use]
+    pub fn as_unix_secs_f64(self) -> f64 {
+        let secs = self.to_unix_nanos().div_euclid(Self::NANOS_PER_SEC);
+        let nanos = self.to_unix_nanos().rem_euclid(Self::NANOS_PER_SEC);
+        secs as f64 + nanos as f64 / Self::NANOS_PER_SEC as f64
+    }
+
+    /// Returns the latest representable [`NtTime`], which is [`NtTime::MAX`].
+    #[must_use]
+    #[inline]
+    pub const fn max_value() -> NtTime {
+        Self::MAX
+    }
+
+    /// Returns whether this is the [`NtTime`] epoch (1601-01-01), which 7z archives also use
+    /// for unset times.
+    #[must_use]
+    #[inline]
+    pub const fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the time elapsed from `earlier` to this time, or `None` if `earlier` is later
+    /// than this time.
+    ///
+    /// # Arguments
+    /// * `earlier` - The start of the duration
+    #[must_use]
+    pub fn duration_since(&self, earlier: NtTime) -> Option<Duration> {
+        let file_times = self.0.checked_sub(earlier.0)?;
+        Some(Duration::new(
+            file_times / Self::FILE_TIMES_PER_SEC,
+            (file_times % Self::FILE_TIMES_PER_SEC) as u32 * Self::NANOS_PER_FILE_TIME as u32,
+        ))
+    }
+
+    /// Returns this time plus `duration`, or `None` if the result is after [`NtTime::MAX`].
+    ///
+    /// The duration is rounded down to the 100 nanosecond resolution of [`NtTime`].
+    ///
+    /// # Arguments
+    /// * `duration` - The duration to add
+    #[must_use]
+    pub fn checked_add(&self, duration: Duration) -> Option<NtTime> {
+        let file_times =
+            u64::try_from(duration.as_nanos() / Self::NANOS_PER_FILE_TIME as u128).ok()?;
+        let file_time = self.0.checked_add(file_times)?;
+        (file_time <= Self::MAX.0).then_some(Self::new(file_time))
+    }
+
+    /// Converts this [`NtTime`] to a [`SystemTime`].
+    ///
+    /// Returns `None` if the date is not representable as a [`SystemTime`] on this platform.
+    #[must_use]
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        let nanos = self.to_unix_nanos();
+        let duration = Duration::new(
+            u64::try_from(nanos.unsigned_abs() / Self::NANOS_PER_SEC as u128).ok()?,
+            (nanos.unsigned_abs() % Self::NANOS_PER_SEC as u128) as u32,
+        );
+        if nanos >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(duration)
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(duration)
+        }
+    }
+}
+
+impl std::fmt::Display for NtTime {
+    /// Formats the time as ISO 8601 timestamp in UTC, like `2024-05-17T08:30:00Z`. The fraction
+    /// of the second is only shown if it is not 0, with the 100 nanosecond resolution of
+    /// [`NtTime`].
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const SECS_PER_DAY: u64 = 86400;
+        let secs = self.0 / Self::FILE_TIMES_PER_SEC;
+        let fraction = self.0 % Self::FILE_TIMES_PER_SEC;
+        let time_of_day = secs % SECS_PER_DAY;
+
+        // Civil date from the days since 0000-03-01, so that the leap day is the last day of a
+        // year. 1601-01-01 is day 584_694 of that calendar.
+        let days = secs / SECS_PER_DAY + 584_694;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+            time_of_day / 3600,
+            time_of_day / 60 % 60,
+            time_of_day % 60
+        )?;
+        if fraction != 0 {
+            write!(f, ".{fraction:07}")?;
+        }
+        write!(f, "Z")
     }
 }
 
@@ -85,39 +245,31 @@ impl TryFrom<i64> for NtTime {
     }
 }
 
-impl From<NtTime> for std::time::SystemTime {
-    /// Converts a [`NtTime`] to a [`SystemTime`](std::time::SystemTime).
+impl From<NtTime> for SystemTime {
+    /// Converts a [`NtTime`] to a [`SystemTime`].
+    ///
+    /// # Panics
+    /// If the date is not representable as a [`SystemTime`] on this platform. Use
+    /// [`NtTime::to_system_time`] for a fallible conversion.
     #[inline]
     fn from(file_time: NtTime) -> Self {
-        let duration = std::time::Duration::new(
-            file_time.0 / NtTime::FILE_TIMES_PER_SEC,
-            u32::try_from((file_time.0 % NtTime::FILE_TIMES_PER_SEC) * 100)
-                .expect("the number of nanoseconds is not a valid `u32`"),
-        );
-
-        (std::time::SystemTime::UNIX_EPOCH - (NtTime::UNIX_EPOCH.sub(NtTime::NT_TIME_EPOCH)))
-            + duration
+        file_time
+            .to_system_time()
+            .expect("the NtTime is not representable as a SystemTime")
     }
 }
 
-impl TryFrom<std::time::SystemTime> for NtTime {
+impl TryFrom<SystemTime> for NtTime {
     type Error = NtTimeError;
 
-    /// Converts a [`SystemTime`](std::time::SystemTime) to a `FileTime`.
+    /// Converts a [`SystemTime`] to a [`NtTime`]. Dates after [`NtTime::MAX`] saturate.
     #[inline]
-    fn try_from(st: std::time::SystemTime) -> Result<Self, Self::Error> {
-        use std::time::SystemTime;
-
-        let elapsed = st
-            .duration_since(
-                SystemTime::UNIX_EPOCH - (NtTime::UNIX_EPOCH.sub(NtTime::NT_TIME_EPOCH)),
-            )
-            .map(|d| d.as_nanos())
-            .map_err(|_| NtTimeError::Negative)?;
-
-        let file_time = u64::try_from(elapsed / 100).map_err(|_| NtTimeError::Overflow)?;
-
-        Ok(Self::new(file_time))
+    fn try_from(st: SystemTime) -> Result<Self, Self::Error> {
+        let nanos = match st.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(duration) => i128::try_from(duration.as_nanos()).unwrap_or(i128::MAX),
+            Err(err) => -i128::try_from(err.duration().as_nanos()).unwrap_or(i128::MAX),
+        };
+        Self::from_unix_nanos(nanos)
     }
 }
 
@@ -134,3 +286,182 @@ impl From<nt_time::FileTime> for NtTime {
         Self::new(value.to_raw())
     }
 }
+
+#[cfg(feature = "chrono")]
+impl From<NtTime> for chrono::DateTime<chrono::Utc> {
+    /// Converts a [`NtTime`] to a [`chrono::DateTime`]. All [`NtTime`] values are representable.
+    fn from(value: NtTime) -> Self {
+        let nanos = value.to_unix_nanos();
+        let secs = nanos.div_euclid(NtTime::NANOS_PER_SEC) as i64;
+        let subsec_nanos = nanos.rem_euclid(NtTime::NANOS_PER_SEC) as u32;
+        chrono::DateTime::from_timestamp(secs, subsec_nanos)
+            .expect("all NtTime values are representable by chrono")
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> TryFrom<chrono::DateTime<Tz>> for NtTime {
+    type Error = NtTimeError;
+
+    /// Converts a [`chrono::DateTime`] to a [`NtTime`]. Dates after [`NtTime::MAX`] saturate.
+    fn try_from(value: chrono::DateTime<Tz>) -> Result<Self, Self::Error> {
+        let nanos = i128::from(value.timestamp()) * NtTime::NANOS_PER_SEC
+            + i128::from(value.timestamp_subsec_nanos());
+        Self::from_unix_nanos(nanos)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<NtTime> for ::time::OffsetDateTime {
+    type Error = NtTimeError;
+
+    /// Converts a [`NtTime`] to a [`time::OffsetDateTime`] in UTC. Fails with
+    /// [`NtTimeError::Overflow`] for dates after the year 9999, unless the `large-dates`
+    /// feature of `time` is enabled.
+    fn try_from(value: NtTime) -> Result<Self, Self::Error> {
+        ::time::OffsetDateTime::from_unix_timestamp_nanos(value.to_unix_nanos())
+            .map_err(|_| NtTimeError::Overflow)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<::time::OffsetDateTime> for NtTime {
+    type Error = NtTimeError;
+
+    /// Converts a [`time::OffsetDateTime`] to a [`NtTime`]. Dates after [`NtTime::MAX`]
+    /// saturate.
+    fn try_from(value: ::time::OffsetDateTime) -> Result<Self, Self::Error> {
+        Self::from_unix_nanos(value.unix_timestamp_nanos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn unix_epoch() {
+        assert_eq!(NtTime::UNIX_EPOCH.to_unix_nanos(), 0);
+        assert_eq!(
+            NtTime::UNIX_EPOCH.to_system_time(),
+            Some(SystemTime::UNIX_EPOCH)
+        );
+        assert_eq!(NtTime::from_unix_timestamp(0), Ok(NtTime::UNIX_EPOCH));
+        assert_eq!(
+            NtTime::from_unix_timestamp(-11_644_473_600),
+            Ok(NtTime::NT_TIME_EPOCH)
+        );
+        assert_eq!(
+            NtTime::from_unix_timestamp(-11_644_473_601),
+            Err(NtTimeError::Negative)
+        );
+        assert_eq!(
+            NtTime::new(NtTime::UNIX_EPOCH.0 + 5).as_unix_secs_f64(),
+            5e-7
+        );
+    }
+
+    #[test]
+    fn saturates_after_max() {
+        assert_eq!(NtTime::from_unix_nanos(i128::MAX), Ok(NtTime::MAX));
+        let max_nanos = NtTime::MAX.to_unix_nanos();
+        assert_eq!(NtTime::from_unix_nanos(max_nanos), Ok(NtTime::MAX));
+        assert_eq!(NtTime::from_unix_nanos(max_nanos + 100), Ok(NtTime::MAX));
+        assert_eq!(
+            NtTime::from_unix_nanos(max_nanos - 100).unwrap().0,
+            i64::MAX as u64 - 1
+        );
+    }
+
+    #[test]
+    fn rounds_down_to_file_time_resolution() {
+        assert_eq!(NtTime::from_unix_nanos(199).unwrap().to_unix_nanos(), 100);
+        assert_eq!(NtTime::from_unix_nanos(-1).unwrap().to_unix_nanos(), -100);
+        assert_eq!(NtTime::from_unix_nanos(-100).unwrap().to_unix_nanos(), -100);
+    }
+
+    #[test]
+    fn arithmetic_and_display() {
+        assert!(NtTime::NT_TIME_EPOCH.is_zero());
+        assert!(!NtTime::UNIX_EPOCH.is_zero());
+        assert_eq!(NtTime::max_value(), NtTime::MAX);
+        assert!(NtTime::NT_TIME_EPOCH < NtTime::UNIX_EPOCH);
+
+        assert_eq!(NtTime::NT_TIME_EPOCH.to_string(), "1601-01-01T00:00:00Z");
+        assert_eq!(NtTime::UNIX_EPOCH.to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(NtTime::MAX.to_string(), "30828-09-14T02:48:05.4775807Z");
+        assert_eq!(
+            NtTime::from_unix_nanos(951_782_400_000_000_100)
+                .unwrap()
+                .to_string(),
+            "2000-02-29T00:00:00.0000001Z"
+        );
+
+        // 2038-01-19T03:14:07Z is the last second representable with a signed 32-bit time_t.
+        let before_2038 = NtTime::from_unix_timestamp(i32::MAX as i64).unwrap();
+        assert_eq!(before_2038.to_string(), "2038-01-19T03:14:07Z");
+        let after_2038 = before_2038
+            .checked_add(Duration::from_secs(86400 * 365))
+            .unwrap();
+        assert_eq!(after_2038.to_string(), "2039-01-19T03:14:07Z");
+        assert_eq!(
+            after_2038.duration_since(NtTime::UNIX_EPOCH),
+            Some(Duration::from_secs(i32::MAX as u64 + 86400 * 365))
+        );
+        assert_eq!(
+            after_2038.duration_since(before_2038),
+            Some(Duration::from_secs(86400 * 365))
+        );
+        assert_eq!(before_2038.duration_since(after_2038), None);
+        assert_eq!(
+            NtTime::MAX.duration_since(NtTime::NT_TIME_EPOCH),
+            Some(Duration::new(922_337_203_685, 477_580_700))
+        );
+
+        assert_eq!(
+            NtTime::UNIX_EPOCH.checked_add(Duration::from_nanos(199)),
+            Some(NtTime::new(NtTime::UNIX_EPOCH.0 + 1))
+        );
+        assert_eq!(NtTime::MAX.checked_add(Duration::ZERO), Some(NtTime::MAX));
+        assert_eq!(NtTime::MAX.checked_add(Duration::from_nanos(100)), None);
+        assert_eq!(NtTime::UNIX_EPOCH.checked_add(Duration::MAX), None);
+    }
+
+    #[test]
+    fn round_trip_across_valid_range() {
+        let mut rng = rand::rng();
+        for _ in 0..10_000 {
+            let time = NtTime::new(rng.random_range(0..=NtTime::MAX.0));
+
+            assert_eq!(NtTime::from_unix_nanos(time.to_unix_nanos()), Ok(time));
+
+            if let Some(system_time) = time.to_system_time() {
+                assert_eq!(NtTime::try_from(system_time), Ok(time));
+            }
+
+            #[cfg(feature = "chrono")]
+            {
+                let date_time: chrono::DateTime<chrono::Utc> = time.into();
+                assert_eq!(NtTime::try_from(date_time), Ok(time));
+                use chrono::{Datelike, Timelike};
+                let formatted = format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                    date_time.year(),
+                    date_time.month(),
+                    date_time.day(),
+                    date_time.hour(),
+                    date_time.minute(),
+                    date_time.second()
+                );
+                assert!(time.to_string().starts_with(&formatted));
+            }
+
+            #[cfg(feature = "time")]
+            if let Ok(date_time) = ::time::OffsetDateTime::try_from(time) {
+                assert_eq!(NtTime::try_from(date_time), Ok(time));
+            }
+        }
+    }
+}
@@ -1,7 +1,11 @@
 #[cfg(all(feature = "compress", feature = "util", not(target_arch = "wasm32")))]
 pub(crate) mod compress;
 #[cfg(all(feature = "util", not(target_arch = "wasm32")))]
+pub(crate) mod convert;
+#[cfg(all(feature = "util", not(target_arch = "wasm32")))]
 pub(crate) mod decompress;
+#[cfg(all(feature = "compress", feature = "util", not(target_arch = "wasm32")))]
+pub(crate) mod merge;
 
 #[cfg(target_arch = "wasm32")]
 pub(crate) mod wasm;
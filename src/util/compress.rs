@@ -2,13 +2,13 @@
 
 use std::{
     fs::File,
-    io::{Seek, Write},
+    io::{Cursor, Seek, Write},
     path::{Path, PathBuf},
 };
 
 #[cfg(feature = "aes256")]
 use crate::encoder_options::AesEncoderOptions;
-use crate::{ArchiveEntry, ArchiveWriter, EncoderMethod, Error, Password, writer::LazyFileReader};
+use crate::{ArchiveEntry, ArchiveWriter, EncoderMethod, Error, Password, SourceReader};
 
 /// Compresses a source file or directory to a destination writer.
 ///
@@ -105,6 +105,23 @@ pub fn compress_to_path_encrypted(
     Ok(())
 }
 
+/// Creates an in-memory archive of the given entries, each compressed with `method`.
+///
+/// # Arguments
+/// * `entries` - The names and contents of the files to put into the archive
+/// * `method` - The compression method to use for the content
+pub fn create_archive_bytes(
+    entries: Vec<(String, Vec<u8>)>,
+    method: EncoderMethod,
+) -> Result<Vec<u8>, Error> {
+    let mut archive_writer = ArchiveWriter::new(Cursor::new(Vec::new()))?;
+    archive_writer.set_content_methods(vec![method.into()]);
+    for (name, data) in entries {
+        archive_writer.push_archive_entry(ArchiveEntry::new_file(&name), Some(data.as_slice()))?;
+    }
+    Ok(archive_writer.finish_into_bytes()?)
+}
+
 fn compress_path<W: Write + Seek, P: AsRef<Path>>(
     src: P,
     root: &Path,
@@ -120,6 +137,9 @@ fn compress_path<W: Write + Seek, P: AsRef<Path>>(
     let path = src.as_ref();
     if path.is_dir() {
         archive_writer.push_archive_entry::<&[u8]>(entry, None)?;
+        // Collect the children first, so that the directory handle is closed before recursing.
+        // This keeps the number of open file descriptors bounded for deep directory trees.
+        let mut children = Vec::new();
         for dir in path
             .read_dir()
             .map_err(|e| Error::io_msg(e, "error read dir"))?
@@ -127,9 +147,12 @@ fn compress_path<W: Write + Seek, P: AsRef<Path>>(
             let dir = dir?;
             let ftype = dir.file_type()?;
             if ftype.is_dir() || ftype.is_file() {
-                compress_path(dir.path(), root, archive_writer)?;
+                children.push(dir.path());
             }
         }
+        for child in children {
+            compress_path(child, root, archive_writer)?;
+        }
     } else {
         archive_writer.push_archive_entry(
             entry,
@@ -176,6 +199,109 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         encode_path(false, &path, self, filter)?;
         Ok(self)
     }
+
+    /// Recursively adds all files and directories below `base` to the archive.
+    ///
+    /// Every file is added as its own non-solid entry and every directory as an empty
+    /// directory entry. Entry names are the paths relative to `base`, using `/` as separator
+    /// and prefixed with `prefix` if it is not empty. The `base` directory itself is not added.
+    ///
+    /// Returns the total number of entries added.
+    ///
+    /// # Arguments
+    /// * `base` - Path to the directory to walk
+    /// * `prefix` - Prefix prepended to every entry name, e.g. `"data"` produces `"data/file.txt"`
+    pub fn push_directory_tree(
+        &mut self,
+        base: impl AsRef<Path>,
+        prefix: &str,
+    ) -> Result<usize, Error> {
+        self.push_directory_tree_with_options(base, prefix, &DirectoryTreeOptions::default())
+    }
+
+    /// Same as [`ArchiveWriter::push_directory_tree`], but allows to configure the walk
+    /// with [`DirectoryTreeOptions`].
+    ///
+    /// # Arguments
+    /// * `base` - Path to the directory to walk
+    /// * `prefix` - Prefix prepended to every entry name
+    /// * `options` - Options controlling which entries are added
+    pub fn push_directory_tree_with_options(
+        &mut self,
+        base: impl AsRef<Path>,
+        prefix: &str,
+        options: &DirectoryTreeOptions,
+    ) -> Result<usize, Error> {
+        let base = base.as_ref();
+        if !base.is_dir() {
+            return Err(Error::io_msg(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "Not a directory"),
+                format!("Failed to walk directory tree: {base:?}"),
+            ));
+        }
+        let prefix = prefix.trim_end_matches('/');
+        push_directory_tree(base, prefix, options, self)
+    }
+}
+
+/// Options for [`ArchiveWriter::push_directory_tree_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryTreeOptions {
+    /// Skip files and directories whose name starts with a `.`. Hidden directories are
+    /// skipped together with their content.
+    pub ignore_hidden: bool,
+}
+
+impl DirectoryTreeOptions {
+    /// Sets whether hidden files and directories should be skipped.
+    pub fn with_ignore_hidden(mut self, ignore_hidden: bool) -> Self {
+        self.ignore_hidden = ignore_hidden;
+        self
+    }
+}
+
+fn push_directory_tree<W: Write + Seek>(
+    dir: &Path,
+    name_prefix: &str,
+    options: &DirectoryTreeOptions,
+    archive_writer: &mut ArchiveWriter<W>,
+) -> Result<usize, Error> {
+    let mut children = dir
+        .read_dir()
+        .map_err(|e| Error::io_msg(e, format!("Failed to read dir: {dir:?}")))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::io_msg(e, format!("Failed to read dir: {dir:?}")))?;
+    // Sort to get a stable entry order independent of the file system.
+    children.sort_by_key(|child| child.file_name());
+
+    let mut count = 0;
+    for child in children {
+        let file_name = child.file_name().to_string_lossy().to_string();
+        if options.ignore_hidden && file_name.starts_with('.') {
+            continue;
+        }
+        let entry_name = if name_prefix.is_empty() {
+            file_name
+        } else {
+            format!("{name_prefix}/{file_name}")
+        };
+
+        let path = child.path();
+        let file_type = child.file_type()?;
+        if file_type.is_dir() {
+            let entry = ArchiveEntry::from_path(&path, entry_name.clone());
+            archive_writer.push_archive_entry::<&[u8]>(entry, None)?;
+            count += 1;
+            count += push_directory_tree(&path, &entry_name, options, archive_writer)?;
+        } else if file_type.is_file() {
+            let entry = ArchiveEntry::from_path(&path, entry_name);
+            let file = File::open(&path)
+                .map_err(|e| Error::file_open(e, path.to_string_lossy().to_string()))?;
+            archive_writer.push_archive_entry(entry, Some(file))?;
+            count += 1;
+        }
+    }
+    Ok(count)
 }
 
 fn collect_file_paths(
@@ -188,13 +314,17 @@ fn collect_file_paths(
         return Ok(());
     }
     if path.is_dir() {
+        let mut children = Vec::new();
         for dir in path.read_dir()? {
             let dir = dir?;
             let ftype = dir.file_type()?;
             if ftype.is_file() || ftype.is_dir() {
-                collect_file_paths(dir.path(), paths, filter)?;
+                children.push(dir.path());
             }
         }
+        for child in children {
+            collect_file_paths(child, paths, filter)?;
+        }
     } else {
         paths.push(path.to_path_buf())
     }
@@ -224,7 +354,10 @@ fn encode_path<W: Write + Seek>(
 
             zip.push_archive_entry(
                 ArchiveEntry::from_path(ele.as_path(), name),
-                Some(File::open(ele.as_path())?),
+                Some(
+                    File::open(ele.as_path())
+                        .map_err(|e| Error::file_open(e, ele.to_string_lossy().to_string()))?,
+                ),
             )?;
         }
         return Ok(());
@@ -238,7 +371,10 @@ fn encode_path<W: Write + Seek>(
         if size >= MAX_BLOCK_SIZE {
             zip.push_archive_entry(
                 ArchiveEntry::from_path(ele.as_path(), name),
-                Some(File::open(ele.as_path())?),
+                Some(
+                    File::open(ele.as_path())
+                        .map_err(|e| Error::file_open(e, ele.to_string_lossy().to_string()))?,
+                ),
             )?;
             continue;
         }
@@ -250,7 +386,7 @@ fn encode_path<W: Write + Seek>(
         }
         file_size += size;
         entries.push(ArchiveEntry::from_path(ele.as_path(), name));
-        files.push(LazyFileReader::new(ele).into());
+        files.push(SourceReader::from_path(ele));
     }
     if !entries.is_empty() {
         zip.push_archive_entries(entries, files)?;
@@ -0,0 +1,571 @@
+//! Helper functions to convert between 7z and tar archives
+
+#[cfg(feature = "compress")]
+use std::io::Cursor;
+use std::io::{self, Read, Seek, Write};
+
+use crate::{ArchiveEntry, ArchiveReader, Error, Password, WindowsAttributes};
+#[cfg(feature = "compress")]
+use crate::{ArchiveWriter, NtTime, SourceReader};
+
+const BLOCK_SIZE: usize = 512;
+/// Largest value of the 11 octal digits of the size and modification time fields.
+const MAX_OCTAL_11: u64 = 0o77777777777;
+#[cfg(feature = "compress")]
+const UNIX_DIRECTORY: u32 = 0o040000;
+#[cfg(feature = "compress")]
+const UNIX_REGULAR_FILE: u32 = 0o100000;
+const UNIX_SYMLINK: u32 = 0o120000;
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+
+/// Converts the 7z archive `reader` into a tar archive written to `writer`.
+///
+/// The entries are converted in the order they are stored in the blocks, so the data of solid
+/// archives is decoded in a single pass and streamed into the tar archive without buffering
+/// whole entries. Entries without data, like directories, follow at the end. The tar headers
+/// carry the name, size and modification time of every entry, and the unix permissions if the
+/// archive stores them. Symbolic links, entries with the unix file type of a link whose data is
+/// the target, are written as tar symbolic links. Names, link targets and sizes that don't fit
+/// into a ustar header are written as PAX extended headers. Anti-items are skipped.
+///
+/// # Arguments
+/// * `reader` - The 7z archive to convert
+/// * `writer` - Writer the tar archive is written to
+/// * `password` - Password of the 7z archive, [`Password::empty`] if it isn't encrypted
+pub fn sevenz_to_tar<R: Read + Seek, W: Write>(
+    reader: R,
+    mut writer: W,
+    password: Password,
+) -> Result<W, Error> {
+    let mut archive = ArchiveReader::new(reader, password)?;
+    archive.for_each_entries(|entry, data| {
+        if entry.is_anti_item() {
+            return Ok(true);
+        }
+        let map_err = |e| Error::io_msg(e, format!("Write tar entry:{}", entry.name()));
+        if is_symlink(entry) {
+            let mut target = Vec::new();
+            data.read_to_end(&mut target).map_err(map_err)?;
+            let target = String::from_utf8_lossy(&target);
+            write_tar_header(&mut writer, entry, 0, Some(&target)).map_err(map_err)?;
+            return Ok(true);
+        }
+        let size = if entry.is_directory() {
+            0
+        } else {
+            entry.size()
+        };
+        write_tar_header(&mut writer, entry, size, None).map_err(map_err)?;
+        if size > 0 {
+            let written = std::io::copy(data, &mut writer).map_err(map_err)?;
+            if written != size {
+                return Err(Error::other(format!(
+                    "Entry {} has {written} bytes instead of {size}",
+                    entry.name()
+                )));
+            }
+            write_padding(&mut writer, size).map_err(map_err)?;
+        }
+        Ok(true)
+    })?;
+    // The end of the archive is marked by two empty blocks.
+    writer
+        .write_all(&[0; 2 * BLOCK_SIZE])
+        .map_err(|e| Error::io_msg(e, "Write end of tar archive"))?;
+    Ok(writer)
+}
+
+/// Converts the tar archive `reader` into 7z entries added to `writer`.
+///
+/// The tar archive is read sequentially. Entries are compressed solid in blocks of at least
+/// `solid_block_size` bytes, which are buffered in memory until the block is complete. Entries
+/// that aren't smaller than `solid_block_size` are streamed into a block of their own, so a
+/// `solid_block_size` of 0 compresses every entry separately. Directories and empty files don't
+/// need a block and are added when they are read.
+///
+/// The name, modification time and unix permissions of regular files and directories are
+/// converted, including the names and sizes of GNU and PAX extended headers. Symbolic links are
+/// stored like 7-Zip stores them, as entries with the unix file type of a link and the target as
+/// data. Other entry types, like hard links and devices, can't be stored in 7z archives and are
+/// skipped.
+///
+/// Returns the names of the skipped entries.
+///
+/// # Arguments
+/// * `reader` - The tar archive to convert
+/// * `writer` - Writer the entries are added to. It needs to be finished by the caller
+/// * `solid_block_size` - Uncompressed size of the solid blocks in bytes
+#[cfg(feature = "compress")]
+pub fn tar_to_sevenz<R: Read, W: Write + Seek>(
+    mut reader: R,
+    writer: &mut ArchiveWriter<W>,
+    solid_block_size: u64,
+) -> Result<Vec<String>, Error> {
+    let mut block_entries = Vec::new();
+    let mut block_data = Vec::new();
+    let mut block_size = 0;
+    let mut long_name = None;
+    let mut long_link_name = None;
+    let mut pax = PaxHeader::default();
+    let mut skipped = Vec::new();
+
+    while let Some(header) = read_tar_header(&mut reader)? {
+        let size = header.size;
+        match header.kind {
+            // GNU long name of the next entry.
+            b'L' => {
+                let mut name = read_tar_data(&mut reader, size, "GNU long name")?;
+                if let Some(end) = name.iter().position(|&b| b == 0) {
+                    name.truncate(end);
+                }
+                long_name = Some(String::from_utf8_lossy(&name).into_owned());
+                continue;
+            }
+            // GNU long link target of the next entry.
+            b'K' => {
+                let mut name = read_tar_data(&mut reader, size, "GNU long link name")?;
+                if let Some(end) = name.iter().position(|&b| b == 0) {
+                    name.truncate(end);
+                }
+                long_link_name = Some(String::from_utf8_lossy(&name).into_owned());
+                continue;
+            }
+            // PAX extended header of the next entry.
+            b'x' => {
+                let data = read_tar_data(&mut reader, size, "PAX header")?;
+                pax = PaxHeader::parse(&data)?;
+                continue;
+            }
+            // PAX global header, which only carries defaults that aren't converted.
+            b'g' => {
+                read_tar_data(&mut reader, size, "PAX global header")?;
+                continue;
+            }
+            _ => {}
+        }
+
+        let name = pax
+            .path
+            .take()
+            .or_else(|| long_name.take())
+            .unwrap_or(header.name);
+        let name = name.trim_start_matches("./").trim_end_matches('/');
+        let link_name = pax
+            .link_path
+            .take()
+            .or_else(|| long_link_name.take())
+            .unwrap_or(header.link_name);
+        let size = pax.size.take().unwrap_or(size);
+        let mtime = pax.mtime.take().unwrap_or(header.mtime as i64);
+        let mode = header.mode & 0o7777;
+        let (mut entry, size) = match header.kind {
+            b'0' | b'\0' | b'7' => {
+                let entry = ArchiveEntry::new_file(name).with_windows_attributes(unix_attributes(
+                    WindowsAttributes::empty(),
+                    UNIX_REGULAR_FILE | mode,
+                ));
+                (entry, size)
+            }
+            b'5' => {
+                // Directories have no data, but skip it in case there is some anyway.
+                read_tar_data(&mut reader, size, name)?;
+                // The directory the archive was created in has no name of its own.
+                if name.is_empty() {
+                    continue;
+                }
+                let entry = ArchiveEntry::new_directory(name).with_windows_attributes(
+                    unix_attributes(WindowsAttributes::DIRECTORY, UNIX_DIRECTORY | mode),
+                );
+                (entry, 0)
+            }
+            b'2' => {
+                read_tar_data(&mut reader, size, name)?;
+                let mut entry = ArchiveEntry::new_file(name).with_windows_attributes(
+                    unix_attributes(WindowsAttributes::empty(), UNIX_SYMLINK | mode),
+                );
+                if let Ok(date) = NtTime::from_unix_timestamp(mtime) {
+                    entry = entry.with_last_modified_date(date);
+                }
+                // The target is the data of the entry, which isn't read from the tar archive.
+                block_size += link_name.len() as u64;
+                block_data.push(link_name.into_bytes());
+                block_entries.push(entry);
+                if block_size >= solid_block_size {
+                    push_tar_block(writer, &mut block_entries, &mut block_data)?;
+                    block_size = 0;
+                }
+                continue;
+            }
+            _ => {
+                read_tar_data(&mut reader, size, name)?;
+                skipped.push(name.to_string());
+                continue;
+            }
+        };
+        if let Ok(date) = NtTime::from_unix_timestamp(mtime) {
+            entry = entry.with_last_modified_date(date);
+        }
+
+        if size == 0 {
+            writer.push_archive_entry::<&[u8]>(entry, None)?;
+        } else if size >= solid_block_size {
+            let name = entry.name.clone();
+            let pushed = writer.push_archive_entry(entry, Some((&mut reader).take(size)))?;
+            if pushed.size() != size {
+                return Err(Error::io_msg(
+                    io::ErrorKind::UnexpectedEof.into(),
+                    format!("Read tar entry:{name}"),
+                ));
+            }
+            skip_padding(&mut reader, size)?;
+        } else {
+            block_data.push(read_tar_data(&mut reader, size, &entry.name)?);
+            block_entries.push(entry);
+            block_size += size;
+            if block_size >= solid_block_size {
+                push_tar_block(writer, &mut block_entries, &mut block_data)?;
+                block_size = 0;
+            }
+        }
+    }
+    push_tar_block(writer, &mut block_entries, &mut block_data)?;
+    Ok(skipped)
+}
+
+#[cfg(feature = "compress")]
+fn push_tar_block<W: Write + Seek>(
+    writer: &mut ArchiveWriter<W>,
+    entries: &mut Vec<ArchiveEntry>,
+    data: &mut Vec<Vec<u8>>,
+) -> Result<(), Error> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let readers = data
+        .drain(..)
+        .map(|data| SourceReader::new(Cursor::new(data)))
+        .collect();
+    writer.push_archive_entries(std::mem::take(entries), readers)?;
+    Ok(())
+}
+
+#[cfg(feature = "compress")]
+fn unix_attributes(attributes: WindowsAttributes, mode: u32) -> u32 {
+    (attributes | WindowsAttributes::UNIX_EXTENSION).bits() | (mode << 16)
+}
+
+/// Returns whether `entry` is a symbolic link, a file with the unix file type of a link.
+fn is_symlink(entry: &ArchiveEntry) -> bool {
+    let attributes = entry.windows_attributes_typed();
+    entry.has_windows_attributes
+        && !entry.is_directory()
+        && attributes.contains(WindowsAttributes::UNIX_EXTENSION)
+        && (attributes.bits() >> 16) & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK
+}
+
+/// Writes the tar header of `entry`, or of a symbolic link to `link_name`.
+fn write_tar_header<W: Write>(
+    writer: &mut W,
+    entry: &ArchiveEntry,
+    size: u64,
+    link_name: Option<&str>,
+) -> io::Result<()> {
+    let mut name = entry.name().to_string();
+    if entry.is_directory() {
+        name.push('/');
+    }
+    let mtime = match entry.has_last_modified_date {
+        true => {
+            let secs = entry
+                .last_modified_date()
+                .to_unix_nanos()
+                .div_euclid(1_000_000_000);
+            secs.clamp(0, MAX_OCTAL_11 as i128) as u64
+        }
+        false => 0,
+    };
+    let attributes = entry.windows_attributes_typed();
+    let mode =
+        if entry.has_windows_attributes && attributes.contains(WindowsAttributes::UNIX_EXTENSION) {
+            (attributes.bits() >> 16) & 0o7777
+        } else {
+            let mode = if entry.is_directory() { 0o755 } else { 0o644 };
+            match attributes.contains(WindowsAttributes::READONLY) {
+                true => mode & !0o222,
+                false => mode,
+            }
+        };
+
+    let mut header = [0u8; BLOCK_SIZE];
+    let (prefix, short_name) = split_ustar_name(&name);
+    let mut pax_records = String::new();
+    match short_name {
+        Some(short_name) => {
+            header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+            header[..short_name.len()].copy_from_slice(short_name.as_bytes());
+        }
+        None => {
+            add_pax_record(&mut pax_records, "path", &name);
+            let mut end = name.len().min(100);
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            header[..end].copy_from_slice(&name.as_bytes()[..end]);
+        }
+    }
+    if let Some(link_name) = link_name {
+        if link_name.len() <= 100 {
+            header[157..157 + link_name.len()].copy_from_slice(link_name.as_bytes());
+        } else {
+            add_pax_record(&mut pax_records, "linkpath", link_name);
+        }
+    }
+    if size > MAX_OCTAL_11 {
+        add_pax_record(&mut pax_records, "size", &size.to_string());
+    }
+    write_octal(&mut header[100..108], mode as u64);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size.min(MAX_OCTAL_11));
+    write_octal(&mut header[136..148], mtime);
+    header[156] = match (entry.is_directory(), link_name) {
+        (true, _) => b'5',
+        (false, Some(_)) => b'2',
+        (false, None) => b'0',
+    };
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    if !pax_records.is_empty() {
+        let mut pax_header = header;
+        pax_header[..100].fill(0);
+        pax_header[345..500].fill(0);
+        let pax_name = b"././@PaxHeader";
+        pax_header[..pax_name.len()].copy_from_slice(pax_name);
+        write_octal(&mut pax_header[124..136], pax_records.len() as u64);
+        pax_header[156] = b'x';
+        write_checksum(&mut pax_header);
+        writer.write_all(&pax_header)?;
+        writer.write_all(pax_records.as_bytes())?;
+        write_padding(writer, pax_records.len() as u64)?;
+    }
+    write_checksum(&mut header);
+    writer.write_all(&header)
+}
+
+/// Splits `name` into the prefix and name fields of a ustar header, or returns `None` as the
+/// name if it doesn't fit.
+fn split_ustar_name(name: &str) -> (&str, Option<&str>) {
+    if name.len() <= 100 {
+        return ("", Some(name));
+    }
+    // The separator between the prefix and the name is not stored.
+    let split = name
+        .match_indices('/')
+        .map(|(index, _)| index)
+        .find(|&index| index > 0 && index <= 155 && (1..=100).contains(&(name.len() - index - 1)));
+    match split {
+        Some(index) => (&name[..index], Some(&name[index + 1..])),
+        None => ("", None),
+    }
+}
+
+fn add_pax_record(records: &mut String, key: &str, value: &str) {
+    // The length of a record includes the digits of the length itself.
+    let len = key.len() + value.len() + 3;
+    let mut total = len + len.to_string().len();
+    if total.to_string().len() != len.to_string().len() {
+        total += 1;
+    }
+    records.push_str(&format!("{total} {key}={value}\n"));
+}
+
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let octal = format!("{value:0digits$o}");
+    field[..digits].copy_from_slice(octal.as_bytes());
+    field[digits] = 0;
+}
+
+fn write_checksum(header: &mut [u8; BLOCK_SIZE]) {
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+}
+
+fn write_padding<W: Write>(writer: &mut W, size: u64) -> io::Result<()> {
+    let padding = (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE;
+    writer.write_all(&[0; BLOCK_SIZE][..padding])
+}
+
+#[cfg(feature = "compress")]
+struct TarHeader {
+    name: String,
+    link_name: String,
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    kind: u8,
+}
+
+/// The values of a PAX extended header that are converted.
+#[cfg(feature = "compress")]
+#[derive(Default)]
+struct PaxHeader {
+    path: Option<String>,
+    link_path: Option<String>,
+    size: Option<u64>,
+    mtime: Option<i64>,
+}
+
+#[cfg(feature = "compress")]
+impl PaxHeader {
+    fn parse(mut data: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::other("Invalid PAX extended header");
+        let mut header = Self::default();
+        while !data.is_empty() {
+            let space = data.iter().position(|&b| b == b' ').ok_or_else(invalid)?;
+            let len: usize = std::str::from_utf8(&data[..space])
+                .ok()
+                .and_then(|len| len.parse().ok())
+                .filter(|&len| len > space && len <= data.len())
+                .ok_or_else(invalid)?;
+            let record = data[space + 1..len]
+                .strip_suffix(b"\n")
+                .ok_or_else(invalid)?;
+            data = &data[len..];
+            let Some(equals) = record.iter().position(|&b| b == b'=') else {
+                return Err(invalid());
+            };
+            let (key, value) = (&record[..equals], &record[equals + 1..]);
+            let value = String::from_utf8_lossy(value);
+            match key {
+                b"path" => header.path = Some(value.into_owned()),
+                b"linkpath" => header.link_path = Some(value.into_owned()),
+                b"size" => header.size = Some(value.parse().map_err(|_| invalid())?),
+                // Fractions of seconds are dropped.
+                b"mtime" => {
+                    let secs = value.split('.').next().unwrap_or_default();
+                    header.mtime = Some(secs.parse().map_err(|_| invalid())?);
+                }
+                _ => {}
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// Reads the next header of the tar archive, or `None` at the end of the archive.
+#[cfg(feature = "compress")]
+fn read_tar_header<R: Read>(reader: &mut R) -> Result<Option<TarHeader>, Error> {
+    let mut header = [0u8; BLOCK_SIZE];
+    let mut read = 0;
+    while read < BLOCK_SIZE {
+        match reader.read(&mut header[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(Error::io_msg(e, "Read tar header")),
+        }
+    }
+    // Archives may end without the empty blocks that mark the end.
+    if read == 0 || header.iter().all(|&b| b == 0) {
+        return Ok(None);
+    }
+    if read < BLOCK_SIZE {
+        return Err(Error::io_msg(
+            io::ErrorKind::UnexpectedEof.into(),
+            "Read tar header",
+        ));
+    }
+
+    let stored = parse_octal(&header[148..156]).ok_or_else(invalid_tar_header)?;
+    header[148..156].fill(b' ');
+    let unsigned: u64 = header.iter().map(|&b| b as u64).sum();
+    // Some old implementations summed signed bytes.
+    let signed: i64 = header.iter().map(|&b| b as i8 as i64).sum();
+    if stored != unsigned && stored as i64 != signed {
+        return Err(Error::other("Invalid tar header checksum"));
+    }
+
+    let field = |range: std::ops::Range<usize>| {
+        let field = &header[range];
+        let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+    let mut name = field(0..100);
+    if &header[257..262] == b"ustar" {
+        let prefix = field(345..500);
+        if !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+    }
+    Ok(Some(TarHeader {
+        name,
+        link_name: field(157..257),
+        mode: parse_octal(&header[100..108]).unwrap_or_default() as u32,
+        size: parse_numeric(&header[124..136]).ok_or_else(invalid_tar_header)?,
+        mtime: parse_numeric(&header[136..148]).unwrap_or_default(),
+        kind: header[156],
+    }))
+}
+
+#[cfg(feature = "compress")]
+fn invalid_tar_header() -> Error {
+    Error::other("Invalid tar header")
+}
+
+/// Parses an octal field, or a big-endian binary number if the highest bit of the field is set,
+/// like GNU tar writes values that don't fit.
+#[cfg(feature = "compress")]
+fn parse_numeric(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 == 0 {
+        return parse_octal(field);
+    }
+    let (&first, rest) = field.split_first()?;
+    let (high, low) = rest.split_at(rest.len().saturating_sub(8));
+    // Negative and too large values can't be converted.
+    if first & 0x7F != 0 || high.iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(low.iter().fold(0, |value, &b| (value << 8) | b as u64))
+}
+
+#[cfg(feature = "compress")]
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let digits = field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| b != 0 && b != b' ');
+    // Empty fields are 0.
+    let mut value = 0u64;
+    for &digit in digits {
+        if !(b'0'..=b'7').contains(&digit) {
+            return None;
+        }
+        value = value.checked_mul(8)? | (digit - b'0') as u64;
+    }
+    Some(value)
+}
+
+/// Reads the `size` bytes of data of a tar entry and the padding after it.
+#[cfg(feature = "compress")]
+fn read_tar_data<R: Read>(reader: &mut R, size: u64, name: &str) -> Result<Vec<u8>, Error> {
+    let map_err = |e| Error::io_msg(e, format!("Read tar entry:{name}"));
+    let mut data = Vec::new();
+    reader.take(size).read_to_end(&mut data).map_err(map_err)?;
+    if data.len() as u64 != size {
+        return Err(map_err(io::ErrorKind::UnexpectedEof.into()));
+    }
+    skip_padding(reader, size)?;
+    Ok(data)
+}
+
+#[cfg(feature = "compress")]
+fn skip_padding<R: Read>(reader: &mut R, size: u64) -> Result<(), Error> {
+    let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+    // The padding of the last entry may be missing, so a short read is no error.
+    io::copy(&mut reader.take(padding), &mut io::sink())
+        .map_err(|e| Error::io_msg(e, "Read tar padding"))?;
+    Ok(())
+}
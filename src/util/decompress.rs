@@ -23,6 +23,103 @@ pub fn decompress_file(src_path: impl AsRef<Path>, dest: impl AsRef<Path>) -> Re
     decompress(file, dest)
 }
 
+/// Options controlling which metadata is restored when extracting entries to the file system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// Restore the last modified time of files. Defaults to `true`.
+    pub restore_mtime: bool,
+    /// Restore the last access time of files. Defaults to `true`.
+    pub restore_atime: bool,
+    /// Restore the creation time of files. Only supported on Windows and macOS.
+    /// Defaults to `true`.
+    pub restore_ctime_windows: bool,
+    /// Mark files as read-only if the read-only Windows attribute is set. Defaults to `false`.
+    pub restore_readonly: bool,
+    /// Restore the Windows attributes of files and directories, like hidden and system. Only
+    /// supported on Windows. Defaults to `true`. The read-only attribute is controlled by
+    /// `restore_readonly`.
+    pub restore_windows_attributes: bool,
+    /// Restore Unix permission bits stored in the high 16 bits of the Windows attributes.
+    /// Only supported on Unix. Defaults to `false`.
+    pub restore_permissions: bool,
+    /// Extract entries to `dest.join(name)` without sanitizing their names. Entries containing
+    /// `..` components or absolute paths can then be written outside of the destination
+    /// directory. Defaults to `false`.
+    pub allow_unsafe_paths: bool,
+    /// What to do with files that already exist. Defaults to [`OverwriteMode::Always`].
+    pub overwrite: OverwriteMode,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            restore_mtime: true,
+            restore_atime: true,
+            restore_ctime_windows: true,
+            restore_readonly: false,
+            restore_windows_attributes: true,
+            restore_permissions: false,
+            allow_unsafe_paths: false,
+            overwrite: OverwriteMode::Always,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Sets whether entry names are used as-is, without protection against path traversal.
+    ///
+    /// Only use this for trusted archives.
+    pub fn allow_unsafe_paths(mut self, allow_unsafe_paths: bool) -> Self {
+        self.allow_unsafe_paths = allow_unsafe_paths;
+        self
+    }
+
+    /// Sets what to do with files that already exist.
+    pub fn overwrite(mut self, overwrite: OverwriteMode) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+}
+
+/// Decompresses an archive file to a destination directory, restoring metadata as configured
+/// by `options`.
+///
+/// # Arguments
+/// * `src_path` - Path to the source archive file
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `options` - Options controlling which metadata is restored
+pub fn decompress_file_with_options(
+    src_path: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<(), Error> {
+    let file = std::fs::File::open(src_path.as_ref())
+        .map_err(|e| Error::file_open(e, src_path.as_ref().to_string_lossy().to_string()))?;
+    decompress_with_options(file, dest, options)
+}
+
+/// Decompresses an archive from a reader to a destination directory, restoring metadata as
+/// configured by `options`.
+///
+/// # Arguments
+/// * `src_reader` - Reader containing the archive data
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `options` - Options controlling which metadata is restored
+#[cfg(not(target_arch = "wasm32"))]
+pub fn decompress_with_options<R: Read + Seek>(
+    src_reader: R,
+    dest: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<(), Error> {
+    decompress_impl(
+        src_reader,
+        dest,
+        Password::empty(),
+        options.allow_unsafe_paths,
+        |entry, reader, dest| extract_entry(entry, reader, dest, options),
+    )
+}
+
 /// Decompresses an archive file to a destination directory with a custom extraction function.
 ///
 /// The extraction function is called for each entry in the archive, allowing custom handling
@@ -65,7 +162,37 @@ pub fn decompress_with_extract_fn<R: Read + Seek>(
     dest: impl AsRef<Path>,
     extract_fn: impl FnMut(&ArchiveEntry, &mut dyn Read, &PathBuf) -> Result<bool, Error>,
 ) -> Result<(), Error> {
-    decompress_impl(src_reader, dest, Password::empty(), extract_fn)
+    decompress_impl(src_reader, dest, Password::empty(), false, extract_fn)
+}
+
+/// Decompresses an archive from a reader to a destination directory with a custom extraction
+/// function, a password and options.
+///
+/// Entry names are sanitized unless [`ExtractOptions::allow_unsafe_paths`] is set. The other
+/// options are not applied by this function, `extract_fn` can use [`apply_entry_metadata`] to
+/// restore the metadata of the entries it writes.
+///
+/// # Arguments
+/// * `src_reader` - Reader containing the archive data
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `password` - Password to decrypt the archive, [`Password::empty()`] if it isn't encrypted
+/// * `options` - Options controlling how entry names are handled
+/// * `extract_fn` - Custom function to handle each archive entry during extraction
+#[cfg(not(target_arch = "wasm32"))]
+pub fn decompress_with_extract_fn_and_options<R: Read + Seek>(
+    src_reader: R,
+    dest: impl AsRef<Path>,
+    password: Password,
+    options: &ExtractOptions,
+    extract_fn: impl FnMut(&ArchiveEntry, &mut dyn Read, &PathBuf) -> Result<bool, Error>,
+) -> Result<(), Error> {
+    decompress_impl(
+        src_reader,
+        dest,
+        password,
+        options.allow_unsafe_paths,
+        extract_fn,
+    )
 }
 
 /// Decompresses an encrypted archive file with the given password.
@@ -97,7 +224,31 @@ pub fn decompress_with_password<R: Read + Seek>(
     dest: impl AsRef<Path>,
     password: Password,
 ) -> Result<(), Error> {
-    decompress_impl(src_reader, dest, password, default_entry_extract_fn)
+    decompress_impl(src_reader, dest, password, false, default_entry_extract_fn)
+}
+
+/// Decompresses an encrypted archive from a reader with the given password, restoring metadata
+/// as configured by `options`.
+///
+/// # Arguments
+/// * `src_reader` - Reader containing the encrypted archive data
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `password` - Password to decrypt the archive
+/// * `options` - Options controlling which metadata is restored
+#[cfg(all(feature = "aes256", not(target_arch = "wasm32")))]
+pub fn decompress_with_password_and_options<R: Read + Seek>(
+    src_reader: R,
+    dest: impl AsRef<Path>,
+    password: Password,
+    options: &ExtractOptions,
+) -> Result<(), Error> {
+    decompress_with_extract_fn_and_options(
+        src_reader,
+        dest,
+        password,
+        options,
+        |entry, reader, dest| extract_entry(entry, reader, dest, options),
+    )
 }
 
 /// Decompresses an encrypted archive from a reader with a custom extraction function and password.
@@ -117,7 +268,7 @@ pub fn decompress_with_extract_fn_and_password<R: Read + Seek>(
     password: Password,
     extract_fn: impl FnMut(&ArchiveEntry, &mut dyn Read, &PathBuf) -> Result<bool, Error>,
 ) -> Result<(), Error> {
-    decompress_impl(src_reader, dest, password, extract_fn)
+    decompress_impl(src_reader, dest, password, false, extract_fn)
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -125,6 +276,7 @@ fn decompress_impl<R: Read + Seek>(
     mut src_reader: R,
     dest: impl AsRef<Path>,
     password: Password,
+    allow_unsafe_paths: bool,
     mut extract_fn: impl FnMut(&ArchiveEntry, &mut dyn Read, &PathBuf) -> Result<bool, Error>,
 ) -> Result<(), Error> {
     use std::io::SeekFrom;
@@ -133,19 +285,362 @@ fn decompress_impl<R: Read + Seek>(
     src_reader.seek(SeekFrom::Start(pos))?;
     let mut seven = ArchiveReader::new(src_reader, password)?;
     let dest = PathBuf::from(dest.as_ref());
+
+    if allow_unsafe_paths {
+        if !dest.exists() {
+            std::fs::create_dir_all(&dest)?;
+        }
+        seven.for_each_entries(|entry, reader| {
+            let dest_path = dest.join(entry.name());
+            extract_fn(entry, reader, &dest_path)
+        })?;
+        return Ok(());
+    }
+
+    // Reject hostile entries before anything is written.
+    check_entry_names(&seven.archive().files)?;
+
     if !dest.exists() {
         std::fs::create_dir_all(&dest)?;
     }
+    let canonical_dest = dest
+        .canonicalize()
+        .map_err(|e| Error::io_msg(e, format!("Failed to canonicalize {dest:?}")))?;
+
     seven.for_each_entries(|entry, reader| {
-        let dest_path = dest.join(entry.name());
+        let dest_path = dest.join(sanitize_entry_name(entry.name())?);
+        ensure_inside_dest(&canonical_dest, &dest_path, entry.name())?;
         extract_fn(entry, reader, &dest_path)
     })?;
 
     Ok(())
 }
 
+/// Number of entries processed by [`decompress_file_with_filter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FilteredExtractionStats {
+    /// Entries that matched the filter and were written to the destination.
+    pub written: usize,
+    /// Entries that didn't match the filter.
+    pub skipped: usize,
+    /// Blocks that were not decoded at all, because none of their entries matched the filter.
+    pub skipped_blocks: usize,
+}
+
+/// Decompresses the entries of an archive file that match `filter` to a destination directory.
+///
+/// Blocks without any matching entry are not decoded at all. Solid blocks are only decoded up
+/// to their last matching entry.
+///
+/// # Arguments
+/// * `src_path` - Path to the source archive file
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `filter` - Function that returns `true` for entries that should be extracted, see
+///   [`glob_filter`]
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::{decompress_file_with_filter, glob_filter};
+///
+/// let stats = decompress_file_with_filter(
+///     "path/to/archive.7z",
+///     "path/to/dest",
+///     glob_filter("docs/**/*.md"),
+/// )
+/// .unwrap();
+/// println!("{} written, {} skipped", stats.written, stats.skipped);
+/// ```
+pub fn decompress_file_with_filter(
+    src_path: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    filter: impl Fn(&ArchiveEntry) -> bool,
+) -> Result<FilteredExtractionStats, Error> {
+    let file = std::fs::File::open(src_path.as_ref())
+        .map_err(|e| Error::file_open(e, src_path.as_ref().to_string_lossy().to_string()))?;
+    decompress_with_filter(file, dest, filter)
+}
+
+/// Decompresses the entries of an archive from a reader that match `filter` to a destination
+/// directory. See [`decompress_file_with_filter`].
+///
+/// # Arguments
+/// * `src_reader` - Reader containing the archive data
+/// * `dest` - Path to the destination directory where files will be extracted
+/// * `filter` - Function that returns `true` for entries that should be extracted
+#[cfg(not(target_arch = "wasm32"))]
+pub fn decompress_with_filter<R: Read + Seek>(
+    src_reader: R,
+    dest: impl AsRef<Path>,
+    filter: impl Fn(&ArchiveEntry) -> bool,
+) -> Result<FilteredExtractionStats, Error> {
+    let mut seven = ArchiveReader::new(src_reader, Password::empty())?;
+    let dest = dest.as_ref();
+    let archive = seven.archive();
+    let matches: Vec<bool> = archive.files.iter().map(&filter).collect();
+
+    // Reject hostile entries before anything is written.
+    for (entry, _) in archive.files.iter().zip(&matches).filter(|(_, m)| **m) {
+        sanitize_entry_name(entry.name())?;
+    }
+    if !dest.exists() {
+        std::fs::create_dir_all(dest)?;
+    }
+    let canonical_dest = dest
+        .canonicalize()
+        .map_err(|e| Error::io_msg(e, format!("Failed to canonicalize {dest:?}")))?;
+
+    let mut stats = FilteredExtractionStats {
+        skipped: matches.iter().filter(|m| !**m).count(),
+        ..Default::default()
+    };
+    let options = ExtractOptions::default();
+    let mut extract = |entry: &ArchiveEntry, reader: &mut dyn Read| {
+        let dest_path = dest.join(sanitize_entry_name(entry.name())?);
+        ensure_inside_dest(&canonical_dest, &dest_path, entry.name())?;
+        extract_entry(entry, reader, &dest_path, &options)?;
+        stats.written += 1;
+        Ok::<_, Error>(())
+    };
+
+    for block_index in 0..seven.archive().blocks.len() {
+        // The block decoder visits the entries in the same order.
+        let block_matches: Vec<bool> = seven
+            .archive()
+            .stream_map
+            .block_entry_indices(block_index)
+            .map(|file_index| matches[file_index])
+            .collect();
+        let mut remaining = block_matches.iter().filter(|m| **m).count();
+        if remaining == 0 {
+            stats.skipped_blocks += 1;
+            continue;
+        }
+
+        let mut block_matches = block_matches.into_iter();
+        seven.for_each_block_entries(block_index, &mut |entry, reader| {
+            if block_matches.next() == Some(true) {
+                extract(entry, reader)?;
+                remaining -= 1;
+            }
+            // Stop decoding the block after its last matching entry.
+            Ok(remaining > 0)
+        })?;
+    }
+
+    // Entries without data, like directories and empty files.
+    let archive = seven.archive();
+    for (entry, matched) in archive.files.iter().zip(&matches) {
+        if *matched && !entry.has_stream {
+            extract(entry, &mut [0u8; 0].as_slice())?;
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Returns a filter for [`decompress_file_with_filter`] that matches entry names against the
+/// glob `pattern`, see [`glob_matches`].
+///
+/// # Arguments
+/// * `pattern` - The glob pattern to match entry names against
+pub fn glob_filter(pattern: &str) -> impl Fn(&ArchiveEntry) -> bool + use<> {
+    let pattern = pattern.to_string();
+    move |entry| glob_matches(&pattern, entry.name())
+}
+
+/// Returns whether the entry name `name` matches the glob `pattern`.
+///
+/// `?` matches any character and `*` any number of characters, except for the `/` separator.
+/// A `**` path component matches any number of directories, so `docs/**/*.md` matches both
+/// `docs/a.md` and `docs/a/b.md`.
+///
+/// # Arguments
+/// * `pattern` - The glob pattern, using `/` as separator
+/// * `name` - The entry name to match
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let name: Vec<&str> = name.split('/').collect();
+    glob_matches_components(&pattern, &name)
+}
+
+fn glob_matches_components(pattern: &[&str], name: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&"**", rest)) => (0..=name.len()).any(|i| glob_matches_components(rest, &name[i..])),
+        Some((component, rest)) => name
+            .split_first()
+            .is_some_and(|(name_component, name_rest)| {
+                glob_matches_component(component, name_component)
+                    && glob_matches_components(rest, name_rest)
+            }),
+    }
+}
+
+/// Matches a single path component against a pattern containing `*` and `?` wildcards.
+fn glob_matches_component(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the position in the name it was matched up to.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                star = Some((p, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Converts an entry name into a path relative to the destination directory.
+///
+/// Leading separators, drive letters and UNC prefixes are stripped and `.` components are
+/// dropped. Names containing `..` components are rejected. On Windows, reserved device names
+/// and trailing dots or spaces are sanitized as well.
+#[cfg(not(target_arch = "wasm32"))]
+fn sanitize_entry_name(name: &str) -> Result<PathBuf, Error> {
+    #[cfg(windows)]
+    let separators: &[char] = &['/', '\\'];
+    #[cfg(not(windows))]
+    let separators: &[char] = &['/'];
+
+    #[cfg(windows)]
+    let components = strip_windows_prefix(name).split(separators);
+    #[cfg(not(windows))]
+    let components = name.split(separators);
+
+    let mut path = PathBuf::new();
+    for component in components {
+        match component {
+            "" | "." => {}
+            ".." => return Err(Error::UnsafeEntryNames(vec![name.to_string()])),
+            #[cfg(windows)]
+            component => path.push(sanitize_windows_component(component)),
+            #[cfg(not(windows))]
+            component => path.push(component),
+        }
+    }
+
+    Ok(path)
+}
+
+/// Returns an error listing all entries whose names can't be sanitized.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_entry_names(files: &[ArchiveEntry]) -> Result<(), Error> {
+    let unsafe_names: Vec<String> = files
+        .iter()
+        .filter(|entry| sanitize_entry_name(entry.name()).is_err())
+        .map(|entry| entry.name().to_string())
+        .collect();
+    if !unsafe_names.is_empty() {
+        return Err(Error::UnsafeEntryNames(unsafe_names));
+    }
+    Ok(())
+}
+
+/// Strips a drive letter or UNC prefix from an entry name.
+#[cfg(windows)]
+fn strip_windows_prefix(name: &str) -> &str {
+    let is_separator = |c: char| c == '/' || c == '\\';
+
+    if name.starts_with(is_separator) && name[1..].starts_with(is_separator) {
+        // Skip the server and share, or the "?" and drive of a verbatim path.
+        let mut rest = name.trim_start_matches(is_separator);
+        for _ in 0..2 {
+            rest = rest
+                .split_once(is_separator)
+                .map_or("", |(_, rest)| rest.trim_start_matches(is_separator));
+        }
+        return rest;
+    }
+
+    let bytes = name.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        return &name[2..];
+    }
+
+    name
+}
+
+/// Replaces characters and names that have a special meaning on Windows.
+#[cfg(windows)]
+fn sanitize_windows_component(component: &str) -> String {
+    const RESERVED_NAMES: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let mut component = component
+        .trim_end_matches(['.', ' '])
+        .replace([':', '<', '>', '"', '|', '?', '*'], "_");
+    if component.is_empty() {
+        component.push('_');
+    }
+
+    let stem = component.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        component.insert(0, '_');
+    }
+
+    component
+}
+
+/// Makes sure that `path` doesn't escape `canonical_dest`, for example through a symbolic link
+/// that already exists in the destination directory.
+///
+/// Symbolic links are checked even if their target doesn't exist, since writing through them
+/// would create the target.
+#[cfg(not(target_arch = "wasm32"))]
+fn ensure_inside_dest(canonical_dest: &Path, path: &Path, name: &str) -> Result<(), Error> {
+    let mut ancestor = path;
+    while std::fs::symlink_metadata(ancestor).is_err() {
+        match ancestor.parent() {
+            Some(parent) => ancestor = parent,
+            None => return Ok(()),
+        }
+    }
+
+    let canonical = match ancestor.canonicalize() {
+        Ok(canonical) => canonical,
+        // A dangling symbolic link.
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::UnsafeEntryNames(vec![name.to_string()]));
+        }
+        Err(e) => {
+            return Err(Error::io_msg(
+                e,
+                format!("Failed to canonicalize {ancestor:?}"),
+            ));
+        }
+    };
+    if !canonical.starts_with(canonical_dest) {
+        return Err(Error::UnsafeEntryNames(vec![name.to_string()]));
+    }
+
+    Ok(())
+}
+
 /// Default extraction function that handles standard file and directory extraction.
 ///
+/// Restores metadata as configured by [`ExtractOptions::default()`].
+///
 /// # Arguments
 /// * `entry` - Archive entry being processed
 /// * `reader` - Reader for the entry's data
@@ -155,6 +650,16 @@ pub fn default_entry_extract_fn(
     entry: &ArchiveEntry,
     reader: &mut dyn Read,
     dest: &PathBuf,
+) -> Result<bool, Error> {
+    extract_entry(entry, reader, dest, &ExtractOptions::default())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn extract_entry(
+    entry: &ArchiveEntry,
+    reader: &mut dyn Read,
+    dest: &PathBuf,
+    options: &ExtractOptions,
 ) -> Result<bool, Error> {
     use std::{fs::File, io::BufWriter};
 
@@ -163,8 +668,14 @@ pub fn default_entry_extract_fn(
         if !dir.exists() {
             std::fs::create_dir_all(dir)?;
         }
+        #[cfg(windows)]
+        restore_directory_attributes(entry, dir, options)?;
     } else {
         let path = dest;
+        if !should_overwrite(entry, path, options.overwrite) {
+            std::io::copy(reader, &mut std::io::sink())?;
+            return Ok(true);
+        }
         path.parent().and_then(|p| {
             if !p.exists() {
                 std::fs::create_dir_all(p).ok()
@@ -174,21 +685,400 @@ pub fn default_entry_extract_fn(
         });
         let file = File::create(path)
             .map_err(|e| Error::file_open(e, path.to_string_lossy().to_string()))?;
+        let mut writer = BufWriter::new(file);
         if entry.size() > 0 {
-            let mut writer = BufWriter::new(file);
             std::io::copy(reader, &mut writer)?;
+        }
+        let file = writer
+            .into_inner()
+            .map_err(|e| Error::io_msg(e.into_error(), "Failed to flush extracted file"))?;
+        drop(file);
 
-            let file = writer.get_mut();
-            let file_times = FileTimes::new()
-                .set_accessed(entry.access_date().into())
-                .set_modified(entry.last_modified_date().into());
+        apply_entry_metadata(entry, path, options)?;
+    }
 
-            #[cfg(any(windows, target_os = "macos"))]
-            let file_times = file_times.set_created(entry.creation_date().into());
+    Ok(true)
+}
 
+/// Applies the metadata stored in `entry` to the already extracted file at `path`.
+///
+/// This can be used by custom extraction functions (see [`ArchiveReader::for_each_entries`])
+/// to restore timestamps and attributes the same way [`decompress_file_with_options`] does.
+/// Timestamps are only applied if they are present in the entry. Errors setting the
+/// timestamps are ignored, since not all file systems support them.
+///
+/// # Arguments
+/// * `entry` - Archive entry the file was extracted from
+/// * `path` - Path of the extracted file
+/// * `options` - Options controlling which metadata is restored
+#[cfg(not(target_arch = "wasm32"))]
+pub fn apply_entry_metadata(
+    entry: &ArchiveEntry,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<(), Error> {
+    let mut file_times = FileTimes::new();
+    let mut has_times = false;
+    if options.restore_atime && entry.has_access_date {
+        file_times = file_times.set_accessed(entry.access_date().into());
+        has_times = true;
+    }
+    if options.restore_mtime && entry.has_last_modified_date {
+        file_times = file_times.set_modified(entry.last_modified_date().into());
+        has_times = true;
+    }
+    #[cfg(any(windows, target_os = "macos"))]
+    if options.restore_ctime_windows && entry.has_creation_date {
+        file_times = file_times.set_created(entry.creation_date().into());
+        has_times = true;
+    }
+
+    if has_times {
+        if let Ok(file) = std::fs::File::options().write(true).open(path) {
             let _ = file.set_times(file_times);
         }
     }
 
+    if !entry.has_windows_attributes {
+        return Ok(());
+    }
+    let attributes = entry.windows_attributes_typed();
+
+    #[cfg(unix)]
+    if options.restore_permissions && attributes.contains(WindowsAttributes::UNIX_EXTENSION) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = (attributes.bits() >> 16) & 0o7777;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .map_err(|e| Error::io_msg(e, format!("Failed to set permissions of {path:?}")))?;
+    }
+
+    #[cfg(windows)]
+    if options.restore_windows_attributes {
+        set_windows_attributes(path, attributes)?;
+    }
+
+    if options.restore_readonly && attributes.contains(WindowsAttributes::READONLY) {
+        let mut permissions = std::fs::metadata(path)
+            .map_err(|e| Error::io_msg(e, format!("Failed to read metadata of {path:?}")))?
+            .permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(path, permissions)
+            .map_err(|e| Error::io_msg(e, format!("Failed to set permissions of {path:?}")))?;
+    }
+
+    Ok(())
+}
+
+/// Applies the Windows attributes of a directory entry, which doesn't get any other metadata
+/// restored.
+#[cfg(windows)]
+fn restore_directory_attributes(
+    entry: &ArchiveEntry,
+    path: &Path,
+    options: &ExtractOptions,
+) -> Result<(), Error> {
+    if options.restore_windows_attributes && entry.has_windows_attributes {
+        set_windows_attributes(path, entry.windows_attributes_typed())?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_windows_attributes(path: &Path, attributes: WindowsAttributes) -> Result<(), Error> {
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows_sys::Win32::Storage::FileSystem::SetFileAttributesW;
+
+    // Attributes like DIRECTORY or the Unix extension can't be set with SetFileAttributesW.
+    // READONLY is applied separately if `ExtractOptions::restore_readonly` is set.
+    let settable = WindowsAttributes::HIDDEN
+        | WindowsAttributes::SYSTEM
+        | WindowsAttributes::ARCHIVE
+        | WindowsAttributes::TEMPORARY
+        | WindowsAttributes::OFFLINE
+        | WindowsAttributes::NOT_CONTENT_INDEXED;
+    let attributes = attributes & settable;
+    if attributes.is_empty() {
+        return Ok(());
+    }
+
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    // SAFETY: `wide_path` is a valid, null terminated UTF-16 string.
+    if unsafe { SetFileAttributesW(wide_path.as_ptr(), attributes.bits()) } == 0 {
+        return Err(Error::io_msg(
+            std::io::Error::last_os_error(),
+            format!("Failed to set attributes of {path:?}"),
+        ));
+    }
+    Ok(())
+}
+
+/// What to do when an extracted file already exists in the destination directory.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverwriteMode {
+    /// Keep the existing file and skip the entry.
+    Never,
+    /// Always replace the existing file.
+    #[default]
+    Always,
+    /// Replace the existing file only if the entry was modified after it. Entries without a
+    /// last modified date are skipped.
+    IfNewer,
+}
+
+/// Number of files processed by [`ArchiveReader::extract_all_to`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractionStats {
+    /// Files that were written to the destination.
+    pub extracted: usize,
+    /// Files that already existed and were kept because of the [`OverwriteMode`].
+    pub skipped: usize,
+    /// Files that couldn't be written, for example because of missing permissions.
+    pub failed: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<R: Read + Seek> ArchiveReader<R> {
+    /// Extracts all entries of the archive to the directory `dest`.
+    ///
+    /// Errors while decoding the archive abort the extraction. Files that can't be written are
+    /// counted as failed and the extraction continues with the next entry.
+    ///
+    /// # Arguments
+    /// * `dest` - Path to the destination directory, created if it doesn't exist
+    /// * `options` - Options controlling overwriting, metadata and path safety
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use sevenz_rust2::{ArchiveReader, ExtractOptions, OverwriteMode, Password};
+    ///
+    /// let mut reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+    /// let options = ExtractOptions::default().overwrite(OverwriteMode::IfNewer);
+    /// let stats = reader
+    ///     .extract_all_to(Path::new("path/to/dest"), &options)
+    ///     .unwrap();
+    /// println!("{} extracted, {} skipped", stats.extracted, stats.skipped);
+    /// ```
+    pub fn extract_all_to(
+        &mut self,
+        dest: &Path,
+        options: &ExtractOptions,
+    ) -> Result<ExtractionStats, Error> {
+        self.extract_all_to_with_hook(dest, options, |_, _| Ok(()))
+    }
+
+    /// Extracts all entries of the archive to the directory `dest` like
+    /// [`ArchiveReader::extract_all_to`], calling `on_extracted` for every file and directory
+    /// after it was written and its metadata was restored as configured by `options`.
+    ///
+    /// The hook receives the entry and the path it was extracted to, so that metadata can be
+    /// applied that `options` doesn't cover, or restored differently after disabling it in
+    /// `options`. Files that were skipped or failed are not passed to the hook. Errors returned
+    /// by the hook abort the extraction.
+    ///
+    /// # Arguments
+    /// * `dest` - Path to the destination directory, created if it doesn't exist
+    /// * `options` - Options controlling overwriting, metadata and path safety
+    /// * `on_extracted` - Function called with every extracted entry and its path
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use sevenz_rust2::{ArchiveReader, ExtractOptions, Password};
+    ///
+    /// let mut reader = ArchiveReader::open("path/to/archive.7z", Password::empty()).unwrap();
+    /// let options = ExtractOptions {
+    ///     restore_mtime: false,
+    ///     ..Default::default()
+    /// };
+    /// reader
+    ///     .extract_all_to_with_hook(Path::new("path/to/dest"), &options, |entry, path| {
+    ///         println!("{} extracted to {path:?}", entry.name());
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn extract_all_to_with_hook(
+        &mut self,
+        dest: &Path,
+        options: &ExtractOptions,
+        mut on_extracted: impl FnMut(&ArchiveEntry, &Path) -> Result<(), Error>,
+    ) -> Result<ExtractionStats, Error> {
+        let strict = !options.allow_unsafe_paths;
+        if strict {
+            // Reject hostile entries before anything is written.
+            check_entry_names(&self.archive().files)?;
+        }
+
+        if !dest.exists() {
+            std::fs::create_dir_all(dest)?;
+        }
+        let canonical_dest = match strict {
+            true => Some(
+                dest.canonicalize()
+                    .map_err(|e| Error::io_msg(e, format!("Failed to canonicalize {dest:?}")))?,
+            ),
+            false => None,
+        };
+
+        let mut stats = ExtractionStats::default();
+
+        self.for_each_entries(|entry, reader| {
+            let path = match &canonical_dest {
+                Some(canonical_dest) => {
+                    let path = dest.join(sanitize_entry_name(entry.name())?);
+                    ensure_inside_dest(canonical_dest, &path, entry.name())?;
+                    path
+                }
+                None => dest.join(entry.name()),
+            };
+
+            if entry.is_anti_item() {
+                return Ok(true);
+            }
+            if entry.is_directory() {
+                if !path.exists() {
+                    std::fs::create_dir_all(&path)?;
+                }
+                #[cfg(windows)]
+                restore_directory_attributes(entry, &path, options)?;
+                on_extracted(entry, &path)?;
+                return Ok(true);
+            }
+
+            if !should_overwrite(entry, &path, options.overwrite) {
+                std::io::copy(reader, &mut std::io::sink())?;
+                stats.skipped += 1;
+                return Ok(true);
+            }
+
+            match extract_file(reader, &path)? {
+                true => match apply_entry_metadata(entry, &path, options) {
+                    Ok(()) => {
+                        stats.extracted += 1;
+                        on_extracted(entry, &path)?;
+                    }
+                    Err(_) => stats.failed += 1,
+                },
+                false => stats.failed += 1,
+            }
+
+            Ok(true)
+        })?;
+
+        Ok(stats)
+    }
+
+    /// Extracts all entries of the archive to the directory `dest` with the default
+    /// [`ExtractOptions`], consuming the reader.
+    ///
+    /// Convenience for one-shot extraction, see [`ArchiveReader::extract_all_to`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use sevenz_rust2::{ArchiveReader, Password};
+    ///
+    /// ArchiveReader::open("path/to/archive.7z", Password::empty())
+    ///     .unwrap()
+    ///     .into_extract_all(Path::new("path/to/dest"))
+    ///     .unwrap();
+    /// ```
+    pub fn into_extract_all(self, dest: &Path) -> Result<ExtractionStats, Error> {
+        self.into_extract_all_with_options(dest, &ExtractOptions::default())
+    }
+
+    /// Extracts all entries of the archive to the directory `dest` with `options`, consuming
+    /// the reader. See [`ArchiveReader::extract_all_to`].
+    pub fn into_extract_all_with_options(
+        mut self,
+        dest: &Path,
+        options: &ExtractOptions,
+    ) -> Result<ExtractionStats, Error> {
+        self.extract_all_to(dest, options)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn should_overwrite(entry: &ArchiveEntry, path: &Path, mode: OverwriteMode) -> bool {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return true;
+    };
+
+    match mode {
+        OverwriteMode::Never => false,
+        OverwriteMode::Always => true,
+        OverwriteMode::IfNewer => {
+            if !entry.has_last_modified_date {
+                return false;
+            }
+            match metadata.modified() {
+                Ok(modified) => entry
+                    .last_modified_date()
+                    .to_system_time()
+                    .is_some_and(|date| date > modified),
+                Err(_) => true,
+            }
+        }
+    }
+}
+
+/// Writes the data of `reader` to a new file at `path`. Returns `false` if the file couldn't be
+/// written, in which case the rest of `reader` is discarded. Errors reading the data are returned.
+///
+/// The data is written to a temporary file next to `path`, which replaces `path` once it is
+/// complete, so that a failed extraction doesn't leave a partially written file behind.
+#[cfg(not(target_arch = "wasm32"))]
+fn extract_file(reader: &mut dyn Read, path: &Path) -> Result<bool, Error> {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+    };
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.partial", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut writer = File::create(&temp_path).ok().map(BufWriter::new);
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                if writer.take().is_some() {
+                    let _ = std::fs::remove_file(&temp_path);
+                }
+                return Err(e.into());
+            }
+        };
+        if n == 0 {
+            break;
+        }
+        if let Some(w) = writer.as_mut() {
+            if w.write_all(&buf[..n]).is_err() {
+                writer = None;
+                let _ = std::fs::remove_file(&temp_path);
+            }
+        }
+    }
+
+    let Some(writer) = writer else {
+        return Ok(false);
+    };
+    if writer.into_inner().is_err() || std::fs::rename(&temp_path, path).is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+        return Ok(false);
+    }
+
     Ok(true)
 }
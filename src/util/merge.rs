@@ -0,0 +1,157 @@
+//! Helper functions to merge 7z archives
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{Archive, ArchiveEntry, ArchiveReader, ArchiveWriter, BlockDecoder, Error, Password};
+
+/// Options for [`merge_archives_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Skip entries whose name and CRC already exist in the destination archive.
+    pub dedup: bool,
+    /// Copy compressed blocks verbatim instead of decompressing and compressing them again.
+    ///
+    /// Copied blocks keep their original compression methods and encryption, so encrypted
+    /// sources must use the same password as the destination archive.
+    pub copy_blocks: bool,
+}
+
+impl MergeOptions {
+    /// Sets whether duplicate entries should be skipped.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Sets whether compressed blocks should be copied without recompression.
+    pub fn with_copy_blocks(mut self, copy_blocks: bool) -> Self {
+        self.copy_blocks = copy_blocks;
+        self
+    }
+}
+
+/// Merges the entries of multiple archives into `dest`.
+///
+/// The entries of each source archive are decompressed and compressed again with the content
+/// methods of `dest`. Entries are added in the order of the sources.
+///
+/// # Arguments
+/// * `sources` - Paths of the source archives together with their passwords
+/// * `dest` - Writer the entries are added to
+pub fn merge_archives<W: Write + Seek>(
+    sources: &[(impl AsRef<Path>, Password)],
+    dest: &mut ArchiveWriter<W>,
+) -> Result<(), Error> {
+    merge_archives_with_options(sources, dest, &MergeOptions::default())
+}
+
+/// Same as [`merge_archives`], but allows to configure the merge with [`MergeOptions`].
+///
+/// # Arguments
+/// * `sources` - Paths of the source archives together with their passwords
+/// * `dest` - Writer the entries are added to
+/// * `options` - Options controlling the merge
+pub fn merge_archives_with_options<W: Write + Seek>(
+    sources: &[(impl AsRef<Path>, Password)],
+    dest: &mut ArchiveWriter<W>,
+    options: &MergeOptions,
+) -> Result<(), Error> {
+    let mut existing: HashSet<(String, Option<u64>)> = if options.dedup {
+        dest.entries().iter().map(dedup_key).collect()
+    } else {
+        HashSet::new()
+    };
+
+    for (path, password) in sources {
+        if options.copy_blocks {
+            copy_archive_blocks(path.as_ref(), password, dest, options, &mut existing)?;
+            continue;
+        }
+        let mut reader = ArchiveReader::open(path.as_ref(), password.clone())?;
+        reader.for_each_entries(|entry, data| {
+            if options.dedup && !existing.insert(dedup_key(entry)) {
+                return Ok(true);
+            }
+
+            let entry = entry.clone();
+            if entry.has_stream {
+                dest.push_archive_entry(entry, Some(data))?;
+            } else {
+                dest.push_archive_entry::<&[u8]>(entry, None)?;
+            }
+            Ok(true)
+        })?;
+    }
+
+    Ok(())
+}
+
+fn copy_archive_blocks<W: Write + Seek>(
+    path: &Path,
+    password: &Password,
+    dest: &mut ArchiveWriter<W>,
+    options: &MergeOptions,
+    existing: &mut HashSet<(String, Option<u64>)>,
+) -> Result<(), Error> {
+    let mut source =
+        File::open(path).map_err(|e| Error::file_open(e, path.to_string_lossy().to_string()))?;
+    let archive = Archive::read(&mut source, password)?;
+    let pack_stream_ranges = archive.pack_stream_ranges();
+    let mut copied_blocks = vec![false; archive.blocks.len()];
+
+    for (file_index, entry) in archive.files.iter().enumerate() {
+        let Some(block_index) = archive.stream_map.file_block_index[file_index] else {
+            if options.dedup && !existing.insert(dedup_key(entry)) {
+                continue;
+            }
+            dest.push_archive_entry::<&[u8]>(entry.clone(), None)?;
+            continue;
+        };
+        if copied_blocks[block_index] {
+            continue;
+        }
+        copied_blocks[block_index] = true;
+
+        let block = &archive.blocks[block_index];
+        let entries: Vec<ArchiveEntry> = archive
+            .files
+            .iter()
+            .zip(archive.stream_map.file_block_index.iter())
+            .filter(|(_, index)| **index == Some(block_index))
+            .map(|(entry, _)| entry.clone())
+            .collect();
+        let has_duplicates = options.dedup
+            && entries
+                .iter()
+                .any(|entry| existing.contains(&dedup_key(entry)));
+
+        if !has_duplicates && block.packed_stream_indices().len() == 1 {
+            existing.extend(entries.iter().map(dedup_key));
+            let pack_index = archive.stream_map.block_first_pack_stream_index()[block_index];
+            let (offset, length) = pack_stream_ranges[pack_index];
+            source.seek(SeekFrom::Start(offset))?;
+            dest.push_raw_block(block, &entries, (&mut source).take(length))?;
+        } else {
+            let decoder = BlockDecoder::new(1, block_index, &archive, password, &mut source);
+            decoder.for_each_entries(&mut |entry, data| {
+                if options.dedup && !existing.insert(dedup_key(entry)) {
+                    return Ok(true);
+                }
+                dest.push_archive_entry(entry.clone(), Some(data))?;
+                Ok(true)
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn dedup_key(entry: &ArchiveEntry) -> (String, Option<u64>) {
+    let crc = if entry.has_crc { Some(entry.crc) } else { None };
+    (entry.name.clone(), crc)
+}
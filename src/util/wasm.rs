@@ -1,9 +1,13 @@
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
+use gloo_timers::future::TimeoutFuture;
 use js_sys::*;
 use wasm_bindgen::prelude::*;
 
-use crate::*;
+use crate::{codec::CodecWriter, *};
+
+/// Number of bytes processed by the async functions before yielding to the event loop.
+const ASYNC_CHUNK_SIZE: usize = 1024 * 1024;
 
 /// Decompresses a 7z archive in WebAssembly environment.
 ///
@@ -79,6 +83,121 @@ pub fn compress(entries: Vec<JsString>, datas: Vec<Uint8Array>) -> Result<Uint8A
     Ok(out.data)
 }
 
+/// Compresses `data` into a 7z archive with a single entry named `data`, without blocking the
+/// browser main thread.
+///
+/// The data is compressed in chunks, and control is yielded back to the event loop after each
+/// chunk.
+///
+/// # Arguments
+/// * `data` - The data to compress
+/// * `method_name` - Name of the compression method, like `LZMA2` or `COPY`
+/// * `progress` - Optional JavaScript callback, called with the number of processed bytes and the
+///   total number of bytes after each chunk
+#[wasm_bindgen]
+pub async fn compress_to_vec_async(
+    data: Vec<u8>,
+    method_name: &str,
+    progress: Option<Function>,
+) -> Result<Vec<u8>, JsValue> {
+    let method = EncoderMethod::by_name(method_name)
+        .ok_or_else(|| JsValue::from(format!("Unknown compression method: {method_name}")))?;
+    let config = EncoderConfiguration::new(method);
+
+    let mut compressed = Vec::new();
+    let mut codec_writer =
+        CodecWriter::new(&mut compressed, config.clone()).map_err(to_js_error)?;
+    let mut processed = 0;
+    for chunk in data.chunks(ASYNC_CHUNK_SIZE) {
+        codec_writer.write_all(chunk).map_err(to_js_error)?;
+        processed += chunk.len();
+        report_progress(progress.as_ref(), processed, data.len());
+        TimeoutFuture::new(0).await;
+    }
+    codec_writer.finish().map_err(to_js_error)?;
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).map_err(to_js_error)?;
+    let entry = ArchiveEntry::new_file("data");
+    if data.is_empty() {
+        writer
+            .push_archive_entry::<&[u8]>(entry, None)
+            .map_err(to_js_error)?;
+    } else {
+        writer
+            .push_raw_stream(
+                entry,
+                &compressed,
+                data.len() as u64,
+                crc32fast::hash(&data),
+                vec![config],
+            )
+            .map_err(to_js_error)?;
+    }
+    Ok(writer.finish().map_err(to_js_error)?.into_inner())
+}
+
+/// Decompresses the single file of a 7z archive, like one created by [`compress_to_vec_async`],
+/// without blocking the browser main thread.
+///
+/// The data is decompressed in chunks, and control is yielded back to the event loop after each
+/// chunk.
+///
+/// # Arguments
+/// * `data` - The archive data
+/// * `progress` - Optional JavaScript callback, called with the number of decompressed bytes and
+///   the total number of bytes after each chunk
+#[wasm_bindgen]
+pub async fn decompress_vec_async(
+    data: Vec<u8>,
+    progress: Option<Function>,
+) -> Result<Vec<u8>, JsValue> {
+    let mut reader =
+        ArchiveReader::new(Cursor::new(data), Password::empty()).map_err(to_js_error)?;
+    let mut files = reader
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_directory());
+    let (file_index, total) = match (files.next(), files.next()) {
+        (Some((index, entry)), None) => (index, entry.size() as usize),
+        _ => return Err(JsValue::from("Archive must contain exactly one file")),
+    };
+
+    let mut entry_reader = reader.entry_reader(file_index).map_err(to_js_error)?;
+    let mut output = Vec::with_capacity(total);
+    let mut buf = vec![0u8; ASYNC_CHUNK_SIZE];
+    loop {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match entry_reader.read(&mut buf[filled..]).map_err(to_js_error)? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        output.extend_from_slice(&buf[..filled]);
+        report_progress(progress.as_ref(), output.len(), total);
+        TimeoutFuture::new(0).await;
+    }
+    Ok(output)
+}
+
+fn report_progress(progress: Option<&Function>, processed: usize, total: usize) {
+    if let Some(progress) = progress {
+        let _ = progress.call2(
+            &JsValue::NULL,
+            &JsValue::from(processed as f64),
+            &JsValue::from(total as f64),
+        );
+    }
+}
+
+fn to_js_error(error: impl std::fmt::Display) -> JsValue {
+    JsValue::from(error.to_string())
+}
+
 struct Uint8ArrayStream {
     data: Uint8Array,
     pos: usize,
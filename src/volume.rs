@@ -0,0 +1,250 @@
+//! Archives split into multiple volumes, named like `archive.7z.001`, `archive.7z.002`, …
+//!
+//! The volumes are plain slices of the archive, so the signature header is stored in the first
+//! volume and the end header in the last one.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+#[cfg(feature = "compress")]
+use std::{fs::OpenOptions, io::Write};
+
+use crate::Error;
+
+/// Returns the path of the volume with the zero-based `index`.
+fn volume_path(base_path: &Path, index: usize) -> PathBuf {
+    let mut path = base_path.as_os_str().to_owned();
+    path.push(format!(".{:03}", index + 1));
+    PathBuf::from(path)
+}
+
+fn seek_position(pos: u64, len: u64, seek: SeekFrom) -> std::io::Result<u64> {
+    let new_pos = match seek {
+        SeekFrom::Start(pos) => Some(pos),
+        SeekFrom::End(offset) => len.checked_add_signed(offset),
+        SeekFrom::Current(offset) => pos.checked_add_signed(offset),
+    };
+    new_pos.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+/// Writer that splits the data into volumes of a fixed size.
+///
+/// Created by [`ArchiveWriter::new_multivolume`](crate::ArchiveWriter::new_multivolume).
+#[cfg(feature = "compress")]
+pub struct VolumeWriter {
+    base_path: PathBuf,
+    volume_size: u64,
+    volume_count: usize,
+    /// The open volume, its index and the position in it.
+    current: Option<(usize, File, u64)>,
+    pos: u64,
+    len: u64,
+}
+
+#[cfg(feature = "compress")]
+impl VolumeWriter {
+    /// Creates a writer that writes volumes next to `base_path`.
+    ///
+    /// # Arguments
+    /// * `base_path` - The path of the archive, without the volume number
+    /// * `volume_size` - The maximal size of each volume in bytes
+    pub fn new(base_path: impl Into<PathBuf>, volume_size: u64) -> Result<Self, Error> {
+        if volume_size == 0 {
+            return Err(Error::other("Volume size must not be 0"));
+        }
+        Ok(Self {
+            base_path: base_path.into(),
+            volume_size,
+            volume_count: 0,
+            current: None,
+            pos: 0,
+            len: 0,
+        })
+    }
+
+    /// Returns the number of volumes that were created.
+    pub fn volume_count(&self) -> usize {
+        self.volume_count
+    }
+
+    /// Returns the paths of the volumes that were created.
+    pub fn volume_paths(&self) -> Vec<PathBuf> {
+        (0..self.volume_count)
+            .map(|index| volume_path(&self.base_path, index))
+            .collect()
+    }
+
+    fn open_volume(&mut self, index: usize) -> std::io::Result<()> {
+        if matches!(self.current, Some((current, ..)) if current == index) {
+            return Ok(());
+        }
+        if let Some((_, mut file, _)) = self.current.take() {
+            file.flush()?;
+        }
+        // Volumes are created in order, even if a seek skipped some of them.
+        while self.volume_count <= index {
+            let path = volume_path(&self.base_path, self.volume_count);
+            let file = File::create(&path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+            self.volume_count += 1;
+            self.current = Some((self.volume_count - 1, file, 0));
+        }
+        if !matches!(self.current, Some((current, ..)) if current == index) {
+            let path = volume_path(&self.base_path, index);
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+            self.current = Some((index, file, 0));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "compress")]
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let index = usize::try_from(self.pos / self.volume_size)
+            .map_err(|_| std::io::Error::other("Too many volumes"))?;
+        let offset = self.pos % self.volume_size;
+        self.open_volume(index)?;
+        let Some((_, file, file_pos)) = self.current.as_mut() else {
+            unreachable!("volume was opened");
+        };
+        if *file_pos != offset {
+            file.seek(SeekFrom::Start(offset))?;
+            *file_pos = offset;
+        }
+        let len = buf.len().min((self.volume_size - offset) as usize);
+        let written = file.write(&buf[..len])?;
+        *file_pos += written as u64;
+        self.pos += written as u64;
+        self.len = self.len.max(self.pos);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.current.as_mut() {
+            Some((_, file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "compress")]
+impl Seek for VolumeWriter {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = seek_position(self.pos, self.len, pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Reader over the volumes of an archive that was split into multiple files.
+///
+/// # Example
+/// ```no_run
+/// use sevenz_rust2::*;
+///
+/// let volumes = MultiVolumeReader::open("path/to/archive.7z").unwrap();
+/// let mut reader = ArchiveReader::new(volumes, Password::empty()).unwrap();
+/// ```
+pub struct MultiVolumeReader {
+    paths: Vec<PathBuf>,
+    /// Offset of every volume in the archive.
+    offsets: Vec<u64>,
+    len: u64,
+    /// The open volume, its index and the position in it.
+    current: Option<(usize, File, u64)>,
+    pos: u64,
+}
+
+impl MultiVolumeReader {
+    /// Opens the volumes `<base_path>.001`, `<base_path>.002`, … up to the first volume that
+    /// doesn't exist.
+    ///
+    /// # Arguments
+    /// * `base_path` - The path of the archive, without the volume number
+    pub fn open(base_path: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut paths = Vec::new();
+        let mut offsets = Vec::new();
+        let mut len = 0;
+        loop {
+            let path = volume_path(base_path.as_ref(), paths.len());
+            let metadata = match path.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && !paths.is_empty() => break,
+                Err(e) => return Err(Error::file_open(e, path.to_string_lossy().to_string())),
+            };
+            offsets.push(len);
+            len += metadata.len();
+            paths.push(path);
+        }
+        Ok(Self {
+            paths,
+            offsets,
+            len,
+            current: None,
+            pos: 0,
+        })
+    }
+
+    /// Returns the paths of the volumes.
+    pub fn volume_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Read for MultiVolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        let index = self.offsets.partition_point(|&offset| offset <= self.pos) - 1;
+        let offset = self.pos - self.offsets[index];
+        if !matches!(self.current, Some((current, ..)) if current == index) {
+            let path = &self.paths[index];
+            let file = File::open(path)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+            self.current = Some((index, file, 0));
+        }
+        let Some((_, file, file_pos)) = self.current.as_mut() else {
+            unreachable!("volume was opened");
+        };
+        if *file_pos != offset {
+            file.seek(SeekFrom::Start(offset))?;
+            *file_pos = offset;
+        }
+        let volume_end = self.offsets.get(index + 1).copied().unwrap_or(self.len);
+        let len = buf.len().min((volume_end - self.pos) as usize);
+        let read = file.read(&mut buf[..len])?;
+        if read == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Volume {} is shorter than expected",
+                    self.paths[index].display()
+                ),
+            ));
+        }
+        *file_pos += read as u64;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for MultiVolumeReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pos = seek_position(self.pos, self.len, pos)?;
+        Ok(self.pos)
+    }
+}
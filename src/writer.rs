@@ -1,5 +1,7 @@
+#[cfg(feature = "async")]
+mod async_push;
 mod counting_writer;
-#[cfg(all(feature = "util", not(target_arch = "wasm32")))]
+#[cfg(not(target_arch = "wasm32"))]
 mod lazy_file_reader;
 mod pack_info;
 mod seq_reader;
@@ -8,35 +10,53 @@ mod unpack_info;
 
 use std::{
     cell::Cell,
-    io::{Read, Seek, Write},
+    collections::{HashMap, HashSet},
+    io::{BufWriter, Cursor, Read, Seek, Write},
     rc::Rc,
     sync::Arc,
 };
 #[cfg(not(target_arch = "wasm32"))]
-use std::{fs::File, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::Path,
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
 
 pub(crate) use counting_writer::CountingWriter;
 use crc32fast::Hasher;
 
-#[cfg(all(feature = "util", not(target_arch = "wasm32")))]
-pub(crate) use self::lazy_file_reader::LazyFileReader;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::lazy_file_reader::LazyFileReader;
 pub(crate) use self::seq_reader::SeqReader;
 pub use self::source_reader::SourceReader;
 use self::{pack_info::PackInfo, unpack_info::UnpackInfo};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::VolumeWriter;
 use crate::{
-    ArchiveEntry, AutoFinish, AutoFinisher, ByteWriter, Error,
+    ArchiveEntry, AutoFinish, AutoFinisher, Block, ByteWriter, Error,
     archive::*,
     bitset::{BitSet, write_bit_set},
-    encoder,
+    encoder::{self, FinishableWrite},
 };
+#[cfg(feature = "aes256")]
+use crate::{Password, encoder_options::AesEncoderOptions};
 
 macro_rules! write_times {
     //write_i64
-    ($fn_name:tt, $nid:expr, $has_time:tt, $time:tt) => {
-        write_times!($fn_name, $nid, $has_time, $time, write_u64);
+    ($fn_name:tt, $nid:expr, $stored:tt, $has_time:tt, $time:tt) => {
+        write_times!($fn_name, $nid, $stored, $has_time, $time, write_u64);
     };
-    ($fn_name:tt, $nid:expr, $has_time:tt, $time:tt, $write_fn:tt) => {
-        fn $fn_name<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
+    ($fn_name:tt, $nid:expr, $stored:tt, $has_time:tt, $time:tt, $write_fn:tt) => {
+        fn $fn_name(&self, header: &mut Vec<u8>, aligned: bool) -> std::io::Result<()> {
+            if !self.stored_metadata.$stored {
+                return Ok(());
+            }
             let mut num = 0;
             for entry in self.files.iter() {
                 if entry.$has_time {
@@ -44,7 +64,6 @@ macro_rules! write_times {
                 }
             }
             if num > 0 {
-                header.write_u8($nid)?;
                 let mut temp: Vec<u8> = Vec::with_capacity(128);
                 let mut out = &mut temp;
                 if num != self.files.len() {
@@ -60,12 +79,21 @@ macro_rules! write_times {
                     out.write_u8(1)?;
                 }
                 out.write_u8(0)?;
+                let values_offset = out.len();
                 for file in self.files.iter() {
                     if file.$has_time {
                         out.$write_fn((file.$time).into())?;
                     }
                 }
                 out.flush()?;
+                let value_size = (temp.len() - values_offset) / num;
+                self.write_alignment_dummy(
+                    header,
+                    aligned,
+                    1 + encoded_u64_len(temp.len() as u64) + values_offset,
+                    value_size,
+                )?;
+                header.write_u8($nid)?;
                 write_u64(header, temp.len() as u64)?;
                 header.write_all(&temp)?;
             }
@@ -76,14 +104,171 @@ macro_rules! write_times {
 
 type Result<T> = std::result::Result<T, Error>;
 
+const DEFAULT_READ_BUFFER_SIZE: usize = 4096;
+const MIN_READ_BUFFER_SIZE: usize = 512;
+const MAX_READ_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+
+/// Selects the compression methods for an entry. See [`ArchiveWriter::set_method_selector`].
+pub type MethodSelector =
+    Box<dyn Fn(&ArchiveEntry) -> Option<Vec<EncoderConfiguration>> + Send + Sync>;
+
+/// Whether the header of an archive is compressed, see
+/// [`ArchiveWriter::set_header_compression`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderCompression {
+    /// Compress the header with LZMA, unless the compressed header would be larger.
+    #[default]
+    Auto,
+    /// Always compress the header with LZMA.
+    Always,
+    /// Write a plain header. Encrypted headers are always compressed.
+    Never,
+}
+
+/// How [`ArchiveWriter`] handles entries with a name that was already added, see
+/// [`ArchiveWriter::set_duplicate_entry_policy`].
+#[derive(Debug, Default, Clone, Copy)]
+pub enum DuplicateEntryPolicy {
+    /// Add the entry. The archive contains multiple entries with the same name.
+    #[default]
+    Allow,
+    /// Add the entry, but call the function with the duplicate name first.
+    Warn(fn(&str)),
+    /// Don't add the entry and return [`Error::DuplicateEntry`].
+    Reject,
+}
+
+impl DuplicateEntryPolicy {
+    /// Handles the duplicate `name` according to the policy.
+    fn apply(self, name: &str) -> Result<()> {
+        match self {
+            DuplicateEntryPolicy::Allow => Ok(()),
+            DuplicateEntryPolicy::Warn(warn) => {
+                warn(name);
+                Ok(())
+            }
+            DuplicateEntryPolicy::Reject => Err(Error::DuplicateEntry(name.to_string())),
+        }
+    }
+}
+
+/// The metadata of the entries that [`ArchiveWriter`] stores in the header, see
+/// [`ArchiveWriter::set_stored_metadata`].
+///
+/// Metadata that is disabled is left out of the header, even if the entries have it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoredMetadata {
+    /// Store the last modified dates.
+    pub mtime: bool,
+    /// Store the creation dates.
+    pub ctime: bool,
+    /// Store the last access dates.
+    pub atime: bool,
+    /// Store the Windows attributes, which also hold the Unix permissions.
+    pub win_attributes: bool,
+}
+
+impl Default for StoredMetadata {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl StoredMetadata {
+    /// Stores all metadata the entries have.
+    pub const ALL: Self = Self {
+        mtime: true,
+        ctime: true,
+        atime: true,
+        win_attributes: true,
+    };
+
+    /// Stores no dates and attributes, only the names and sizes of the entries.
+    pub const NONE: Self = Self {
+        mtime: false,
+        ctime: false,
+        atime: false,
+        win_attributes: false,
+    };
+}
+
 /// Writes a 7z archive file.
+///
+/// # Compatibility
+///
+/// Some metadata is stored in header properties that only this crate reads:
+///
+/// * The comment of the archive, see [`ArchiveWriter::set_comment`], is stored in the archive
+///   properties, which 7-Zip skips without a message.
+/// * The comments of the files, see [`ArchiveEntry::comment`], and the digests written by
+///   `ArchiveWriter::set_store_sha256` and `ArchiveWriter::set_store_blake3_hash` are stored in
+///   file properties that 7-Zip doesn't know. 7-Zip skips them as well, but reports the
+///   archive as using an unsupported feature. The files can still be listed and extracted.
 pub struct ArchiveWriter<W: Write> {
     output: W,
+    /// Length of the data in front of the archive.
+    prefix_len: u64,
     files: Vec<ArchiveEntry>,
     content_methods: Arc<Vec<EncoderConfiguration>>,
+    method_selector: Option<MethodSelector>,
+    auto_bcj: bool,
     pack_info: PackInfo,
     unpack_info: UnpackInfo,
     encrypt_header: bool,
+    sort_entries: bool,
+    solid_block_file_count_limit: usize,
+    store_pack_crcs: bool,
+    compute_crcs: bool,
+    header_compression: HeaderCompression,
+    duplicate_entry_policy: DuplicateEntryPolicy,
+    stored_metadata: StoredMetadata,
+    /// Number of the added entries with each name, only tracked if duplicates are not allowed.
+    entry_names: HashMap<String, usize>,
+    /// Number of the leading entries of `files` whose names are counted in `entry_names`.
+    named_entries: usize,
+    /// Name of the last entry when it was handed out by `last_entry_mut`, to check a new name.
+    renamed_entry_name: Option<String>,
+    read_buffer_size: usize,
+    write_buffer_size: usize,
+    pack_stream_alignment: u64,
+    #[cfg(not(target_arch = "wasm32"))]
+    max_in_flight_bytes: u64,
+    #[cfg(feature = "sha256")]
+    store_sha256: bool,
+    #[cfg(feature = "blake3-checksum")]
+    store_blake3_hash: bool,
+    /// Time when the first entry was pushed.
+    #[cfg(not(target_arch = "wasm32"))]
+    started: Option<Instant>,
+    comment: Option<String>,
+}
+
+/// Statistics of a finished archive, returned by [`ArchiveWriter::finish_with_stats`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FinishStats {
+    /// Number of entries that are not directories.
+    pub total_files: usize,
+    /// Number of directory entries.
+    pub total_dirs: usize,
+    /// Sum of the sizes of all entries.
+    pub total_uncompressed_bytes: u64,
+    /// Sum of the sizes of all packed streams, without the headers.
+    pub total_compressed_bytes: u64,
+    /// Time since the first entry was pushed, zero if no entry was pushed.
+    pub elapsed: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FinishStats {
+    /// Returns the compressed size relative to the uncompressed size, e.g. `0.25` if the data
+    /// was compressed to a quarter. Returns `1.0` if there is no data.
+    pub fn compression_ratio(&self) -> f64 {
+        if self.total_uncompressed_bytes == 0 {
+            return 1.0;
+        }
+        self.total_compressed_bytes as f64 / self.total_uncompressed_bytes as f64
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -96,27 +281,120 @@ impl ArchiveWriter<File> {
     }
 }
 
+/// Writes a 7z archive split into multiple volumes, see [`ArchiveWriter::new_multivolume`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type MultiVolumeWriter = ArchiveWriter<VolumeWriter>;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ArchiveWriter<VolumeWriter> {
+    /// Creates a 7z archive that is split into volumes of `volume_size` bytes, named
+    /// `<base_path>.001`, `<base_path>.002`, … The last volume may be smaller.
+    ///
+    /// The archive can be read again with [`MultiVolumeReader`](crate::MultiVolumeReader).
+    ///
+    /// # Arguments
+    /// * `base_path` - The path of the archive, without the volume number
+    /// * `volume_size` - The maximal size of each volume in bytes
+    pub fn new_multivolume(base_path: &Path, volume_size: u64) -> Result<MultiVolumeWriter> {
+        Self::new(VolumeWriter::new(base_path, volume_size)?)
+    }
+}
+
+impl ArchiveWriter<Cursor<Vec<u8>>> {
+    /// Finishes the compression and returns the bytes of the archive.
+    ///
+    /// Convenience for archives written to a [`Cursor`] over a [`Vec`].
+    pub fn finish_into_bytes(self) -> std::io::Result<Vec<u8>> {
+        Ok(self.finish()?.into_inner())
+    }
+}
+
 impl<W: Write + Seek> ArchiveWriter<W> {
     /// Prepares writer to write a 7z archive to.
-    pub fn new(mut writer: W) -> Result<Self> {
-        writer.seek(std::io::SeekFrom::Start(SIGNATURE_HEADER_SIZE))?;
+    pub fn new(writer: W) -> Result<Self> {
+        Self::new_with_prefix(writer, &[])
+    }
+
+    /// Prepares writer to write a 7z archive, preceded by `prefix`.
+    ///
+    /// This creates self-extracting archives when `prefix` is an executable stub that extracts
+    /// the archive appended to it. Such archives can be read with [`Archive::read_sfx`].
+    ///
+    /// # Arguments
+    /// * `writer` - The writer to write the archive to
+    /// * `prefix` - The data to write in front of the archive
+    pub fn new_with_prefix(mut writer: W, prefix: &[u8]) -> Result<Self> {
+        writer.seek(std::io::SeekFrom::Start(0))?;
+        writer.write_all(prefix)?;
+        let prefix_len = prefix.len() as u64;
+        // Until the archive is finished, the start header points to an empty header, so that
+        // an interrupted archive is still recognized as a 7z archive.
+        writer.write_all(&signature_header(0, &[])?)?;
 
         Ok(Self {
             output: writer,
+            prefix_len,
             files: Default::default(),
             content_methods: Arc::new(vec![EncoderConfiguration::new(EncoderMethod::LZMA2)]),
+            method_selector: None,
+            auto_bcj: false,
             pack_info: Default::default(),
             unpack_info: Default::default(),
             encrypt_header: true,
+            sort_entries: false,
+            solid_block_file_count_limit: 0,
+            store_pack_crcs: true,
+            compute_crcs: true,
+            header_compression: HeaderCompression::Auto,
+            duplicate_entry_policy: DuplicateEntryPolicy::Allow,
+            stored_metadata: StoredMetadata::ALL,
+            entry_names: HashMap::new(),
+            named_entries: 0,
+            renamed_entry_name: None,
+            read_buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            write_buffer_size: 0,
+            pack_stream_alignment: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            max_in_flight_bytes: 64 * 1024 * 1024,
+            #[cfg(feature = "sha256")]
+            store_sha256: false,
+            #[cfg(feature = "blake3-checksum")]
+            store_blake3_hash: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            started: None,
+            comment: None,
         })
     }
 
+    /// Sets the comment of the archive, see [`ArchiveWriter::set_comment`].
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        self.set_comment(comment);
+        self
+    }
+
+    /// Sets the comment of the archive, which is read with [`Archive::comment`]. An empty
+    /// comment removes it.
+    ///
+    /// See [compatibility](ArchiveWriter#compatibility) for how other tools handle it.
+    pub fn set_comment(&mut self, comment: &str) -> &mut Self {
+        self.comment = (!comment.is_empty()).then(|| comment.to_string());
+        self
+    }
+
+    /// Returns the comment of the archive, if it has one.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
     /// Returns a wrapper around `self` that will finish the stream on drop.
     pub fn auto_finish(self) -> AutoFinisher<Self> {
         AutoFinisher(Some(self))
     }
 
     /// Sets the default compression methods to use for entry data. Default is LZMA2.
+    ///
+    /// The chain is validated with [`EncoderConfiguration::validate`] before any entry data is
+    /// written, so an invalid configuration is reported without corrupting the output.
     pub fn set_content_methods(&mut self, content_methods: Vec<EncoderConfiguration>) -> &mut Self {
         if content_methods.is_empty() {
             return self;
@@ -125,11 +403,321 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         self
     }
 
+    /// Sets the default compression methods to `method`, with the BCJ filter for `arch` applied
+    /// to the entry data before it is compressed.
+    ///
+    /// See [`EncoderConfiguration::with_bcj_filter`].
+    pub fn set_content_methods_with_bcj(
+        &mut self,
+        method: EncoderConfiguration,
+        arch: BcjArch,
+    ) -> &mut Self {
+        self.set_content_methods(method.with_bcj_filter(arch))
+    }
+
+    /// Sets the default compression methods to the ones that 7-Zip uses for the compression
+    /// level `level` (0-9), like `7z a -mx=level`.
+    ///
+    /// See [`EncoderConfiguration::preset`]. 7-Zip also filters executables with BCJ, which can
+    /// be enabled with [`ArchiveWriter::set_auto_bcj`].
+    pub fn set_compression_level(&mut self, level: u32) -> &mut Self {
+        self.set_content_methods(vec![EncoderConfiguration::preset(level)])
+    }
+
+    /// Whether to apply the x86 BCJ filter to entries whose name suggests a Windows executable
+    /// or library, like `.exe` and `.dll`, as 7-Zip does. Default is `false`.
+    ///
+    /// For non-solid compression, the filter is only added to chains with a compression codec
+    /// other than COPY and without a filter, including the chains of
+    /// [`ArchiveWriter::set_method_selector`]. [`ArchiveWriter::push_archive_entries`] packs the
+    /// executables into a separate solid block with the filter, like 7-Zip, while
+    /// [`ArchiveWriter::push_solid_block_with_methods`] uses the given methods as they are.
+    pub fn set_auto_bcj(&mut self, enabled: bool) -> &mut Self {
+        self.auto_bcj = enabled;
+        self
+    }
+
+    /// Sets a function that selects the compression methods per entry for non-solid
+    /// compression.
+    ///
+    /// The selector is consulted by [`ArchiveWriter::push_archive_entry`] for every entry with
+    /// data. If it returns `None`, the default content methods are used.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut writer = ArchiveWriter::create("path/to/dest.7z").expect("create writer ok");
+    /// writer.set_method_selector(Box::new(|entry: &ArchiveEntry| {
+    ///     if entry.name().ends_with(".jpg") {
+    ///         Some(vec![EncoderMethod::COPY.into()])
+    ///     } else {
+    ///         None
+    ///     }
+    /// }));
+    /// ```
+    pub fn set_method_selector(&mut self, selector: MethodSelector) -> &mut Self {
+        self.method_selector = Some(selector);
+        self
+    }
+
+    /// Returns the entries that have been added so far.
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.files
+    }
+
+    /// Returns the entry that was added last, or `None` if no entry has been added yet.
+    ///
+    /// This allows to set metadata that is only known after the data of the entry was read,
+    /// like the modification time of a download. The header is written by
+    /// [`ArchiveWriter::finish`], so it contains the changed values.
+    ///
+    /// The name, comment, dates and attributes can safely be changed. The size, CRC,
+    /// `has_stream`, `is_directory` and the other fields that describe the stored data must not
+    /// be changed, since they have to match the packed streams that were already written.
+    ///
+    /// A changed name is checked against the duplicate entry policy by the next push or by
+    /// [`ArchiveWriter::finish`].
+    pub fn last_entry_mut(&mut self) -> Option<&mut ArchiveEntry> {
+        if !matches!(self.duplicate_entry_policy, DuplicateEntryPolicy::Allow)
+            && let Some(entry) = self.files.last()
+        {
+            // The name may change, so it is counted again by the next check.
+            if self.named_entries == self.files.len() {
+                if let Some(count) = self.entry_names.get_mut(&entry.name) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.entry_names.remove(&entry.name);
+                    }
+                }
+                self.named_entries -= 1;
+            }
+            self.renamed_entry_name
+                .get_or_insert_with(|| entry.name.clone());
+        }
+        self.files.last_mut()
+    }
+
     /// Whether to enable the encryption of the -header. Default is `true`.
     pub fn set_encrypt_header(&mut self, enabled: bool) {
         self.encrypt_header = enabled;
     }
 
+    /// Encrypts the data of the entries with `password`, but leaves the header unencrypted.
+    ///
+    /// The content methods are set to AES followed by LZMA2 and header encryption is disabled,
+    /// see [`ArchiveWriter::set_encrypt_header`]. This allows listing the entries without the
+    /// password, which is faster when browsing encrypted archives.
+    ///
+    /// Note that the names, sizes, timestamps and CRCs of the entries are readable by anyone.
+    /// The CRC of small or predictable files can reveal their content, so use full encryption
+    /// if the metadata is sensitive.
+    #[cfg(feature = "aes256")]
+    pub fn with_encrypt_entries_only(&mut self, password: Password) -> &mut Self {
+        self.encrypt_header = false;
+        self.set_content_methods(vec![
+            AesEncoderOptions::new(password).into(),
+            EncoderConfiguration::new(EncoderMethod::LZMA2),
+        ])
+    }
+
+    /// Sets whether the header is compressed. Default is [`HeaderCompression::Auto`].
+    ///
+    /// A plain header can be inspected without decoding it, which is useful for debugging.
+    /// If the header is encrypted, see [`ArchiveWriter::set_encrypt_header`], it is always
+    /// compressed.
+    pub fn set_header_compression(&mut self, header_compression: HeaderCompression) {
+        self.header_compression = header_compression;
+    }
+
+    /// Sets which dates and attributes of the entries are stored. Default is
+    /// [`StoredMetadata::ALL`].
+    ///
+    /// Leaving out metadata makes the header smaller and doesn't reveal when and where the
+    /// files were created. The entries keep their fields, they are only ignored when the
+    /// header is written by [`ArchiveWriter::finish`].
+    pub fn set_stored_metadata(&mut self, stored_metadata: StoredMetadata) -> &mut Self {
+        self.stored_metadata = stored_metadata;
+        self
+    }
+
+    /// Sets how entries with a name that was already added are handled. Default is
+    /// [`DuplicateEntryPolicy::Allow`].
+    ///
+    /// 7z archives can contain multiple entries with the same name, but most tools only
+    /// extract one of them. Names are compared exactly, so `a.txt` and `A.txt` are different.
+    pub fn set_duplicate_entry_policy(&mut self, policy: DuplicateEntryPolicy) -> &mut Self {
+        self.duplicate_entry_policy = policy;
+        self.entry_names.clear();
+        self.named_entries = 0;
+        self.renamed_entry_name = None;
+        self
+    }
+
+    /// Checks `names` against the names of the added entries and each other, according to the
+    /// duplicate entry policy. The names are counted once the entries were added, by the next
+    /// check.
+    fn check_entry_names<'n>(&mut self, names: impl IntoIterator<Item = &'n str>) -> Result<()> {
+        // Every push checks the names first, so the time of the first push is taken here.
+        #[cfg(not(target_arch = "wasm32"))]
+        self.started.get_or_insert_with(Instant::now);
+        if matches!(self.duplicate_entry_policy, DuplicateEntryPolicy::Allow) {
+            return Ok(());
+        }
+        self.count_entry_names()?;
+        let mut new_names = HashSet::new();
+        for name in names {
+            if self.entry_names.contains_key(name) || !new_names.insert(name) {
+                self.duplicate_entry_policy.apply(name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the names of the entries that were added or renamed since the last check. An
+    /// entry that was renamed to a duplicate with [`ArchiveWriter::last_entry_mut`] stays
+    /// uncounted if the policy rejects it, so that it is reported until it is renamed again.
+    fn count_entry_names(&mut self) -> Result<()> {
+        if matches!(self.duplicate_entry_policy, DuplicateEntryPolicy::Allow) {
+            return Ok(());
+        }
+        while let Some(entry) = self.files.get(self.named_entries) {
+            // The names of pushed entries were checked by the push, only a new name of the last
+            // entry is checked here.
+            let renamed = self.named_entries + 1 == self.files.len()
+                && self
+                    .renamed_entry_name
+                    .as_ref()
+                    .is_some_and(|name| *name != entry.name);
+            if renamed && self.entry_names.contains_key(&entry.name) {
+                self.duplicate_entry_policy.apply(&entry.name)?;
+            }
+            *self.entry_names.entry(entry.name.clone()).or_default() += 1;
+            self.named_entries += 1;
+        }
+        self.renamed_entry_name = None;
+        Ok(())
+    }
+
+    /// Whether to store the CRC of every packed stream in the header. Default is `true`.
+    ///
+    /// The CRCs allow verifying the compressed data without decompressing it, see
+    /// [`Archive::verify_pack_streams`].
+    pub fn set_store_pack_crcs(&mut self, enabled: bool) {
+        self.store_pack_crcs = enabled;
+    }
+
+    /// Whether to compute the CRCs of entries and blocks while compressing. Default is `true`.
+    ///
+    /// CRCs are optional in 7z archives, so disabling them saves the time to compute them at
+    /// the cost of not detecting corrupted data when extracting. This also applies to the CRCs
+    /// of packed streams, see [`ArchiveWriter::set_store_pack_crcs`]. Entries added with
+    /// [`ArchiveWriter::push_raw_stream`] and [`ArchiveWriter::push_raw_block`] keep the CRCs
+    /// that were passed in.
+    pub fn set_compute_crcs(&mut self, enabled: bool) {
+        self.compute_crcs = enabled;
+    }
+
+    /// Whether to compute the SHA-256 digest of every entry and store it in the header. Default
+    /// is `false`.
+    ///
+    /// 7z archives have no standard field for digests other than CRC32, so they are stored in
+    /// a property of this crate, see [compatibility](ArchiveWriter#compatibility). The digests
+    /// are available from [`ArchiveEntry::sha256`] when reading the archive.
+    #[cfg(feature = "sha256")]
+    pub fn set_store_sha256(&mut self, enabled: bool) {
+        self.store_sha256 = enabled;
+    }
+
+    /// Whether to compute the BLAKE3 hash of every entry and store it in the header. Default is
+    /// `false`.
+    ///
+    /// The hashes are stored in a property of this crate, see
+    /// [compatibility](ArchiveWriter#compatibility). The reader verifies them while extracting
+    /// the entries, in addition to the CRC32.
+    #[cfg(feature = "blake3-checksum")]
+    pub fn set_store_blake3_hash(&mut self, enabled: bool) {
+        self.store_blake3_hash = enabled;
+    }
+
+    /// Sets the maximal number of compressed bytes that
+    /// [`ArchiveWriter::push_archive_entries_parallel`] buffers before they are written to the
+    /// output. Default is 64 MiB.
+    ///
+    /// Entries that are already being compressed are always finished, so the buffered data can
+    /// exceed the limit by the compressed size of up to one entry per thread. A thread can
+    /// always start an entry while nothing is buffered, so a limit of 0 compresses the entries
+    /// in parallel only until the first one is done.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_max_in_flight_bytes(&mut self, max_in_flight_bytes: u64) {
+        self.max_in_flight_bytes = max_in_flight_bytes;
+    }
+
+    /// Sets the size of the buffer that the data of the entries is read into before it is
+    /// compressed. Default is 4 KiB.
+    ///
+    /// Larger buffers reduce the number of reads, which improves the throughput of readers with
+    /// expensive calls, like network streams. The size is clamped to 512 bytes..=4 MiB.
+    pub fn set_read_buffer_size(&mut self, size: usize) {
+        self.read_buffer_size = size.clamp(MIN_READ_BUFFER_SIZE, MAX_READ_BUFFER_SIZE);
+    }
+
+    /// Sets the size of the buffer that the compressed data is collected in before it is
+    /// written to the output. Default is 0, which writes the output of the codecs directly.
+    ///
+    /// Codecs write their output in small pieces, so an output without buffering of its own,
+    /// like a [`File`], profits from a buffer of 64 KiB or more. The buffer is flushed after
+    /// every block.
+    pub fn set_write_buffer_size(&mut self, size: usize) {
+        self.write_buffer_size = size;
+    }
+
+    /// Aligns the start of every packed stream and of the header to a multiple of `alignment`
+    /// bytes, counted from the beginning of the output. Default is 0, which writes the streams
+    /// without gaps.
+    ///
+    /// Aligned streams can be accessed efficiently through memory maps, see
+    /// [`Archive::pack_stream_ranges`]. The 7z format has no gaps between packed streams, so
+    /// the padding in front of a stream is stored as a block without any entries, which uses
+    /// the COPY method and has no sub-streams. The format allows such blocks and 7-Zip's
+    /// reader accepts them, but other readers might reject them. If the header is not
+    /// compressed, the file names, times and attributes inside of it are aligned with dummy
+    /// properties, like 7-Zip does.
+    pub fn set_pack_stream_alignment(&mut self, alignment: u64) {
+        self.pack_stream_alignment = alignment;
+    }
+
+    /// Whether [`ArchiveWriter::push_archive_entries`] sorts the entries of a solid block by
+    /// their file extension and name before compressing them. Default is `false`.
+    ///
+    /// Files of the same type usually compress better when they are next to each other. This
+    /// is the same strategy 7-Zip uses.
+    pub fn set_sort_entries_for_compression(&mut self, enabled: bool) {
+        self.sort_entries = enabled;
+    }
+
+    /// Sets the maximal number of entries in a solid block written by
+    /// [`ArchiveWriter::push_archive_entries`] and
+    /// [`ArchiveWriter::push_solid_block_with_methods`]. Default is `0`, which means no limit.
+    ///
+    /// When more entries are pushed at once, the current block is finished after `count`
+    /// entries and a new block is started for the remaining ones. Smaller blocks compress
+    /// slightly worse, but a single entry can be extracted without decompressing all entries in
+    /// front of it.
+    pub fn set_solid_block_file_count_limit(&mut self, count: usize) {
+        self.solid_block_file_count_limit = count;
+    }
+
+    /// Finishes the current solid block and flushes all data written so far to the output.
+    ///
+    /// Every push completes its blocks before it returns, so the next push always starts a new
+    /// block. This only ensures that the packed streams reach the output without calling
+    /// [`ArchiveWriter::finish`], for example before handing a file to another process.
+    pub fn flush_solid_block(&mut self) -> Result<()> {
+        self.output.flush()?;
+        Ok(())
+    }
+
     /// Non-solid compression - Adds an archive `entry` with data from `reader`.
     ///
     /// # Example
@@ -151,82 +739,477 @@ impl<W: Write + Seek> ArchiveWriter<W> {
     /// ```
     pub fn push_archive_entry<R: Read>(
         &mut self,
-        mut entry: ArchiveEntry,
+        entry: ArchiveEntry,
         reader: Option<R>,
     ) -> Result<&ArchiveEntry> {
-        if !entry.is_directory {
-            if let Some(mut r) = reader {
-                let mut compressed_len = 0;
-                let mut compressed = CompressWrapWriter::new(&mut self.output, &mut compressed_len);
-
-                let mut more_sizes: Vec<Rc<Cell<usize>>> =
-                    Vec::with_capacity(self.content_methods.len() - 1);
-
-                let (crc, size) = {
-                    let mut w = Self::create_writer(
-                        &self.content_methods,
-                        &mut compressed,
-                        &mut more_sizes,
-                    )?;
-                    let mut write_len = 0;
-                    let mut w = CompressWrapWriter::new(&mut w, &mut write_len);
-                    let mut buf = [0u8; 4096];
+        let methods = self.entry_methods(&entry, reader.is_some());
+        self.push_entry_with_methods(entry, reader, methods)
+    }
+
+    /// Returns the methods to compress `entry` with, as chosen by the method selector.
+    fn entry_methods(
+        &self,
+        entry: &ArchiveEntry,
+        has_data: bool,
+    ) -> Arc<Vec<EncoderConfiguration>> {
+        let methods = match &self.method_selector {
+            Some(selector) if !entry.is_directory && has_data => selector(entry).map(Arc::new),
+            _ => None,
+        };
+        let methods = methods.unwrap_or_else(|| self.content_methods.clone());
+        if has_data && !entry.is_directory && is_executable(entry) {
+            if let Some(bcj_methods) = self.auto_bcj_methods(&methods) {
+                return bcj_methods;
+            }
+        }
+        methods
+    }
+
+    /// Returns `methods` with the x86 BCJ filter, if [`ArchiveWriter::set_auto_bcj`] is enabled
+    /// and the chain compresses the data and has no filter yet.
+    fn auto_bcj_methods(
+        &self,
+        methods: &[EncoderConfiguration],
+    ) -> Option<Arc<Vec<EncoderConfiguration>>> {
+        let applies = self.auto_bcj
+            && methods.len() < EncoderConfiguration::MAX_CHAIN_LENGTH
+            && methods
+                .iter()
+                .any(|conf| conf.method.is_compressor() && conf.method != EncoderMethod::COPY)
+            && !methods.iter().any(|conf| conf.method.is_filter());
+        applies.then(|| {
+            let mut methods = methods.to_vec();
+            methods.push(EncoderConfiguration::new(EncoderMethod::BCJ_X86_FILTER));
+            Arc::new(methods)
+        })
+    }
+
+    /// Non-solid compression - Adds an archive `entry` with data from `reader`, compressed with
+    /// `methods` instead of the default content methods.
+    ///
+    /// This allows to mix compression methods in one archive, for example to store already
+    /// compressed files with [`EncoderMethod::COPY`].
+    ///
+    /// # Arguments
+    /// * `entry` - The entry to add
+    /// * `reader` - Reader providing the data of the entry
+    /// * `methods` - The encoder chain to compress the entry data with
+    pub fn push_archive_entry_with_methods<R: Read>(
+        &mut self,
+        entry: ArchiveEntry,
+        reader: Option<R>,
+        methods: Vec<EncoderConfiguration>,
+    ) -> Result<&ArchiveEntry> {
+        self.push_entry_with_methods(entry, reader, Arc::new(methods))
+    }
+
+    /// Non-solid compression - Adds multiple archive entries, compressing up to `thread_count`
+    /// of them at the same time.
+    ///
+    /// Every entry is compressed into its own block like with
+    /// [`ArchiveWriter::push_archive_entry`], but into a buffer in memory. The buffers are written
+    /// to the output in the order of `entries`, so the archive is the same as if the entries were
+    /// added one after another. This speeds up compressing many small files, for which the
+    /// multithreading of LZMA2 doesn't help. No new entry is started while the buffered
+    /// compressed data exceeds [`ArchiveWriter::set_max_in_flight_bytes`].
+    ///
+    /// # Arguments
+    /// * `entries` - The entries to add, together with readers providing their data
+    /// * `thread_count` - The number of threads to compress with. With 1 or less, the entries are
+    ///   compressed directly into the output on the current thread
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn push_archive_entries_parallel<R: Read + Send>(
+        &mut self,
+        entries: Vec<(ArchiveEntry, Option<R>)>,
+        thread_count: u32,
+    ) -> Result<&mut Self> {
+        if thread_count <= 1 {
+            for (entry, reader) in entries {
+                self.push_archive_entry(entry, reader)?;
+            }
+            return Ok(self);
+        }
+        self.check_entry_names(entries.iter().map(|(entry, _)| entry.name()))?;
+
+        let mut pending = Vec::with_capacity(entries.len());
+        let mut jobs = Vec::with_capacity(entries.len());
+        for (entry, reader) in entries {
+            let methods = self.entry_methods(&entry, reader.is_some());
+            let reader = reader.filter(|_| !entry.is_directory);
+            if reader.is_some() {
+                EncoderConfiguration::validate(&methods)?;
+            }
+            jobs.push(Mutex::new(reader.map(|reader| {
+                (reader, entry.name().to_string(), methods.clone())
+            })));
+            pending.push(Some((entry, methods)));
+        }
+
+        // The entries are compressed into memory, which needs no buffering.
+        let settings = EncodeSettings {
+            write_buffer_size: 0,
+            ..self.encode_settings()
+        };
+        let max_in_flight_bytes = self.max_in_flight_bytes;
+        let next_job = AtomicUsize::new(0);
+        let cancelled = AtomicBool::new(false);
+        let in_flight_bytes = Mutex::new(0u64);
+        let written = Condvar::new();
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            for _ in 0..thread_count.min(jobs.len() as u32) {
+                let sender = sender.clone();
+                let (jobs, next_job, cancelled) = (&jobs, &next_job, &cancelled);
+                let (in_flight_bytes, written) = (&in_flight_bytes, &written);
+                scope.spawn(move || {
                     loop {
-                        match r.read(&mut buf) {
-                            Ok(n) => {
-                                if n == 0 {
-                                    break;
-                                }
-                                w.write_all(&buf[..n]).map_err(|e| {
-                                    Error::io_msg(e, format!("Encode entry:{}", entry.name()))
-                                })?;
+                        {
+                            let mut in_flight = in_flight_bytes.lock().unwrap();
+                            // Without any buffered data, a thread may always start the next
+                            // entry, so that a limit of 0 doesn't stop all threads.
+                            while *in_flight > 0
+                                && *in_flight >= max_in_flight_bytes
+                                && !cancelled.load(Ordering::Relaxed)
+                            {
+                                in_flight = written.wait(in_flight).unwrap();
                             }
-                            Err(e) => {
-                                return Err(Error::io_msg(
-                                    e,
-                                    format!("Encode entry:{}", entry.name()),
-                                ));
+                        }
+                        if cancelled.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        // Jobs are taken in order, so the next entry to write is always in
+                        // progress or done and waiting for memory can't dead lock.
+                        let index = next_job.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(index) else {
+                            break;
+                        };
+                        let job = job.lock().unwrap().take();
+                        let result = match job {
+                            Some((reader, name, methods)) => {
+                                let mut buffer = Vec::new();
+                                encode_entry(reader, &name, &methods, &mut buffer, settings)
+                                    .map(|encoded| encoded.map(|encoded| (buffer, encoded)))
                             }
+                            None => Ok(None),
+                        };
+                        if let Ok(Some((buffer, _))) = &result {
+                            *in_flight_bytes.lock().unwrap() += buffer.len() as u64;
+                        }
+                        if sender.send((index, result)).is_err() {
+                            break;
                         }
                     }
-                    w.flush()
-                        .map_err(|e| Error::io_msg(e, format!("Encode entry:{}", entry.name())))?;
-                    w.write(&[])
-                        .map_err(|e| Error::io_msg(e, format!("Encode entry:{}", entry.name())))?;
-
-                    (w.crc_value(), write_len)
-                };
-                let compressed_crc = compressed.crc_value();
+                });
+            }
+            drop(sender);
+
+            let mut results = BTreeMap::new();
+            let mut next = 0;
+            let mut error = None;
+            'receive: for (index, result) in receiver {
+                results.insert(index, result);
+                while let Some(result) = results.remove(&next) {
+                    let (entry, methods) = pending[next].take().unwrap();
+                    next += 1;
+                    let encoded = match result {
+                        Ok(Some((buffer, encoded))) => {
+                            let write_result = self.align_pack_stream().and_then(|_| {
+                                self.output.write_all(&buffer).map_err(|e| {
+                                    Error::io_msg(e, format!("Encode entry:{}", entry.name()))
+                                })
+                            });
+                            *in_flight_bytes.lock().unwrap() -= buffer.len() as u64;
+                            written.notify_all();
+                            if let Err(e) = write_result {
+                                error = Some(e);
+                                break 'receive;
+                            }
+                            Some(encoded)
+                        }
+                        Ok(None) => None,
+                        Err(e) => {
+                            error = Some(e);
+                            break 'receive;
+                        }
+                    };
+                    self.add_encoded_entry(entry, methods, encoded);
+                }
+            }
+            if let Some(error) = error {
+                cancelled.store(true, Ordering::Relaxed);
+                // Hold the lock, so that no worker misses the notification between checking
+                // the flag and waiting.
+                let _in_flight = in_flight_bytes.lock().unwrap();
+                written.notify_all();
+                return Err(error);
+            }
+            Ok(())
+        })?;
+        Ok(self)
+    }
+
+    fn push_entry_with_methods<R: Read>(
+        &mut self,
+        entry: ArchiveEntry,
+        reader: Option<R>,
+        methods: Arc<Vec<EncoderConfiguration>>,
+    ) -> Result<&ArchiveEntry> {
+        self.check_entry_names([entry.name()])?;
+        let settings = self.encode_settings();
+        let encoded = match reader {
+            Some(mut reader) if !entry.is_directory && self.pack_stream_alignment > 1 => {
+                EncoderConfiguration::validate(&methods)?;
+                // Readers without any data get no stream, so the output is only padded once
+                // data was read.
+                let mut first = vec![0u8; self.read_buffer_size];
+                let n = reader
+                    .read(&mut first)
+                    .map_err(|e| Error::io_msg(e, format!("Encode entry:{}", entry.name())))?;
+                if n == 0 {
+                    None
+                } else {
+                    first.truncate(n);
+                    self.align_pack_stream()?;
+                    let reader = Cursor::new(first).chain(reader);
+                    encode_entry(reader, entry.name(), &methods, &mut self.output, settings)?
+                }
+            }
+            Some(reader) if !entry.is_directory => {
+                EncoderConfiguration::validate(&methods)?;
+                encode_entry(reader, entry.name(), &methods, &mut self.output, settings)?
+            }
+            _ => None,
+        };
+        Ok(self.add_encoded_entry(entry, methods, encoded))
+    }
+
+    fn encode_settings(&self) -> EncodeSettings {
+        EncodeSettings {
+            compute_crcs: self.compute_crcs,
+            read_buffer_size: self.read_buffer_size,
+            write_buffer_size: self.write_buffer_size,
+            #[cfg(feature = "sha256")]
+            store_sha256: self.store_sha256,
+            #[cfg(feature = "blake3-checksum")]
+            store_blake3_hash: self.store_blake3_hash,
+        }
+    }
+
+    /// Adds `entry` with the data that was written by [`encode_entry`], or as an
+    /// entry without data if `encoded` is `None`.
+    fn add_encoded_entry(
+        &mut self,
+        mut entry: ArchiveEntry,
+        methods: Arc<Vec<EncoderConfiguration>>,
+        encoded: Option<EncodedEntry>,
+    ) -> &ArchiveEntry {
+        entry.sha256 = None;
+        entry.blake3_hash = None;
+        match encoded {
+            Some(encoded) => {
                 entry.has_stream = true;
-                entry.size = size as u64;
-                entry.crc = crc as u64;
-                entry.has_crc = true;
-                entry.compressed_crc = compressed_crc as u64;
-                entry.compressed_size = compressed_len as u64;
+                entry.size = encoded.size;
+                entry.crc = encoded.crc.unwrap_or_default() as u64;
+                entry.has_crc = encoded.crc.is_some();
+                entry.compressed_crc = encoded.compressed_crc.unwrap_or_default() as u64;
+                entry.compressed_size = encoded.compressed_size;
+                #[cfg(feature = "sha256")]
+                {
+                    entry.sha256 = encoded.sha256;
+                }
+                #[cfg(feature = "blake3-checksum")]
+                {
+                    entry.blake3_hash = encoded.blake3_hash;
+                }
                 self.pack_info
-                    .add_stream(compressed_len as u64, compressed_crc);
+                    .add_stream(encoded.compressed_size, encoded.compressed_crc);
 
-                let mut sizes = Vec::with_capacity(more_sizes.len() + 1);
-                sizes.extend(more_sizes.iter().map(|s| s.get() as u64));
-                sizes.push(size as u64);
+                let mut sizes = encoded.more_sizes;
+                sizes.push(encoded.size);
+                self.unpack_info.add(methods, sizes, encoded.crc);
+            }
+            None => {
+                entry.has_stream = false;
+                entry.size = 0;
+                entry.compressed_size = 0;
+                entry.has_crc = false;
+            }
+        }
+        self.files.push(entry);
+        self.files.last().unwrap()
+    }
+
+    /// Returns the number of independently decodable LZMA2 chunks of the last written block.
+    ///
+    /// Returns `None` if no block was written yet or the last block isn't LZMA2 compressed.
+    /// Blocks with more than one chunk can be decoded using multiple threads, see
+    /// [`Lzma2Options::set_independent_chunk_size`](crate::encoder_options::Lzma2Options::set_independent_chunk_size).
+    pub fn last_block_chunk_count(&self) -> Option<u64> {
+        self.unpack_info.blocks.last()?.lzma2_chunk_count()
+    }
 
-                self.unpack_info
-                    .add(self.content_methods.clone(), sizes, crc);
+    /// Writes zeros up to the next multiple of the pack stream alignment. Returns the number
+    /// of bytes written.
+    fn write_alignment_gap(&mut self) -> std::io::Result<u64> {
+        if self.pack_stream_alignment <= 1 {
+            return Ok(0);
+        }
+        let position = self.output.stream_position()?;
+        let gap = position.next_multiple_of(self.pack_stream_alignment) - position;
+        std::io::copy(&mut std::io::repeat(0).take(gap), &mut self.output)?;
+        Ok(gap)
+    }
 
-                self.files.push(entry);
-                return Ok(self.files.last().unwrap());
+    /// Pads the output, so that the next packed stream starts aligned. The padding in front of
+    /// the first stream is skipped by the pack position, later padding is stored as a block
+    /// without entries.
+    fn align_pack_stream(&mut self) -> Result<()> {
+        let gap = self
+            .write_alignment_gap()
+            .map_err(|e| Error::io_msg(e, "Write alignment padding"))?;
+        match (gap, self.pack_info.len()) {
+            (0, _) => {}
+            (gap, 0) => self.pack_info.pos += gap,
+            (gap, _) => {
+                self.pack_info.add_stream(gap, None);
+                self.unpack_info.add_padding(gap);
             }
         }
-        entry.has_stream = false;
-        entry.size = 0;
-        entry.compressed_size = 0;
-        entry.has_crc = false;
+        Ok(())
+    }
+
+    /// Adds an archive `entry` with already compressed data.
+    ///
+    /// The `compressed` bytes are written verbatim to the archive and must be the output of
+    /// the single coder in `methods`, including matching coder properties (for example the
+    /// dictionary size of LZMA2). `uncompressed_size` and `crc32` are stored as given without
+    /// verification, so the caller is responsible for their correctness.
+    ///
+    /// # Arguments
+    /// * `entry` - The entry to add
+    /// * `compressed` - The compressed data of the entry
+    /// * `uncompressed_size` - The size of the entry data after decompression
+    /// * `crc32` - The CRC32 of the uncompressed entry data
+    /// * `methods` - The coder that produced `compressed`. Only a single coder is supported
+    pub fn push_raw_stream(
+        &mut self,
+        mut entry: ArchiveEntry,
+        compressed: &[u8],
+        uncompressed_size: u64,
+        crc32: u32,
+        methods: Vec<EncoderConfiguration>,
+    ) -> Result<&ArchiveEntry> {
+        if methods.len() != 1 {
+            return Err(Error::unsupported(
+                "Raw streams are only supported with exactly one coder",
+            ));
+        }
+        EncoderConfiguration::validate(&methods)?;
+        self.check_entry_names([entry.name()])?;
+
+        self.align_pack_stream()?;
+        self.output
+            .write_all(compressed)
+            .map_err(|e| Error::io_msg(e, format!("Write raw stream:{}", entry.name())))?;
+
+        let compressed_crc = crc32fast::hash(compressed);
+        entry.has_stream = true;
+        entry.is_directory = false;
+        entry.size = uncompressed_size;
+        entry.crc = crc32 as u64;
+        entry.has_crc = true;
+        entry.compressed_crc = compressed_crc as u64;
+        entry.compressed_size = compressed.len() as u64;
+        self.pack_info
+            .add_stream(compressed.len() as u64, Some(compressed_crc));
+        self.unpack_info
+            .add(Arc::new(methods), vec![uncompressed_size], Some(crc32));
+
         self.files.push(entry);
         Ok(self.files.last().unwrap())
     }
 
+    /// Copies a block of an existing archive without recompressing it.
+    ///
+    /// The `packed` bytes are written verbatim and the coder configuration of `block_meta`,
+    /// including coder properties and bind pairs, is duplicated into the new archive. This is
+    /// the "copy block" primitive that allows fast archive updates and transcoding tools.
+    ///
+    /// `entries` must be the entries with data streams of the block in their original order,
+    /// with sizes and CRCs as read from the source archive. Only blocks with a single packed
+    /// stream are supported.
+    ///
+    /// # Arguments
+    /// * `block_meta` - The block as read from the source archive
+    /// * `entries` - The entries stored in the block
+    /// * `packed` - The packed stream of the block, see [`Archive::pack_stream_ranges`]
+    pub fn push_raw_block(
+        &mut self,
+        block_meta: &Block,
+        entries: &[ArchiveEntry],
+        mut packed: impl Read,
+    ) -> Result<&mut Self> {
+        if block_meta.packed_streams.len() != 1 {
+            return Err(Error::unsupported(
+                "Raw blocks are only supported with exactly one packed stream",
+            ));
+        }
+        if entries.is_empty() || entries.iter().any(|entry| !entry.has_stream) {
+            return Err(Error::other(
+                "Raw block entries must all have a data stream",
+            ));
+        }
+        if entries.iter().any(|entry| !entry.has_crc) {
+            return Err(Error::other("Raw block entries must all have a CRC"));
+        }
+        let total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        if total_size != block_meta.get_unpack_size() {
+            return Err(Error::other(format!(
+                "Raw block entries have size {total_size}, but block unpacks to {}",
+                block_meta.get_unpack_size()
+            )));
+        }
+        self.check_entry_names(entries.iter().map(|entry| entry.name()))?;
+
+        self.align_pack_stream()?;
+        let mut compressed_len = 0;
+        let mut compressed =
+            CompressWrapWriter::new(&mut self.output, &mut compressed_len, self.compute_crcs);
+        std::io::copy(&mut packed, &mut compressed)
+            .map_err(|e| Error::io_msg(e, "Copy raw block"))?;
+        let compressed_crc = compressed.crc_value();
+
+        let sub_stream_sizes = entries.iter().map(|entry| entry.size).collect();
+        let sub_stream_crcs = entries.iter().map(|entry| entry.crc as u32).collect();
+        let crc = if block_meta.has_crc {
+            block_meta.crc as u32
+        } else if entries.len() == 1 {
+            entries[0].crc as u32
+        } else {
+            0
+        };
+
+        self.pack_info
+            .add_stream(compressed_len, self.compute_crcs.then_some(compressed_crc));
+        self.unpack_info
+            .add_raw(block_meta.clone(), crc, sub_stream_sizes, sub_stream_crcs);
+        for (i, entry) in entries.iter().enumerate() {
+            let mut entry = entry.clone();
+            (entry.compressed_size, entry.compressed_crc) = match i {
+                0 => (compressed_len, compressed_crc as u64),
+                _ => (0, 0),
+            };
+            self.files.push(entry);
+        }
+        Ok(self)
+    }
+
     /// Solid compression - packs `entries` into one pack.
     ///
+    /// With [`ArchiveWriter::set_auto_bcj`], the executables among `entries` are packed into a
+    /// separate block with the x86 BCJ filter, which follows the block of the other entries.
+    ///
     /// # Panics
     /// * If `entries`'s length not equals to `reader.reader_len()`
     pub fn push_archive_entries<R: Read>(
@@ -234,171 +1217,351 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         entries: Vec<ArchiveEntry>,
         reader: Vec<SourceReader<R>>,
     ) -> Result<&mut Self> {
-        let mut entries = entries;
+        assert_eq!(reader.len(), entries.len());
+        let methods = self.content_methods.clone();
+        let Some(bcj_methods) = self.auto_bcj_methods(&methods) else {
+            return self.push_entries_with_methods(entries, reader, methods);
+        };
+        // The names of both blocks are checked together, so that neither block is written if
+        // one of them has a duplicate name.
+        self.check_entry_names(entries.iter().map(|entry| entry.name()))?;
+        let (executables, others): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .zip(reader)
+            .partition(|(entry, _)| !entry.is_directory && is_executable(entry));
+        if !others.is_empty() || executables.is_empty() {
+            let (entries, reader) = others.into_iter().unzip();
+            self.push_checked_entries_with_methods(entries, reader, methods)?;
+        }
+        if !executables.is_empty() {
+            let (entries, reader) = executables.into_iter().unzip();
+            self.push_checked_entries_with_methods(entries, reader, bcj_methods)?;
+        }
+        Ok(self)
+    }
+
+    /// Solid compression - packs `entries` into one pack, compressed with `methods` instead of
+    /// the default content methods.
+    ///
+    /// This allows to use a different encoder chain for every solid block, for example to store
+    /// already compressed files with [`EncoderMethod::COPY`] and compress text with LZMA2.
+    ///
+    /// # Arguments
+    /// * `entries` - The entries to add
+    /// * `reader` - Readers providing the data of the entries
+    /// * `methods` - The encoder chain to compress the block with
+    ///
+    /// # Panics
+    /// * If `entries`'s length not equals to `reader.reader_len()`
+    pub fn push_solid_block_with_methods<R: Read>(
+        &mut self,
+        entries: Vec<ArchiveEntry>,
+        reader: Vec<SourceReader<R>>,
+        methods: Vec<EncoderConfiguration>,
+    ) -> Result<&mut Self> {
+        self.push_entries_with_methods(entries, reader, Arc::new(methods))
+    }
+
+    fn push_entries_with_methods<R: Read>(
+        &mut self,
+        entries: Vec<ArchiveEntry>,
+        reader: Vec<SourceReader<R>>,
+        content_methods: Arc<Vec<EncoderConfiguration>>,
+    ) -> Result<&mut Self> {
+        self.check_entry_names(entries.iter().map(|entry| entry.name()))?;
+        self.push_checked_entries_with_methods(entries, reader, content_methods)
+    }
+
+    /// Same as [`ArchiveWriter::push_entries_with_methods`], for entries whose names were
+    /// already checked.
+    fn push_checked_entries_with_methods<R: Read>(
+        &mut self,
+        entries: Vec<ArchiveEntry>,
+        reader: Vec<SourceReader<R>>,
+        content_methods: Arc<Vec<EncoderConfiguration>>,
+    ) -> Result<&mut Self> {
+        let (mut entries, mut reader) = match self.sort_entries {
+            true => sort_entries_for_compression(entries, reader),
+            false => (entries, reader),
+        };
+        assert_eq!(reader.len(), entries.len());
+        let limit = self.solid_block_file_count_limit;
+        if limit > 0 {
+            while entries.len() > limit {
+                let remaining_entries = entries.split_off(limit);
+                let remaining_reader = reader.split_off(limit);
+                self.push_solid_block(entries, reader, content_methods.clone())?;
+                entries = remaining_entries;
+                reader = remaining_reader;
+            }
+        }
+        self.push_solid_block(entries, reader, content_methods)
+    }
+
+    fn push_solid_block<R: Read>(
+        &mut self,
+        mut entries: Vec<ArchiveEntry>,
+        mut reader: Vec<SourceReader<R>>,
+        content_methods: Arc<Vec<EncoderConfiguration>>,
+    ) -> Result<&mut Self> {
+        for source in reader.iter_mut() {
+            source.set_compute_crc(self.compute_crcs);
+            #[cfg(feature = "sha256")]
+            source.set_compute_sha256(self.store_sha256);
+            #[cfg(feature = "blake3-checksum")]
+            source.set_compute_blake3(self.store_blake3_hash);
+        }
         let mut r = SeqReader::new(reader);
         assert_eq!(r.reader_len(), entries.len());
+        EncoderConfiguration::validate(&content_methods)?;
+
+        fn entries_names(entries: &[ArchiveEntry]) -> String {
+            let mut names = String::with_capacity(512);
+            for ele in entries.iter() {
+                names.push_str(&ele.name);
+                names.push(';');
+                if names.len() > 512 {
+                    break;
+                }
+            }
+            names
+        }
+
+        let mut buf = vec![0u8; self.read_buffer_size];
+        let read = |r: &mut SeqReader<SourceReader<R>>, buf: &mut [u8]| {
+            r.read(buf).map_err(|e| {
+                let name = entries
+                    .get(r.current_index())
+                    .map(|entry| entry.name.as_str())
+                    .unwrap_or_default();
+                Error::io_msg(e, format!("Read source:{name}"))
+            })
+        };
+
+        // If none of the readers yields any data, all entries are empty files and no block is
+        // written at all.
+        let mut n = read(&mut r, &mut buf)?;
+        if n == 0 {
+            for entry in entries.iter_mut() {
+                entry.has_stream = false;
+                entry.size = 0;
+                entry.compressed_size = 0;
+                entry.has_crc = false;
+                entry.sha256 = None;
+                entry.blake3_hash = None;
+            }
+            self.files.extend(entries);
+            return Ok(self);
+        }
+
+        self.align_pack_stream()?;
         let mut compressed_len = 0;
-        let mut compressed = CompressWrapWriter::new(&mut self.output, &mut compressed_len);
-        let content_methods = &self.content_methods;
-        let mut more_sizes: Vec<Rc<Cell<usize>>> = Vec::with_capacity(content_methods.len() - 1);
+        let mut compressed = CompressWrapWriter::new(
+            BufWriter::with_capacity(self.write_buffer_size, &mut self.output),
+            &mut compressed_len,
+            self.compute_crcs,
+        );
+        let mut more_sizes: Vec<Rc<Cell<u64>>> = Vec::with_capacity(content_methods.len() - 1);
 
         let (crc, size) = {
-            let mut w = Self::create_writer(content_methods, &mut compressed, &mut more_sizes)?;
+            let mut encoder = create_writer(&content_methods, &mut compressed, &mut more_sizes)?;
             let mut write_len = 0;
-            let mut w = CompressWrapWriter::new(&mut w, &mut write_len);
-            let mut buf = [0u8; 4096];
-
-            fn entries_names(entries: &[ArchiveEntry]) -> String {
-                let mut names = String::with_capacity(512);
-                for ele in entries.iter() {
-                    names.push_str(&ele.name);
-                    names.push(';');
-                    if names.len() > 512 {
-                        break;
-                    }
-                }
-                names
-            }
+            let mut w = CompressWrapWriter::new(&mut encoder, &mut write_len, self.compute_crcs);
 
-            loop {
-                match r.read(&mut buf) {
-                    Ok(n) => {
-                        if n == 0 {
-                            break;
-                        }
-                        w.write_all(&buf[..n]).map_err(|e| {
-                            Error::io_msg(e, format!("Encode entries:{}", entries_names(&entries)))
-                        })?;
-                    }
-                    Err(e) => {
-                        return Err(Error::io_msg(
-                            e,
-                            format!("Encode entries:{}", entries_names(&entries)),
-                        ));
-                    }
-                }
+            while n > 0 {
+                w.write_all(&buf[..n]).map_err(|e| {
+                    Error::io_msg(e, format!("Encode entries:{}", entries_names(&entries)))
+                })?;
+                n = read(&mut r, &mut buf)?;
             }
             w.flush().map_err(|e| {
-                let mut names = String::with_capacity(512);
-                for ele in entries.iter() {
-                    names.push_str(&ele.name);
-                    names.push(';');
-                    if names.len() > 512 {
-                        break;
-                    }
-                }
-                Error::io_msg(e, format!("Encode entry:{names}"))
+                Error::io_msg(e, format!("Encode entry:{}", entries_names(&entries)))
             })?;
-            w.write(&[]).map_err(|e| {
+            let crc = w.crc_value();
+            drop(w);
+            encoder.finish().map_err(|e| {
                 Error::io_msg(e, format!("Encode entry:{}", entries_names(&entries)))
             })?;
 
-            (w.crc_value(), write_len)
+            (crc, write_len)
         };
+        compressed
+            .flush()
+            .map_err(|e| Error::io_msg(e, format!("Encode entries:{}", entries_names(&entries))))?;
         let compressed_crc = compressed.crc_value();
+        drop(compressed);
+
+        // Entries without any data are stored as empty files. They keep their position, the
+        // sub-streams of the block are assigned to the entries with data in order.
+        let mut block_entries = Vec::with_capacity(entries.len());
         let mut sub_stream_crcs = Vec::with_capacity(entries.len());
         let mut sub_stream_sizes = Vec::with_capacity(entries.len());
-        for i in 0..entries.len() {
-            let entry = &mut entries[i];
-            let ri = &r[i];
+        for (mut entry, ri) in entries.into_iter().zip(r.iter()) {
+            entry.size = ri.read_count();
+            entry.compressed_size = 0;
+            entry.compressed_crc = 0;
+            entry.sha256 = None;
+            entry.blake3_hash = None;
+            if entry.size == 0 {
+                entry.has_stream = false;
+                entry.has_crc = false;
+                block_entries.push(entry);
+                continue;
+            }
+            entry.has_stream = true;
             entry.crc = ri.crc_value() as u64;
-            entry.size = ri.read_count() as u64;
+            entry.has_crc = self.compute_crcs;
+            #[cfg(feature = "sha256")]
+            {
+                entry.sha256 = ri.sha256_value();
+            }
+            #[cfg(feature = "blake3-checksum")]
+            {
+                entry.blake3_hash = ri.blake3_value();
+            }
             sub_stream_crcs.push(entry.crc as u32);
             sub_stream_sizes.push(entry.size);
-            entry.has_crc = true;
+            block_entries.push(entry);
+        }
+        // Codecs buffer data internally, so the packed size can't be split between the entries
+        // of a block. Like the reader, attribute it to the first entry.
+        if let Some(first) = block_entries.iter_mut().find(|entry| entry.has_stream) {
+            first.compressed_size = compressed_len;
+            first.compressed_crc = compressed_crc as u64;
         }
 
         self.pack_info
-            .add_stream(compressed_len as u64, compressed_crc);
+            .add_stream(compressed_len, self.compute_crcs.then_some(compressed_crc));
 
         let mut sizes = Vec::with_capacity(more_sizes.len() + 1);
-        sizes.extend(more_sizes.iter().map(|s| s.get() as u64));
-        sizes.push(size as u64);
+        sizes.extend(more_sizes.iter().map(|s| s.get()));
+        sizes.push(size);
 
         self.unpack_info.add_multiple(
-            content_methods.clone(),
+            content_methods,
             sizes,
-            crc,
-            entries.len() as u64,
+            self.compute_crcs.then_some(crc),
+            sub_stream_sizes.len() as u64,
             sub_stream_sizes,
             sub_stream_crcs,
         );
 
-        self.files.extend(entries);
+        self.files.extend(block_entries);
         Ok(self)
     }
 
-    fn create_writer<'a, O: Write + 'a>(
-        methods: &[EncoderConfiguration],
-        out: O,
-        more_sized: &mut Vec<Rc<Cell<usize>>>,
-    ) -> Result<Box<dyn Write + 'a>> {
-        let mut encoder: Box<dyn Write> = Box::new(out);
-        let mut first = true;
-        for mc in methods.iter() {
-            if !first {
-                let counting = CountingWriter::new(encoder);
-                more_sized.push(counting.counting());
-                encoder = Box::new(encoder::add_encoder(counting, mc)?);
-            } else {
-                let counting = CountingWriter::new(encoder);
-                encoder = Box::new(encoder::add_encoder(counting, mc)?);
-            }
-            first = false;
-        }
-        Ok(encoder)
+    /// Finishes the compression.
+    ///
+    /// Shorthand for [`ArchiveWriter::finish_with_stats`] without the statistics.
+    pub fn finish(self) -> std::io::Result<W> {
+        self.write_end().map(|(output, _)| output)
     }
 
-    /// Finishes the compression.
-    pub fn finish(mut self) -> std::io::Result<W> {
+    /// Finishes the compression and returns statistics about the written archive.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut writer = ArchiveWriter::create("path/to/dest.7z").unwrap();
+    /// let source = std::fs::File::open("path/to/file.txt").unwrap();
+    /// writer
+    ///     .push_archive_entry(ArchiveEntry::new_file("file.txt"), Some(source))
+    ///     .unwrap();
+    /// let (_, stats) = writer.finish_with_stats().unwrap();
+    /// println!(
+    ///     "{} files compressed to {:.0}% in {:?}",
+    ///     stats.total_files,
+    ///     stats.compression_ratio() * 100.0,
+    ///     stats.elapsed
+    /// );
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn finish_with_stats(self) -> std::io::Result<(W, FinishStats)> {
+        let total_dirs = self.files.iter().filter(|entry| entry.is_directory).count();
+        let stats = FinishStats {
+            total_files: self.files.len() - total_dirs,
+            total_dirs,
+            total_uncompressed_bytes: self.files.iter().map(|entry| entry.size).sum(),
+            total_compressed_bytes: self.pack_info.sizes.iter().sum(),
+            elapsed: self
+                .started
+                .map(|started| started.elapsed())
+                .unwrap_or_default(),
+        };
+        let (output, _) = self.write_end()?;
+        Ok((output, stats))
+    }
+
+    /// Finishes the archive with the entries that were pushed successfully, dropping the data
+    /// of a push that failed or panicked part way.
+    ///
+    /// The header is written directly after the last complete packed stream, so the archive
+    /// is consistent again. Returns the output and the length of the archive including the
+    /// prefix. Data that was written after that position stays in the output, a file can be
+    /// truncated with [`File::set_len`](std::fs::File::set_len).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sevenz_rust2::*;
+    ///
+    /// let mut writer = ArchiveWriter::create("path/to/dest.7z").unwrap();
+    /// let source = std::fs::File::open("path/to/file.txt").unwrap();
+    /// let result = writer.push_archive_entry(ArchiveEntry::new_file("file.txt"), Some(source));
+    /// if result.is_err() {
+    ///     let (file, len) = writer.abort().unwrap();
+    ///     file.set_len(len).unwrap();
+    /// }
+    /// ```
+    pub fn abort(mut self) -> std::io::Result<(W, u64)> {
+        let streams_end = self.prefix_len
+            + SIGNATURE_HEADER_SIZE
+            + self.pack_info.pos
+            + self.pack_info.sizes.iter().sum::<u64>();
+        self.output.seek(std::io::SeekFrom::Start(streams_end))?;
+        self.write_end()
+    }
+
+    /// Writes the header and the start header. Returns the output and the length of the
+    /// archive including the prefix.
+    fn write_end(mut self) -> std::io::Result<(W, u64)> {
+        self.count_entry_names().map_err(std::io::Error::other)?;
         let mut header: Vec<u8> = Vec::with_capacity(64 * 1024);
         self.write_encoded_header(&mut header)?;
+        self.write_alignment_gap()?;
         let header_pos = self.output.stream_position()?;
         self.output.write_all(&header)?;
-        let crc32 = crc32fast::hash(&header);
-        let mut hh = [0u8; SIGNATURE_HEADER_SIZE as usize];
-        {
-            let mut hhw = hh.as_mut_slice();
-            //sig
-            hhw.write_all(SEVEN_Z_SIGNATURE)?;
-            //version
-            hhw.write_u8(0)?;
-            hhw.write_u8(4)?;
-            //placeholder for crc: index = 8
-            hhw.write_u32(0)?;
-
-            // start header
-            hhw.write_u64(header_pos - SIGNATURE_HEADER_SIZE)?;
-            hhw.write_u64(0xFFFFFFFF & header.len() as u64)?;
-            hhw.write_u32(crc32)?;
-        }
-        let crc32 = crc32fast::hash(&hh[12..]);
-        hh[8..12].copy_from_slice(&crc32.to_le_bytes());
-
-        self.output.seek(std::io::SeekFrom::Start(0))?;
-        self.output.write_all(&hh)?;
+        let signature_header = signature_header(
+            header_pos - self.prefix_len - SIGNATURE_HEADER_SIZE,
+            &header,
+        )?;
+
+        self.output
+            .seek(std::io::SeekFrom::Start(self.prefix_len))?;
+        self.output.write_all(&signature_header)?;
         self.output.flush()?;
-        Ok(self.output)
+        Ok((self.output, header_pos + header.len() as u64))
     }
 
-    fn write_header<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
+    /// Writes the header. If `aligned` is set, the properties of the files are aligned with
+    /// dummy properties, which is only useful if the header is not compressed.
+    fn write_header(&mut self, header: &mut Vec<u8>, aligned: bool) -> std::io::Result<()> {
         header.write_u8(K_HEADER)?;
+        self.write_archive_properties(header)?;
         header.write_u8(K_MAIN_STREAMS_INFO)?;
         self.write_streams_info(header)?;
-        self.write_files_info(header)?;
+        self.write_files_info(header, aligned)?;
         header.write_u8(K_END)?;
         Ok(())
     }
 
-    fn write_encoded_header<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
+    fn write_encoded_header(&mut self, header: &mut Vec<u8>) -> std::io::Result<()> {
+        // Aligning the properties is pointless if the header is compressed, so the header is
+        // only written with alignment if it is stored as-is.
         let mut raw_header = Vec::with_capacity(64 * 1024);
-        self.write_header(&mut raw_header)?;
+        self.write_header(&mut raw_header, false)?;
         let mut pack_info = PackInfo::default();
 
-        let position = self.output.stream_position()?;
-        let pos = position - SIGNATURE_HEADER_SIZE;
-        pack_info.pos = pos;
-
         let mut more_sizes = vec![];
         let size = raw_header.len() as u64;
         let crc32 = crc32fast::hash(&raw_header);
@@ -416,44 +1579,55 @@ impl<W: Write + Seek> ArchiveWriter<W> {
             }
         }
 
+        if !must_encrypt_header && self.header_compression == HeaderCompression::Never {
+            return self.write_header(header, true);
+        }
+
         methods.push(EncoderConfiguration::new(EncoderMethod::LZMA));
 
         let methods = Arc::new(methods);
 
+        // The header is encoded into a buffer first, so that nothing is written to the output
+        // if the raw header is used instead.
         let mut encoded_data = Vec::with_capacity(size as usize / 2);
 
         let mut compress_size = 0;
-        let mut compressed = CompressWrapWriter::new(&mut encoded_data, &mut compress_size);
+        let mut compressed = CompressWrapWriter::new(&mut encoded_data, &mut compress_size, true);
         {
-            let mut encoder = Self::create_writer(&methods, &mut compressed, &mut more_sizes)
+            let mut encoder = create_writer(&methods, &mut compressed, &mut more_sizes)
                 .map_err(std::io::Error::other)?;
             encoder.write_all(&raw_header)?;
             encoder.flush()?;
-            let _ = encoder.write(&[])?;
+            encoder.finish()?;
         }
 
         let compress_crc = compressed.crc_value();
         let compress_size = *compressed.bytes_written;
 
-        if !must_encrypt_header && compress_size as u64 + 20 >= size {
+        if !must_encrypt_header
+            && self.header_compression == HeaderCompression::Auto
+            && compress_size + 20 >= size
+        {
             // We have an unencrypted header and the compression made increased the data size,
             // so we write the raw header data without compressing it to save space.
-            header.write_all(&raw_header)?;
-            return Ok(());
+            return self.write_header(header, true);
         }
-        self.output.write_all(&encoded_data[..compress_size])?;
+        self.write_alignment_gap()?;
+        let position = self.output.stream_position()?;
+        pack_info.pos = position - self.prefix_len - SIGNATURE_HEADER_SIZE;
+        self.output.write_all(&encoded_data)?;
 
-        pack_info.add_stream(compress_size as u64, compress_crc);
+        pack_info.add_stream(compress_size, Some(compress_crc));
 
         let mut unpack_info = UnpackInfo::default();
         let mut sizes = Vec::with_capacity(1 + more_sizes.len());
-        sizes.extend(more_sizes.iter().map(|s| s.get() as u64));
+        sizes.extend(more_sizes.iter().map(|s| s.get()));
         sizes.push(size);
-        unpack_info.add(methods, sizes, crc32);
+        unpack_info.add(methods, sizes, Some(crc32));
 
         header.write_u8(K_ENCODED_HEADER)?;
 
-        pack_info.write_to(header)?;
+        pack_info.write_to(header, self.store_pack_crcs)?;
         unpack_info.write_to(header)?;
         unpack_info.write_substreams(header)?;
 
@@ -464,7 +1638,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
 
     fn write_streams_info<H: Write>(&mut self, header: &mut H) -> std::io::Result<()> {
         if self.pack_info.len() > 0 {
-            self.pack_info.write_to(header)?;
+            self.pack_info.write_to(header, self.store_pack_crcs)?;
             self.unpack_info.write_to(header)?;
         }
         self.unpack_info.write_substreams(header)?;
@@ -473,17 +1647,37 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         Ok(())
     }
 
-    fn write_files_info<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
+    fn write_archive_properties(&self, header: &mut Vec<u8>) -> std::io::Result<()> {
+        let Some(comment) = &self.comment else {
+            return Ok(());
+        };
+        header.write_u8(K_ARCHIVE_PROPERTIES)?;
+        header.write_u8(K_COMMENT)?;
+        let mut temp: Vec<u8> = Vec::with_capacity(comment.len() * 2 + 2);
+        for c in comment.encode_utf16() {
+            temp.write_all(&c.to_le_bytes())?;
+        }
+        temp.write_all(&[0u8; 2])?;
+        write_u64(header, temp.len() as u64)?;
+        header.write_all(&temp)?;
+        header.write_u8(K_END)?;
+        Ok(())
+    }
+
+    fn write_files_info(&self, header: &mut Vec<u8>, aligned: bool) -> std::io::Result<()> {
         header.write_u8(K_FILES_INFO)?;
         write_u64(header, self.files.len() as u64)?;
         self.write_file_empty_streams(header)?;
         self.write_file_empty_files(header)?;
         self.write_file_anti_items(header)?;
-        self.write_file_names(header)?;
-        self.write_file_ctimes(header)?;
-        self.write_file_atimes(header)?;
-        self.write_file_mtimes(header)?;
-        self.write_file_windows_attrs(header)?;
+        self.write_file_names(header, aligned)?;
+        self.write_file_comments(header)?;
+        self.write_file_ctimes(header, aligned)?;
+        self.write_file_atimes(header, aligned)?;
+        self.write_file_mtimes(header, aligned)?;
+        self.write_file_windows_attrs(header, aligned)?;
+        self.write_file_digests(header, K_SHA256, |file| file.sha256)?;
+        self.write_file_digests(header, K_BLAKE3_HASH, |file| file.blake3_hash)?;
         header.write_u8(K_END)?;
         Ok(())
     }
@@ -513,23 +1707,19 @@ impl<W: Write + Seek> ArchiveWriter<W> {
     }
 
     fn write_file_empty_files<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
-        let mut has_empty = false;
-        let mut empty_stream_counter = 0;
-        let mut bitset = BitSet::new();
-        for entry in self.files.iter() {
-            if !entry.has_stream {
-                let is_dir = entry.is_directory();
-                has_empty |= !is_dir;
-                if !is_dir {
-                    bitset.insert(empty_stream_counter);
-                }
-                empty_stream_counter += 1;
+        let empty_stream_count = self.files.iter().filter(|entry| !entry.has_stream).count();
+        // The bit set must cover all empty streams, even if the trailing bits are not set.
+        let mut bitset = BitSet::with_capacity(empty_stream_count);
+        let empty_streams = self.files.iter().filter(|entry| !entry.has_stream);
+        for (i, entry) in empty_streams.enumerate() {
+            if !entry.is_directory() {
+                bitset.insert(i);
             }
         }
-        if has_empty {
+        if bitset.len() > 0 {
             header.write_u8(K_EMPTY_FILE)?;
 
-            let mut temp: Vec<u8> = Vec::with_capacity(bitset.len() / 8 + 1);
+            let mut temp: Vec<u8> = Vec::with_capacity(empty_stream_count / 8 + 1);
             write_bit_set(&mut temp, &bitset)?;
             write_u64(header, temp.len() as u64)?;
             header.write_all(&temp)?;
@@ -538,23 +1728,19 @@ impl<W: Write + Seek> ArchiveWriter<W> {
     }
 
     fn write_file_anti_items<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
-        let mut has_anti = false;
-        let mut counter = 0;
-        let mut bitset = BitSet::new();
-        for entry in self.files.iter() {
-            if !entry.has_stream {
-                let is_anti = entry.is_anti_item();
-                has_anti |= !is_anti;
-                if !is_anti {
-                    bitset.insert(counter);
-                }
-                counter += 1;
+        let empty_stream_count = self.files.iter().filter(|entry| !entry.has_stream).count();
+        // The bit set must cover all empty streams, even if the trailing bits are not set.
+        let mut bitset = BitSet::with_capacity(empty_stream_count);
+        let empty_streams = self.files.iter().filter(|entry| !entry.has_stream);
+        for (i, entry) in empty_streams.enumerate() {
+            if entry.is_anti_item() {
+                bitset.insert(i);
             }
         }
-        if has_anti {
+        if bitset.len() > 0 {
             header.write_u8(K_ANTI)?;
 
-            let mut temp: Vec<u8> = Vec::with_capacity(bitset.len() / 8 + 1);
+            let mut temp: Vec<u8> = Vec::with_capacity(empty_stream_count / 8 + 1);
             write_bit_set(&mut temp, &bitset)?;
             write_u64(header, temp.len() as u64)?;
             header.write_all(temp.as_slice())?;
@@ -562,8 +1748,7 @@ impl<W: Write + Seek> ArchiveWriter<W> {
         Ok(())
     }
 
-    fn write_file_names<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
-        header.write_u8(K_NAME)?;
+    fn write_file_names(&self, header: &mut Vec<u8>, aligned: bool) -> std::io::Result<()> {
         let mut temp: Vec<u8> = Vec::with_capacity(128);
         let out = &mut temp;
         out.write_u8(0)?;
@@ -574,27 +1759,126 @@ impl<W: Write + Seek> ArchiveWriter<W> {
             }
             out.write_all(&[0u8; 2])?;
         }
+        // The names follow the ID, the size and the external flag.
+        self.write_alignment_dummy(header, aligned, 2 + encoded_u64_len(temp.len() as u64), 16)?;
+        header.write_u8(K_NAME)?;
         write_u64(header, temp.len() as u64)?;
         header.write_all(temp.as_slice())?;
         Ok(())
     }
 
+    /// Writes a dummy property, so that the data `data_offset` bytes after the ID of the next
+    /// property is aligned to `alignment` bytes within the header. Only done if the pack
+    /// streams are aligned, which aligns the header as well, and the header is `aligned`.
+    fn write_alignment_dummy(
+        &self,
+        header: &mut Vec<u8>,
+        aligned: bool,
+        data_offset: usize,
+        alignment: usize,
+    ) -> std::io::Result<()> {
+        if !aligned || self.pack_stream_alignment <= 1 || alignment <= 1 {
+            return Ok(());
+        }
+        let misalignment = (header.len() + data_offset) % alignment;
+        if misalignment == 0 {
+            return Ok(());
+        }
+        // The dummy property needs at least two bytes for its ID and size.
+        let mut padding = alignment - misalignment;
+        if padding < 2 {
+            padding += alignment;
+        }
+        header.write_u8(K_DUMMY)?;
+        write_u64(header, padding as u64 - 2)?;
+        header.resize(header.len() + padding - 2, 0);
+        Ok(())
+    }
+
+    fn write_file_comments<H: Write>(&self, header: &mut H) -> std::io::Result<()> {
+        if self.files.iter().all(|file| file.comment().is_none()) {
+            return Ok(());
+        }
+        header.write_u8(K_FILE_COMMENT)?;
+        let mut temp: Vec<u8> = Vec::with_capacity(128);
+        let out = &mut temp;
+        out.write_u8(0)?;
+        for file in self.files.iter() {
+            // Files without a comment are written with an empty one.
+            for c in file.comment().unwrap_or_default().encode_utf16() {
+                let buf = c.to_le_bytes();
+                out.write_all(&buf)?;
+            }
+            out.write_all(&[0u8; 2])?;
+        }
+        write_u64(header, temp.len() as u64)?;
+        header.write_all(temp.as_slice())?;
+        Ok(())
+    }
+
+    /// Writes the 32 byte digests that `digest` returns for the files as property `id`.
+    fn write_file_digests<H: Write>(
+        &self,
+        header: &mut H,
+        id: u8,
+        digest: impl Fn(&ArchiveEntry) -> Option<[u8; 32]>,
+    ) -> std::io::Result<()> {
+        let num = self
+            .files
+            .iter()
+            .filter(|file| digest(file).is_some())
+            .count();
+        if num == 0 {
+            return Ok(());
+        }
+        header.write_u8(id)?;
+        let mut temp: Vec<u8> = Vec::with_capacity(1 + num * 32);
+        if num != self.files.len() {
+            temp.write_u8(0)?;
+            let mut defined = BitSet::with_capacity(self.files.len());
+            for (i, file) in self.files.iter().enumerate() {
+                if digest(file).is_some() {
+                    defined.insert(i);
+                }
+            }
+            write_bit_set(&mut temp, &defined)?;
+        } else {
+            temp.write_u8(1)?;
+        }
+        temp.write_u8(0)?;
+        for digest in self.files.iter().filter_map(digest) {
+            temp.write_all(&digest)?;
+        }
+        write_u64(header, temp.len() as u64)?;
+        header.write_all(&temp)?;
+        Ok(())
+    }
+
     write_times!(
         write_file_ctimes,
         K_C_TIME,
+        ctime,
         has_creation_date,
         creation_date
     );
-    write_times!(write_file_atimes, K_A_TIME, has_access_date, access_date);
+    write_times!(
+        write_file_atimes,
+        K_A_TIME,
+        atime,
+        has_access_date,
+        access_date
+    );
     write_times!(
         write_file_mtimes,
         K_M_TIME,
+        mtime,
         has_last_modified_date,
         last_modified_date
     );
     write_times!(
         write_file_windows_attrs,
         K_WIN_ATTRIBUTES,
+        win_attributes,
         has_windows_attributes,
         windows_attributes,
         write_u32
@@ -602,11 +1886,65 @@ impl<W: Write + Seek> ArchiveWriter<W> {
 }
 
 impl<W: Write + Seek> AutoFinish for ArchiveWriter<W> {
-    fn finish_ignore_error(self) {
-        let _ = self.finish();
+    fn finish_on_drop(self) -> std::io::Result<()> {
+        self.finish().map(|_| ())
     }
 }
 
+/// Returns the signature header that points to `header`, written `next_header_offset` bytes
+/// after the signature header.
+fn signature_header(
+    next_header_offset: u64,
+    header: &[u8],
+) -> std::io::Result<[u8; SIGNATURE_HEADER_SIZE as usize]> {
+    let mut hh = [0u8; SIGNATURE_HEADER_SIZE as usize];
+    {
+        let mut hhw = hh.as_mut_slice();
+        //sig
+        hhw.write_all(SEVEN_Z_SIGNATURE)?;
+        //version
+        hhw.write_u8(0)?;
+        hhw.write_u8(4)?;
+        //placeholder for crc: index = 8
+        hhw.write_u32(0)?;
+
+        // start header
+        hhw.write_u64(next_header_offset)?;
+        hhw.write_u64(header.len() as u64)?;
+        hhw.write_u32(crc32fast::hash(header))?;
+    }
+    let crc32 = crc32fast::hash(&hh[12..]);
+    hh[8..12].copy_from_slice(&crc32.to_le_bytes());
+    Ok(hh)
+}
+
+/// Sorts entries and their readers by `(extension, name)`, the extension compared in lowercase.
+fn sort_entries_for_compression<R: Read>(
+    entries: Vec<ArchiveEntry>,
+    reader: Vec<SourceReader<R>>,
+) -> (Vec<ArchiveEntry>, Vec<SourceReader<R>>) {
+    assert_eq!(reader.len(), entries.len());
+
+    let mut pairs: Vec<_> = entries.into_iter().zip(reader).collect();
+    pairs.sort_by_cached_key(|(entry, _)| {
+        let extension = entry.extension().unwrap_or_default().to_ascii_lowercase();
+        (extension, entry.name().to_string())
+    });
+    pairs.into_iter().unzip()
+}
+
+/// Extensions of the executables and libraries that 7-Zip compresses with the x86 BCJ filter.
+const EXECUTABLE_EXTENSIONS: [&str; 5] = ["dll", "exe", "ocx", "sfx", "sys"];
+
+/// Returns whether `entry` is named like an executable, see [`ArchiveWriter::set_auto_bcj`].
+fn is_executable(entry: &ArchiveEntry) -> bool {
+    entry.extension().is_some_and(|extension| {
+        EXECUTABLE_EXTENSIONS
+            .iter()
+            .any(|exe| extension.eq_ignore_ascii_case(exe))
+    })
+}
+
 pub(crate) fn write_u64<W: Write>(header: &mut W, mut value: u64) -> std::io::Result<()> {
     let mut first = 0;
     let mut mask = 0x80;
@@ -629,26 +1967,146 @@ pub(crate) fn write_u64<W: Write>(header: &mut W, mut value: u64) -> std::io::Re
     Ok(())
 }
 
+/// Returns the number of bytes [`write_u64`] writes for `value`.
+fn encoded_u64_len(value: u64) -> usize {
+    (0..8)
+        .find(|&i| value < 1u64 << (7 * (i + 1)))
+        .map_or(9, |i| i + 1)
+}
+
+/// Compresses the data of a single entry with `methods` into `output`. Returns `None` if the
+/// reader yields no data.
+fn encode_entry<R: Read, O: Write>(
+    reader: R,
+    name: &str,
+    methods: &[EncoderConfiguration],
+    output: O,
+    settings: EncodeSettings,
+) -> Result<Option<EncodedEntry>> {
+    // The CRC is computed while compressing, the reader only computes the digest.
+    let mut r = SourceReader::new(reader);
+    r.set_compute_crc(false);
+    #[cfg(feature = "sha256")]
+    r.set_compute_sha256(settings.store_sha256);
+    #[cfg(feature = "blake3-checksum")]
+    r.set_compute_blake3(settings.store_blake3_hash);
+    let mut buf = vec![0u8; settings.read_buffer_size];
+    let encode_error = |e| Error::io_msg(e, format!("Encode entry:{name}"));
+
+    // Readers without any data are stored as empty files, without a stream.
+    let mut n = r.read(&mut buf).map_err(encode_error)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    let mut compressed_size = 0;
+    let mut compressed = CompressWrapWriter::new(
+        BufWriter::with_capacity(settings.write_buffer_size, output),
+        &mut compressed_size,
+        settings.compute_crcs,
+    );
+
+    let mut more_sizes: Vec<Rc<Cell<u64>>> = Vec::with_capacity(methods.len() - 1);
+
+    let (crc, size) = {
+        let mut encoder = create_writer(methods, &mut compressed, &mut more_sizes)?;
+        let mut write_len = 0;
+        let mut w = CompressWrapWriter::new(&mut encoder, &mut write_len, settings.compute_crcs);
+        while n > 0 {
+            w.write_all(&buf[..n]).map_err(encode_error)?;
+            n = r.read(&mut buf).map_err(encode_error)?;
+        }
+        w.flush().map_err(encode_error)?;
+        let crc = w.crc_value();
+        drop(w);
+        encoder.finish().map_err(encode_error)?;
+
+        (crc, write_len)
+    };
+    compressed.flush().map_err(encode_error)?;
+    let compressed_crc = compressed.crc_value();
+    Ok(Some(EncodedEntry {
+        size,
+        crc: settings.compute_crcs.then_some(crc),
+        compressed_size,
+        compressed_crc: settings.compute_crcs.then_some(compressed_crc),
+        more_sizes: more_sizes.iter().map(|s| s.get()).collect(),
+        #[cfg(feature = "sha256")]
+        sha256: r.sha256_value(),
+        #[cfg(feature = "blake3-checksum")]
+        blake3_hash: r.blake3_value(),
+    }))
+}
+
+fn create_writer<'a, O: Write + 'a>(
+    methods: &[EncoderConfiguration],
+    out: &'a mut O,
+    more_sized: &mut Vec<Rc<Cell<u64>>>,
+) -> Result<Box<dyn FinishableWrite + 'a>> {
+    let mut encoder: Box<dyn FinishableWrite + 'a> = Box::new(out);
+    let mut first = true;
+    for mc in methods.iter() {
+        if !first {
+            let counting = CountingWriter::new(encoder);
+            more_sized.push(counting.counting());
+            encoder = Box::new(encoder::add_encoder(counting, mc)?);
+        } else {
+            let counting = CountingWriter::new(encoder);
+            encoder = Box::new(encoder::add_encoder(counting, mc)?);
+        }
+        first = false;
+    }
+    Ok(encoder)
+}
+
+/// Settings of the writer that are needed to compress an entry on another thread.
+#[derive(Clone, Copy)]
+struct EncodeSettings {
+    compute_crcs: bool,
+    read_buffer_size: usize,
+    write_buffer_size: usize,
+    #[cfg(feature = "sha256")]
+    store_sha256: bool,
+    #[cfg(feature = "blake3-checksum")]
+    store_blake3_hash: bool,
+}
+
+/// Sizes and checksums of an entry compressed by [`encode_entry`].
+struct EncodedEntry {
+    size: u64,
+    crc: Option<u32>,
+    compressed_size: u64,
+    compressed_crc: Option<u32>,
+    /// Sizes of the intermediate streams between the coders.
+    more_sizes: Vec<u64>,
+    #[cfg(feature = "sha256")]
+    sha256: Option<[u8; 32]>,
+    #[cfg(feature = "blake3-checksum")]
+    blake3_hash: Option<[u8; 32]>,
+}
+
 struct CompressWrapWriter<'a, W> {
     writer: W,
-    crc: Hasher,
+    crc: Option<Hasher>,
     cache: Vec<u8>,
-    bytes_written: &'a mut usize,
+    bytes_written: &'a mut u64,
 }
 
 impl<'a, W: Write> CompressWrapWriter<'a, W> {
-    pub fn new(writer: W, bytes_written: &'a mut usize) -> Self {
+    pub fn new(writer: W, bytes_written: &'a mut u64, compute_crc: bool) -> Self {
         Self {
             writer,
-            crc: Hasher::new(),
+            crc: compute_crc.then(Hasher::new),
             cache: Vec::with_capacity(8192),
             bytes_written,
         }
     }
 
+    /// Returns the CRC of the written data, or 0 if no CRC is computed.
     pub fn crc_value(&mut self) -> u32 {
-        let crc = std::mem::replace(&mut self.crc, Hasher::new());
-        crc.finalize()
+        match self.crc.as_mut() {
+            Some(crc) => std::mem::replace(crc, Hasher::new()).finalize(),
+            None => 0,
+        }
     }
 }
 
@@ -656,8 +2114,10 @@ impl<W: Write> Write for CompressWrapWriter<'_, W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         self.cache.resize(buf.len(), Default::default());
         let len = self.writer.write(buf)?;
-        self.crc.update(&buf[..len]);
-        *self.bytes_written += len;
+        if let Some(crc) = self.crc.as_mut() {
+            crc.update(&buf[..len]);
+        }
+        *self.bytes_written += len as u64;
         Ok(len)
     }
 
@@ -0,0 +1,167 @@
+use std::{
+    io::{Cursor, Read, Seek, Write},
+    sync::Arc,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use super::{ArchiveWriter, EncodeSettings, EncodedEntry, Result, encode_entry};
+use crate::{ArchiveEntry, EncoderConfiguration, Error};
+
+/// Number of chunks read from the asynchronous reader that may wait for the encoder thread.
+const PENDING_CHUNKS: usize = 4;
+
+impl<W: Write + Seek> ArchiveWriter<W> {
+    /// Non-solid compression - Adds an archive `entry` with data from the asynchronous `reader`.
+    ///
+    /// Works like [`ArchiveWriter::push_archive_entry`], but the data is read from a
+    /// [`tokio::io::AsyncRead`], like the body of an HTTP response, without buffering it
+    /// completely. The data is compressed on the blocking thread pool of tokio, so the future
+    /// must run inside a tokio runtime. The compressed data is written to the output
+    /// synchronously, so the output should be fast to write to, like a file or memory.
+    ///
+    /// The returned future is `Send` if `W` and `R` are, so it can be spawned on a
+    /// multi-threaded runtime.
+    ///
+    /// # Arguments
+    /// * `entry` - The entry to add
+    /// * `reader` - Asynchronous reader providing the data of the entry
+    pub async fn push_archive_entry_async<R: AsyncRead + Unpin>(
+        &mut self,
+        entry: ArchiveEntry,
+        reader: Option<R>,
+    ) -> Result<&ArchiveEntry> {
+        let methods = self.entry_methods(&entry, reader.is_some());
+        self.check_entry_names([entry.name()])?;
+        let encoded = match reader {
+            Some(mut reader) if !entry.is_directory => {
+                EncoderConfiguration::validate(&methods)?;
+                // Readers without any data get no stream, so the output is only padded once
+                // data was read.
+                let mut chunk = Vec::with_capacity(self.read_buffer_size);
+                reader
+                    .read_buf(&mut chunk)
+                    .await
+                    .map_err(|e| Error::io_msg(e, format!("Encode entry:{}", entry.name())))?;
+                if chunk.is_empty() {
+                    None
+                } else {
+                    self.align_pack_stream()?;
+                    self.encode_entry_async(reader, chunk, entry.name(), methods.clone())
+                        .await?
+                }
+            }
+            _ => None,
+        };
+        Ok(self.add_encoded_entry(entry, methods, encoded))
+    }
+
+    /// Compresses `chunk` and the rest of the data of `reader` with `methods` into the output.
+    ///
+    /// The chunks are sent to [`encode_entry`] on a blocking task, which sends back the
+    /// compressed data. Only the channels are held across `await` points.
+    async fn encode_entry_async<R: AsyncRead + Unpin>(
+        &mut self,
+        mut reader: R,
+        mut chunk: Vec<u8>,
+        name: &str,
+        methods: Arc<Vec<EncoderConfiguration>>,
+    ) -> Result<Option<EncodedEntry>> {
+        let encode_error = |e| Error::io_msg(e, format!("Encode entry:{name}"));
+        let mut settings = self.encode_settings();
+        // The compressed data is sent in chunks of the write buffer size.
+        settings.write_buffer_size = settings.write_buffer_size.max(settings.read_buffer_size);
+        let (chunk_sender, chunk_receiver) = mpsc::channel(PENDING_CHUNKS);
+        let (compressed_sender, mut compressed_receiver) = mpsc::unbounded_channel();
+        let encoder = spawn_encoder(
+            chunk_receiver,
+            compressed_sender,
+            name.to_string(),
+            methods,
+            settings,
+        );
+
+        while !chunk.is_empty() {
+            if chunk_sender.send(chunk).await.is_err() {
+                // The encoder failed, its error is returned below.
+                break;
+            }
+            while let Ok(compressed) = compressed_receiver.try_recv() {
+                self.output.write_all(&compressed).map_err(encode_error)?;
+            }
+            chunk = Vec::with_capacity(self.read_buffer_size);
+            reader.read_buf(&mut chunk).await.map_err(encode_error)?;
+        }
+        drop(chunk_sender);
+        while let Some(compressed) = compressed_receiver.recv().await {
+            self.output.write_all(&compressed).map_err(encode_error)?;
+        }
+        match encoder.await {
+            Ok(encoded) => encoded,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(encode_error(std::io::Error::other(e))),
+        }
+    }
+}
+
+/// Runs [`encode_entry`] on a blocking task, reading the chunks from `chunks` and sending the
+/// compressed data to `compressed`.
+fn spawn_encoder(
+    chunks: mpsc::Receiver<Vec<u8>>,
+    compressed: mpsc::UnboundedSender<Vec<u8>>,
+    name: String,
+    methods: Arc<Vec<EncoderConfiguration>>,
+    settings: EncodeSettings,
+) -> JoinHandle<Result<Option<EncodedEntry>>> {
+    tokio::task::spawn_blocking(move || {
+        let reader = ChunkReader {
+            chunks,
+            chunk: Cursor::new(Vec::new()),
+        };
+        encode_entry(reader, &name, &methods, ChunkWriter(compressed), settings)
+    })
+}
+
+/// Reads the chunks received from the asynchronous reader, until the sender is dropped.
+struct ChunkReader {
+    chunks: mpsc::Receiver<Vec<u8>>,
+    chunk: Cursor<Vec<u8>>,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = Read::read(&mut self.chunk, buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.chunk = Cursor::new(chunk),
+                None => return Ok(0),
+            }
+        }
+    }
+}
+
+/// Sends the compressed data back to the asynchronous side.
+struct ChunkWriter(mpsc::UnboundedSender<Vec<u8>>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "The asynchronous writer stopped",
+            )
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -4,10 +4,12 @@ use std::{
     rc::Rc,
 };
 
+use crate::encoder::FinishableWrite;
+
 pub(crate) struct CountingWriter<W> {
     inner: W,
-    counting: Rc<Cell<usize>>,
-    written_bytes: usize,
+    counting: Rc<Cell<u64>>,
+    written_bytes: u64,
 }
 
 impl<W> CountingWriter<W> {
@@ -19,15 +21,19 @@ impl<W> CountingWriter<W> {
         }
     }
 
-    pub(crate) fn counting(&self) -> Rc<Cell<usize>> {
+    pub(crate) fn counting(&self) -> Rc<Cell<u64>> {
         Rc::clone(&self.counting)
     }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
 }
 
 impl<W: Write> Write for CountingWriter<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let len = self.inner.write(buf)?;
-        self.written_bytes += len;
+        self.written_bytes += len as u64;
         self.counting.set(self.written_bytes);
         Ok(len)
     }
@@ -37,6 +43,12 @@ impl<W: Write> Write for CountingWriter<W> {
     }
 }
 
+impl<'a> FinishableWrite for CountingWriter<Box<dyn FinishableWrite + 'a>> {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        self.inner.finish()
+    }
+}
+
 impl<W: Write + Seek> Seek for CountingWriter<W> {
     fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
         self.inner.seek(pos)
@@ -1,12 +1,22 @@
 use std::{fs::File, io::Read, path::PathBuf};
 
-pub(crate) struct LazyFileReader {
+/// A reader that opens its file only when data is first requested and closes it again
+/// once all data was read.
+///
+/// This allows to pass a large number of source files to
+/// [`ArchiveWriter::push_archive_entries`](crate::ArchiveWriter::push_archive_entries) without
+/// keeping a file handle open for each of them. IO errors contain the path of the file.
+pub struct LazyFileReader {
     path: PathBuf,
     reader: Option<File>,
     end: bool,
 }
 
 impl LazyFileReader {
+    /// Creates a new lazy reader for the file at `path`.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file to read
     pub fn new(path: PathBuf) -> Self {
         Self {
             path,
@@ -14,6 +24,10 @@ impl LazyFileReader {
             end: false,
         }
     }
+
+    fn with_path(&self, e: std::io::Error) -> std::io::Error {
+        std::io::Error::new(e.kind(), format!("{}: {e}", self.path.display()))
+    }
 }
 
 impl Read for LazyFileReader {
@@ -22,9 +36,12 @@ impl Read for LazyFileReader {
             return Ok(0);
         }
         if self.reader.is_none() {
-            self.reader = Some(File::open(&self.path)?);
+            self.reader = Some(File::open(&self.path).map_err(|e| self.with_path(e))?);
         }
-        let n = self.reader.as_mut().unwrap().read(buf)?;
+        let n = match self.reader.as_mut().unwrap().read(buf) {
+            Ok(n) => n,
+            Err(e) => return Err(self.with_path(e)),
+        };
         if n == 0 {
             self.end = true;
             self.reader = None;
@@ -24,6 +24,11 @@ impl<R> SeqReader<R> {
     pub(crate) fn reader_len(&self) -> usize {
         self.readers.len()
     }
+
+    /// Index of the reader that is currently read from.
+    pub(crate) fn current_index(&self) -> usize {
+        self.current
+    }
 }
 
 impl<R: Read> Read for SeqReader<R> {
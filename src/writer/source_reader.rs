@@ -1,6 +1,13 @@
 use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
 
 use crc32fast::Hasher;
+#[cfg(feature = "sha256")]
+use sha2::{Digest, Sha256};
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::LazyFileReader;
 
 /// A wrapper around a reader that tracks read count and CRC32.
 ///
@@ -8,9 +15,18 @@ use crc32fast::Hasher;
 /// the CRC32 checksum of the data.
 pub struct SourceReader<R> {
     reader: R,
-    size: usize,
+    size: u64,
     crc: Hasher,
     crc_value: u32,
+    compute_crc: bool,
+    #[cfg(feature = "sha256")]
+    sha256: Option<Sha256>,
+    #[cfg(feature = "sha256")]
+    sha256_value: Option<[u8; 32]>,
+    #[cfg(feature = "blake3-checksum")]
+    blake3: Option<blake3::Hasher>,
+    #[cfg(feature = "blake3-checksum")]
+    blake3_value: Option<[u8; 32]>,
 }
 
 impl<R> From<R> for SourceReader<R> {
@@ -24,11 +40,29 @@ impl<R: Read> Read for SourceReader<R> {
         let n = self.reader.read(buf)?;
         if self.crc_value == 0 {
             if n > 0 {
-                self.size += n;
-                self.crc.update(&buf[..n]);
+                self.size += n as u64;
+                if self.compute_crc {
+                    self.crc.update(&buf[..n]);
+                }
+                #[cfg(feature = "sha256")]
+                if let Some(sha256) = self.sha256.as_mut() {
+                    sha256.update(&buf[..n]);
+                }
+                #[cfg(feature = "blake3-checksum")]
+                if let Some(blake3) = self.blake3.as_mut() {
+                    blake3.update(&buf[..n]);
+                }
             } else {
                 let crc = std::mem::replace(&mut self.crc, Hasher::new());
                 self.crc_value = crc.finalize();
+                #[cfg(feature = "sha256")]
+                if let Some(sha256) = self.sha256.take() {
+                    self.sha256_value = Some(sha256.finalize().into());
+                }
+                #[cfg(feature = "blake3-checksum")]
+                if let Some(blake3) = self.blake3.take() {
+                    self.blake3_value = Some(blake3.finalize().into());
+                }
             }
         }
         Ok(n)
@@ -46,11 +80,52 @@ impl<R> SourceReader<R> {
             size: 0,
             crc: Hasher::new(),
             crc_value: 0,
+            compute_crc: true,
+            #[cfg(feature = "sha256")]
+            sha256: None,
+            #[cfg(feature = "sha256")]
+            sha256_value: None,
+            #[cfg(feature = "blake3-checksum")]
+            blake3: None,
+            #[cfg(feature = "blake3-checksum")]
+            blake3_value: None,
         }
     }
 
+    /// Sets whether the CRC of the read data is computed, see
+    /// [`ArchiveWriter::set_compute_crcs`](crate::ArchiveWriter::set_compute_crcs).
+    pub(crate) fn set_compute_crc(&mut self, enabled: bool) {
+        self.compute_crc = enabled;
+    }
+
+    /// Sets whether the SHA-256 digest of the read data is computed, see
+    /// [`ArchiveWriter::set_store_sha256`](crate::ArchiveWriter::set_store_sha256).
+    #[cfg(feature = "sha256")]
+    pub(crate) fn set_compute_sha256(&mut self, enabled: bool) {
+        self.sha256 = enabled.then(Sha256::new);
+    }
+
+    /// Returns the SHA-256 digest of all data read, once all data has been read.
+    #[cfg(feature = "sha256")]
+    pub(crate) fn sha256_value(&self) -> Option<[u8; 32]> {
+        self.sha256_value
+    }
+
+    /// Sets whether the BLAKE3 hash of the read data is computed, see
+    /// [`ArchiveWriter::set_store_blake3_hash`](crate::ArchiveWriter::set_store_blake3_hash).
+    #[cfg(feature = "blake3-checksum")]
+    pub(crate) fn set_compute_blake3(&mut self, enabled: bool) {
+        self.blake3 = enabled.then(blake3::Hasher::new);
+    }
+
+    /// Returns the BLAKE3 hash of all data read, once all data has been read.
+    #[cfg(feature = "blake3-checksum")]
+    pub(crate) fn blake3_value(&self) -> Option<[u8; 32]> {
+        self.blake3_value
+    }
+
     /// Returns the total number of bytes read so far.
-    pub fn read_count(&self) -> usize {
+    pub fn read_count(&self) -> u64 {
         self.size
     }
 
@@ -61,3 +136,19 @@ impl<R> SourceReader<R> {
         self.crc_value
     }
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SourceReader<LazyFileReader> {
+    /// Creates a source reader for the file at `path`.
+    ///
+    /// The file is only opened once its data is read and closed again after all of its bytes
+    /// were consumed, so many sources can be passed to
+    /// [`ArchiveWriter::push_archive_entries`](crate::ArchiveWriter::push_archive_entries) at
+    /// once without exhausting file descriptors.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the file to read
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        Self::new(LazyFileReader::new(path.into()))
+    }
+}
@@ -1,7 +1,10 @@
 use std::{io::Write, sync::Arc};
 
 use super::*;
-use crate::EncoderConfiguration;
+use crate::{
+    Block, EncoderConfiguration, EncoderMethod,
+    encoder_options::{EncoderOptions, Lzma2Options},
+};
 #[derive(Debug, Clone, Default)]
 pub(crate) struct UnpackInfo {
     pub(crate) blocks: Vec<BlockInfo>,
@@ -12,12 +15,13 @@ impl UnpackInfo {
         &mut self,
         methods: Arc<Vec<EncoderConfiguration>>,
         sizes: Vec<u64>,
-        crc: u32,
+        crc: Option<u32>,
     ) {
         self.blocks.push(BlockInfo {
             methods,
             sizes,
-            crc,
+            crc: crc.unwrap_or_default(),
+            has_crc: crc.is_some(),
             num_sub_unpack_streams: 1,
             ..Default::default()
         })
@@ -27,7 +31,7 @@ impl UnpackInfo {
         &mut self,
         methods: Arc<Vec<EncoderConfiguration>>,
         sizes: Vec<u64>,
-        crc: u32,
+        crc: Option<u32>,
         num_sub_unpack_streams: u64,
         sub_stream_sizes: Vec<u64>,
         sub_stream_crcs: Vec<u32>,
@@ -35,10 +39,42 @@ impl UnpackInfo {
         self.blocks.push(BlockInfo {
             methods,
             sizes,
-            crc,
+            crc: crc.unwrap_or_default(),
+            has_crc: crc.is_some(),
             num_sub_unpack_streams,
             sub_stream_crcs,
             sub_stream_sizes,
+            ..Default::default()
+        })
+    }
+
+    /// Adds a block of `size` zeros without any entries, which pads the following packed
+    /// stream to an aligned offset.
+    pub(crate) fn add_padding(&mut self, size: u64) {
+        self.blocks.push(BlockInfo {
+            methods: Arc::new(vec![EncoderConfiguration::new(EncoderMethod::COPY)]),
+            sizes: vec![size],
+            num_sub_unpack_streams: 0,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn add_raw(
+        &mut self,
+        block: Block,
+        crc: u32,
+        sub_stream_sizes: Vec<u64>,
+        sub_stream_crcs: Vec<u32>,
+    ) {
+        self.blocks.push(BlockInfo {
+            sizes: block.unpack_sizes.clone(),
+            crc,
+            has_crc: true,
+            num_sub_unpack_streams: sub_stream_sizes.len() as u64,
+            sub_stream_sizes,
+            sub_stream_crcs,
+            raw_block: Some(block),
+            ..Default::default()
         })
     }
 
@@ -96,7 +132,19 @@ impl UnpackInfo {
 
         // We always write the CRC values in the substreams info.
         let mut crcs_to_write = Vec::new();
+        let num_streams: u64 = self.blocks.iter().map(|f| f.num_sub_unpack_streams).sum();
+        // The bit set must cover all streams, even if the trailing bits are not set.
+        let mut crcs_defined = BitSet::with_capacity(num_streams as usize);
+        let mut stream_index = 0;
         for f in &self.blocks {
+            let first_stream_index = stream_index;
+            stream_index += f.num_sub_unpack_streams as usize;
+            if !f.has_crc {
+                continue;
+            }
+            for i in first_stream_index..stream_index {
+                crcs_defined.insert(i);
+            }
             if f.num_sub_unpack_streams > 1 {
                 // Multiple substreams - write all CRCs.
                 for &crc in &f.sub_stream_crcs {
@@ -117,7 +165,12 @@ impl UnpackInfo {
 
         if !crcs_to_write.is_empty() {
             header.write_u8(K_CRC)?;
-            header.write_u8(1)?; // all CRCs defined.
+            if crcs_defined.len() as u64 == num_streams {
+                header.write_u8(1)?; // all CRCs defined.
+            } else {
+                header.write_u8(0)?;
+                write_bit_set(&mut *header, &crcs_defined)?;
+            }
             for crc in crcs_to_write {
                 header.write_u32(crc)?;
             }
@@ -133,9 +186,13 @@ pub(crate) struct BlockInfo {
     pub(crate) methods: Arc<Vec<EncoderConfiguration>>,
     pub(crate) sizes: Vec<u64>,
     pub(crate) crc: u32,
+    /// Whether the CRCs of the block were computed. Blocks written without CRCs store none.
+    pub(crate) has_crc: bool,
     pub(crate) num_sub_unpack_streams: u64,
     pub(crate) sub_stream_sizes: Vec<u64>,
     pub(crate) sub_stream_crcs: Vec<u32>,
+    /// Coder layout copied verbatim from an existing archive.
+    pub(crate) raw_block: Option<Block>,
 }
 
 impl BlockInfo {
@@ -144,6 +201,9 @@ impl BlockInfo {
         header: &mut W,
         cache: &mut Vec<u8>,
     ) -> std::io::Result<()> {
+        if let Some(block) = &self.raw_block {
+            return Self::write_raw_block(block, header);
+        }
         cache.clear();
         let mut num_coders = 0;
         for mc in self.methods.iter() {
@@ -159,6 +219,57 @@ impl BlockInfo {
         Ok(())
     }
 
+    /// Returns the number of independently decodable LZMA2 chunks of this block.
+    pub(crate) fn lzma2_chunk_count(&self) -> Option<u64> {
+        let options = self.methods.iter().find_map(|mc| match &mc.options {
+            Some(EncoderOptions::Lzma2(options)) => Some(options.clone()),
+            _ if mc.method.id() == EncoderMethod::LZMA2.id() => Some(Lzma2Options::default()),
+            _ => None,
+        })?;
+        // Filters don't change the size of the data, so the LZMA2 input has the same size as
+        // the uncompressed block.
+        let size = *self.sizes.last()?;
+        match options.independent_chunk_size() {
+            Some(chunk_size) => Some(size.div_ceil(chunk_size).max(1)),
+            None => Some(1),
+        }
+    }
+
+    fn write_raw_block<H: Write>(block: &Block, header: &mut H) -> std::io::Result<()> {
+        write_u64(header, block.coders.len() as u64)?;
+        for coder in block.coders.iter() {
+            let id = coder.encoder_method_id();
+            let is_simple = coder.num_in_streams == 1 && coder.num_out_streams == 1;
+            let mut codec_flags = id.len() as u8;
+            if !is_simple {
+                codec_flags |= 0x10;
+            }
+            if !coder.properties.is_empty() {
+                codec_flags |= 0x20;
+            }
+            header.write_u8(codec_flags)?;
+            header.write_all(id)?;
+            if !is_simple {
+                write_u64(header, coder.num_in_streams)?;
+                write_u64(header, coder.num_out_streams)?;
+            }
+            if !coder.properties.is_empty() {
+                write_u64(header, coder.properties.len() as u64)?;
+                header.write_all(&coder.properties)?;
+            }
+        }
+        for bind_pair in block.bind_pairs.iter() {
+            write_u64(header, bind_pair.in_index)?;
+            write_u64(header, bind_pair.out_index)?;
+        }
+        if block.packed_streams.len() > 1 {
+            for &index in block.packed_streams.iter() {
+                write_u64(header, index)?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_single_codec<H: Write>(
         &self,
         mc: &EncoderConfiguration,
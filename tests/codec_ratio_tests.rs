@@ -0,0 +1,65 @@
+//! Checks that the compression ratios of the codecs on the benchmark corpus stay within 10% of
+//! the baselines in `benches/codec_baselines.json`.
+//!
+//! To regenerate the baselines after an intended change, run
+//! `SEVENZ_UPDATE_BASELINES=1 cargo test --all-features --test codec_ratio_tests`.
+
+#[cfg(feature = "compress")]
+#[path = "../benches/common/mod.rs"]
+mod common;
+
+#[cfg(feature = "compress")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "compress")]
+const BASELINES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/codec_baselines.json");
+#[cfg(feature = "compress")]
+const TOLERANCE: f64 = 0.1;
+
+#[cfg(feature = "compress")]
+#[test]
+fn codec_ratios_match_baselines() {
+    let mut baselines: BTreeMap<String, f64> = match std::fs::read(BASELINES_PATH) {
+        Ok(json) => serde_json::from_slice(&json).unwrap(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => panic!("Failed to read {BASELINES_PATH}: {e}"),
+    };
+    let update = std::env::var_os("SEVENZ_UPDATE_BASELINES").is_some();
+
+    let mut failures = Vec::new();
+    for file in common::corpus() {
+        for (name, methods) in common::configurations() {
+            let archive = common::compress(&file.data, &methods);
+            assert!(
+                common::decompress(&archive) == file.data,
+                "{name}/{} doesn't round trip",
+                file.name
+            );
+            let ratio = common::ratio(&file.data, &archive);
+            let key = format!("{name}/{}", file.name);
+            if update {
+                baselines.insert(key, (ratio * 10_000.0).round() / 10_000.0);
+                continue;
+            }
+            match baselines.get(&key) {
+                Some(&baseline) if ((ratio - baseline) / baseline).abs() <= TOLERANCE => {}
+                Some(&baseline) => {
+                    failures.push(format!("{key}: ratio {ratio:.4}, baseline {baseline:.4}"))
+                }
+                None => failures.push(format!("{key}: no baseline")),
+            }
+        }
+    }
+
+    if update {
+        let json = serde_json::to_string_pretty(&baselines).unwrap();
+        std::fs::write(BASELINES_PATH, json + "\n").unwrap();
+    }
+    assert!(
+        failures.is_empty(),
+        "Compression ratios differ from the baselines by more than {}%, regenerate them with \
+         SEVENZ_UPDATE_BASELINES=1 if this is intended:\n{}",
+        TOLERANCE * 100.0,
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "codec")]
+
+#[cfg(feature = "compress")]
+use std::io::{Read, Write};
+
+#[cfg(feature = "compress")]
+use sevenz_rust2::{codec::*, encoder_options::*};
+
+#[cfg(feature = "compress")]
+fn test_data() -> Vec<u8> {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut data = text.repeat(4);
+    data.extend((0..=255u8).cycle().take(10_000));
+    data
+}
+
+#[cfg(feature = "compress")]
+fn read_all(mut reader: impl Read) -> Vec<u8> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).unwrap();
+    data
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn lzma_round_trip() {
+    let data = test_data();
+    let mut compressed = Vec::new();
+    let mut writer = lzma::writer(&mut compressed, LzmaOptions::from_level(6)).unwrap();
+    writer.write_all(&data).unwrap();
+    let properties = writer.properties().to_vec();
+    writer.finish().unwrap();
+    assert_eq!(properties.len(), 5);
+    assert!(compressed.len() < data.len());
+
+    let reader = lzma::reader(compressed.as_slice(), data.len() as u64, &properties).unwrap();
+    assert_eq!(read_all(reader), data);
+    assert!(lzma::reader(compressed.as_slice(), data.len() as u64, &properties[..1]).is_err());
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn lzma2_round_trip() {
+    let data = test_data();
+    for options in [
+        Lzma2Options::from_level(6),
+        Lzma2Options::from_level_mt(6, 2, 1 << 16),
+    ] {
+        let mut compressed = Vec::new();
+        let mut writer = lzma2::writer(&mut compressed, options).unwrap();
+        writer.write_all(&data).unwrap();
+        let properties = writer.properties().to_vec();
+        writer.finish().unwrap();
+        assert_eq!(properties.len(), 1);
+        assert!(compressed.len() < data.len());
+
+        let reader = lzma2::reader(compressed.as_slice(), data.len() as u64, &properties).unwrap();
+        assert_eq!(read_all(reader), data);
+    }
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn lzma_properties_match_7zip() {
+    // 7-Zip writes `5D 00 00 80 00` for LZMA with lc=3, lp=0, pb=2 and an 8 MiB dictionary.
+    let writer = lzma::writer(Vec::new(), LzmaOptions::from_level(6)).unwrap();
+    assert_eq!(writer.properties(), [0x5D, 0x00, 0x00, 0x80, 0x00]);
+    writer.finish().unwrap();
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn lzma2_dictionary_size_is_rounded_up() {
+    let data = test_data();
+    let mut options = Lzma2Options::from_level(6);
+    options.set_dictionary_size(5_000_000);
+    assert_eq!(options.dictionary_size(), 6 << 20);
+    options.set_dictionary_size(1 << 20);
+    assert_eq!(options.dictionary_size(), 1 << 20);
+    options.set_dictionary_size((1 << 20) + 1);
+    assert_eq!(options.dictionary_size(), 3 << 19);
+    options.set_dictionary_size(0);
+    assert_eq!(options.dictionary_size(), 4096);
+
+    options.set_dictionary_size(100_000);
+    assert_eq!(options.dictionary_size(), 128 << 10);
+    let mut compressed = Vec::new();
+    let mut writer = lzma2::writer(&mut compressed, options).unwrap();
+    writer.write_all(&data).unwrap();
+    // 128 KiB is stored as `2 << (10 / 2 + 11)`.
+    assert_eq!(writer.properties(), [10]);
+    let properties = writer.properties().to_vec();
+    writer.finish().unwrap();
+
+    let reader = lzma2::reader(compressed.as_slice(), data.len() as u64, &properties).unwrap();
+    assert_eq!(read_all(reader), data);
+}
+
+#[cfg(all(feature = "compress", feature = "ppmd"))]
+#[test]
+fn ppmd_round_trip() {
+    let data = test_data();
+    let mut compressed = Vec::new();
+    let mut writer = ppmd::writer(&mut compressed, 6, 16 << 20).unwrap();
+    writer.write_all(&data).unwrap();
+    let properties = writer.properties().to_vec();
+    writer.finish().unwrap();
+    assert_eq!(properties, [6, 0, 0, 0, 1]);
+    assert!(compressed.len() < data.len());
+
+    let reader = ppmd::reader(compressed.as_slice(), data.len() as u64, &properties).unwrap();
+    assert_eq!(read_all(reader), data);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn delta_round_trip() {
+    let data = test_data();
+    for distance in [1, 4, 256] {
+        let mut filtered = Vec::new();
+        let mut writer = delta::writer(&mut filtered, distance).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(filtered.len(), data.len());
+        assert_ne!(filtered, data);
+
+        let reader = delta::reader(filtered.as_slice(), distance).unwrap();
+        assert_eq!(read_all(reader), data);
+    }
+    assert!(delta::reader([].as_slice(), 0).is_err());
+    assert!(delta::reader([].as_slice(), 257).is_err());
+}
@@ -1,5 +1,6 @@
 #[cfg(all(feature = "compress", feature = "util"))]
 use std::{
+    borrow::Cow,
     fs::File,
     hash::{Hash, Hasher},
     io::{Cursor, Read},
@@ -141,6 +142,194 @@ fn compress_folder_with_nested_folder() {
     );
 }
 
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_directory_tree() {
+    let temp_dir = tempdir().unwrap();
+    let folder = temp_dir.path().join("folder");
+    std::fs::create_dir_all(folder.join("a/b")).unwrap();
+    std::fs::create_dir_all(folder.join("empty")).unwrap();
+    std::fs::create_dir_all(folder.join(".hidden_dir")).unwrap();
+    std::fs::write(folder.join("text.txt"), "text content").unwrap();
+    std::fs::write(folder.join("a/binary.bin"), [0u8, 1, 2, 255, 254]).unwrap();
+    std::fs::write(folder.join("a/b/empty.txt"), "").unwrap();
+    std::fs::write(folder.join(".hidden"), "hidden").unwrap();
+    std::fs::write(folder.join(".hidden_dir/file.txt"), "hidden").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let count = writer
+        .push_directory_tree_with_options(
+            &folder,
+            "root",
+            &DirectoryTreeOptions::default().with_ignore_hidden(true),
+        )
+        .unwrap();
+    assert_eq!(count, 6);
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let mut names: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .map(|entry| (entry.name().to_string(), entry.is_directory()))
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            ("root/a".to_string(), true),
+            ("root/a/b".to_string(), true),
+            ("root/a/b/empty.txt".to_string(), false),
+            ("root/a/binary.bin".to_string(), false),
+            ("root/empty".to_string(), true),
+            ("root/text.txt".to_string(), false),
+        ]
+    );
+    assert_eq!(reader.read_file("root/text.txt").unwrap(), b"text content");
+    assert_eq!(
+        reader.read_file("root/a/binary.bin").unwrap(),
+        [0u8, 1, 2, 255, 254]
+    );
+    assert!(reader.read_file("root/a/b/empty.txt").unwrap().is_empty());
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert_eq!(writer.push_directory_tree(&folder, "").unwrap(), 9);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn archive_entry_from_dir_entry() {
+    let temp_dir = tempdir().unwrap();
+    let base = temp_dir.path();
+    std::fs::create_dir(base.join("sub")).unwrap();
+    std::fs::write(base.join("file.txt"), "content").unwrap();
+    std::fs::write(base.join("sub/nested.txt"), "nested").unwrap();
+
+    let mut entries: Vec<ArchiveEntry> = std::fs::read_dir(base)
+        .unwrap()
+        .map(|dir_entry| ArchiveEntry::try_from(&dir_entry.unwrap()).unwrap())
+        .collect();
+    entries.sort_by(|a, b| a.name().cmp(b.name()));
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name(), "file.txt");
+    assert!(entries[0].has_stream());
+    assert!(entries[0].has_last_modified_date);
+    assert_eq!(entries[1].name(), "sub");
+    assert!(entries[1].is_directory());
+
+    let builder = ArchiveEntryBuilder::with_base(base);
+    let nested: Vec<ArchiveEntry> = std::fs::read_dir(base.join("sub"))
+        .unwrap()
+        .map(|dir_entry| builder.build(&dir_entry.unwrap()).unwrap())
+        .collect();
+    assert_eq!(nested.len(), 1);
+    assert_eq!(nested[0].name(), "sub/nested.txt");
+    assert!(!nested[0].is_directory());
+
+    let other_dir = tempdir().unwrap();
+    std::fs::write(other_dir.path().join("outside.txt"), "").unwrap();
+    let outside = std::fs::read_dir(other_dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    assert!(builder.build(&outside).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_restores_timestamps_and_readonly() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("times.7z");
+
+    let modified = NtTime::new(133_000_000_000_000_001);
+    let accessed = NtTime::new(133_100_000_000_000_003);
+    let mut entry = ArchiveEntry::new_file("file.txt");
+    entry.has_last_modified_date = true;
+    entry.last_modified_date = modified;
+    entry.has_access_date = true;
+    entry.access_date = accessed;
+    entry.has_windows_attributes = true;
+    entry.windows_attributes = 0x1;
+    let mut empty_entry = entry.clone();
+    empty_entry.name = "empty.txt".to_string();
+
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer
+        .push_archive_entry(entry, Some(b"content".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry(empty_entry, Some(b"".as_slice()))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let decompress_dest = temp_dir.path().join("decompress");
+    let options = ExtractOptions {
+        restore_readonly: true,
+        ..Default::default()
+    };
+    decompress_file_with_options(&dest, &decompress_dest, &options).expect("decompress ok");
+
+    for name in ["file.txt", "empty.txt"] {
+        let metadata = std::fs::metadata(decompress_dest.join(name)).unwrap();
+        assert_eq!(
+            NtTime::try_from(metadata.modified().unwrap()).unwrap(),
+            modified
+        );
+        assert_eq!(
+            NtTime::try_from(metadata.accessed().unwrap()).unwrap(),
+            accessed
+        );
+        assert!(metadata.permissions().readonly());
+    }
+
+    let decompress_dest = temp_dir.path().join("decompress_no_times");
+    let options = ExtractOptions {
+        restore_mtime: false,
+        ..Default::default()
+    };
+    decompress_file_with_options(&dest, &decompress_dest, &options).expect("decompress ok");
+    let metadata = std::fs::metadata(decompress_dest.join("file.txt")).unwrap();
+    assert_ne!(
+        NtTime::try_from(metadata.modified().unwrap()).unwrap(),
+        modified
+    );
+    assert!(!metadata.permissions().readonly());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_restores_timestamps_of_empty_files() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("empty_times.7z");
+
+    let modified = NtTime::from_unix_timestamp(1_000_000_000).unwrap();
+    let mut entry = ArchiveEntry::new_file("empty.txt");
+    entry.has_last_modified_date = true;
+    entry.last_modified_date = modified;
+    // Without a stored date, the time of the extraction is kept.
+    let undated_entry = ArchiveEntry::new_file("undated.txt");
+
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer.push_archive_entry::<&[u8]>(entry, None).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(undated_entry, None)
+        .unwrap();
+    writer.finish().unwrap();
+
+    let decompress_dest = temp_dir.path().join("decompress");
+    decompress_file(&dest, &decompress_dest).expect("decompress ok");
+    let metadata = std::fs::metadata(decompress_dest.join("empty.txt")).unwrap();
+    assert_eq!(metadata.len(), 0);
+    assert_eq!(
+        NtTime::try_from(metadata.modified().unwrap()).unwrap(),
+        modified
+    );
+    let metadata = std::fs::metadata(decompress_dest.join("undated.txt")).unwrap();
+    assert!(NtTime::try_from(metadata.modified().unwrap()).unwrap() > modified);
+}
+
 #[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
 #[test]
 fn compress_one_file_with_random_content_encrypted() {
@@ -204,9 +393,8 @@ fn test_compression_method(methods: &[EncoderConfiguration]) {
         .iter()
         .filter(|file| !file.is_directory)
         .for_each(|file| {
-            let mut file_methods = Vec::<EncoderMethod>::new();
-            reader
-                .file_compression_methods(file.name(), &mut file_methods)
+            let file_methods = reader
+                .file_methods(file.name())
                 .expect("can't read compression method");
 
             for (file_method, method) in file_methods.iter().zip(methods) {
@@ -268,6 +456,45 @@ fn compress_with_delta_lzma2_algorithm() {
     }
 }
 
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_delta_distances() {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    // 16-bit samples, which the delta filter is meant for.
+    let mut content: Vec<u8> = (0..20_000u16)
+        .flat_map(|i| (i.wrapping_mul(7) / 3).to_le_bytes())
+        .collect();
+    content.extend_from_slice(&text);
+
+    let compressors = [
+        EncoderMethod::LZMA,
+        EncoderMethod::LZMA2,
+        #[cfg(feature = "zstd")]
+        EncoderMethod::ZSTD,
+    ];
+    for compressor in compressors {
+        for distance in [1, 2, 4, 16, 255, 256] {
+            let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+            writer.set_content_methods(vec![
+                compressor.into(),
+                DeltaOptions::from_distance(distance).into(),
+            ]);
+            writer
+                .push_archive_entry(ArchiveEntry::new_file("data"), Some(content.as_slice()))
+                .unwrap();
+            let bytes = writer.finish_into_bytes().unwrap();
+
+            let mut reader = ArchiveReader::from_seekable_buffer(bytes, Password::empty()).unwrap();
+            assert_eq!(
+                reader.read_file("data").unwrap(),
+                content,
+                "{} with delta distance {distance}",
+                compressor.name()
+            );
+        }
+    }
+}
+
 #[cfg(all(feature = "compress", feature = "util"))]
 #[test]
 fn compress_with_bcj_x86_lzma2_algorithm() {
@@ -340,6 +567,89 @@ fn compress_with_bcj_riscv_lzma2_algorithm() {
     ]);
 }
 
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_bcj_filter_builder() {
+    let content = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let compress = |methods: Option<(EncoderConfiguration, BcjArch)>| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        if let Some((method, arch)) = methods {
+            writer.set_content_methods_with_bcj(method, arch);
+        }
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("decompress_x86.exe"),
+                Some(content.as_slice()),
+            )
+            .unwrap();
+        writer.finish_into_bytes().unwrap()
+    };
+
+    let chain = EncoderConfiguration::new(EncoderMethod::LZMA2).with_bcj_filter(BcjArch::Arm64);
+    assert_eq!(chain[0].method, EncoderMethod::LZMA2);
+    assert_eq!(chain[1].method, EncoderMethod::BCJ_ARM64_FILTER);
+    test_compression_method(&chain);
+
+    let lzma2 = compress(None);
+    let bcj_lzma2 = compress(Some((EncoderMethod::LZMA2.into(), BcjArch::X86)));
+    assert!(bcj_lzma2.len() < lzma2.len());
+
+    let mut reader = ArchiveReader::from_seekable_buffer(bcj_lzma2, Password::empty()).unwrap();
+    let methods = reader.file_methods("decompress_x86.exe").unwrap();
+    assert_eq!(methods[0].name(), EncoderMethod::LZMA2.name());
+    assert_eq!(methods[1].name(), EncoderMethod::BCJ_X86_FILTER.name());
+    assert_eq!(reader.read_file("decompress_x86.exe").unwrap(), content);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_bcj_start_offset() {
+    // A segment of the binary, as if a code section was compressed on its own.
+    let content = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let segment = &content[0x400..0x400 + 0x4000];
+    let bcj_properties = |archive: &Archive| {
+        let block = &archive.blocks[0];
+        let coder = block
+            .coders
+            .iter()
+            .find(|coder| coder.encoder_method_id() == EncoderMethod::ID_BCJ_X86);
+        coder.unwrap().properties().to_vec()
+    };
+    let compress = |bcj: EncoderConfiguration| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![EncoderMethod::COPY.into(), bcj]);
+        writer
+            .push_archive_entry(ArchiveEntry::new_file("text"), Some(segment))
+            .unwrap();
+        writer.finish_into_bytes().unwrap()
+    };
+
+    let default = compress(EncoderMethod::BCJ_X86_FILTER.into());
+    let archive = Archive::read(&mut Cursor::new(default.as_slice()), &Password::empty()).unwrap();
+    assert!(bcj_properties(&archive).is_empty());
+
+    let shifted = compress(BcjX86Options::from_start_offset(0x401000).into());
+    let archive = Archive::read(&mut Cursor::new(shifted.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(bcj_properties(&archive), 0x401000u32.to_le_bytes());
+    // The filter converts the branch targets relative to the start offset.
+    assert_ne!(shifted, default);
+
+    let mut reader = ArchiveReader::from_seekable_buffer(shifted, Password::empty()).unwrap();
+    assert_eq!(reader.read_file("text").unwrap(), segment);
+
+    for bcj in [
+        EncoderConfiguration::from(BcjArmOptions::from_start_offset(0x8000)),
+        BcjArm64Options::from_start_offset(0x8000).into(),
+        BcjArmThumbOptions::from_start_offset(0x8000).into(),
+        BcjIa64Options::from_start_offset(0x8000).into(),
+        BcjSparcOptions::from_start_offset(0x8000).into(),
+        BcjPpcOptions::from_start_offset(0x8000).into(),
+        BcjRiscVOptions::from_start_offset(0x8000).into(),
+    ] {
+        test_compression_method(&[EncoderMethod::LZMA2.into(), bcj]);
+    }
+}
+
 #[cfg(all(feature = "compress", feature = "util"))]
 #[test]
 fn compress_with_lzma_algorithm() {
@@ -358,6 +668,44 @@ fn compress_with_ppmd_algorithm() {
     test_compression_method(&[EncoderMethod::PPMD.into()]);
 }
 
+#[cfg(all(feature = "compress", feature = "util", feature = "ppmd"))]
+#[test]
+fn compress_with_ppmd_reports_codec_info() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        PpmdOptions::from_order_memory_size(12, 3 << 20).into(),
+    ]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let reader = ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+    let mut infos = Vec::new();
+    reader.file_codec_info("apache2.txt", &mut infos).unwrap();
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].method, EncoderMethod::PPMD);
+    let options = PpmdOptions::from_properties(&infos[0].properties).unwrap();
+    assert_eq!(options.order(), 12);
+    assert_eq!(options.memory_size(), 3 << 20);
+    assert!(PpmdOptions::from_properties(&infos[0].properties[..4]).is_err());
+
+    infos.clear();
+    reader.file_codec_info("dir", &mut infos).unwrap();
+    assert!(infos.is_empty());
+    assert!(matches!(
+        reader.file_codec_info("missing", &mut infos),
+        Err(Error::FileNotFound)
+    ));
+}
+
 #[cfg(all(feature = "compress", feature = "util", feature = "brotli"))]
 #[test]
 fn compress_with_brotli_standard_algorithm() {
@@ -378,6 +726,50 @@ fn compress_with_bzip2_algorithm() {
     test_compression_method(&[EncoderMethod::BZIP2.into()]);
 }
 
+#[cfg(all(feature = "compress", feature = "util", feature = "bzip2"))]
+#[test]
+fn compress_with_bzip2_multiple_streams() {
+    test_compression_method(&[Bzip2Options::from_level(6)
+        .with_stream_size(64 * 1024)
+        .into()]);
+
+    let content = std::fs::read("tests/resources/apache2.txt")
+        .unwrap()
+        .repeat(20);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        Bzip2Options::from_level(6)
+            .with_stream_size(64 * 1024)
+            .into(),
+    ]);
+    let entry = writer
+        .push_archive_entry(ArchiveEntry::new_file("data.txt"), Some(content.as_slice()))
+        .unwrap()
+        .clone();
+    let archive = writer.finish().unwrap().into_inner();
+
+    // The packed stream directly follows the signature header and is a plain multi-stream
+    // BZIP2 file, of which a single stream decoder only decodes the first stream.
+    let packed = &archive[32..32 + entry.compressed_size as usize];
+    let mut decoded = Vec::new();
+    bzip2::read::MultiBzDecoder::new(packed)
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, content);
+    let mut first_stream = Vec::new();
+    bzip2::read::BzDecoder::new(packed)
+        .read_to_end(&mut first_stream)
+        .unwrap();
+    assert_eq!(first_stream, content[..64 * 1024]);
+
+    for threads in [1, 2, 4] {
+        let mut reader =
+            ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+        reader.set_thread_count(threads);
+        assert_eq!(reader.read_file("data.txt").unwrap(), content);
+    }
+}
+
 #[cfg(all(feature = "compress", feature = "util", feature = "deflate"))]
 #[test]
 fn compress_with_deflate_algorithm() {
@@ -398,12 +790,458 @@ fn compress_with_lz4_skippable_algorithm() {
         .into()]);
 }
 
+#[cfg(all(feature = "compress", feature = "util", feature = "lz4"))]
+#[test]
+fn compress_with_lz4_content_checksum_and_linked_blocks() {
+    test_compression_method(&[Lz4Options::default()
+        .with_content_checksum(true)
+        .with_block_independence(false)
+        .into()]);
+
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let compressed_size = |options: Lz4Options| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![options.into()]);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("apache2.txt"),
+                Some(content.as_slice()),
+            )
+            .unwrap()
+            .compressed_size
+    };
+    // The content checksum appends a 4 byte xxHash32 to the frame.
+    assert_eq!(
+        compressed_size(Lz4Options::default().with_content_checksum(true)),
+        compressed_size(Lz4Options::default()) + 4
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "snappy"))]
+#[test]
+fn compress_with_snappy_algorithm() {
+    test_compression_method(&[SnappyOptions.into()]);
+
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::SNAPPY.into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let mut reader =
+        ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+    assert_eq!(
+        reader.archive().blocks[0].coders[0].encoder_method_id(),
+        EncoderMethod::ID_SNAPPY
+    );
+    assert_eq!(reader.read_file("apache2.txt").unwrap(), content);
+}
+
 #[cfg(all(feature = "compress", feature = "util", feature = "lz4"))]
 #[test]
 fn compress_with_zstd_algorithm() {
     test_compression_method(&[EncoderMethod::ZSTD.into()]);
 }
 
+#[cfg(feature = "compress")]
+#[test]
+fn compress_with_aligned_pack_streams() {
+    const ALIGNMENT: u64 = 4096;
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    for header_compression in [HeaderCompression::Never, HeaderCompression::Always] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_pack_stream_alignment(ALIGNMENT);
+        writer.set_header_compression(header_compression);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("first.txt"),
+                Some(content.as_slice()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entry(ArchiveEntry::new_file("empty.txt"), Some([].as_slice()))
+            .unwrap();
+        writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+        writer
+            .push_archive_entry(ArchiveEntry::new_file("copy.txt"), Some(&content[..1000]))
+            .unwrap();
+        writer
+            .push_archive_entries(
+                vec![
+                    ArchiveEntry::new_file("solid1.txt"),
+                    ArchiveEntry::new_file("solid2.txt"),
+                ],
+                vec![
+                    SourceReader::new(content.as_slice()),
+                    SourceReader::new(content.as_slice()),
+                ],
+            )
+            .unwrap();
+        let archive = writer.finish_into_bytes().unwrap();
+
+        let next_header_offset = u64::from_le_bytes(archive[12..20].try_into().unwrap());
+        assert_eq!((32 + next_header_offset) % ALIGNMENT, 0);
+
+        let mut reader =
+            ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+        let archive_info = reader.archive();
+        assert!(archive_info.verify_structure().is_empty());
+        // The streams of the entries are aligned, the padding streams in between aren't.
+        let ranges = archive_info.pack_stream_ranges();
+        let stream_map = &archive_info.stream_map;
+        let mut block_indices: Vec<usize> = stream_map
+            .file_block_index
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+        block_indices.dedup();
+        assert_eq!(block_indices.len(), 3);
+        for block_index in block_indices {
+            let (offset, _) = ranges[stream_map.block_first_pack_stream_index()[block_index]];
+            assert_eq!(offset % ALIGNMENT, 0);
+        }
+
+        if header_compression == HeaderCompression::Never {
+            // The names in the plain header are aligned with a dummy property.
+            let name: Vec<u8> = "first.txt"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            let name_offset = archive
+                .windows(name.len())
+                .position(|window| window == name)
+                .unwrap();
+            assert_eq!(name_offset % 16, 0);
+        }
+
+        assert_eq!(reader.read_file("first.txt").unwrap(), content);
+        assert_eq!(reader.read_file("empty.txt").unwrap(), b"");
+        assert_eq!(reader.read_file("copy.txt").unwrap(), &content[..1000]);
+        assert_eq!(reader.read_file("solid2.txt").unwrap(), content);
+        let mut names = Vec::new();
+        reader
+            .for_each_entries(|entry, entry_reader| {
+                names.push(entry.name().to_string());
+                std::io::copy(entry_reader, &mut std::io::sink())?;
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(
+            names,
+            [
+                "first.txt",
+                "copy.txt",
+                "solid1.txt",
+                "solid2.txt",
+                "empty.txt"
+            ]
+        );
+    }
+}
+
+/// Reader that panics after yielding some data.
+#[cfg(feature = "compress")]
+struct PanickingReader(usize);
+
+#[cfg(feature = "compress")]
+impl Read for PanickingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0 == 0 {
+            panic!("source failed");
+        }
+        let len = buf.len().min(self.0);
+        buf[..len].fill(b'x');
+        self.0 -= len;
+        Ok(len)
+    }
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn interrupted_archive_is_detected_or_aborted() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let file = NamedTempFile::new().unwrap();
+
+    let mut writer = ArchiveWriter::new(file.reopen().unwrap()).unwrap();
+    // A new archive is an empty archive until it is finished.
+    let bytes = std::fs::read(file.path()).unwrap();
+    assert_eq!(bytes.len(), 32);
+    let archive = Archive::read(&mut Cursor::new(bytes), &Password::empty()).unwrap();
+    assert!(archive.files.is_empty());
+
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("first.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    // Stored data is written right away.
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("second.txt"),
+                Some(PanickingReader(100_000)),
+            )
+            .map(|_| ())
+    }));
+    assert!(result.is_err());
+
+    // If the process dies now, the archive has data but no header.
+    let bytes = std::fs::read(file.path()).unwrap();
+    assert!(bytes.len() > 32);
+    assert!(ArchiveReader::new(Cursor::new(bytes), Password::empty()).is_err());
+
+    let (output, len) = writer.abort().unwrap();
+    assert!(output.metadata().unwrap().len() > len);
+    output.set_len(len).unwrap();
+    let mut reader = ArchiveReader::open(file.path(), Password::empty()).unwrap();
+    assert!(reader.archive().verify_structure().is_empty());
+    assert_eq!(reader.archive().files.len(), 1);
+    assert_eq!(reader.read_file("first.txt").unwrap(), content);
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn auto_finish_reports_errors() {
+    let mut bytes = vec![0; 1024];
+    drop(
+        ArchiveWriter::new(Cursor::new(bytes.as_mut_slice()))
+            .unwrap()
+            .auto_finish(),
+    );
+    assert!(take_auto_finish_error().is_none());
+    assert!(ArchiveReader::new(Cursor::new(bytes), Password::empty()).is_ok());
+
+    // The header doesn't fit behind the signature header.
+    let mut bytes = vec![0; 36];
+    drop(
+        ArchiveWriter::new(Cursor::new(bytes.as_mut_slice()))
+            .unwrap()
+            .auto_finish(),
+    );
+    assert!(take_auto_finish_error().is_some());
+    assert!(take_auto_finish_error().is_none());
+}
+
+/// Counts the write calls that reach the output.
+#[cfg(feature = "compress")]
+struct CountingOutput<W> {
+    inner: W,
+    writes: usize,
+}
+
+#[cfg(feature = "compress")]
+impl<W: std::io::Write> std::io::Write for CountingOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writes += 1;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "compress")]
+impl<W: std::io::Seek> std::io::Seek for CountingOutput<W> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn compress_with_large_io_buffers() {
+    const CHUNK_SIZE: usize = 1024 * 1024;
+    const CHUNK_COUNT: usize = 100;
+    let chunk: Vec<u8> = (0..CHUNK_SIZE).map(|i| (i % 251) as u8).collect();
+    let source = std::iter::repeat_n(chunk.as_slice(), CHUNK_COUNT).fold(
+        Box::new(std::io::empty()) as Box<dyn Read>,
+        |reader, chunk| Box::new(reader.chain(chunk)),
+    );
+
+    let output = CountingOutput {
+        inner: tempfile().unwrap(),
+        writes: 0,
+    };
+    let mut writer = ArchiveWriter::new(output).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer.set_read_buffer_size(CHUNK_SIZE);
+    writer.set_write_buffer_size(CHUNK_SIZE);
+    let entry = writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(source))
+        .unwrap()
+        .clone();
+    assert_eq!(entry.size, (CHUNK_SIZE * CHUNK_COUNT) as u64);
+    let output = writer.finish().unwrap();
+    // A 4 KiB buffer would need more than 25000 writes.
+    assert!(output.writes < 2 * CHUNK_COUNT, "{} writes", output.writes);
+
+    let mut reader = ArchiveReader::new(output.inner, Password::empty()).unwrap();
+    reader
+        .for_each_entries(|_, entry_reader| {
+            let mut data = vec![0; CHUNK_SIZE];
+            for _ in 0..CHUNK_COUNT {
+                entry_reader.read_exact(&mut data)?;
+                assert!(data == chunk);
+            }
+            assert_eq!(entry_reader.read(&mut data)?, 0);
+            Ok(true)
+        })
+        .unwrap();
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn compress_with_small_io_buffers() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    for write_buffer_size in [0, 100, 64 * 1024] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        // Clamped to 512 bytes.
+        writer.set_read_buffer_size(1);
+        writer.set_write_buffer_size(write_buffer_size);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("single.txt"),
+                Some(content.as_slice()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entries(
+                vec![
+                    ArchiveEntry::new_file("solid1.txt"),
+                    ArchiveEntry::new_file("solid2.txt"),
+                ],
+                vec![
+                    SourceReader::new(content.as_slice()),
+                    SourceReader::new(content.as_slice()),
+                ],
+            )
+            .unwrap();
+        let archive = writer.finish_into_bytes().unwrap();
+
+        let mut reader =
+            ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+        for name in ["single.txt", "solid1.txt", "solid2.txt"] {
+            assert_eq!(reader.read_file(name).unwrap(), content);
+        }
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "zstd"))]
+#[test]
+fn compress_with_zstd_multiple_frames() {
+    test_compression_method(&[ZstandardOptions::default()
+        .with_frame_size(64 * 1024)
+        .into()]);
+
+    let content = std::fs::read("tests/resources/apache2.txt")
+        .unwrap()
+        .repeat(20);
+    let compress = |options: ZstandardOptions| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![options.into()]);
+        let entry = writer
+            .push_archive_entry(ArchiveEntry::new_file("data.txt"), Some(content.as_slice()))
+            .unwrap()
+            .clone();
+        (entry, writer.finish().unwrap().into_inner())
+    };
+    let (single_entry, _) = compress(ZstandardOptions::default());
+    let (entry, archive) = compress(ZstandardOptions::default().with_frame_size(64 * 1024));
+
+    // Independent frames can't reference each other, which costs some compression ratio.
+    assert!(entry.compressed_size > single_entry.compressed_size);
+
+    // The packed stream directly follows the signature header and is a plain ZSTD file of
+    // concatenated frames.
+    let packed = &archive[32..32 + entry.compressed_size as usize];
+    assert_eq!(zstd::stream::decode_all(packed).unwrap(), content);
+    let mut frame_ends = Vec::new();
+    let mut offset = 0;
+    while offset < packed.len() {
+        offset += zstd::zstd_safe::find_frame_compressed_size(&packed[offset..]).unwrap();
+        frame_ends.push(offset);
+    }
+    assert_eq!(frame_ends.len(), content.len().div_ceil(64 * 1024));
+    assert_eq!(
+        zstd::stream::decode_all(&packed[..frame_ends[0]]).unwrap(),
+        content[..64 * 1024]
+    );
+
+    for threads in [1, 2, 4] {
+        let mut reader =
+            ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+        reader.set_thread_count(threads);
+        assert_eq!(reader.read_file("data.txt").unwrap(), content);
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "zstd"))]
+#[test]
+fn compress_with_zstd_seekable_algorithm() {
+    test_compression_method(&[ZstandardOptions::default()
+        .with_seekable_frame_size(64 * 1024)
+        .into()]);
+
+    let content: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        ZstandardOptions::default()
+            .with_seekable_frame_size(64 * 1024)
+            .into(),
+    ]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("first.bin"),
+                ArchiveEntry::new_file("data.bin"),
+            ],
+            vec![
+                SourceReader::new(b"first".as_slice()),
+                SourceReader::new(content.as_slice()),
+            ],
+        )
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+
+    // Streaming decoding skips the seek table.
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("first.bin").unwrap(), b"first");
+    assert_eq!(reader.read_file("data.bin").unwrap(), content);
+
+    let (offset, length) = reader.archive().pack_stream_ranges()[0];
+    let end = (offset + length) as usize;
+    assert_eq!(
+        u32::from_le_bytes(data[end - 4..end].try_into().unwrap()),
+        0x8F92EAB1
+    );
+
+    // Random access only decodes the frame containing the range, so corrupting the first frame
+    // doesn't affect it.
+    let start = offset as usize;
+    data[start + 16..start + 64].fill(0xFF);
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(
+        reader.read_file_range("data.bin", 200_000, 1000).unwrap(),
+        &content[200_000..201_000]
+    );
+    assert_eq!(
+        reader.read_file_range("data.bin", 299_990, 100).unwrap(),
+        &content[299_990..]
+    );
+    assert!(reader.read_file("data.bin").is_err());
+}
+
 #[cfg(all(feature = "compress", feature = "aes256"))]
 #[test]
 fn encrypted_file_header_requires_password_to_read() {
@@ -424,9 +1262,3387 @@ fn encrypted_file_header_requires_password_to_read() {
         writer.finish().unwrap();
     }
 
+    assert!(Archive::header_encrypted(&mut Cursor::new(bytes.as_slice())).unwrap());
     let result = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty());
     assert!(
-        result.is_err(),
+        matches!(result, Err(Error::PasswordRequired)),
         "Reading an encrypted archive header without a password should not be possible"
     );
+    let result = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::new("wrong"));
+    assert!(
+        matches!(result, Err(Error::WrongPassword)),
+        "{:?}",
+        result.err()
+    );
+    assert!(Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::new("test")).is_ok());
+    let reader = ArchiveReader::from_seekable_buffer(bytes, Password::new("test")).unwrap();
+    assert!(reader.entry_is_encrypted("apache2.txt"));
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn encrypt_entries_only_leaves_header_readable() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.with_encrypt_entries_only(Password::new("test"));
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    let bytes = writer.finish_into_bytes().unwrap();
+
+    assert!(!Archive::header_encrypted(&mut Cursor::new(bytes.as_slice())).unwrap());
+    let mut reader = ArchiveReader::from_seekable_buffer(bytes.clone(), Password::empty()).unwrap();
+    assert_eq!(reader.archive().files[0].name(), "apache2.txt");
+    assert!(reader.entry_is_encrypted("apache2.txt"));
+    assert!(matches!(
+        reader.read_file("apache2.txt"),
+        Err(Error::PasswordRequired)
+    ));
+
+    let mut reader = ArchiveReader::from_seekable_buffer(bytes, Password::new("test")).unwrap();
+    assert_eq!(reader.read_file("apache2.txt").unwrap(), content);
+}
+
+#[cfg(all(feature = "compress", feature = "aes256"))]
+#[test]
+fn invalid_encoder_chain_is_rejected_before_writing() {
+    assert!(
+        EncoderConfiguration::validate(&[
+            AesEncoderOptions::new(Password::new("test")).into(),
+            EncoderMethod::LZMA2.into(),
+            DeltaOptions::from_distance(2).into(),
+        ])
+        .is_ok()
+    );
+    assert!(matches!(
+        EncoderConfiguration::validate(&[EncoderMethod::AES256_SHA256.into()]),
+        Err(Error::PasswordRequired)
+    ));
+    assert!(
+        EncoderConfiguration::validate(&[
+            AesEncoderOptions::new(Password::empty()).into(),
+            EncoderMethod::LZMA2.into(),
+        ])
+        .is_err()
+    );
+    assert!(
+        EncoderConfiguration::validate(&[
+            EncoderMethod::LZMA2.into(),
+            AesEncoderOptions::new(Password::new("test")).into(),
+        ])
+        .is_err()
+    );
+    assert!(
+        EncoderConfiguration::validate(&[
+            EncoderMethod::BCJ_X86_FILTER.into(),
+            EncoderMethod::LZMA2.into(),
+        ])
+        .is_err()
+    );
+    assert!(
+        EncoderConfiguration::validate(&[EncoderMethod::LZMA2.into(), EncoderMethod::LZMA.into()])
+            .is_err()
+    );
+    assert!(EncoderConfiguration::validate(&[EncoderMethod::BCJ2_FILTER.into()]).is_err());
+    assert!(
+        EncoderConfiguration::validate(&[
+            EncoderMethod::LZMA2.into(),
+            EncoderMethod::BCJ_X86_FILTER.into(),
+            EncoderMethod::DELTA_FILTER.into(),
+            EncoderMethod::DELTA_FILTER.into(),
+            EncoderMethod::DELTA_FILTER.into(),
+        ])
+        .is_err()
+    );
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::AES256_SHA256.into()]);
+    let result = writer.push_archive_entry(
+        ArchiveEntry::new_file("file.txt"),
+        Some(b"content".as_slice()),
+    );
+    assert!(matches!(result, Err(Error::PasswordRequired)));
+
+    // The rejected entry must not have left any data behind.
+    writer.set_content_methods(vec![EncoderMethod::LZMA2.into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("file.txt"),
+            Some(b"content".as_slice()),
+        )
+        .unwrap();
+    let output = writer.finish().unwrap().into_inner();
+    let mut reader = ArchiveReader::new(Cursor::new(output.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.archive().files.len(), 1);
+    assert_eq!(reader.read_file("file.txt").unwrap(), b"content");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_per_entry_methods() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_method_selector(Box::new(|entry: &ArchiveEntry| {
+        if entry.name().ends_with(".jpg") {
+            Some(vec![EncoderMethod::COPY.into()])
+        } else {
+            None
+        }
+    }));
+    // A writer with a method selector can still be moved to another thread.
+    let mut writer = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                writer
+                    .push_archive_entry(
+                        ArchiveEntry::new_file("image.jpg"),
+                        Some(content.as_slice()),
+                    )
+                    .unwrap();
+                writer
+            })
+            .join()
+            .unwrap()
+    });
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("text.txt"), Some(content.as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry_with_methods(
+            ArchiveEntry::new_file("delta.bin"),
+            Some(content.as_slice()),
+            vec![
+                EncoderMethod::LZMA.into(),
+                DeltaOptions::from_distance(4).into(),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let expected = [
+        ("image.jpg", vec![EncoderMethod::COPY]),
+        ("text.txt", vec![EncoderMethod::LZMA2]),
+        (
+            "delta.bin",
+            vec![EncoderMethod::LZMA, EncoderMethod::DELTA_FILTER],
+        ),
+    ];
+    for (name, expected_methods) in expected {
+        assert_eq!(
+            reader.file_methods(name).unwrap(),
+            expected_methods,
+            "{name}"
+        );
+        assert_eq!(reader.read_file(name).unwrap(), content, "{name}");
+    }
+
+    let temp_dir = tempdir().unwrap();
+    decompress(Cursor::new(data.as_slice()), temp_dir.path()).unwrap();
+    for name in ["image.jpg", "text.txt", "delta.bin"] {
+        assert_eq!(std::fs::read(temp_dir.path().join(name)).unwrap(), content);
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_anti_items() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("file.txt"),
+            Some(b"content".as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_anti_item("deleted.txt"), None)
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    // Enough trailing directories, so that the bit sets span multiple bytes.
+    for i in 0..10 {
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory(&format!("dir{i}")), None)
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let files = &reader.archive().files;
+    assert_eq!(files.len(), 13);
+    let anti_items: Vec<_> = files
+        .iter()
+        .filter(|entry| entry.is_anti_item())
+        .map(|entry| entry.name())
+        .collect();
+    assert_eq!(anti_items, ["deleted.txt"]);
+    assert!(!files[1].is_directory());
+    assert!(!files[2].is_directory());
+    assert!(files[3..].iter().all(|entry| entry.is_directory()));
+    assert_eq!(reader.read_file("file.txt").unwrap(), b"content");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_file_comments() {
+    let entries = [
+        ("a.txt", "first file", Some(b"content of a".as_slice())),
+        (
+            "b.txt",
+            "zweite Datei: äöü",
+            Some(b"content of b".as_slice()),
+        ),
+        ("empty.txt", "空文件", None),
+        ("dir", "directory", None),
+        (
+            "dir/c.txt",
+            "last file 📦",
+            Some(b"content of c".as_slice()),
+        ),
+    ];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    for (name, comment, content) in entries {
+        let entry = if name == "dir" {
+            let mut entry = ArchiveEntry::new_directory(name);
+            entry.comment = Some(comment.to_string());
+            entry
+        } else {
+            ArchiveEntry::new_file_with_comment(name, comment)
+        };
+        writer.push_archive_entry(entry, content).unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let files = &reader.archive().files;
+    assert_eq!(files.len(), entries.len());
+    for (name, comment, _) in entries {
+        let entry = files.iter().find(|entry| entry.name() == name).unwrap();
+        assert_eq!(entry.comment(), Some(comment));
+    }
+    assert_eq!(reader.read_file("dir/c.txt").unwrap(), b"content of c");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn merge_three_archives() {
+    let temp_dir = tempdir().unwrap();
+    let mut sources = Vec::new();
+    for i in 0..3 {
+        let path = temp_dir.path().join(format!("source{i}.7z"));
+        let mut writer = ArchiveWriter::create(&path).unwrap();
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file(&format!("file{i}.txt")),
+                Some(format!("content {i}").as_bytes()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("shared.txt"),
+                Some(b"shared".as_slice()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory(&format!("dir{i}")), None)
+            .unwrap();
+        writer.finish().unwrap();
+        sources.push((path, Password::empty()));
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    merge_archives(&sources, &mut writer).unwrap();
+    assert_eq!(writer.entries().len(), 9);
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.archive().files.len(), 9);
+    for i in 0..3 {
+        assert_eq!(
+            reader.read_file(&format!("file{i}.txt")).unwrap(),
+            format!("content {i}").as_bytes()
+        );
+        assert!(
+            reader
+                .archive()
+                .files
+                .iter()
+                .any(|entry| entry.name() == format!("dir{i}") && entry.is_directory())
+        );
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    merge_archives_with_options(
+        &sources,
+        &mut writer,
+        &MergeOptions::default().with_dedup(true),
+    )
+    .unwrap();
+    let shared_count = writer
+        .entries()
+        .iter()
+        .filter(|entry| entry.name() == "shared.txt")
+        .count();
+    assert_eq!(shared_count, 1);
+    assert_eq!(writer.entries().len(), 7);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn merge_archives_copying_raw_blocks() {
+    let temp_dir = tempdir().unwrap();
+    let solid_path = temp_dir.path().join("solid.7z");
+    let mut writer = ArchiveWriter::create(&solid_path).unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("a.txt"),
+                ArchiveEntry::new_file("b.txt"),
+            ],
+            vec![
+                SourceReader::new(b"alpha ".repeat(100).as_slice()),
+                SourceReader::new(b"beta ".repeat(100).as_slice()),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer.finish().unwrap();
+
+    let archive = Archive::open(&solid_path).unwrap();
+    let ranges = archive.pack_stream_ranges();
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0], (SIGNATURE_HEADER_SIZE, archive.pack_sizes()[0]));
+    assert_eq!(archive.blocks[0].packed_stream_indices(), &[0]);
+
+    let plain_path = temp_dir.path().join("plain.7z");
+    let mut writer = ArchiveWriter::create(&plain_path).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("c.txt"), Some(b"gamma".as_slice()))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    merge_archives_with_options(
+        &[
+            (solid_path, Password::empty()),
+            (plain_path, Password::empty()),
+        ],
+        &mut writer,
+        &MergeOptions::default().with_copy_blocks(true),
+    )
+    .unwrap();
+    assert_eq!(writer.entries().len(), 4);
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.archive().blocks.len(), 2);
+    for block in reader.archive().blocks.iter() {
+        assert_eq!(
+            block.coders[0].encoder_method_id(),
+            EncoderMethod::LZMA2.id()
+        );
+    }
+    assert_eq!(reader.read_file("a.txt").unwrap(), b"alpha ".repeat(100));
+    assert_eq!(reader.read_file("b.txt").unwrap(), b"beta ".repeat(100));
+    assert_eq!(reader.read_file("c.txt").unwrap(), b"gamma");
+    assert!(
+        reader
+            .archive()
+            .files
+            .iter()
+            .any(|entry| entry.name() == "dir" && entry.is_directory())
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_raw_stream() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    let source = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(source.as_slice()), &Password::empty()).unwrap();
+    let entry = archive.files[0].clone();
+    let start = (SIGNATURE_HEADER_SIZE + archive.pack_pos()) as usize;
+    let end = start + archive.pack_sizes()[0] as usize;
+    let compressed = &source[start..end];
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let raw_entry = writer
+        .push_raw_stream(
+            ArchiveEntry::new_file("raw.txt"),
+            compressed,
+            entry.size,
+            entry.crc as u32,
+            vec![EncoderMethod::LZMA2.into()],
+        )
+        .unwrap();
+    assert_eq!(raw_entry.size, content.len() as u64);
+    assert_eq!(raw_entry.compressed_size, compressed.len() as u64);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("normal.txt"),
+            Some(b"normal".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("raw.txt").unwrap(), content);
+    assert_eq!(reader.read_file("normal.txt").unwrap(), b"normal");
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert!(
+        writer
+            .push_raw_stream(
+                ArchiveEntry::new_file("raw.txt"),
+                compressed,
+                entry.size,
+                entry.crc as u32,
+                vec![
+                    EncoderMethod::LZMA2.into(),
+                    EncoderMethod::DELTA_FILTER.into()
+                ],
+            )
+            .is_err()
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn read_file_range_from_solid_and_non_solid_archives() {
+    let content: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("first.bin"),
+            Some(b"first".as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(content.as_slice()))
+        .unwrap();
+    let non_solid = writer.finish().unwrap().into_inner();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("first.bin"),
+                ArchiveEntry::new_file("data.bin"),
+            ],
+            vec![
+                SourceReader::new(b"first".as_slice()),
+                SourceReader::new(content.as_slice()),
+            ],
+        )
+        .unwrap();
+    let solid = writer.finish().unwrap().into_inner();
+
+    for data in [non_solid, solid] {
+        let mut reader =
+            ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+        assert_eq!(
+            reader.read_file_range("data.bin", 0, 256).unwrap(),
+            &content[..256]
+        );
+        assert_eq!(
+            reader.read_file_range("data.bin", 5000, 100).unwrap(),
+            &content[5000..5100]
+        );
+        assert_eq!(
+            reader.read_file_range("data.bin", 9900, 1000).unwrap(),
+            &content[9900..]
+        );
+        assert!(
+            reader
+                .read_file_range("data.bin", 20_000, 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        let mut out = Vec::new();
+        let written = reader
+            .read_file_range_to_writer("data.bin", 256, 256, &mut out)
+            .unwrap();
+        assert_eq!(written, 256);
+        assert_eq!(out, &content[256..512]);
+        assert!(matches!(
+            reader.read_file_range("missing.bin", 0, 1),
+            Err(Error::FileNotFound)
+        ));
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_solid_from_lazy_path_sources() {
+    let temp_dir = tempdir().unwrap();
+    let mut entries = Vec::new();
+    let mut sources = Vec::new();
+    for i in 0..64 {
+        let path = temp_dir.path().join(format!("file{i}.txt"));
+        std::fs::write(&path, format!("content {i}")).unwrap();
+        entries.push(ArchiveEntry::new_file(&format!("file{i}.txt")));
+        sources.push(SourceReader::from_path(path));
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.push_archive_entries(entries, sources).unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    for i in 0..64 {
+        assert_eq!(
+            reader.read_file(&format!("file{i}.txt")).unwrap(),
+            format!("content {i}").as_bytes()
+        );
+    }
+
+    let missing = temp_dir.path().join("missing.txt");
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let Err(error) = writer.push_archive_entries(
+        vec![
+            ArchiveEntry::new_file("file0.txt"),
+            ArchiveEntry::new_file("missing.txt"),
+        ],
+        vec![
+            SourceReader::from_path(temp_dir.path().join("file0.txt")),
+            SourceReader::from_path(&missing),
+        ],
+    ) else {
+        panic!("missing source file must fail");
+    };
+    let error = error.to_string();
+    assert!(error.contains("missing.txt"), "{error}");
+    assert!(error.contains(&missing.display().to_string()), "{error}");
+    assert!(!error.contains("file0.txt"), "{error}");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn verify_pack_crcs_detects_corrupted_pack_stream() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("a.txt"),
+            Some(b"first file".as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("b.txt"),
+            Some(b"second file".as_slice()),
+        )
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+    assert!(archive.pack_crc(0).is_some());
+    assert!(archive.pack_crc(1).is_some());
+    assert!(archive.pack_crc(2).is_none());
+    assert!(
+        archive
+            .verify_pack_crcs(&mut Cursor::new(data.as_slice()))
+            .unwrap()
+    );
+
+    let (offset, _) = archive.pack_stream_ranges()[1];
+    data[offset as usize] ^= 0xFF;
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!(!reader.verify_pack_crcs().unwrap());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn store_pack_crcs_including_zero_crcs() {
+    let write = |store_pack_crcs: bool| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_store_pack_crcs(store_pack_crcs);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("a.txt"),
+                Some(b"first file".as_slice()),
+            )
+            .unwrap();
+        // The CRC of this stored packed stream is 0.
+        writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("zero_crc.bin"),
+                Some([0x9D, 0x0A, 0xD9, 0x6D].as_slice()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("b.txt"),
+                Some(b"second file".as_slice()),
+            )
+            .unwrap();
+        writer.finish().unwrap().into_inner()
+    };
+
+    let mut data = write(true);
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let ranges = reader.archive().pack_stream_ranges();
+    assert_eq!(ranges.len(), 3);
+    for (index, (offset, length)) in ranges.iter().copied().enumerate() {
+        let packed = &data[offset as usize..(offset + length) as usize];
+        assert_eq!(
+            reader.archive().pack_crc(index),
+            Some(crc32fast::hash(packed))
+        );
+    }
+    assert_eq!(reader.archive().pack_crc(1), Some(0));
+    assert!(reader.verify_pack_streams().unwrap().is_empty());
+    assert_eq!(reader.read_file("b.txt").unwrap(), b"second file");
+
+    let (offset, _) = ranges[2];
+    data[offset as usize] ^= 0xFF;
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.verify_pack_streams().unwrap(), [2]);
+
+    let data = write(false);
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!((0..3).all(|index| reader.archive().pack_crc(index).is_none()));
+    assert!(reader.verify_pack_streams().unwrap().is_empty());
+    assert_eq!(reader.read_file("a.txt").unwrap(), b"first file");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_entries_in_parallel() {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let mut contents: Vec<Vec<u8>> = (1..20).map(|i| text[..2000].repeat(i)).collect();
+    contents.push(Vec::new());
+    let entries = || {
+        let mut entries: Vec<_> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                (
+                    ArchiveEntry::new_file(&format!("file{i}.txt")),
+                    Some(data.as_slice()),
+                )
+            })
+            .collect();
+        entries.insert(3, (ArchiveEntry::new_directory("dir"), None));
+        entries
+    };
+    let configure = |writer: &mut ArchiveWriter<Cursor<Vec<u8>>>| {
+        writer.set_method_selector(Box::new(|entry: &ArchiveEntry| {
+            entry
+                .name()
+                .ends_with("5.txt")
+                .then(|| vec![EncoderMethod::COPY.into()])
+        }));
+    };
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    configure(&mut writer);
+    for (entry, reader) in entries() {
+        writer.push_archive_entry(entry, reader).unwrap();
+    }
+    let sequential = writer.finish().unwrap().into_inner();
+
+    // The output doesn't depend on the order in which the threads finish.
+    for max_in_flight_bytes in [0, 1, u64::MAX] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        configure(&mut writer);
+        writer.set_max_in_flight_bytes(max_in_flight_bytes);
+        writer.push_archive_entries_parallel(entries(), 4).unwrap();
+        assert_eq!(writer.finish().unwrap().into_inner(), sequential);
+    }
+
+    let mut reader =
+        ArchiveReader::new(Cursor::new(sequential.as_slice()), Password::empty()).unwrap();
+    for (i, data) in contents.iter().enumerate() {
+        assert_eq!(&reader.read_file(&format!("file{i}.txt")).unwrap(), data);
+    }
+    assert!(reader.entry_by_name("dir").unwrap().is_directory());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_entries_in_parallel_reports_errors() {
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("read failed"))
+        }
+    }
+
+    let entries: Vec<(ArchiveEntry, Option<Box<dyn Read + Send>>)> = vec![
+        (
+            ArchiveEntry::new_file("ok.txt"),
+            Some(Box::new(b"data".as_slice())),
+        ),
+        (
+            ArchiveEntry::new_file("failing.txt"),
+            Some(Box::new(FailingReader)),
+        ),
+        (
+            ArchiveEntry::new_file("after.txt"),
+            Some(Box::new(b"data".as_slice())),
+        ),
+    ];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let Err(error) = writer.push_archive_entries_parallel(entries, 2) else {
+        panic!("reading failing.txt must fail");
+    };
+    assert!(error.to_string().contains("failing.txt"), "{error}");
+    assert_eq!(writer.entries().len(), 1);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_partial_reads_verify_crc() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("first.txt"),
+                ArchiveEntry::new_file("second.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first solid file".as_slice()),
+                SourceReader::new(b"second solid file".as_slice()),
+            ],
+        )
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+
+    let read_first_bytes = |data: &[u8]| {
+        let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+        let mut first_bytes = Vec::new();
+        reader
+            .for_each_entries(|_, data| {
+                let mut byte = [0u8; 1];
+                data.read_exact(&mut byte)?;
+                first_bytes.push(byte[0]);
+                Ok(true)
+            })
+            .map(|_| first_bytes)
+    };
+    assert_eq!(read_first_bytes(&data).unwrap(), b"fs");
+
+    // Corrupt the last byte of the second entry, which the closure never reads.
+    let offset = 32 + "first solid file".len() + "second solid file".len() - 1;
+    data[offset] ^= 0xFF;
+    assert!(read_first_bytes(&data).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_without_crcs() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("with_crc.txt"),
+            Some(b"checked".as_slice()),
+        )
+        .unwrap();
+    writer.set_compute_crcs(false);
+    let entry = writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("single.txt"),
+            Some(b"not checked".as_slice()),
+        )
+        .unwrap();
+    assert!(!entry.has_crc);
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("solid1.txt"),
+                ArchiveEntry::new_file("solid2.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first solid file".as_slice()),
+                SourceReader::new(b"second solid file".as_slice()),
+            ],
+        )
+        .unwrap();
+    assert!(writer.entries()[2..].iter().all(|entry| !entry.has_crc));
+    writer.set_compute_crcs(true);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("last.txt"),
+            Some(b"checked again".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let has_crc: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .map(|entry| (entry.name(), entry.has_crc))
+        .collect();
+    assert_eq!(
+        has_crc,
+        [
+            ("with_crc.txt", true),
+            ("single.txt", false),
+            ("solid1.txt", false),
+            ("solid2.txt", false),
+            ("last.txt", true),
+        ]
+    );
+    assert_eq!(
+        reader.archive().files[0].crc,
+        crc32fast::hash(b"checked") as u64
+    );
+    assert!(reader.archive().pack_crc(0).is_some());
+    assert!(reader.archive().pack_crc(1).is_none());
+    assert!(reader.verify_pack_streams().unwrap().is_empty());
+    assert_eq!(reader.read_file("with_crc.txt").unwrap(), b"checked");
+    assert_eq!(reader.read_file("single.txt").unwrap(), b"not checked");
+    assert_eq!(
+        reader.read_file("solid2.txt").unwrap(),
+        b"second solid file"
+    );
+    assert_eq!(reader.read_file("last.txt").unwrap(), b"checked again");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_header_compression_modes() {
+    let write = |header_compression: HeaderCompression, entries: usize| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_header_compression(header_compression);
+        for i in 0..entries {
+            writer
+                .push_archive_entry(
+                    ArchiveEntry::new_file(&format!("dir/file{i}.txt")),
+                    Some(format!("content {i}").as_bytes()),
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    };
+    // Returns the first byte of the header, after checking that the header ends the archive.
+    let header_type = |data: &[u8]| {
+        let offset = u64::from_le_bytes(data[12..20].try_into().unwrap()) as usize;
+        let size = u64::from_le_bytes(data[20..28].try_into().unwrap()) as usize;
+        assert_eq!(32 + offset + size, data.len());
+        data[32 + offset]
+    };
+    const K_HEADER: u8 = 0x01;
+    const K_ENCODED_HEADER: u8 = 0x17;
+
+    for (header_compression, entries, expected) in [
+        (HeaderCompression::Auto, 1, K_HEADER),
+        (HeaderCompression::Auto, 50, K_ENCODED_HEADER),
+        (HeaderCompression::Always, 1, K_ENCODED_HEADER),
+        (HeaderCompression::Never, 50, K_HEADER),
+    ] {
+        let data = write(header_compression, entries);
+        assert_eq!(header_type(&data), expected, "{header_compression:?}");
+
+        let mut reader =
+            ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+        assert_eq!(reader.archive().files.len(), entries);
+        let name = format!("dir/file{}.txt", entries - 1);
+        assert_eq!(
+            reader.read_file(&name).unwrap(),
+            format!("content {}", entries - 1).as_bytes()
+        );
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_lzma2_with_independent_chunks_single_threaded() {
+    fn count_independent_lzma2_chunks(mut data: &[u8]) -> u64 {
+        let mut count = 0;
+        loop {
+            let control = data[0];
+            if control == 0x00 {
+                return count;
+            }
+            if control >= 0xE0 || control == 0x01 {
+                count += 1;
+            }
+            let chunk_len = if control >= 0x80 {
+                let compressed = u16::from_be_bytes([data[3], data[4]]) as usize + 1;
+                let header = if control >= 0xC0 { 6 } else { 5 };
+                header + compressed
+            } else {
+                3 + u16::from_be_bytes([data[1], data[2]]) as usize + 1
+            };
+            data = &data[chunk_len..];
+        }
+    }
+
+    let words = [
+        "lorem",
+        "ipsum",
+        "dolor",
+        "sit",
+        "amet",
+        "consectetur",
+        "adipiscing",
+    ];
+    let mut content = Vec::with_capacity(1024 * 1024);
+    let mut state = 12345u32;
+    while content.len() < 1024 * 1024 {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        content.extend_from_slice(words[(state >> 16) as usize % words.len()].as_bytes());
+        content.push(b' ');
+    }
+
+    let mut options = Lzma2Options::from_level(1);
+    options.set_dictionary_size(64 * 1024);
+    options.set_independent_chunk_size(256 * 1024);
+    assert_eq!(options.independent_chunk_size(), Some(256 * 1024));
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![options.into()]);
+    assert_eq!(writer.last_block_chunk_count(), None);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.txt"), Some(content.as_slice()))
+        .unwrap();
+    let expected_chunks = (content.len() as u64).div_ceil(256 * 1024);
+    assert_eq!(writer.last_block_chunk_count(), Some(expected_chunks));
+    let data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+    let (offset, length) = archive.pack_stream_ranges()[0];
+    let packed = &data[offset as usize..(offset + length) as usize];
+    assert_eq!(count_independent_lzma2_chunks(packed), expected_chunks);
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    reader.set_thread_count(4);
+    assert_eq!(reader.read_file("data.txt").unwrap(), content);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_entry_with_typed_windows_attributes() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("readonly.txt");
+    std::fs::write(&path, b"hello").unwrap();
+    let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&path, permissions).unwrap();
+
+    let mut entry = ArchiveEntry::from_path(&path, "readonly.txt".to_string());
+    #[cfg(windows)]
+    {
+        assert!(entry.has_windows_attributes);
+        assert!(
+            entry
+                .windows_attributes_typed()
+                .contains(WindowsAttributes::READONLY)
+        );
+    }
+    entry.set_windows_attributes_typed(WindowsAttributes::READONLY | WindowsAttributes::HIDDEN);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(entry, Some(b"hello".as_slice()))
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+    let entry = &archive.files[0];
+    assert!(entry.has_windows_attributes);
+    let attributes = entry.windows_attributes_typed();
+    assert_eq!(attributes, WindowsAttributes::from(0x3));
+    assert!(attributes.contains(WindowsAttributes::HIDDEN));
+    assert!(!attributes.contains(WindowsAttributes::SYSTEM));
+    assert_eq!(
+        attributes & WindowsAttributes::READONLY,
+        WindowsAttributes::READONLY
+    );
+
+    let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(&path, permissions).unwrap();
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn iterate_entries_with_block_information() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("dir/first.txt"),
+                ArchiveEntry::new_file("dir/second.txt"),
+            ],
+            vec![
+                SourceReader::new(b"first".as_slice()),
+                SourceReader::new(b"second".as_slice()),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("copy.txt"), Some(b"copy".as_slice()))
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let entries: Vec<_> = reader.entry_handles().collect();
+    let names: Vec<_> = entries.iter().map(|handle| handle.entry().name()).collect();
+    assert_eq!(
+        names,
+        [
+            "dir",
+            "dir/first.txt",
+            "dir/second.txt",
+            "empty.txt",
+            "copy.txt"
+        ]
+    );
+    for (index, handle) in entries.iter().enumerate() {
+        assert_eq!(handle.index(), index);
+        assert!(!handle.is_encrypted());
+    }
+
+    // Directory and empty file have no data.
+    for handle in [entries[0], entries[3]] {
+        assert_eq!(handle.block_index(), None);
+        assert_eq!(handle.compressed_size(), 0);
+        assert!(handle.methods().is_empty());
+    }
+
+    // Both files of the solid block share it, the packed size is attributed to the first one.
+    assert_eq!(entries[1].block_index(), Some(0));
+    assert_eq!(entries[2].block_index(), Some(0));
+    assert_eq!(
+        entries[1].compressed_size(),
+        reader.archive().pack_sizes()[0]
+    );
+    assert_eq!(entries[2].compressed_size(), 0);
+    assert_eq!(entries[1].methods(), [EncoderMethod::LZMA2]);
+    assert_eq!(
+        entries[1].methods(),
+        reader.file_methods("dir/second.txt").unwrap()
+    );
+
+    assert_eq!(entries[4].block_index(), Some(1));
+    assert_eq!(entries[4].methods(), [EncoderMethod::COPY]);
+    assert!(reader.file_methods("empty.txt").unwrap().is_empty());
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
+#[test]
+fn iterate_entries_of_encrypted_archive() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new(Password::new("secret")).into(),
+        EncoderMethod::LZMA2.into(),
+    ]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("secret.txt"),
+            Some(b"secret".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::new("secret")).unwrap();
+    let handle = reader.entry_handles().next().unwrap();
+    assert!(handle.is_encrypted());
+    assert_eq!(
+        handle.methods(),
+        [EncoderMethod::AES256_SHA256, EncoderMethod::LZMA2]
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_entries_sorted_by_extension() {
+    fn pseudo_random(mut seed: u32, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect()
+    }
+
+    // Files of the same type share most of their content, but are further apart than the
+    // dictionary size when stored in their original order.
+    let mut files = Vec::new();
+    for i in 0..4u8 {
+        for (seed, extension) in [(1, "c"), (2, "h"), (3, "TXT"), (4, "jpg")] {
+            let mut content = pseudo_random(seed, 3000);
+            content[0] = i;
+            files.push((format!("dir/file{i}.{extension}"), content));
+        }
+    }
+
+    let compress = |sort: bool| {
+        let mut options = Lzma2Options::from_level(6);
+        options.set_dictionary_size(4096);
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![options.into()]);
+        writer.set_sort_entries_for_compression(sort);
+        writer
+            .push_archive_entries(
+                files
+                    .iter()
+                    .map(|(name, _)| ArchiveEntry::new_file(name))
+                    .collect(),
+                files
+                    .iter()
+                    .map(|(_, content)| SourceReader::new(content.as_slice()))
+                    .collect(),
+            )
+            .unwrap();
+        writer.finish().unwrap().into_inner()
+    };
+
+    let unsorted = compress(false);
+    let sorted = compress(true);
+    assert!(
+        sorted.len() < unsorted.len(),
+        "sorted: {}, unsorted: {}",
+        sorted.len(),
+        unsorted.len()
+    );
+
+    let mut reader = ArchiveReader::new(Cursor::new(sorted.as_slice()), Password::empty()).unwrap();
+    let names: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .map(|file| file.name().to_string())
+        .collect();
+    assert_eq!(
+        &names[..5],
+        [
+            "dir/file0.c",
+            "dir/file1.c",
+            "dir/file2.c",
+            "dir/file3.c",
+            "dir/file0.h"
+        ]
+    );
+    assert_eq!(names[15], "dir/file3.TXT");
+    for (name, content) in &files {
+        assert_eq!(&reader.read_file(name).unwrap(), content);
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn extract_all_to_with_overwrite_modes() {
+    let old = NtTime::from_unix_timestamp(1_000_000_000).unwrap();
+    let new = NtTime::from_unix_timestamp(2_000_000_000).unwrap();
+    let existing =
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    for (name, modified) in [("older.txt", old), ("newer.txt", new), ("dir/new.txt", new)] {
+        let mut entry = ArchiveEntry::new_file(name);
+        entry.has_last_modified_date = true;
+        entry.last_modified_date = modified;
+        writer
+            .push_archive_entry(entry, Some(b"archive".as_slice()))
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let temp_dir = tempdir().unwrap();
+    let expected = [
+        (OverwriteMode::Never, 1, 2, ["existing", "existing"]),
+        (OverwriteMode::Always, 3, 0, ["archive", "archive"]),
+        (OverwriteMode::IfNewer, 2, 1, ["existing", "archive"]),
+    ];
+    for (mode, extracted, skipped, contents) in expected {
+        let dest = temp_dir.path().join(format!("{mode:?}"));
+        std::fs::create_dir_all(&dest).unwrap();
+        for name in ["older.txt", "newer.txt"] {
+            let file = File::create(dest.join(name)).unwrap();
+            std::io::Write::write_all(&mut &file, b"existing").unwrap();
+            file.set_modified(existing).unwrap();
+        }
+
+        let mut reader =
+            ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+        let options = ExtractOptions {
+            overwrite: mode,
+            ..Default::default()
+        };
+        let stats = reader.extract_all_to(&dest, &options).unwrap();
+        assert_eq!(
+            stats,
+            ExtractionStats {
+                extracted,
+                skipped,
+                failed: 0
+            },
+            "{mode:?}"
+        );
+
+        for (name, content) in ["older.txt", "newer.txt"].into_iter().zip(contents) {
+            assert_eq!(
+                std::fs::read_to_string(dest.join(name)).unwrap(),
+                content,
+                "{mode:?} {name}"
+            );
+        }
+        let metadata = std::fs::metadata(dest.join("dir/new.txt")).unwrap();
+        assert_eq!(NtTime::try_from(metadata.modified().unwrap()).unwrap(), new);
+    }
+
+    // A directory in place of a file can't be overwritten.
+    let dest = temp_dir.path().join("failed");
+    std::fs::create_dir_all(dest.join("dir/new.txt")).unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let stats = reader
+        .extract_all_to(&dest, &ExtractOptions::default())
+        .unwrap();
+    assert_eq!(
+        stats,
+        ExtractionStats {
+            extracted: 2,
+            skipped: 0,
+            failed: 1
+        }
+    );
+    assert_eq!(std::fs::read(dest.join("newer.txt")).unwrap(), b"archive");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn extract_all_to_leaves_no_partial_files() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(b"content".as_slice()))
+        .unwrap();
+    let mut data = writer.finish().unwrap().into_inner();
+    // Corrupt the stored data, so that the CRC check fails after it was read.
+    data[32] ^= 0xFF;
+
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("dest");
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert!(
+        reader
+            .extract_all_to(&dest, &ExtractOptions::default())
+            .is_err()
+    );
+    assert_eq!(std::fs::read_dir(&dest).unwrap().count(), 0);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn extract_all_to_with_path_safety_modes() {
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("nested/dest");
+
+    let mut reader =
+        ArchiveReader::open("tests/resources/hostile/traversal.7z", Password::empty()).unwrap();
+    let result = reader.extract_all_to(&dest, &ExtractOptions::default());
+    assert!(matches!(result, Err(Error::UnsafeEntryNames(_))));
+    assert!(!dest.join("safe.txt").exists());
+
+    let options = ExtractOptions::default().allow_unsafe_paths(true);
+    let stats = reader.extract_all_to(&dest, &options).unwrap();
+    assert_eq!(stats.extracted, 2);
+    assert_eq!(
+        std::fs::read(temp_dir.path().join("evil.txt")).unwrap(),
+        b"evil"
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+fn archive_with_files(names: &[&str]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    for name in names {
+        writer
+            .push_archive_entry(ArchiveEntry::new_file(name), Some(name.as_bytes()))
+            .unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_reports_all_unsafe_entry_names() {
+    let data = archive_with_files(&["../a.txt", "ok.txt", "dir/../../b.txt"]);
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("nested/dest");
+
+    match decompress(Cursor::new(data.as_slice()), &dest) {
+        Err(Error::UnsafeEntryNames(names)) => assert_eq!(names, ["../a.txt", "dir/../../b.txt"]),
+        result => panic!("unsafe names must be rejected, got {result:?}"),
+    }
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    match reader.extract_all_to(&dest, &ExtractOptions::default()) {
+        Err(Error::UnsafeEntryNames(names)) => assert_eq!(names, ["../a.txt", "dir/../../b.txt"]),
+        result => panic!("unsafe names must be rejected, got {result:?}"),
+    }
+    assert!(!dest.join("ok.txt").exists());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_with_extract_fn_and_options_allows_unsafe_paths() {
+    let data = archive_with_files(&["../a.txt"]);
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("dest");
+
+    let result = decompress_with_extract_fn_and_options(
+        Cursor::new(data.as_slice()),
+        &dest,
+        Password::empty(),
+        &ExtractOptions::default(),
+        default_entry_extract_fn,
+    );
+    assert!(matches!(result, Err(Error::UnsafeEntryNames(_))));
+
+    decompress_with_extract_fn_and_options(
+        Cursor::new(data.as_slice()),
+        &dest,
+        Password::empty(),
+        &ExtractOptions::default().allow_unsafe_paths(true),
+        default_entry_extract_fn,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read(temp_dir.path().join("a.txt")).unwrap(),
+        b"../a.txt"
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util", unix))]
+#[test]
+fn decompress_rejects_dangling_symlinks_in_dest() {
+    let data = archive_with_files(&["link"]);
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("dest");
+    std::fs::create_dir_all(&dest).unwrap();
+    let outside = temp_dir.path().join("outside.txt");
+    std::os::unix::fs::symlink(&outside, dest.join("link")).unwrap();
+
+    let result = decompress(Cursor::new(data.as_slice()), &dest);
+    assert!(matches!(result, Err(Error::UnsafeEntryNames(_))));
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let result = reader.extract_all_to(&dest, &ExtractOptions::default());
+    assert!(matches!(result, Err(Error::UnsafeEntryNames(_))));
+    assert!(!outside.exists());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn extract_all_to_calls_hook() {
+    let modified = NtTime::from_unix_timestamp(1_000_000_000).unwrap();
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    for name in ["dir/a.txt", "b.txt"] {
+        let mut entry = ArchiveEntry::new_file(name);
+        entry.has_last_modified_date = true;
+        entry.last_modified_date = modified;
+        writer
+            .push_archive_entry(entry, Some(name.as_bytes()))
+            .unwrap();
+    }
+    let data = writer.finish().unwrap().into_inner();
+
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("hook");
+    std::fs::create_dir_all(&dest).unwrap();
+    std::fs::write(dest.join("b.txt"), b"existing").unwrap();
+    // The hook restores the modification time itself.
+    let options = ExtractOptions {
+        overwrite: OverwriteMode::Never,
+        restore_mtime: false,
+        ..Default::default()
+    };
+    let mut extracted = Vec::new();
+    let stats = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty())
+        .unwrap()
+        .extract_all_to_with_hook(&dest, &options, |entry, path| {
+            assert!(path.exists());
+            if !entry.is_directory() {
+                let file = File::options().write(true).open(path)?;
+                file.set_modified(entry.last_modified_date().into())?;
+            }
+            extracted.push(entry.name().to_string());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(stats.extracted, 1);
+    assert_eq!(stats.skipped, 1);
+    // Skipped files are not passed to the hook.
+    extracted.sort();
+    assert_eq!(extracted, ["dir", "dir/a.txt"]);
+    let metadata = std::fs::metadata(dest.join("dir/a.txt")).unwrap();
+    assert_eq!(
+        NtTime::try_from(metadata.modified().unwrap()).unwrap(),
+        modified
+    );
+
+    // Errors of the hook abort the extraction.
+    let result = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty())
+        .unwrap()
+        .extract_all_to_with_hook(&temp_dir.path().join("abort"), &options, |_, _| {
+            Err(Error::Other("hook failed".into()))
+        });
+    assert!(matches!(result, Err(Error::Other(message)) if message == "hook failed"));
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn into_extract_all_consumes_reader() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir/empty"), None)
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("dir/a.txt"), Some(b"a".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("b.txt"), Some(b"b".as_slice()))
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let temp_dir = tempdir().unwrap();
+    let dest = temp_dir.path().join("default");
+    let stats = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty())
+        .unwrap()
+        .into_extract_all(&dest)
+        .unwrap();
+    assert_eq!(stats.extracted, 2);
+    assert_eq!(std::fs::read(dest.join("dir/a.txt")).unwrap(), b"a");
+    assert_eq!(std::fs::read(dest.join("b.txt")).unwrap(), b"b");
+    assert!(dest.join("dir/empty").is_dir());
+
+    std::fs::write(dest.join("b.txt"), b"existing").unwrap();
+    let options = ExtractOptions {
+        overwrite: OverwriteMode::Never,
+        ..Default::default()
+    };
+    let stats = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty())
+        .unwrap()
+        .into_extract_all_with_options(&dest, &options)
+        .unwrap();
+    assert_eq!(
+        stats,
+        ExtractionStats {
+            extracted: 0,
+            skipped: 2,
+            failed: 0
+        }
+    );
+    assert_eq!(std::fs::read(dest.join("b.txt")).unwrap(), b"existing");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_solid_blocks_with_different_methods() {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_solid_block_with_methods(
+            vec![
+                ArchiveEntry::new_file("stored/a.bin"),
+                ArchiveEntry::new_file("stored/b.bin"),
+            ],
+            vec![
+                SourceReader::new(b"already".as_slice()),
+                SourceReader::new(b"compressed".as_slice()),
+            ],
+            vec![EncoderMethod::COPY.into()],
+        )
+        .unwrap();
+    writer
+        .push_solid_block_with_methods(
+            vec![
+                ArchiveEntry::new_file("text/a.txt"),
+                ArchiveEntry::new_file("text/b.txt"),
+            ],
+            vec![
+                SourceReader::new(text.as_slice()),
+                SourceReader::new(text.as_slice()),
+            ],
+            vec![EncoderMethod::LZMA.into()],
+        )
+        .unwrap();
+    // The default content methods are not changed.
+    writer
+        .push_archive_entries(
+            vec![ArchiveEntry::new_file("default.txt")],
+            vec![SourceReader::new(text.as_slice())],
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.archive().blocks.len(), 3);
+    let expected = [
+        ("stored/a.bin", EncoderMethod::COPY, b"already".to_vec()),
+        ("stored/b.bin", EncoderMethod::COPY, b"compressed".to_vec()),
+        ("text/a.txt", EncoderMethod::LZMA, text.clone()),
+        ("text/b.txt", EncoderMethod::LZMA, text.clone()),
+        ("default.txt", EncoderMethod::LZMA2, text.clone()),
+    ];
+    for (name, method, content) in expected {
+        assert_eq!(reader.file_methods(name).unwrap(), [method], "{name}");
+        assert_eq!(reader.read_file(name).unwrap(), content, "{name}");
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_zero_length_entries() {
+    let mut empty_with_attributes = ArchiveEntry::new_file("readonly.txt");
+    empty_with_attributes.set_windows_attributes_typed(WindowsAttributes::READONLY);
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("empty.txt"), Some(b"".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer
+        .push_archive_entry(empty_with_attributes, Some(b"".as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("solid/a.txt"),
+                ArchiveEntry::new_file("solid/empty.txt"),
+                ArchiveEntry::new_file("solid/b.txt"),
+            ],
+            vec![
+                SourceReader::new(b"a".as_slice()),
+                SourceReader::new(b"".as_slice()),
+                SourceReader::new(b"b".as_slice()),
+            ],
+        )
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![ArchiveEntry::new_file("solid/only_empty.txt")],
+            vec![SourceReader::new(b"".as_slice())],
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let mut reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.archive().blocks.len(), 1);
+    for name in [
+        "empty.txt",
+        "readonly.txt",
+        "solid/empty.txt",
+        "solid/only_empty.txt",
+    ] {
+        let entry = reader.archive().files.iter().find(|e| e.name() == name);
+        let entry = entry.unwrap();
+        assert!(!entry.is_directory(), "{name}");
+        assert!(!entry.has_stream(), "{name}");
+        assert_eq!(entry.size(), 0, "{name}");
+        assert_eq!(reader.read_file(name).unwrap(), b"", "{name}");
+    }
+    let readonly = reader
+        .archive()
+        .files
+        .iter()
+        .find(|e| e.name() == "readonly.txt");
+    assert_eq!(
+        readonly.unwrap().windows_attributes_typed(),
+        WindowsAttributes::READONLY
+    );
+    let dir = reader.archive().files.iter().find(|e| e.name() == "dir");
+    assert!(dir.unwrap().is_directory());
+    assert_eq!(reader.read_file("solid/a.txt").unwrap(), b"a");
+    assert_eq!(reader.read_file("solid/b.txt").unwrap(), b"b");
+    // The entries keep the order they were pushed in.
+    let names: Vec<_> = reader.archive().files.iter().map(|e| e.name()).collect();
+    assert_eq!(
+        names,
+        [
+            "empty.txt",
+            "dir",
+            "readonly.txt",
+            "solid/a.txt",
+            "solid/empty.txt",
+            "solid/b.txt",
+            "solid/only_empty.txt"
+        ]
+    );
+
+    let temp_dir = tempdir().unwrap();
+    decompress(Cursor::new(data.as_slice()), temp_dir.path()).unwrap();
+    assert!(temp_dir.path().join("empty.txt").is_file());
+    assert!(temp_dir.path().join("readonly.txt").is_file());
+    assert!(temp_dir.path().join("solid/empty.txt").is_file());
+    assert!(temp_dir.path().join("dir").is_dir());
+
+    // The empty file between the entries of the solid block doesn't disturb filtering.
+    let filtered = temp_dir.path().join("filtered");
+    let calls = std::cell::Cell::new(0);
+    let stats = decompress_with_filter(Cursor::new(data.as_slice()), &filtered, |entry| {
+        calls.set(calls.get() + 1);
+        entry.name().starts_with("solid/") && entry.name() != "solid/a.txt"
+    })
+    .unwrap();
+    assert_eq!(stats.written, 3);
+    // The filter is called once per entry.
+    assert_eq!(calls.get(), names.len());
+    assert_eq!(std::fs::read(filtered.join("solid/b.txt")).unwrap(), b"b");
+    assert!(filtered.join("solid/empty.txt").is_file());
+    assert!(!filtered.join("solid/a.txt").exists());
+}
+
+#[cfg(all(windows, feature = "compress", feature = "util"))]
+#[test]
+fn decompress_restores_windows_attributes() {
+    let temp_dir = tempdir().unwrap();
+    let mut file = ArchiveEntry::new_file("readonly.txt");
+    file.set_windows_attributes_typed(WindowsAttributes::READONLY | WindowsAttributes::HIDDEN);
+    let mut dir = ArchiveEntry::new_directory("hidden");
+    dir.set_windows_attributes_typed(WindowsAttributes::DIRECTORY | WindowsAttributes::HIDDEN);
+
+    let dest = temp_dir.path().join("attributes.7z");
+    let mut writer = ArchiveWriter::create(&dest).unwrap();
+    writer.push_archive_entry::<&[u8]>(dir, None).unwrap();
+    writer
+        .push_archive_entry(file, Some(b"read only".as_slice()))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let attributes = |path: &std::path::Path| {
+        use std::os::windows::fs::MetadataExt;
+        WindowsAttributes::from(std::fs::metadata(path).unwrap().file_attributes())
+    };
+
+    // The read-only attribute is only restored with `restore_readonly`.
+    let decompress_dest = temp_dir.path().join("decompress");
+    decompress_file(&dest, &decompress_dest).unwrap();
+    let file_path = decompress_dest.join("readonly.txt");
+    assert!(
+        !std::fs::metadata(&file_path)
+            .unwrap()
+            .permissions()
+            .readonly()
+    );
+    assert!(attributes(&file_path).contains(WindowsAttributes::HIDDEN));
+    assert!(attributes(&decompress_dest.join("hidden")).contains(WindowsAttributes::HIDDEN));
+
+    let decompress_dest = temp_dir.path().join("decompress_readonly");
+    let options = ExtractOptions {
+        restore_readonly: true,
+        ..Default::default()
+    };
+    decompress_file_with_options(&dest, &decompress_dest, &options).unwrap();
+    let file_path = decompress_dest.join("readonly.txt");
+    assert!(
+        std::fs::metadata(&file_path)
+            .unwrap()
+            .permissions()
+            .readonly()
+    );
+    assert!(attributes(&file_path).contains(WindowsAttributes::HIDDEN));
+
+    let mut permissions = std::fs::metadata(&file_path).unwrap().permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    std::fs::set_permissions(&file_path, permissions).unwrap();
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compressed_sizes_match_reader() {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("single.txt"), Some(text.as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("solid/a.txt"),
+                ArchiveEntry::new_file("solid/empty.txt"),
+                ArchiveEntry::new_file("solid/b.txt"),
+            ],
+            vec![
+                SourceReader::new(text.as_slice()),
+                SourceReader::new(b"".as_slice()),
+                SourceReader::new(&text[..1000]),
+            ],
+        )
+        .unwrap();
+    let written: Vec<_> = writer
+        .entries()
+        .iter()
+        .map(|entry| {
+            (
+                entry.name().to_string(),
+                entry.compressed_size,
+                entry.compressed_crc,
+            )
+        })
+        .collect();
+    let data = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+    let read: Vec<_> = archive
+        .files
+        .iter()
+        .map(|entry| {
+            (
+                entry.name().to_string(),
+                entry.compressed_size,
+                entry.compressed_crc,
+            )
+        })
+        .collect();
+    assert_eq!(written, read);
+
+    let pack_sizes: u64 = archive
+        .pack_stream_ranges()
+        .iter()
+        .map(|(_, length)| length)
+        .sum();
+    let compressed_sizes: u64 = archive
+        .files
+        .iter()
+        .map(|entry| entry.compressed_size)
+        .sum();
+    assert_eq!(compressed_sizes, pack_sizes);
+    assert_eq!(read[2].1, 0);
+    assert!(read[1].1 > 0);
+}
+
+/// Archive storage that doesn't keep the entry data in memory. All bytes between the signature
+/// header and `data_end` are assumed to be zero.
+#[cfg(all(feature = "compress", feature = "util"))]
+struct SparseArchive {
+    signature_header: [u8; 32],
+    data_end: u64,
+    tail: Vec<u8>,
+    pos: u64,
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+impl std::io::Write for SparseArchive {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = if self.pos < 32 {
+            let len = buf.len().min(32 - self.pos as usize);
+            self.signature_header[self.pos as usize..][..len].copy_from_slice(&buf[..len]);
+            len
+        } else if self.pos < self.data_end {
+            assert!(buf.iter().all(|&b| b == 0), "entry data must be zero");
+            buf.len().min((self.data_end - self.pos) as usize)
+        } else {
+            let offset = (self.pos - self.data_end) as usize;
+            if self.tail.len() < offset + buf.len() {
+                self.tail.resize(offset + buf.len(), 0);
+            }
+            self.tail[offset..][..buf.len()].copy_from_slice(buf);
+            buf.len()
+        };
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+impl Read for SparseArchive {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = if self.pos < 32 {
+            let len = buf.len().min(32 - self.pos as usize);
+            buf[..len].copy_from_slice(&self.signature_header[self.pos as usize..][..len]);
+            len
+        } else if self.pos < self.data_end {
+            let len = buf.len().min((self.data_end - self.pos) as usize);
+            buf[..len].fill(0);
+            len
+        } else {
+            let tail = self
+                .tail
+                .get((self.pos - self.data_end) as usize..)
+                .unwrap_or_default();
+            let len = buf.len().min(tail.len());
+            buf[..len].copy_from_slice(&tail[..len]);
+            len
+        };
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+impl std::io::Seek for SparseArchive {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let end = self.data_end + self.tail.len() as u64;
+        self.pos = match pos {
+            std::io::SeekFrom::Start(pos) => pos,
+            std::io::SeekFrom::End(offset) => end.checked_add_signed(offset).unwrap(),
+            std::io::SeekFrom::Current(offset) => self.pos.checked_add_signed(offset).unwrap(),
+        };
+        Ok(self.pos)
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+#[ignore = "compresses 5 GiB of data"]
+fn compress_entry_larger_than_4_gib() {
+    const SIZE: u64 = 5 * 1024 * 1024 * 1024;
+    let archive = SparseArchive {
+        signature_header: [0; 32],
+        data_end: 32 + SIZE,
+        tail: Vec::new(),
+        pos: 0,
+    };
+    let mut writer = ArchiveWriter::new(archive).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    let entry = writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("large.bin"),
+            Some(std::io::repeat(0).take(SIZE)),
+        )
+        .unwrap();
+    assert_eq!(entry.size, SIZE);
+    assert_eq!(entry.compressed_size, SIZE);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("small.txt"),
+            Some(b"small".as_slice()),
+        )
+        .unwrap();
+    let mut archive = writer.finish().unwrap();
+
+    archive.pos = 0;
+    let mut reader = ArchiveReader::new(archive, Password::empty()).unwrap();
+    let files = &reader.archive().files;
+    assert_eq!(files[0].name(), "large.bin");
+    assert_eq!(files[0].size(), SIZE);
+    assert_eq!(files[0].compressed_size, SIZE);
+    assert_eq!(reader.archive().pack_stream_ranges()[1], (32 + SIZE, 5));
+    assert_eq!(reader.read_file("small.txt").unwrap(), b"small");
+}
+
+/// Yields `remaining` bytes of a repeated pattern.
+#[cfg(all(feature = "compress", feature = "util", target_pointer_width = "64"))]
+struct RepeatedBytes {
+    pattern: &'static [u8],
+    pos: usize,
+    remaining: u64,
+}
+
+#[cfg(all(feature = "compress", feature = "util", target_pointer_width = "64"))]
+impl Read for RepeatedBytes {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = (buf.len() as u64).min(self.remaining) as usize;
+        for byte in &mut buf[..len] {
+            *byte = self.pattern[self.pos];
+            self.pos = (self.pos + 1) % self.pattern.len();
+        }
+        self.remaining -= len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util", target_pointer_width = "64"))]
+#[test]
+#[ignore = "compresses and decompresses 5 GiB of data"]
+fn compress_and_decompress_lzma2_entry_larger_than_4_gib() {
+    const SIZE: u64 = 5 * 1024 * 1024 * 1024 + 3;
+    const PATTERN: &[u8] = b"sevenz-rust2 stress test\n";
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![Lzma2Options::from_level(1).into()]);
+    let entry = writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("large.txt"),
+            Some(RepeatedBytes {
+                pattern: PATTERN,
+                pos: 0,
+                remaining: SIZE,
+            }),
+        )
+        .unwrap();
+    assert_eq!(entry.size, SIZE);
+    let compressed_size = entry.compressed_size;
+    assert!(compressed_size > 0 && compressed_size < u32::MAX as u64);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("small.txt"),
+            Some(b"small".as_slice()),
+        )
+        .unwrap();
+    let archive = writer.finish().unwrap();
+
+    let mut reader = ArchiveReader::new(archive, Password::empty()).unwrap();
+    let files = &reader.archive().files;
+    assert_eq!(files[0].size(), SIZE);
+    assert_eq!(files[0].compressed_size, compressed_size);
+    assert_eq!(reader.archive().pack_stream_ranges()[0].1, compressed_size);
+
+    let mut sizes = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            let mut expected = RepeatedBytes {
+                pattern: PATTERN,
+                pos: 0,
+                remaining: entry.size(),
+            };
+            let mut buf = vec![0; 1024 * 1024];
+            let mut expected_buf = vec![0; buf.len()];
+            let mut read = 0u64;
+            loop {
+                let n = data.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                if entry.name() == "large.txt" {
+                    expected.read_exact(&mut expected_buf[..n])?;
+                    assert_eq!(buf[..n], expected_buf[..n]);
+                }
+                read += n as u64;
+            }
+            sizes.push(read);
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(sizes, [SIZE, 5]);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_multivolume_archive() {
+    const VOLUME_SIZE: u64 = 64 * 1024;
+    let temp_dir = tempdir().unwrap();
+    let base_path = temp_dir.path().join("archive.7z");
+    let content: Vec<u8> = (0..150 * 1024u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+        .collect();
+
+    let mut writer = ArchiveWriter::new_multivolume(&base_path, VOLUME_SIZE).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(content.as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("small.txt"),
+            Some(b"small".as_slice()),
+        )
+        .unwrap();
+    let volumes = writer.finish().unwrap();
+    assert_eq!(volumes.volume_count(), 3);
+    let paths = volumes.volume_paths();
+    assert!(paths[0].to_string_lossy().ends_with("archive.7z.001"));
+    assert!(paths[2].to_string_lossy().ends_with("archive.7z.003"));
+    for path in &paths[..2] {
+        assert_eq!(std::fs::metadata(path).unwrap().len(), VOLUME_SIZE);
+    }
+    // The signature header is in the first volume.
+    assert!(
+        std::fs::read(&paths[0])
+            .unwrap()
+            .starts_with(b"7z\xBC\xAF\x27\x1C")
+    );
+
+    let volumes = MultiVolumeReader::open(&base_path).unwrap();
+    assert_eq!(volumes.volume_paths(), paths);
+    let mut reader = ArchiveReader::new(volumes, Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.bin").unwrap(), content);
+    assert_eq!(reader.read_file("small.txt").unwrap(), b"small");
+
+    assert!(MultiVolumeReader::open(temp_dir.path().join("missing.7z")).is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_sfx_prefix() {
+    let stub = b"#!/bin/sh\necho not a real extractor\nexit 1\n";
+    let mut writer = ArchiveWriter::new_with_prefix(Cursor::new(Vec::new()), stub).unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("hello.txt"),
+            Some(b"hello world".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+    assert!(data.starts_with(stub));
+    assert!(data[stub.len()..].starts_with(b"7z\xBC\xAF\x27\x1C"));
+
+    let options = ArchiveReadOptions::default().with_search_sfx(true);
+    let mut reader =
+        ArchiveReader::with_options(Cursor::new(data.as_slice()), Password::empty(), &options)
+            .unwrap();
+    assert_eq!(reader.archive().base_offset(), stub.len() as u64);
+    assert_eq!(reader.read_file("hello.txt").unwrap(), b"hello world");
+    assert!(reader.verify_pack_crcs().unwrap());
+    // The archive without the stub is a regular archive.
+    let mut reader =
+        ArchiveReader::new(Cursor::new(&data[stub.len()..]), Password::empty()).unwrap();
+    assert_eq!(reader.archive().base_offset(), 0);
+    assert_eq!(reader.read_file("hello.txt").unwrap(), b"hello world");
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_into_bytes() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(b"first".as_slice()))
+        .unwrap();
+    let data = writer.finish_into_bytes().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("a.txt").unwrap(), b"first");
+
+    let entries = vec![
+        ("a.txt".to_string(), b"first".to_vec()),
+        ("dir/b.txt".to_string(), b"second".repeat(100)),
+        ("empty.txt".to_string(), Vec::new()),
+    ];
+    for method in [EncoderMethod::COPY, EncoderMethod::LZMA2] {
+        let data = create_archive_bytes(entries.clone(), method).unwrap();
+        let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+        assert_eq!(reader.archive().files.len(), entries.len());
+        for (name, content) in &entries {
+            assert_eq!(&reader.read_file(name).unwrap(), content);
+        }
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn decompress_with_glob_filter() {
+    let files: [(&str, &[u8]); 6] = [
+        ("docs/a.md", b"# a"),
+        ("docs/sub/b.md", b"# b"),
+        ("docs/c.txt", b"c"),
+        ("src/main.rs", b"fn main() {}"),
+        ("README.md", b"# readme"),
+        ("docs/empty.md", b""),
+    ];
+    let expected = ["docs/a.md", "docs/sub/b.md", "docs/empty.md"];
+
+    for solid in [false, true] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("docs"), None)
+            .unwrap();
+        if solid {
+            let (entries, readers) = files
+                .iter()
+                .map(|(name, data)| (ArchiveEntry::new_file(name), SourceReader::new(*data)))
+                .unzip();
+            writer.push_archive_entries(entries, readers).unwrap();
+        } else {
+            for (name, data) in files {
+                writer
+                    .push_archive_entry(ArchiveEntry::new_file(name), Some(data))
+                    .unwrap();
+            }
+        }
+        let mut data = writer.finish_into_bytes().unwrap();
+        // Blocks are not decoded after their last matching entry, so corrupting the data of
+        // later entries is unnoticed.
+        let pos = data
+            .windows(12)
+            .position(|window| window == b"fn main() {}")
+            .unwrap();
+        data[pos] ^= 0xFF;
+
+        let temp_dir = tempdir().unwrap();
+        let stats = decompress_with_filter(
+            Cursor::new(data),
+            temp_dir.path(),
+            glob_filter("docs/**/*.md"),
+        )
+        .unwrap();
+        assert_eq!(stats.written, expected.len());
+        assert_eq!(stats.skipped, 4);
+        assert_eq!(stats.skipped_blocks, if solid { 0 } else { 3 });
+        for (name, content) in files {
+            let path = temp_dir.path().join(name);
+            if expected.contains(&name) {
+                assert_eq!(std::fs::read(path).unwrap(), content);
+            } else {
+                assert!(!path.exists(), "{name}");
+            }
+        }
+    }
+
+    assert!(glob_matches("docs/**/*.md", "docs/a.md"));
+    assert!(glob_matches("docs/**/*.md", "docs/a/b/c.md"));
+    assert!(!glob_matches("docs/**/*.md", "docs/a.txt"));
+    assert!(!glob_matches("docs/*.md", "docs/a/b.md"));
+    assert!(glob_matches("**", "a/b"));
+    assert!(glob_matches("*.t?t", "file.txt"));
+    assert!(glob_matches("a*b*c", "aXbYbZc"));
+    assert!(!glob_matches("a*b*c", "aXbYbZ"));
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "sha256"))]
+#[test]
+fn compress_with_sha256_digests() {
+    use sha2::{Digest, Sha256};
+
+    let contents: [&[u8]; 3] = [b"first file", b"second file", b"third file"];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_store_sha256(true);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("single.txt"), Some(contents[0]))
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("dir/solid1.txt"),
+                ArchiveEntry::new_file("dir/solid2.txt"),
+            ],
+            vec![
+                SourceReader::new(contents[1]),
+                SourceReader::new(contents[2]),
+            ],
+        )
+        .unwrap();
+    writer.set_store_sha256(false);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("unhashed.txt"),
+            Some(b"data".as_slice()),
+        )
+        .unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let reader = ArchiveReader::new(Cursor::new(data.as_slice()), Password::empty()).unwrap();
+    let digests: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .map(|entry| (entry.name(), entry.sha256()))
+        .collect();
+    let sha256 = |data: &[u8]| Some(<[u8; 32]>::from(Sha256::digest(data)));
+    assert_eq!(
+        digests,
+        [
+            ("single.txt", sha256(contents[0])),
+            ("dir", None),
+            ("dir/solid1.txt", sha256(contents[1])),
+            ("dir/solid2.txt", sha256(contents[2])),
+            ("unhashed.txt", None),
+        ]
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_metadata_set_after_push() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    assert!(writer.last_entry_mut().is_none());
+
+    // A reader with an unknown length, like a network stream.
+    let source = std::io::Read::chain(&b"hello "[..], &b"world"[..]);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("download"), Some(source))
+        .unwrap();
+    let entry = writer.last_entry_mut().unwrap();
+    assert_eq!(entry.size, 11);
+    entry.name = "download.txt".to_string();
+    entry.last_modified_date = NtTime::new(133_000_000_000_000_000);
+    entry.has_last_modified_date = true;
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(archive), Password::empty()).unwrap();
+    let entry = reader.entry_by_name("download.txt").unwrap();
+    assert_eq!(
+        u64::from(entry.last_modified_date()),
+        133_000_000_000_000_000
+    );
+    assert_eq!(reader.read_file("download.txt").unwrap(), b"hello world");
+    assert!(reader.entry_by_name("download").is_none());
+}
+
+/// Writes an archive with a plain header and an empty file `name`, so that the names can be
+/// patched by the tests.
+#[cfg(all(feature = "compress", feature = "util"))]
+fn archive_with_plain_names(name: &str) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_header_compression(HeaderCompression::Never);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file(name), None::<&[u8]>)
+        .unwrap();
+    writer.finish_into_bytes().unwrap()
+}
+
+/// Updates the size and CRC of the next header and the CRC of the start header.
+#[cfg(all(feature = "compress", feature = "util"))]
+fn fix_header(bytes: &mut [u8], header_size: usize) {
+    let header_offset = 32 + u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+    let header_crc = crc32fast::hash(&bytes[header_offset..header_offset + header_size]);
+    bytes[20..28].copy_from_slice(&(header_size as u64).to_le_bytes());
+    bytes[28..32].copy_from_slice(&header_crc.to_le_bytes());
+    let start_header_crc = crc32fast::hash(&bytes[12..32]);
+    bytes[8..12].copy_from_slice(&start_header_crc.to_le_bytes());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn read_names_with_invalid_utf16() {
+    let mut bytes = archive_with_plain_names("file.txt");
+    let header_size = u64::from_le_bytes(bytes[20..28].try_into().unwrap()) as usize;
+    let name: Vec<u8> = "file.txt"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let position = bytes
+        .windows(name.len())
+        .position(|window| window == name)
+        .unwrap();
+    // Replace the `f` with an unpaired surrogate.
+    bytes[position..position + 2].copy_from_slice(&0xD800u16.to_le_bytes());
+    fix_header(&mut bytes, header_size);
+
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty()).unwrap();
+    let entry = &archive.files[0];
+    assert_eq!(entry.name(), "\u{FFFD}ile.txt");
+    let mut raw_name = vec![0xD800];
+    raw_name.extend("ile.txt".encode_utf16());
+    assert!(matches!(entry.raw_name(), Cow::Borrowed(_)));
+    assert_eq!(entry.raw_name(), raw_name.as_slice());
+
+    let options = ArchiveReadOptions::default().with_strict_names(true);
+    let result = Archive::read_with_options(
+        &mut Cursor::new(bytes.as_slice()),
+        &Password::empty(),
+        &options,
+    );
+    assert!(
+        matches!(
+            result,
+            Err(Error::CorruptHeader {
+                section: "file names",
+                ..
+            })
+        ),
+        "{result:?}"
+    );
+    let result =
+        ArchiveReader::with_options(Cursor::new(bytes.as_slice()), Password::empty(), &options);
+    assert!(
+        matches!(result, Err(Error::CorruptHeader { .. })),
+        "{:?}",
+        result.err()
+    );
+
+    // Valid names don't store their code units twice.
+    let bytes = archive_with_plain_names("file.txt");
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty()).unwrap();
+    assert!(matches!(archive.files[0].raw_name(), Cow::Owned(_)));
+    assert_eq!(
+        archive.files[0].raw_name(),
+        "file.txt".encode_utf16().collect::<Vec<_>>()
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn read_names_without_trailing_nul() {
+    let bytes = archive_with_plain_names("file.txt");
+    let header_size = u64::from_le_bytes(bytes[20..28].try_into().unwrap()) as usize;
+    // kName, the size of the external flag and the name with its NUL, and the external flag.
+    let mut property = vec![0x11, 1 + 18, 0];
+    property.extend("file.txt".encode_utf16().flat_map(u16::to_le_bytes));
+    let position = bytes
+        .windows(property.len())
+        .position(|window| window == property)
+        .unwrap();
+    let nul = position + property.len();
+    assert_eq!(bytes[nul..nul + 2], [0, 0]);
+
+    let mut bytes = [&bytes[..nul], &bytes[nul + 2..]].concat();
+    bytes[position + 1] -= 2;
+    fix_header(&mut bytes, header_size - 2);
+
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(archive.files.len(), 1);
+    assert_eq!(archive.files[0].name(), "file.txt");
+    assert!(matches!(archive.files[0].raw_name(), Cow::Owned(_)));
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn duplicate_entry_policy_reject() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_duplicate_entry_policy(DuplicateEntryPolicy::Reject);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"first"[..]))
+        .unwrap();
+    let result = writer.push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"second"[..]));
+    assert!(matches!(result, Err(Error::DuplicateEntry(name)) if name == "a.txt"));
+
+    // Duplicates within one solid block are rejected before anything is added.
+    let result = writer.push_archive_entries(
+        vec![
+            ArchiveEntry::new_file("b.txt"),
+            ArchiveEntry::new_file("b.txt"),
+        ],
+        vec![
+            SourceReader::new(&b"first"[..]),
+            SourceReader::new(&b"second"[..]),
+        ],
+    );
+    assert!(matches!(result, Err(Error::DuplicateEntry(name)) if name == "b.txt"));
+    assert_eq!(writer.entries().len(), 1);
+
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("b.txt"), Some(&b"third"[..]))
+        .unwrap();
+    let bytes = writer.finish_into_bytes().unwrap();
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty()).unwrap();
+    let names: Vec<_> = archive.files.iter().map(|entry| entry.name()).collect();
+    assert_eq!(names, ["a.txt", "b.txt"]);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn duplicate_entry_policy_reject_after_failed_push_and_rename() {
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("read failed"))
+        }
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_duplicate_entry_policy(DuplicateEntryPolicy::Reject);
+    // A failed push doesn't keep the name.
+    let result = writer.push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(FailingReader));
+    assert!(result.is_err());
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"a"[..]))
+        .unwrap();
+
+    // A renamed entry frees its old name and is checked with its new one.
+    writer.last_entry_mut().unwrap().name = "b.txt".to_string();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"a"[..]))
+        .unwrap();
+    writer.last_entry_mut().unwrap().name = "b.txt".to_string();
+    let result = writer.push_archive_entry(ArchiveEntry::new_file("c.txt"), Some(&b"c"[..]));
+    assert!(matches!(result, Err(Error::DuplicateEntry(name)) if name == "b.txt"));
+    writer.last_entry_mut().unwrap().name = "c.txt".to_string();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("d.txt"), Some(&b"d"[..]))
+        .unwrap();
+
+    writer.last_entry_mut().unwrap().name = "b.txt".to_string();
+    assert!(writer.finish_into_bytes().is_err());
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn duplicate_entry_policy_warn() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+    fn warn(name: &str) {
+        assert_eq!(name, "a.txt");
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_duplicate_entry_policy(DuplicateEntryPolicy::Warn(warn));
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"first"[..]))
+        .unwrap();
+    assert_eq!(WARNINGS.load(Ordering::Relaxed), 0);
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"second"[..]))
+        .unwrap();
+    assert_eq!(WARNINGS.load(Ordering::Relaxed), 1);
+
+    let bytes = writer.finish_into_bytes().unwrap();
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(archive.files.len(), 2);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn duplicate_entry_policy_allow() {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"first"[..]))
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("a.txt"), Some(&b"second"[..]))
+        .unwrap();
+
+    let bytes = writer.finish_into_bytes().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(bytes.as_slice()), Password::empty()).unwrap();
+    let mut contents = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            assert_eq!(entry.name(), "a.txt");
+            let mut content = String::new();
+            data.read_to_string(&mut content)?;
+            contents.push(content);
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(contents, ["first", "second"]);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn stored_metadata_shrinks_header() {
+    fn write(stored_metadata: StoredMetadata) -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_header_compression(HeaderCompression::Never);
+        writer.set_stored_metadata(stored_metadata);
+        let time = NtTime::from(133_000_000_000_000_000);
+        for i in 0..10_000 {
+            let entry = ArchiveEntry::new_file(&format!("file{i}.txt"))
+                .with_last_modified_date(time)
+                .with_creation_date(time)
+                .with_access_date(time)
+                .with_windows_attributes(0x20);
+            writer.push_archive_entry::<&[u8]>(entry, None).unwrap();
+        }
+        writer.finish_into_bytes().unwrap()
+    }
+    let header_size = |bytes: &[u8]| u64::from_le_bytes(bytes[20..28].try_into().unwrap());
+
+    let all = write(StoredMetadata::ALL);
+    let mtime_only = write(StoredMetadata {
+        mtime: true,
+        ..StoredMetadata::NONE
+    });
+    let none = write(StoredMetadata::NONE);
+    assert!(header_size(&none) < header_size(&mtime_only));
+    assert!(header_size(&mtime_only) < header_size(&all));
+
+    let archive =
+        Archive::read(&mut Cursor::new(mtime_only.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(archive.files.len(), 10_000);
+    let entry = &archive.files[1234];
+    assert_eq!(entry.name(), "file1234.txt");
+    assert!(entry.has_last_modified_date);
+    assert!(!entry.has_creation_date && !entry.has_access_date);
+    assert!(!entry.has_windows_attributes);
+
+    let archive = Archive::read(&mut Cursor::new(none.as_slice()), &Password::empty()).unwrap();
+    assert_eq!(archive.files.len(), 10_000);
+    assert!(
+        archive
+            .files
+            .iter()
+            .all(|entry| !entry.has_last_modified_date)
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "digest"))]
+#[test]
+fn compute_entry_digest() {
+    use sha2::{Digest, Sha256};
+
+    let contents: [&[u8]; 3] = [b"first entry", b"second entry", b"third entry"];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entries(
+            (0..3)
+                .map(|i| ArchiveEntry::new_file(&format!("{i}.txt")))
+                .collect(),
+            contents
+                .iter()
+                .map(|data| SourceReader::new(*data))
+                .collect(),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    let bytes = writer.finish_into_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(bytes), Password::empty()).unwrap();
+    assert!(reader.archive().is_solid);
+    for (i, data) in contents.iter().enumerate() {
+        let name = format!("{i}.txt");
+        assert_eq!(reader.entry_crc(&name), Some(crc32fast::hash(data)));
+        let digest = reader.compute_entry_digest::<Sha256>(&name).unwrap();
+        assert_eq!(digest, Sha256::digest(data));
+    }
+    assert_eq!(reader.entry_crc("empty.txt"), None);
+    assert_eq!(
+        reader.compute_entry_digest::<Sha256>("empty.txt").unwrap(),
+        Sha256::digest(b"")
+    );
+    assert_eq!(reader.entry_crc("missing.txt"), None);
+    assert!(matches!(
+        reader.compute_entry_digest::<Sha256>("missing.txt"),
+        Err(Error::FileNotFound)
+    ));
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "aes256"))]
+#[test]
+fn compress_with_aes_wrapped_codecs() {
+    let mut codecs: Vec<EncoderConfiguration> = vec![
+        EncoderMethod::COPY.into(),
+        EncoderMethod::LZMA.into(),
+        EncoderMethod::LZMA2.into(),
+        Lzma2Options::from_level_mt(6, 2, 64 * 1024).into(),
+        DeltaOptions::from_distance(4).into(),
+        EncoderMethod::BCJ_X86_FILTER.into(),
+    ];
+    #[cfg(feature = "ppmd")]
+    codecs.push(EncoderMethod::PPMD.into());
+    #[cfg(feature = "bzip2")]
+    codecs.push(EncoderMethod::BZIP2.into());
+    #[cfg(feature = "brotli")]
+    codecs.extend([
+        BrotliOptions::default().with_skippable_frame_size(0).into(),
+        BrotliOptions::default()
+            .with_skippable_frame_size(64 * 1024)
+            .into(),
+    ]);
+    #[cfg(feature = "deflate")]
+    codecs.push(EncoderMethod::DEFLATE.into());
+    #[cfg(feature = "lz4")]
+    codecs.push(EncoderMethod::LZ4.into());
+    #[cfg(feature = "snappy")]
+    codecs.push(EncoderMethod::SNAPPY.into());
+    #[cfg(feature = "zstd")]
+    codecs.push(EncoderMethod::ZSTD.into());
+
+    let content = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let password = Password::new("secret");
+    for codec in codecs {
+        let name = codec.method.name();
+        // The data is encrypted after being encoded, so AES finishes last.
+        let methods = vec![AesEncoderOptions::new(password.clone()).into(), codec];
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(methods);
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("single.exe"),
+                Some(content.as_slice()),
+            )
+            .unwrap();
+        writer
+            .push_archive_entries(
+                vec![
+                    ArchiveEntry::new_file("a.exe"),
+                    ArchiveEntry::new_file("b.txt"),
+                ],
+                vec![
+                    SourceReader::new(content.as_slice()),
+                    SourceReader::new(&b"odd sized tail"[..]),
+                ],
+            )
+            .unwrap();
+        let bytes = writer.finish_into_bytes().unwrap();
+
+        let mut reader = ArchiveReader::new(Cursor::new(bytes), password.clone()).unwrap();
+        assert_eq!(reader.read_file("single.exe").unwrap(), content, "{name}");
+        assert_eq!(reader.read_file("a.exe").unwrap(), content, "{name}");
+        assert_eq!(
+            reader.read_file("b.txt").unwrap(),
+            b"odd sized tail",
+            "{name}"
+        );
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "async"))]
+#[tokio::test]
+async fn compress_from_async_reader() {
+    let content = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry_async(
+            ArchiveEntry::new_file("data.exe"),
+            Some(tokio::io::BufReader::new(Cursor::new(content.clone()))),
+        )
+        .await
+        .unwrap();
+    // A small buffer makes the reader return the data in many chunks.
+    writer
+        .push_archive_entry_async(
+            ArchiveEntry::new_file("chunked.exe"),
+            Some(tokio::io::BufReader::with_capacity(
+                1000,
+                Cursor::new(content.clone()),
+            )),
+        )
+        .await
+        .unwrap();
+    writer
+        .push_archive_entry_async(
+            ArchiveEntry::new_file("empty.txt"),
+            Some(tokio::io::BufReader::new(Cursor::new(Vec::new()))),
+        )
+        .await
+        .unwrap();
+    writer
+        .push_archive_entry_async::<tokio::io::Empty>(ArchiveEntry::new_directory("dir"), None)
+        .await
+        .unwrap();
+    let entry = &writer.entries()[0];
+    assert_eq!(entry.size, content.len() as u64);
+    assert_eq!(entry.crc, crc32fast::hash(&content) as u64);
+    let async_bytes = writer.finish_into_bytes().unwrap();
+
+    // The archive is the same as when pushing the data synchronously.
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("data.exe"), Some(content.as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("chunked.exe"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("empty.txt"), Some(&[][..]))
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    assert_eq!(async_bytes, writer.finish_into_bytes().unwrap());
+
+    let mut reader = ArchiveReader::new(Cursor::new(async_bytes), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.exe").unwrap(), content);
+    assert_eq!(reader.read_file("chunked.exe").unwrap(), content);
+    let empty = reader.entry_by_name("empty.txt").unwrap();
+    assert!(!empty.has_stream && !empty.is_directory);
+    assert!(reader.entry_by_name("dir").unwrap().is_directory);
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "async"))]
+#[tokio::test]
+async fn compress_from_async_reader_in_spawned_task() {
+    let content = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+
+    // `tokio::spawn` only accepts `Send` futures.
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    let source = tokio::io::BufReader::with_capacity(1000, Cursor::new(content.clone()));
+    let writer = tokio::spawn(async move {
+        writer
+            .push_archive_entry_async(ArchiveEntry::new_file("data.exe"), Some(source))
+            .await
+            .unwrap();
+        writer
+    })
+    .await
+    .unwrap();
+
+    let bytes = writer.finish_into_bytes().unwrap();
+    let mut reader = ArchiveReader::new(Cursor::new(bytes), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.exe").unwrap(), content);
+}
+
+#[cfg(all(
+    feature = "codec",
+    feature = "compress",
+    feature = "util",
+    feature = "aes256"
+))]
+#[test]
+fn decode_raw_aes256_stream_with_key() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let password = Password::new("sevenz");
+    let aes = AesEncoderOptions {
+        password: password.clone(),
+        iv: [2; 16],
+        salt: [1; 16],
+        // The key is the salt followed by the password, without hashing.
+        num_cycles_power: 0x3F,
+    };
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![aes.into(), EncoderMethod::COPY.into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let archive = Archive::read(&mut Cursor::new(bytes.as_slice()), &password).unwrap();
+    let (offset, length) = archive.pack_stream_ranges()[0];
+    let packed = &bytes[offset as usize..(offset + length) as usize];
+
+    let mut aes_key = [0; 32];
+    aes_key[..16].copy_from_slice(&[1; 16]);
+    aes_key[16..16 + password.as_slice().len()].copy_from_slice(password.as_slice());
+    let mut data = Vec::new();
+    codec::decode_raw_aes256_stream(packed, &aes_key, &[2; 16], Some(content.len() as u64))
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, content);
+
+    // Without the size, the padding to the AES block size is kept.
+    data.clear();
+    codec::decode_raw_aes256_stream(packed, &aes_key, &[2; 16], None)
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data.len(), content.len().next_multiple_of(16));
+    assert_eq!(&data[..content.len()], content);
+
+    let coder = archive.blocks[0]
+        .coders
+        .iter()
+        .find(|coder| coder.encoder_method_id() == EncoderMethod::ID_AES256_SHA256)
+        .unwrap();
+    data.clear();
+    codec::decode_raw_stream(
+        packed,
+        coder.encoder_method_id(),
+        coder.properties(),
+        Some(content.len() as u64),
+        Some(&password),
+    )
+    .unwrap()
+    .read_to_end(&mut data)
+    .unwrap();
+    assert_eq!(data, content);
+}
+
+#[cfg(all(feature = "compress", feature = "util", feature = "blake3-checksum"))]
+#[test]
+fn blake3_hash_verification() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    // Without CRCs, the BLAKE3 hash is the only check of the stored data.
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![EncoderMethod::COPY.into()]);
+    writer.set_compute_crcs(false);
+    writer.set_store_blake3_hash(true);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("apache2.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    let mut bytes = writer.finish_into_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(bytes.clone()), Password::empty()).unwrap();
+    let entry = reader.archive().files[0].clone();
+    assert!(!entry.has_crc);
+    assert!(entry.has_blake3_hash());
+    assert_eq!(entry.blake3_hash, Some(*blake3::hash(&content).as_bytes()));
+    assert!(!reader.archive().files[1].has_blake3_hash());
+    assert_eq!(reader.read_file("apache2.txt").unwrap(), content);
+
+    // Modify a byte of the stored data.
+    let (offset, _) = reader.archive().pack_stream_ranges()[0];
+    bytes[offset as usize + 100] ^= 1;
+    let mut reader = ArchiveReader::new(Cursor::new(bytes), Password::empty()).unwrap();
+    // The error of the verifying reader is wrapped in an I/O error.
+    let err = reader.read_file("apache2.txt").unwrap_err();
+    assert!(
+        err.to_string().contains("ChecksumVerificationFailed"),
+        "{err}"
+    );
+    let result = reader.for_each_entries(|_, data| {
+        std::io::copy(data, &mut std::io::sink())?;
+        Ok(true)
+    });
+    let err = result.unwrap_err();
+    assert!(
+        err.to_string().contains("ChecksumVerificationFailed"),
+        "{err}"
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn convert_between_sevenz_and_tar() {
+    fn read_tree(root: &std::path::Path) -> Vec<(String, Option<Vec<u8>>)> {
+        let mut tree = Vec::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                let name = path.strip_prefix(root).unwrap().to_string_lossy();
+                if path.is_dir() {
+                    tree.push((name.to_string(), None));
+                    pending.push(path);
+                } else {
+                    tree.push((name.to_string(), Some(std::fs::read(&path).unwrap())));
+                }
+            }
+        }
+        tree.sort();
+        tree
+    }
+
+    // Names longer than 100 bytes need the ustar prefix field, names without a fitting `/` a
+    // PAX extended header.
+    let long_dir = "dir/".repeat(30);
+    let long_name = format!("{long_dir}{}.txt", "n".repeat(120));
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    let mut executable = ArchiveEntry::new_file("dir/run.sh");
+    executable.has_windows_attributes = true;
+    executable.windows_attributes = 0x8000 | (0o100755 << 16);
+    let files = [
+        ArchiveEntry::new_file("dir/a.txt"),
+        ArchiveEntry::new_file(&format!("{long_dir}b.txt")),
+        ArchiveEntry::new_file(&long_name),
+        executable,
+    ];
+    let contents: Vec<Vec<u8>> = (0..files.len())
+        .map(|i| format!("content {i} ").repeat(100 * i + 1).into_bytes())
+        .collect();
+    writer
+        .push_archive_entries(
+            files.to_vec(),
+            contents
+                .iter()
+                .map(|c| SourceReader::new(c.as_slice()))
+                .collect(),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_file("empty.txt"), None)
+        .unwrap();
+    let sevenz = writer.finish().unwrap().into_inner();
+
+    let tar = sevenz_to_tar(
+        Cursor::new(sevenz.as_slice()),
+        Vec::new(),
+        Password::empty(),
+    )
+    .unwrap();
+    assert_eq!(tar.len() % 512, 0);
+
+    let temp_dir = tempdir().unwrap();
+    decompress(
+        Cursor::new(sevenz.as_slice()),
+        temp_dir.path().join("original"),
+    )
+    .unwrap();
+    let expected = read_tree(&temp_dir.path().join("original"));
+    assert_eq!(expected.len(), long_dir.matches('/').count() + 5);
+
+    for (solid_block_size, block_count) in [(0, 4), (u64::MAX, 1)] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        tar_to_sevenz(tar.as_slice(), &mut writer, solid_block_size).unwrap();
+        let converted = writer.finish().unwrap().into_inner();
+
+        let archive =
+            Archive::read(&mut Cursor::new(converted.as_slice()), &Password::empty()).unwrap();
+        assert_eq!(archive.blocks.len(), block_count);
+        let entry = archive
+            .files
+            .iter()
+            .find(|e| e.name() == "dir/run.sh")
+            .unwrap();
+        assert_eq!(entry.windows_attributes() >> 16, 0o100755);
+
+        let dest = temp_dir.path().join(format!("converted_{block_count}"));
+        decompress(Cursor::new(converted.as_slice()), &dest).unwrap();
+        assert_eq!(read_tree(&dest), expected);
+    }
+
+    // Cut the data of the second entry, which is streamed or buffered depending on the block size.
+    let truncated = &tar[..3 * 512 + 100];
+    for solid_block_size in [0, u64::MAX] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        assert!(tar_to_sevenz(truncated, &mut writer, solid_block_size).is_err());
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn convert_links_between_sevenz_and_tar() {
+    let mut link = ArchiveEntry::new_file("link");
+    link.has_windows_attributes = true;
+    link.windows_attributes = 0x8000 | (0o120777 << 16);
+    let mut long_link = link.clone();
+    long_link.name = "long_link".to_string();
+    let long_target = format!("{}target", "dir/".repeat(30));
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("target"), Some(&b"data"[..]))
+        .unwrap();
+    writer
+        .push_archive_entry(link, Some(&b"target"[..]))
+        .unwrap();
+    writer
+        .push_archive_entry(long_link, Some(long_target.as_bytes()))
+        .unwrap();
+    let sevenz = writer.finish().unwrap().into_inner();
+
+    let tar = sevenz_to_tar(
+        Cursor::new(sevenz.as_slice()),
+        Vec::new(),
+        Password::empty(),
+    )
+    .unwrap();
+    let link_header = tar
+        .chunks(512)
+        .find(|block| block.starts_with(b"link\0"))
+        .unwrap();
+    assert_eq!(link_header[156], b'2');
+    assert_eq!(&link_header[157..164], b"target\0");
+    assert_eq!(&link_header[124..136], b"00000000000\0");
+    // Targets longer than 100 bytes are stored in a PAX extended header.
+    let record = format!("linkpath={long_target}\n");
+    assert!(tar.windows(record.len()).any(|w| w == record.as_bytes()));
+
+    // Hard links can't be stored and are skipped.
+    let mut hard_link = link_header.to_vec();
+    hard_link[..100].fill(0);
+    hard_link[..4].copy_from_slice(b"hard");
+    hard_link[156] = b'1';
+    hard_link[148..156].fill(b' ');
+    let checksum: u32 = hard_link.iter().map(|&b| b as u32).sum();
+    hard_link[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+    let tar = [&tar[..tar.len() - 1024], &hard_link, &[0; 1024]].concat();
+
+    for solid_block_size in [0, u64::MAX] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        let skipped = tar_to_sevenz(tar.as_slice(), &mut writer, solid_block_size).unwrap();
+        assert_eq!(skipped, ["hard"]);
+        let converted = writer.finish().unwrap().into_inner();
+
+        let mut reader =
+            ArchiveReader::new(Cursor::new(converted.as_slice()), Password::empty()).unwrap();
+        assert_eq!(reader.archive().files.len(), 3);
+        for (name, target) in [("link", "target"), ("long_link", long_target.as_str())] {
+            let entry = reader.entry_by_name(name).unwrap();
+            assert_eq!(entry.windows_attributes() >> 16, 0o120777);
+            assert_eq!(reader.read_file(name).unwrap(), target.as_bytes());
+        }
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn finish_with_stats_matches_archive() {
+    let content = b"stats ".repeat(1000);
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("dir"), None)
+        .unwrap();
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("dir/a.txt"),
+            Some(content.as_slice()),
+        )
+        .unwrap();
+    writer
+        .push_archive_entry(ArchiveEntry::new_file("empty.txt"), Some([].as_slice()))
+        .unwrap();
+    writer
+        .push_archive_entries(
+            vec![
+                ArchiveEntry::new_file("b.txt"),
+                ArchiveEntry::new_file("c.txt"),
+            ],
+            vec![
+                SourceReader::new(b"b".as_slice()),
+                SourceReader::new(content.as_slice()),
+            ],
+        )
+        .unwrap();
+    let (output, stats) = writer.finish_with_stats().unwrap();
+
+    let archive = Archive::read(&mut Cursor::new(output.into_inner()), &Password::empty()).unwrap();
+    let dirs = archive
+        .files
+        .iter()
+        .filter(|entry| entry.is_directory())
+        .count();
+    assert_eq!(stats.total_dirs, 1);
+    assert_eq!(stats.total_dirs, dirs);
+    assert_eq!(stats.total_files, 4);
+    assert_eq!(stats.total_files, archive.files.len() - dirs);
+    assert_eq!(stats.total_uncompressed_bytes, 2 * content.len() as u64 + 1);
+    assert_eq!(
+        stats.total_uncompressed_bytes,
+        archive.files.iter().map(|entry| entry.size()).sum::<u64>()
+    );
+    assert_eq!(
+        stats.total_compressed_bytes,
+        archive
+            .pack_stream_ranges()
+            .iter()
+            .map(|(_, length)| length)
+            .sum::<u64>()
+    );
+    let ratio = stats.compression_ratio();
+    assert!(ratio > 0.0 && ratio < 0.5, "{ratio}");
+    assert!(stats.elapsed > std::time::Duration::ZERO);
+
+    let (_, stats) = ArchiveWriter::new(Cursor::new(Vec::new()))
+        .unwrap()
+        .finish_with_stats()
+        .unwrap();
+    assert_eq!(stats, FinishStats::default());
+    assert_eq!(stats.compression_ratio(), 1.0);
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn archive_comment_round_trips() {
+    let comment = "Première ligne\nzweite Zeile: äöü ß\n第三行 🦀\r\n";
+    let long_comment = comment.repeat(500);
+    for (comment, header_compression) in [
+        (comment.to_string(), HeaderCompression::Never),
+        (comment.to_string(), HeaderCompression::Always),
+        (long_comment, HeaderCompression::Never),
+    ] {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new()))
+            .unwrap()
+            .with_comment(&comment);
+        writer.set_header_compression(header_compression);
+        assert_eq!(writer.comment(), Some(comment.as_str()));
+        writer
+            .push_archive_entry(
+                ArchiveEntry::new_file("file.txt"),
+                Some(b"content".as_slice()),
+            )
+            .unwrap();
+        let data = writer.finish_into_bytes().unwrap();
+
+        let archive = Archive::read(&mut Cursor::new(data.as_slice()), &Password::empty()).unwrap();
+        assert_eq!(archive.comment(), Some(comment.as_str()));
+        let mut reader = ArchiveReader::new(Cursor::new(data), Password::empty()).unwrap();
+        assert_eq!(reader.read_file("file.txt").unwrap(), b"content");
+    }
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new()))
+        .unwrap()
+        .with_comment(comment);
+    writer.set_comment("");
+    assert_eq!(writer.comment(), None);
+    let data = writer.finish_into_bytes().unwrap();
+    let archive = Archive::read(&mut Cursor::new(data), &Password::empty()).unwrap();
+    assert_eq!(archive.comment(), None);
+}
+
+/// Compresses `data` as a single entry with `methods`, returning the archive, the compressed
+/// size and the coder properties of the entry.
+#[cfg(all(feature = "compress", feature = "util"))]
+fn compress_with_preset(data: &[u8], methods: EncoderConfiguration) -> (Vec<u8>, u64, Vec<u8>) {
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_content_methods(vec![methods]);
+    let compressed_size = writer
+        .push_archive_entry(ArchiveEntry::new_file("data.bin"), Some(data))
+        .unwrap()
+        .compressed_size;
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let mut reader =
+        ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+    assert_eq!(reader.read_file("data.bin").unwrap(), data);
+    let mut infos = Vec::new();
+    reader.file_codec_info("data.bin", &mut infos).unwrap();
+    assert_eq!(infos.len(), 1);
+    (archive, compressed_size, infos.remove(0).properties)
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compression_level_presets_match_explicit_options() {
+    // A block of noisy text that repeats after 192 KiB, further back than the dictionary of
+    // level 1 reaches.
+    let mut state = 0x2545_f491_u32;
+    let block: Vec<u8> = (0..192 << 10)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            b"abcdefghijklmnop"[(state >> 28) as usize]
+        })
+        .collect();
+    let data = [block.as_slice(), block.as_slice(), block.as_slice()].concat();
+
+    let (archive, compressed_size, _) =
+        compress_with_preset(&data, EncoderConfiguration::preset(0));
+    let reader = ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+    assert_eq!(
+        reader.file_methods("data.bin").unwrap(),
+        [EncoderMethod::COPY]
+    );
+    assert_eq!(compressed_size, data.len() as u64);
+
+    let explicit = |level: u32, dict_size: u32| {
+        let mut options = Lzma2Options::from_level(level);
+        options.set_dictionary_size(dict_size);
+        EncoderConfiguration::from(options)
+    };
+    // The dictionary size is the only property of LZMA2, the fast modes of levels 1 and 3 are
+    // compared with explicit options in the unit tests of `Lzma2Options`.
+    for (level, explicit) in [(1, explicit(1, 64 << 10)), (3, explicit(3, 1 << 20))] {
+        let (_, _, preset_properties) =
+            compress_with_preset(&data, EncoderConfiguration::preset(level));
+        let (_, _, explicit_properties) = compress_with_preset(&data, explicit);
+        assert_eq!(preset_properties, explicit_properties, "level {level}");
+    }
+    for (level, explicit) in [
+        (5, explicit(5, 16 << 20)),
+        (7, explicit(6, 32 << 20)),
+        (9, explicit(6, 64 << 20)),
+    ] {
+        let (_, preset_size, preset_properties) =
+            compress_with_preset(&data, EncoderConfiguration::preset(level));
+        let (_, explicit_size, explicit_properties) = compress_with_preset(&data, explicit);
+        assert_eq!(preset_size, explicit_size, "level {level}");
+        assert_eq!(preset_properties, explicit_properties, "level {level}");
+    }
+
+    let sizes: Vec<u64> = [1, 3, 5, 9]
+        .into_iter()
+        .map(|level| compress_with_preset(&data, EncoderConfiguration::preset(level)).1)
+        .collect();
+    // The 64 KiB dictionary of level 1 doesn't reach the repetitions, the larger ones do.
+    assert!(sizes[0] > 2 * sizes[1], "{sizes:?}");
+    assert!(sizes[2] < sizes[1], "{sizes:?}");
+    assert!(sizes[3] <= sizes[2], "{sizes:?}");
+    // Levels above 9 are treated as 9.
+    assert_eq!(
+        compress_with_preset(&data, EncoderConfiguration::preset(12)).1,
+        sizes[3]
+    );
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_with_compression_level_and_auto_bcj() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let names = ["tool.EXE", "lib/plugin.dll", "readme.txt", "exe"];
+
+    let write = |level: u32, auto_bcj: bool| {
+        let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_compression_level(level).set_auto_bcj(auto_bcj);
+        for name in names {
+            writer
+                .push_archive_entry(ArchiveEntry::new_file(name), Some(content.as_slice()))
+                .unwrap();
+        }
+        writer
+            .push_archive_entry::<&[u8]>(ArchiveEntry::new_directory("bin.exe"), None)
+            .unwrap();
+        writer.finish_into_bytes().unwrap()
+    };
+
+    for (level, auto_bcj, executable_methods, other_methods) in [
+        (
+            5,
+            true,
+            vec![EncoderMethod::LZMA2, EncoderMethod::BCJ_X86_FILTER],
+            vec![EncoderMethod::LZMA2],
+        ),
+        (
+            5,
+            false,
+            vec![EncoderMethod::LZMA2],
+            vec![EncoderMethod::LZMA2],
+        ),
+        (
+            0,
+            true,
+            vec![EncoderMethod::COPY],
+            vec![EncoderMethod::COPY],
+        ),
+    ] {
+        let archive = write(level, auto_bcj);
+        let mut reader =
+            ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+        for name in names {
+            let expected = if name.contains('.') && !name.ends_with(".txt") {
+                &executable_methods
+            } else {
+                &other_methods
+            };
+            assert_eq!(
+                &reader.file_methods(name).unwrap(),
+                expected,
+                "level {level}, {name}"
+            );
+            assert_eq!(reader.read_file(name).unwrap(), content, "{name}");
+        }
+        assert!(reader.archive().files[4].is_directory());
+    }
+}
+
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+fn compress_solid_with_auto_bcj() {
+    let content = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let names = ["tool.exe", "readme.txt", "lib/plugin.DLL", "notes.md"];
+    let entries = names.map(ArchiveEntry::new_file).to_vec();
+    let readers = names
+        .iter()
+        .map(|_| SourceReader::new(content.as_slice()))
+        .collect();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_compression_level(5).set_auto_bcj(true);
+    writer.push_archive_entries(entries, readers).unwrap();
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let mut reader =
+        ArchiveReader::new(Cursor::new(archive.as_slice()), Password::empty()).unwrap();
+    // The executables follow the other entries in their own block.
+    let archive_names: Vec<&str> = reader.archive().files.iter().map(|f| f.name()).collect();
+    assert_eq!(
+        archive_names,
+        ["readme.txt", "notes.md", "tool.exe", "lib/plugin.DLL"]
+    );
+    assert_eq!(reader.archive().blocks.len(), 2);
+    for name in names {
+        let expected = if name.ends_with(".exe") || name.ends_with(".DLL") {
+            vec![EncoderMethod::LZMA2, EncoderMethod::BCJ_X86_FILTER]
+        } else {
+            vec![EncoderMethod::LZMA2]
+        };
+        assert_eq!(reader.file_methods(name).unwrap(), expected, "{name}");
+        assert_eq!(reader.read_file(name).unwrap(), content, "{name}");
+    }
+
+    // A duplicate name in either group rejects both blocks.
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .set_duplicate_entry_policy(DuplicateEntryPolicy::Reject)
+        .set_auto_bcj(true);
+    let names = ["a.txt", "a.exe", "a.exe"];
+    let result = writer.push_archive_entries(
+        names.map(ArchiveEntry::new_file).to_vec(),
+        names
+            .iter()
+            .map(|_| SourceReader::new(content.as_slice()))
+            .collect(),
+    );
+    assert!(result.is_err());
+    let archive = writer.finish_into_bytes().unwrap();
+    let archive = Archive::read(&mut Cursor::new(archive), &Password::empty()).unwrap();
+    assert!(archive.files.is_empty());
+
+    // Each duplicate is reported once, although the entries are pushed as two blocks.
+    static WARNINGS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    fn warn(name: &str) {
+        assert_eq!(name, "a.exe");
+        WARNINGS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer
+        .set_duplicate_entry_policy(DuplicateEntryPolicy::Warn(warn))
+        .set_auto_bcj(true);
+    writer
+        .push_archive_entries(
+            names.map(ArchiveEntry::new_file).to_vec(),
+            names
+                .iter()
+                .map(|_| SourceReader::new(content.as_slice()))
+                .collect(),
+        )
+        .unwrap();
+    assert_eq!(WARNINGS.load(std::sync::atomic::Ordering::Relaxed), 1);
+}
+
+/// Tests archives written with the compression levels and auto BCJ with 7-Zip, which needs to
+/// be installed as `7z`.
+#[cfg(all(feature = "compress", feature = "util"))]
+#[test]
+#[ignore = "runs the 7z binary"]
+fn seven_zip_tests_compression_levels() {
+    let text = std::fs::read("tests/resources/apache2.txt").unwrap();
+    let executable = std::fs::read("tests/resources/decompress_x86.exe").unwrap();
+    let temp_dir = tempdir().unwrap();
+
+    for level in 0..=9 {
+        for auto_bcj in [false, true] {
+            let path = temp_dir.path().join(format!("level{level}_{auto_bcj}.7z"));
+            let mut writer = ArchiveWriter::create(&path).unwrap();
+            writer.set_compression_level(level).set_auto_bcj(auto_bcj);
+            writer
+                .push_archive_entries(
+                    vec![
+                        ArchiveEntry::new_file("license.txt"),
+                        ArchiveEntry::new_file("tool.exe"),
+                    ],
+                    vec![
+                        SourceReader::new(text.as_slice()),
+                        SourceReader::new(executable.as_slice()),
+                    ],
+                )
+                .unwrap();
+            writer.finish().unwrap();
+
+            let output = match std::process::Command::new("7z")
+                .arg("t")
+                .arg(&path)
+                .output()
+            {
+                Ok(output) => output,
+                Err(e) => panic!("Failed to run 7z, which this test needs: {e}"),
+            };
+            assert!(
+                output.status.success(),
+                "7z t failed for level {level}, auto BCJ {auto_bcj}:\n{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
 }
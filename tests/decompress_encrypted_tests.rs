@@ -36,3 +36,132 @@ fn test_decompress_file_with_password_small() {
         ArchiveReader::new(std::fs::File::open(source_file).unwrap(), password).unwrap();
     seven.for_each_entries(|_entry, _reader| Ok(true)).unwrap();
 }
+
+#[test]
+fn test_header_encrypted_preflight() {
+    use std::fs::File;
+
+    use sevenz_rust2::{Archive, ArchiveReader, Password};
+
+    // Only the content of the entries is encrypted.
+    let mut file = File::open("tests/resources/aes_small_test.7z").unwrap();
+    assert!(!Archive::header_encrypted(&mut file).unwrap());
+    let reader = ArchiveReader::new(file, Password::empty()).unwrap();
+    let name = reader.archive().files[0].name().to_string();
+    assert!(reader.entry_is_encrypted(&name));
+    assert!(!reader.entry_is_encrypted("missing"));
+
+    let mut file = File::open("tests/resources/two_files_with_content_lzma.7z").unwrap();
+    assert!(!Archive::header_encrypted(&mut file).unwrap());
+    let reader = ArchiveReader::new(file, Password::empty()).unwrap();
+    assert!(!reader.entry_is_encrypted("file1.txt"));
+}
+
+#[cfg(all(feature = "aes256", feature = "compress"))]
+#[test]
+fn test_key_derivation_power_limit() {
+    use std::io::Cursor;
+
+    use sevenz_rust2::{
+        ArchiveEntry, ArchiveReader, ArchiveWriter, Error, HeaderCompression, Password,
+        encoder_options::{AesEncoderOptions, Lzma2Options},
+    };
+
+    let mut options = AesEncoderOptions::new(Password::new("test"));
+    options.salt = [0x5A; 16];
+    options.iv = [0xA5; 16];
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_encrypt_header(false);
+    writer.set_header_compression(HeaderCompression::Never);
+    writer.set_content_methods(vec![options.into(), Lzma2Options::default().into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("file.txt"),
+            Some(b"content".as_slice()),
+        )
+        .unwrap();
+    let mut bytes = writer.finish_into_bytes().unwrap();
+
+    // Raise the power stored in the plain header to 26 and fix the header CRCs.
+    let mut properties = vec![0xC0 | 8, 0xFF];
+    properties.extend_from_slice(&[0x5A; 16]);
+    properties.extend_from_slice(&[0xA5; 16]);
+    let position = bytes
+        .windows(properties.len())
+        .position(|window| window == properties)
+        .unwrap();
+    bytes[position] = 0xC0 | 26;
+    let header_offset = 32 + u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+    let header_size = u64::from_le_bytes(bytes[20..28].try_into().unwrap()) as usize;
+    let header_crc = crc32fast::hash(&bytes[header_offset..header_offset + header_size]);
+    bytes[28..32].copy_from_slice(&header_crc.to_le_bytes());
+    let start_header_crc = crc32fast::hash(&bytes[12..32]);
+    bytes[8..12].copy_from_slice(&start_header_crc.to_le_bytes());
+
+    let mut reader = ArchiveReader::from_seekable_buffer(bytes, Password::new("test")).unwrap();
+    assert_eq!(reader.key_derivation_power(), Some(26));
+    let result = reader.read_file("file.txt");
+    assert!(
+        matches!(&result, Err(Error::Other(message)) if message == "key derivation cost too high"),
+        "{result:?}"
+    );
+    let result = reader.for_each_entries(|_, _| Ok(true));
+    assert!(result.is_err());
+}
+
+#[cfg(all(feature = "aes256", feature = "compress"))]
+#[test]
+fn test_unhashed_key() {
+    use std::io::Cursor;
+
+    use sevenz_rust2::{
+        ArchiveEntry, ArchiveReader, ArchiveWriter, Password,
+        encoder_options::{AesEncoderOptions, Lzma2Options},
+    };
+
+    // A power of 0x3F uses the salt followed by the password as the key, without hashing.
+    let mut options = AesEncoderOptions::new(Password::new("test"));
+    options.num_cycles_power = 0x3F;
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_encrypt_header(false);
+    writer.set_content_methods(vec![options.into(), Lzma2Options::default().into()]);
+    writer
+        .push_archive_entry(
+            ArchiveEntry::new_file("file.txt"),
+            Some(b"content".as_slice()),
+        )
+        .unwrap();
+    let bytes = writer.finish_into_bytes().unwrap();
+
+    let mut reader = ArchiveReader::from_seekable_buffer(bytes, Password::new("test")).unwrap();
+    assert_eq!(reader.key_derivation_power(), Some(0x3F));
+    assert_eq!(reader.read_file("file.txt").unwrap(), b"content");
+}
+
+#[cfg(feature = "aes256")]
+#[test]
+fn test_decompress_rar29_aes() {
+    use sevenz_rust2::{ArchiveReader, Error, Password};
+
+    // The key of the fixture was derived independently from the documented RAR 2.9 KDF.
+    let path = "tests/resources/rar29_aes.7z";
+    let mut reader = ArchiveReader::open(path, Password::new("sevenz-rust")).unwrap();
+    assert!(reader.entry_is_encrypted("rar29_aes.txt"));
+    let methods = reader.file_methods("rar29_aes.txt").unwrap();
+    assert_eq!(
+        methods.iter().map(|m| m.name()).collect::<Vec<_>>(),
+        ["COPY", "RAR29_AES"]
+    );
+    assert_eq!(
+        reader.read_file("rar29_aes.txt").unwrap(),
+        b"This entry is encrypted with the AES-128 coder of RAR 2.9, the key is derived with SHA-1.\n"
+    );
+
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    assert!(matches!(
+        reader.read_file("rar29_aes.txt"),
+        Err(Error::PasswordRequired)
+    ));
+    let mut reader = ArchiveReader::open(path, Password::new("wrong")).unwrap();
+    assert!(reader.read_file("rar29_aes.txt").is_err());
+}
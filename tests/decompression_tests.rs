@@ -1,13 +1,20 @@
-use std::fs::File;
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, SeekFrom},
+    ops::ControlFlow,
+};
 #[cfg(feature = "util")]
 use std::{
     fs::{read, read_to_string},
     path::PathBuf,
 };
 
+use sevenz_rust2::{
+    Archive, ArchiveReadOptions, ArchiveReader, Block, BlockDecoder, Error, ParseLimits, Password,
+    StructureWarning,
+};
 #[cfg(feature = "util")]
-use sevenz_rust2::decompress_file;
-use sevenz_rust2::{Archive, ArchiveReader, BlockDecoder, Password};
+use sevenz_rust2::{ExtractOptions, decompress, decompress_file, decompress_file_with_options};
 #[cfg(feature = "util")]
 use tempfile::tempdir;
 
@@ -121,6 +128,20 @@ fn decompress_lzma_multiple_files_encoded_header() {
     assert_eq!(read_to_string(file2_path).unwrap(), "file two content\n");
 }
 
+#[test]
+fn reject_encoded_header_with_multiple_blocks() {
+    // The streams info of the encoded header describes a stored block before the header block,
+    // which 7-Zip doesn't accept either.
+    let result = Archive::open("tests/resources/crafted/encoded_header_multiple_blocks.7z");
+    assert!(
+        matches!(
+            &result,
+            Err(Error::CorruptHeader { section: "encoded header", detail }) if detail.contains("found 2")
+        ),
+        "{result:?}"
+    );
+}
+
 #[cfg(feature = "util")]
 #[test]
 fn decompress_delta_lzma_single_file_unencoded_header() {
@@ -251,6 +272,195 @@ fn test_bcj2() {
     }
 }
 
+#[test]
+fn block_decoder_resume_from_checkpoint() {
+    // The progress callback borrows it for as long as the archive is borrowed.
+    let mut progress = Vec::new();
+    let mut file = File::open("tests/resources/solid.7z").unwrap();
+    let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+    let password = Password::empty();
+    let start = archive.stream_map.block_first_file_index[0];
+    let count = BlockDecoder::new(1, 0, &archive, &password, &mut file).entry_count();
+    assert!(count >= 2);
+
+    let mut offset = 0;
+    for file_index in start..start + count {
+        assert_eq!(archive.stream_map.entry_offset_in_block(file_index), offset);
+        offset += archive.files[file_index].size;
+    }
+    assert_eq!(offset, archive.blocks[0].get_unpack_size());
+
+    // Extract the first entry, then stop and capture a checkpoint.
+    let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut file);
+    assert!(decoder.checkpoint().is_none());
+    decoder.decode_entry_to_vec(start).unwrap();
+    let checkpoint = decoder.checkpoint().unwrap();
+    assert_eq!(checkpoint.file_index, start + 1);
+    assert_eq!(checkpoint.unpacked_offset, archive.files[start].size);
+    drop(decoder);
+
+    let last = start + count - 1;
+    let mut expected = Vec::new();
+    BlockDecoder::new(1, 0, &archive, &password, &mut file)
+        .for_each_entries(&mut |entry, reader| {
+            if entry.name() == archive.files[last].name() {
+                reader.read_to_end(&mut expected)?;
+            }
+            Ok(true)
+        })
+        .unwrap();
+
+    let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut file);
+    decoder.set_skip_progress(|skipped, total| progress.push((skipped, total)));
+    decoder.resume_from(last, Some(checkpoint)).unwrap();
+    assert_eq!(decoder.decode_entry_to_vec(last).unwrap(), expected);
+    drop(decoder);
+    let total = archive.stream_map.entry_offset_in_block(last);
+    assert_eq!(progress.last(), Some(&(total, total)));
+    assert!(progress.windows(2).all(|w| w[0].0 < w[1].0));
+
+    // Checkpoints behind the entry or of other archives are rejected.
+    let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut file);
+    assert!(decoder.resume_from(start, Some(checkpoint)).is_err());
+    let mut changed = checkpoint;
+    changed.unpacked_offset += 1;
+    assert!(matches!(
+        decoder.resume_from(last, Some(changed)),
+        Err(Error::CheckpointMismatch { block_index: 0, .. })
+    ));
+    decoder.resume_from(start + 1, None).unwrap();
+    assert_eq!(decoder.checkpoint(), Some(checkpoint));
+    drop(decoder);
+
+    // A checkpoint after the last entry marks the block as complete.
+    let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut file);
+    decoder.resume_from(last, None).unwrap();
+    decoder.decode_entry_to_vec(last).unwrap();
+    let end = decoder.checkpoint().unwrap();
+    assert_eq!(end.file_index, last + 1);
+    assert_eq!(end.unpacked_offset, archive.blocks[0].get_unpack_size());
+    drop(decoder);
+    let mut decoder = BlockDecoder::new(1, 0, &archive, &password, &mut file);
+    decoder.resume_from(end.file_index, Some(end)).unwrap();
+    assert!(decoder.resume_from(last, Some(end)).is_err());
+}
+
+#[test]
+fn block_decoder_random_access() {
+    for path in [
+        "tests/resources/solid.7z",
+        "tests/resources/7za433_7zip_lzma2_bcj2.7z",
+        "tests/resources/interleaved_empty_entries.7z",
+    ] {
+        let mut file = File::open(path).unwrap();
+        let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+        let password = Password::empty();
+        for block_index in 0..archive.blocks.len() {
+            let indices: Vec<_> = archive
+                .stream_map
+                .block_entry_indices(block_index)
+                .collect();
+            let mut expected = Vec::new();
+            BlockDecoder::new(1, block_index, &archive, &password, &mut file)
+                .for_each_entries(&mut |_, reader| {
+                    let mut data = Vec::new();
+                    reader.read_to_end(&mut data)?;
+                    expected.push(data);
+                    Ok(true)
+                })
+                .unwrap();
+            assert_eq!(expected.len(), indices.len(), "{path}");
+
+            let mut decoder = BlockDecoder::new(1, block_index, &archive, &password, &mut file);
+            let positions = 0..indices.len();
+            for position in positions.clone().rev().chain(positions.step_by(2)) {
+                assert_eq!(
+                    decoder.decode_entry_to_vec(indices[position]).unwrap(),
+                    expected[position],
+                    "{path}: {}",
+                    indices[position]
+                );
+            }
+            let last = *indices.last().unwrap();
+            assert!(matches!(
+                decoder.skip_to_entry(last + 1),
+                Err(Error::EntryNotInBlock { file_index, .. }) if file_index == last + 1
+            ));
+
+            // Decoding continues at the entry the decoder was skipped to.
+            decoder.skip_to_entry(last).unwrap();
+            let mut names = Vec::new();
+            decoder
+                .for_each_entries(&mut |entry, reader| {
+                    let mut data = Vec::new();
+                    reader.read_to_end(&mut data)?;
+                    assert_eq!(&data, expected.last().unwrap());
+                    names.push(entry.name().to_string());
+                    Ok(true)
+                })
+                .unwrap();
+            assert_eq!(names, [archive.files[last].name()]);
+        }
+    }
+}
+
+#[test]
+fn block_entries_skip_interleaved_entries_without_data() {
+    // Directories and empty files are listed between the files of both solid blocks.
+    let mut file = File::open("tests/resources/interleaved_empty_entries.7z").unwrap();
+    let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+    let password = Password::empty();
+    let blocks = [["d/a.txt", "b.txt"], ["c.txt", "e.txt"]];
+    assert_eq!(archive.blocks.len(), blocks.len());
+    for (block_index, expected) in blocks.iter().enumerate() {
+        let decoder = BlockDecoder::new(1, block_index, &archive, &password, &mut file);
+        let names: Vec<_> = decoder.entries().map(|entry| entry.name()).collect();
+        assert_eq!(&names, expected);
+        assert_eq!(decoder.entry_count(), expected.len());
+
+        let mut names = Vec::new();
+        decoder
+            .for_each_entries(&mut |entry, reader| {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                assert_eq!(data.len() as u64, entry.size());
+                names.push(entry.name().to_string());
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(&names, expected);
+    }
+
+    let mut reader =
+        ArchiveReader::open("tests/resources/interleaved_empty_entries.7z", password).unwrap();
+    let mut contents = Vec::new();
+    reader
+        .for_each_entries(|entry, reader| {
+            let mut data = String::new();
+            reader.read_to_string(&mut data)?;
+            contents.push((entry.name().to_string(), entry.is_directory(), data));
+            Ok(true)
+        })
+        .unwrap();
+    contents.sort();
+    let expected = [
+        ("b.txt", false, "bravo!"),
+        ("c.txt", false, "charlie"),
+        ("d", true, ""),
+        ("d/a.txt", false, "alpha"),
+        ("d/sub", true, ""),
+        ("d2", true, ""),
+        ("e.txt", false, "echo"),
+        ("empty1.txt", false, ""),
+        ("empty2.txt", false, ""),
+    ];
+    assert_eq!(contents.len(), expected.len());
+    for ((name, is_directory, data), expected) in contents.iter().zip(expected) {
+        assert_eq!((name.as_str(), *is_directory, data.as_str()), expected);
+    }
+    assert_eq!(reader.read_file("e.txt").unwrap(), b"echo");
+}
+
 #[test]
 fn test_entry_compressed_size() {
     let dir = std::fs::read_dir("tests/resources").unwrap();
@@ -260,6 +470,8 @@ fn test_entry_compressed_size() {
             println!("{path:?}");
             let mut file = File::open(path).unwrap();
             let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+            // Archives written by other implementations don't store SHA-256 digests.
+            assert!(archive.files.iter().all(|file| file.sha256().is_none()));
             for i in 0..archive.blocks.len() {
                 let fi = archive.stream_map.block_first_file_index[i];
                 let file = &archive.files[fi];
@@ -277,6 +489,456 @@ fn test_entry_compressed_size() {
     }
 }
 
+#[test]
+fn test_for_each_entries_partial_reads() {
+    let mut reader = ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap();
+    let mut contents = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            let mut content = Vec::new();
+            data.read_to_end(&mut content)?;
+            contents.push((entry.name().to_string(), content));
+            Ok(true)
+        })
+        .unwrap();
+    assert!(
+        contents
+            .iter()
+            .filter(|(_, content)| content.len() > 1)
+            .count()
+            > 1
+    );
+
+    // Reading only the first byte of each entry must not shift the data of the following
+    // entries in the solid block.
+    let mut first_bytes = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            let mut byte = [0u8; 1];
+            let read = data.read(&mut byte)?;
+            first_bytes.push((entry.name().to_string(), byte[..read].to_vec()));
+            Ok(true)
+        })
+        .unwrap();
+    let expected: Vec<_> = contents
+        .iter()
+        .map(|(name, content)| (name.clone(), content.iter().take(1).copied().collect()))
+        .collect();
+    assert_eq!(first_bytes, expected);
+}
+
+#[test]
+fn test_for_each_entries_control_flow() {
+    let mut reader =
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap();
+    let file_count = reader.archive().files.len();
+    assert!(reader.archive().blocks.len() > 1);
+
+    let mut names = Vec::new();
+    reader
+        .for_each_entries_cf(|entry, data| {
+            if let Err(e) = std::io::copy(data, &mut std::io::sink()) {
+                return ControlFlow::Continue(Err(e.into()));
+            }
+            names.push(entry.name().to_string());
+            ControlFlow::Continue(Ok(()))
+        })
+        .unwrap();
+    assert_eq!(names.len(), file_count);
+
+    // Breaking stops decoding the following blocks, too.
+    let mut visited = Vec::new();
+    reader
+        .for_each_entries_cf(|entry, _| {
+            visited.push(entry.name().to_string());
+            match visited.len() {
+                1 => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(Ok(())),
+            }
+        })
+        .unwrap();
+    assert_eq!(visited, names[..1]);
+
+    let result = reader.for_each_entries_cf(|_, _| ControlFlow::Continue(Err(Error::FileNotFound)));
+    assert!(matches!(result, Err(Error::FileNotFound)));
+
+    let mut source = File::open("tests/resources/solid.7z").unwrap();
+    let archive = Archive::read(&mut source, &Password::empty()).unwrap();
+    let password = Password::empty();
+    let block_decoder = BlockDecoder::new(1, 0, &archive, &password, &mut source);
+    let mut count = 0;
+    let flow = block_decoder
+        .for_each_entries_cf(|_, _| {
+            count += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+    assert!(flow.is_break());
+    assert_eq!(count, 1);
+
+    let block_decoder = BlockDecoder::new(1, 0, &archive, &password, &mut source);
+    let flow = block_decoder
+        .for_each_entries_cf(|_, _| ControlFlow::Continue(Ok(())))
+        .unwrap();
+    assert!(flow.is_continue());
+}
+
+#[test]
+fn test_entry_lookup() {
+    let reader = ArchiveReader::open("tests/resources/solid.7z", Password::empty()).unwrap();
+    let files = &reader.archive().files;
+    assert!(!files.is_empty());
+    assert_eq!(reader.entries().len(), files.len());
+    for (index, file) in files.iter().enumerate() {
+        let by_index = reader.entry_by_index(index).unwrap();
+        assert_eq!(by_index.name(), file.name());
+        assert_eq!(reader.entries()[index].name(), file.name());
+        let by_name = reader.entry_by_name(by_index.name()).unwrap();
+        assert_eq!(by_name.name(), by_index.name());
+        assert_eq!(by_name.size(), by_index.size());
+    }
+    assert!(reader.entry_by_index(files.len()).is_none());
+    assert!(reader.entry_by_name("does/not/exist.txt").is_none());
+}
+
+#[test]
+fn test_blocks_iter() {
+    for path in [
+        "tests/resources/solid.7z",
+        "tests/resources/non_solid.7z",
+        "tests/resources/two_empty_file.7z",
+        "tests/resources/interleaved_empty_entries.7z",
+    ] {
+        let reader = ArchiveReader::open(path, Password::empty()).unwrap();
+        let archive = reader.archive();
+        let mut block_count = 0;
+        let mut names = Vec::new();
+        for (block_index, entries) in reader.blocks_iter() {
+            assert_eq!(block_index, block_count);
+            block_count += 1;
+            let entries: Vec<_> = entries.collect();
+            assert_eq!(
+                entries.len(),
+                reader.entries_in_block(block_index).count(),
+                "{path}"
+            );
+            names.extend(entries.iter().map(|entry| entry.name()));
+        }
+        assert_eq!(block_count, archive.blocks.len(), "{path}");
+
+        let expected: Vec<_> = archive
+            .files
+            .iter()
+            .zip(&archive.stream_map.file_block_index)
+            .filter(|(_, block_index)| block_index.is_some())
+            .map(|(file, _)| file.name())
+            .collect();
+        assert_eq!(names, expected, "{path}");
+    }
+}
+
+#[test]
+fn test_decoder_memory_estimate() {
+    // LZMA with an 8 MiB dictionary, plus the probability tables for lc=3, lp=0.
+    let archive = Archive::open("tests/resources/single_file_with_content_lzma.7z").unwrap();
+    assert_eq!(
+        archive.max_block_memory_estimate(),
+        (8 << 20) + (10 + 12) * 1024
+    );
+
+    // PPMD with 256 KiB of model memory.
+    let archive = Archive::open("tests/resources/ppmd.7z").unwrap();
+    assert_eq!(archive.blocks[0].decoder_memory_estimate(), 256 << 10);
+
+    // LZMA2 with a 384 KiB dictionary plus the buffer for a compressed chunk. The BCJ filter
+    // isn't counted.
+    let archive = Archive::open("tests/resources/decompress_example_lzma2_bcj_x86.7z").unwrap();
+    assert_eq!(
+        archive.max_block_memory_estimate(),
+        (384 << 10) + (40 + 64) * 1024
+    );
+
+    // The BCJ2 block uses LZMA2 and two LZMA coders.
+    let archive = Archive::open("tests/resources/7za433_7zip_lzma2_bcj2.7z").unwrap();
+    let estimates: Vec<_> = archive
+        .blocks
+        .iter()
+        .map(|block| block.decoder_memory_estimate())
+        .collect();
+    assert_eq!(archive.max_block_memory_estimate(), estimates[1]);
+    assert!(estimates[1] > estimates[0]);
+
+    let archive = Archive::open("tests/resources/copy.7z").unwrap();
+    assert_eq!(
+        archive.max_block_memory_estimate(),
+        (4 << 10) + (40 + 64) * 1024
+    );
+}
+
+#[test]
+fn test_memory_limit() {
+    let path = "tests/resources/7za433_7zip_lzma2_bcj2.7z";
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    let limit = reader.archive().max_block_memory_estimate();
+    let name = reader.archive().files[0].name().to_string();
+
+    reader.set_memory_limit(Some(limit - 1));
+    assert!(matches!(
+        reader.for_each_entries(|_, _| Ok(true)),
+        Err(Error::MaxMemLimited { .. })
+    ));
+    reader.set_memory_limit(Some(1024));
+    assert!(matches!(
+        reader.read_file(&name),
+        Err(Error::MaxMemLimited { max_kb: 1, .. })
+    ));
+
+    let password = Password::empty();
+    let mut file = File::open(path).unwrap();
+    let mut block_decoder = BlockDecoder::new(1, 0, reader.archive(), &password, &mut file);
+    block_decoder.set_memory_limit(Some(1024));
+    assert!(matches!(
+        block_decoder.for_each_entries(&mut |_, _| Ok(true)),
+        Err(Error::MaxMemLimited { .. })
+    ));
+
+    reader.set_memory_limit(Some(limit));
+    reader.for_each_entries(|_, _| Ok(true)).unwrap();
+    assert!(!reader.read_file(&name).unwrap().is_empty());
+}
+
+#[test]
+fn test_reader_is_send() {
+    // The decoders are built for every read and not stored in the reader, so the reader can be
+    // moved to another thread even though the decoders aren't `Send`.
+    fn assert_send<T: Send>() {}
+    assert_send::<ArchiveReader<File>>();
+    assert_send::<ArchiveReader<Cursor<Vec<u8>>>>();
+
+    let mut reader = ArchiveReader::open(
+        "tests/resources/7za433_7zip_lzma2_bcj2.7z",
+        Password::empty(),
+    )
+    .unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut sizes = Vec::new();
+        reader
+            .for_each_entries(|entry, data| {
+                if entry.has_stream {
+                    sizes.push(std::io::copy(data, &mut std::io::sink())?);
+                }
+                Ok(true)
+            })
+            .unwrap();
+        (reader, sizes)
+    });
+    let (reader, sizes) = handle.join().unwrap();
+    let stream_sizes: Vec<_> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|entry| entry.has_stream)
+        .map(|entry| entry.size)
+        .collect();
+    assert_eq!(sizes, stream_sizes);
+}
+
+#[test]
+fn test_reader_without_send() {
+    // `Rc` is not `Send`, reading must still work.
+    let data: std::rc::Rc<[u8]> = std::fs::read("tests/resources/7za433_7zip_lzma2_bcj2.7z")
+        .unwrap()
+        .into();
+    let mut reader = ArchiveReader::new(Cursor::new(data.clone()), Password::empty()).unwrap();
+    let name = reader
+        .archive()
+        .files
+        .iter()
+        .find(|entry| entry.has_stream)
+        .unwrap()
+        .name()
+        .to_string();
+    let content = reader.read_file(&name).unwrap();
+    reader
+        .for_each_entries(|entry, data| {
+            if entry.name() == name {
+                let mut read = Vec::new();
+                data.read_to_end(&mut read)?;
+                assert_eq!(read, content);
+            }
+            Ok(true)
+        })
+        .unwrap();
+
+    #[cfg(feature = "util")]
+    {
+        let temp_dir = tempdir().unwrap();
+        decompress(Cursor::new(data), temp_dir.path()).unwrap();
+        assert_eq!(std::fs::read(temp_dir.path().join(&name)).unwrap(), content);
+    }
+}
+
+#[test]
+fn test_verify_structure() {
+    let dir = std::fs::read_dir("tests/resources").unwrap();
+    for entry in dir {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().ends_with("7z") {
+            let archive = Archive::open(&path).unwrap();
+            assert_eq!(archive.verify_structure(), [], "{path:?}");
+            for block in archive.blocks.iter() {
+                block.validate().unwrap();
+            }
+        }
+    }
+
+    let mut archive = Archive::open("tests/resources/solid.7z").unwrap();
+    let stream_files: Vec<_> = (0..archive.files.len())
+        .filter(|&i| archive.files[i].has_stream)
+        .collect();
+    let [first, second] = stream_files[..] else {
+        panic!("solid.7z must contain two files with data");
+    };
+    archive.stream_map.file_block_index[first] = Some(7);
+    archive.files[second].crc = 0;
+    let mut orphan = archive.files[second].clone();
+    orphan.crc = 1;
+    archive.files.push(orphan);
+    archive.blocks.push(Block::default());
+    assert!(matches!(
+        archive.blocks.last().unwrap().validate(),
+        Err(Error::CorruptHeader {
+            section: "block",
+            ..
+        })
+    ));
+    let warnings = archive.verify_structure();
+    assert_eq!(
+        warnings,
+        [
+            StructureWarning::OutOfBoundIndex {
+                field: "file block index",
+                index: 7,
+                len: 2,
+            },
+            StructureWarning::ZeroCrc { file_index: second },
+            StructureWarning::OrphanedFile {
+                file_index: archive.files.len() - 1
+            },
+            StructureWarning::SubStreamCountMismatch {
+                block_index: 0,
+                expected: 2,
+                actual: 1,
+            },
+            StructureWarning::UnpackSizeMismatch {
+                block_index: 0,
+                expected: archive.blocks[0].get_unpack_size(),
+                actual: archive.files[second].size(),
+            },
+            StructureWarning::OutOfBoundIndex {
+                field: "block index of pack streams",
+                index: 1,
+                len: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_reader_from_memory() {
+    static ARCHIVE: &[u8] = include_bytes!("resources/non_solid.7z");
+    let mut expected =
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap();
+
+    let mut buffered =
+        ArchiveReader::from_seekable_buffer(ARCHIVE.to_vec(), Password::empty()).unwrap();
+    // `Chain` can't seek.
+    let stream = std::io::Cursor::new(ARCHIVE).chain(std::io::empty());
+    let mut streamed = ArchiveReader::from_non_seekable(stream, Password::empty()).unwrap();
+    let names: Vec<_> = expected
+        .archive()
+        .files
+        .iter()
+        .filter(|file| file.has_stream)
+        .map(|file| file.name.clone())
+        .collect();
+    assert!(!names.is_empty());
+    for name in names {
+        let data = expected.read_file(&name).unwrap();
+        assert_eq!(buffered.read_file(&name).unwrap(), data);
+        assert_eq!(streamed.read_file(&name).unwrap(), data);
+    }
+
+    assert!(ArchiveReader::from_non_seekable(&ARCHIVE[..100], Password::empty()).is_err());
+}
+
+#[test]
+fn test_sfx_archives() {
+    // A fake stub, containing a signature without a valid start header.
+    let mut stub = b"MZ fake stub 7z\xBC\xAF\x27\x1C".to_vec();
+    stub.resize(1000, 0x90);
+
+    for name in ["solid.7z", "non_solid.7z", "copy.7z"] {
+        let path = format!("tests/resources/{name}");
+        let data = std::fs::read(&path).unwrap();
+        let mut sfx = stub.clone();
+        sfx.extend_from_slice(&data);
+
+        let plain = Archive::read(&mut std::io::Cursor::new(&data), &Password::empty()).unwrap();
+        assert_eq!(plain.base_offset(), 0);
+        assert!(matches!(
+            Archive::read(&mut std::io::Cursor::new(&sfx), &Password::empty()),
+            Err(Error::BadSignature(_))
+        ));
+        let archive =
+            Archive::read_sfx(&mut std::io::Cursor::new(&sfx), &Password::empty()).unwrap();
+        assert_eq!(archive.base_offset(), stub.len() as u64);
+        assert_eq!(archive.pack_pos(), plain.pack_pos());
+        assert_eq!(archive.files.len(), plain.files.len());
+        let shifted: Vec<_> = plain
+            .pack_stream_ranges()
+            .into_iter()
+            .map(|(offset, size)| (offset + stub.len() as u64, size))
+            .collect();
+        assert_eq!(archive.pack_stream_ranges(), shifted);
+        assert!(
+            archive
+                .verify_pack_crcs(&mut std::io::Cursor::new(&sfx))
+                .unwrap()
+        );
+
+        let mut plain_reader = ArchiveReader::open(&path, Password::empty()).unwrap();
+        assert!(matches!(
+            ArchiveReader::new(std::io::Cursor::new(&sfx), Password::empty()),
+            Err(Error::BadSignature(_))
+        ));
+        let mut sfx_reader = ArchiveReader::with_options(
+            std::io::Cursor::new(&sfx),
+            Password::empty(),
+            &ArchiveReadOptions::default().with_search_sfx(true),
+        )
+        .unwrap();
+        assert_eq!(sfx_reader.archive().base_offset(), stub.len() as u64);
+        let names: Vec<_> = plain
+            .files
+            .iter()
+            .filter(|file| file.has_stream)
+            .map(|file| file.name.clone())
+            .collect();
+        for name in names {
+            assert_eq!(
+                sfx_reader.read_file(&name).unwrap(),
+                plain_reader.read_file(&name).unwrap()
+            );
+        }
+    }
+
+    assert!(matches!(
+        Archive::read_sfx(&mut std::io::Cursor::new(&stub), &Password::empty()),
+        Err(Error::BadSignature(_))
+    ));
+}
+
 #[test]
 fn test_get_file_by_path() {
     // non_solid.7z and solid.7z are expected to have the same content.
@@ -302,3 +964,527 @@ fn test_get_file_by_path() {
         assert_eq!(&data0, &data1);
     }
 }
+
+#[test]
+fn test_shared_archive_reader_concurrent_reads() {
+    let mut reader =
+        ArchiveReader::open("tests/resources/non_solid.7z", Password::empty()).unwrap();
+    let paths: Vec<String> = reader
+        .archive()
+        .files
+        .iter()
+        .filter(|file| !file.is_directory)
+        .map(|file| file.name.clone())
+        .collect();
+    let expected: Vec<Vec<u8>> = paths
+        .iter()
+        .map(|path| reader.read_file(path).unwrap())
+        .collect();
+    let reader_file_count = reader.archive().files.len();
+    let files = reader.archive().files.as_ptr();
+
+    let shared = reader.into_shared();
+    assert_eq!(shared.archive().files.len(), reader_file_count);
+    // The archive is moved into the shared reader, not copied.
+    assert_eq!(shared.archive().files.as_ptr(), files);
+
+    let handles: Vec<_> = (0..4)
+        .map(|thread_index| {
+            let shared = shared.clone();
+            let paths = paths.clone();
+            let expected = expected.clone();
+            std::thread::spawn(move || {
+                for round in 0..4 {
+                    for offset in 0..paths.len() {
+                        let index = (offset + thread_index + round) % paths.len();
+                        assert_eq!(shared.read_file(&paths[index]).unwrap(), expected[index]);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let mut count = 0;
+    shared
+        .for_each_entries(|entry, _| {
+            if !entry.is_directory() {
+                count += 1;
+            }
+            Ok(true)
+        })
+        .unwrap();
+    assert_eq!(count, paths.len());
+}
+
+#[test]
+fn test_unsupported_methods_are_named() {
+    for (path, file_name, method) in [
+        ("tests/resources/unsupported/lzs.7z", "lzs.txt", "LZS"),
+        (
+            "tests/resources/unsupported/lizard.7z",
+            "lizard.txt",
+            "LIZARD",
+        ),
+        (
+            "tests/resources/unsupported/aes256_cbc.7z",
+            "aes256_cbc.txt",
+            "AES256_CBC",
+        ),
+    ] {
+        let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+        let methods = reader.file_methods(file_name).unwrap();
+        assert_eq!(
+            methods.iter().map(|m| m.name()).collect::<Vec<_>>(),
+            [method]
+        );
+        let error = reader.read_file(file_name).unwrap_err();
+        assert!(
+            matches!(&error, Error::UnsupportedCompressionMethod(name) if name == method),
+            "{error:?}"
+        );
+        assert!(error.is_unsupported());
+    }
+
+    let mut reader = ArchiveReader::open(
+        "tests/resources/unsupported/unknown_coder.7z",
+        Password::empty(),
+    )
+    .unwrap();
+    let error = reader.read_file("lzs.txt").unwrap_err();
+    assert!(
+        matches!(&error, Error::UnsupportedCoder { id } if id == &[0x04, 0xF7, 0x11, 0xFF]),
+        "{error:?}"
+    );
+    assert!(error.is_unsupported());
+    assert!(!error.is_corruption() && !error.is_io());
+}
+
+#[test]
+fn test_crafted_headers_are_rejected() {
+    let dir = std::fs::read_dir("tests/resources/crafted").unwrap();
+    let mut count = 0;
+    for entry in dir {
+        let path = entry.unwrap().path();
+        let mut file = File::open(&path).unwrap();
+        let result = Archive::read(&mut file, &Password::empty());
+        assert!(
+            matches!(&result, Err(Error::LimitExceeded { .. }))
+                || result.as_ref().is_err_and(Error::is_corruption),
+            "{path:?} must be rejected as corrupt: {:?}",
+            result.err()
+        );
+        count += 1;
+    }
+    assert!(count > 0);
+
+    let mut file = File::open("tests/resources/crafted/more_names_than_files.7z").unwrap();
+    assert!(matches!(
+        Archive::read(&mut file, &Password::empty()),
+        Err(Error::CorruptHeader {
+            section: "file names",
+            ..
+        })
+    ));
+
+    for (name, section, detail) in [
+        (
+            "bind_pair_out_of_range",
+            "block",
+            "Block 0: Bind pair input stream 5 is out of range",
+        ),
+        (
+            "bind_pair_self_loop",
+            "block",
+            "Block 0: Coder 0 is not connected to the output",
+        ),
+        (
+            "packed_stream_out_of_range",
+            "block",
+            "Block 0: Packed stream 7 is out of range",
+        ),
+        (
+            "packed_stream_bound_twice",
+            "block",
+            "Block 0: Input stream 1 is used by more than one bind pair or packed stream",
+        ),
+        (
+            "too_few_files_for_sub_streams",
+            "stream map",
+            "Block 0 has 2 sub streams, but there are too few files with data",
+        ),
+        (
+            "many_files_small_header",
+            "files info",
+            "1000000 files don't fit into the header",
+        ),
+    ] {
+        let mut file = File::open(format!("tests/resources/crafted/{name}.7z")).unwrap();
+        let error =
+            Archive::read_with_limits(&mut file, &Password::empty(), &ParseLimits::unlimited())
+                .unwrap_err();
+        assert!(
+            matches!(&error, Error::CorruptHeader { section: s, detail: d } if *s == section && d == detail),
+            "{name}: {error:?}"
+        );
+    }
+
+    let mut file = File::open("tests/resources/crafted/huge_num_files.7z").unwrap();
+    assert!(matches!(
+        Archive::read(&mut file, &Password::empty()),
+        Err(Error::LimitExceeded {
+            limit: "entries",
+            ..
+        })
+    ));
+
+    let mut file = File::open("tests/resources/solid.7z").unwrap();
+    let limits = ParseLimits::default().with_max_entries(1);
+    assert!(matches!(
+        Archive::read_with_limits(&mut file, &Password::empty(), &limits),
+        Err(Error::LimitExceeded { .. })
+    ));
+    assert!(
+        Archive::read_with_limits(&mut file, &Password::empty(), &ParseLimits::unlimited()).is_ok()
+    );
+}
+
+#[test]
+fn test_structured_errors() {
+    let data = std::fs::read("tests/resources/copy.7z").unwrap();
+    let read = |data: &[u8]| Archive::read(&mut Cursor::new(data), &Password::empty());
+
+    let mut corrupt = data.clone();
+    corrupt[20] ^= 0xFF;
+    let stored = u32::from_le_bytes(data[8..12].try_into().unwrap());
+    assert!(matches!(
+        read(&corrupt),
+        Err(Error::HeaderCrcMismatch { expected, actual }) if expected == stored && actual != stored
+    ));
+    let mut corrupt = data.clone();
+    *corrupt.last_mut().unwrap() ^= 0xFF;
+    assert!(matches!(
+        read(&corrupt),
+        Err(Error::HeaderCrcMismatch { .. })
+    ));
+
+    let error = read(&data[..data.len() - 1]).unwrap_err();
+    assert!(matches!(error, Error::Io(..)));
+    assert!(std::error::Error::source(&error).is_some());
+
+    let mut file = File::open("tests/resources/crafted/empty_names_property.7z").unwrap();
+    assert!(matches!(
+        Archive::read(&mut file, &Password::empty()),
+        Err(Error::InvalidFileNamesLength { length: 0 })
+    ));
+    let mut file = File::open("tests/resources/crafted/missing_pack_stream.7z").unwrap();
+    assert!(matches!(
+        Archive::read(&mut file, &Password::empty()),
+        Err(Error::PackStreamIndexOutOfBounds { index: 0 })
+    ));
+    let mut file = File::open("tests/resources/unsupported/start_pos.7z").unwrap();
+    let error = Archive::read(&mut file, &Password::empty()).unwrap_err();
+    assert!(matches!(error, Error::UnsupportedProperty { nid: 0x18 }));
+    assert!(error.is_unsupported());
+
+    let mut source = Cursor::new(data.as_slice());
+    let archive = read(&data).unwrap();
+    let password = Password::empty();
+    let decoder = BlockDecoder::new(1, 5, &archive, &password, &mut source);
+    assert!(matches!(
+        decoder.for_each_entries(&mut |_, _| Ok(true)),
+        Err(Error::BlockIndexOutOfBounds {
+            index: 5,
+            block_count: 1
+        })
+    ));
+}
+
+#[test]
+fn test_additional_streams_info() {
+    let mut reader =
+        ArchiveReader::open("tests/resources/additional_streams.7z", Password::empty()).unwrap();
+    let additional_streams = reader.archive().additional_streams.as_ref().unwrap();
+    assert_eq!(additional_streams.len(), 1);
+    assert_eq!(additional_streams[0].get_unpack_size(), 5);
+    assert_eq!(reader.archive().blocks.len(), 1);
+    assert_eq!(reader.archive().files.len(), 1);
+    assert_eq!(reader.read_file("hello.txt").unwrap(), b"hello");
+}
+
+/// A reader that returns at most one byte per `read` call.
+struct OneByteReader<R>(R);
+
+impl<R: Read> Read for OneByteReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+impl<R: Seek> Seek for OneByteReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn test_reading_from_short_read_source() {
+    for name in ["solid.7z", "non_solid.7z", "two_empty_file.7z", "delta.7z"] {
+        let path = format!("tests/resources/{name}");
+        let expected = Archive::open(&path).unwrap();
+        let mut reader =
+            ArchiveReader::new(OneByteReader(File::open(&path).unwrap()), Password::empty())
+                .unwrap();
+        let archive = reader.archive().clone();
+
+        assert_eq!(archive.files.len(), expected.files.len(), "{name}");
+        assert_eq!(archive.blocks.len(), expected.blocks.len(), "{name}");
+        for (block, expected_block) in archive.blocks.iter().zip(expected.blocks.iter()) {
+            for (coder, expected_coder) in block.coders.iter().zip(expected_block.coders.iter()) {
+                assert_eq!(coder, expected_coder, "{name}");
+            }
+        }
+        for (entry, expected_entry) in archive.files.iter().zip(expected.files.iter()) {
+            assert_eq!(entry.name(), expected_entry.name(), "{name}");
+            assert_eq!(entry.size(), expected_entry.size(), "{name}");
+            assert_eq!(entry.has_stream(), expected_entry.has_stream(), "{name}");
+            assert_eq!(
+                entry.is_directory(),
+                expected_entry.is_directory(),
+                "{name}"
+            );
+        }
+
+        let mut expected_reader = ArchiveReader::open(&path, Password::empty()).unwrap();
+        for entry in archive.files.iter().filter(|entry| entry.has_stream()) {
+            assert_eq!(
+                reader.read_file(entry.name()).unwrap(),
+                expected_reader.read_file(entry.name()).unwrap(),
+                "{name}"
+            );
+        }
+    }
+}
+
+#[cfg(feature = "util")]
+#[test]
+fn decompress_rejects_unsafe_entry_names() {
+    let resources = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/resources/hostile");
+    let temp_dir = tempdir().unwrap();
+    let target = temp_dir.path().join("nested/target");
+
+    match decompress_file(resources.join("traversal.7z"), &target) {
+        Err(Error::UnsafeEntryNames(names)) => assert_eq!(names, ["../../evil.txt"]),
+        result => panic!("traversal.7z must be rejected, got {result:?}"),
+    }
+    // Names are checked before anything is extracted.
+    assert!(!target.join("safe.txt").exists());
+    assert!(!temp_dir.path().join("evil.txt").exists());
+
+    let options = ExtractOptions::default().allow_unsafe_paths(true);
+    decompress_file_with_options(resources.join("traversal.7z"), &target, &options).unwrap();
+    assert_eq!(read(temp_dir.path().join("evil.txt")).unwrap(), b"evil");
+
+    let target = temp_dir.path().join("absolute");
+    decompress_file(resources.join("absolute.7z"), &target).unwrap();
+    assert_eq!(read(target.join("tmp/absolute.txt")).unwrap(), b"absolute");
+
+    let target = temp_dir.path().join("windows");
+    let result = decompress_file(resources.join("windows_traversal.7z"), &target);
+    #[cfg(windows)]
+    assert!(matches!(result, Err(Error::UnsafeEntryNames(_))));
+    // A backslash is a regular character in file names on other platforms.
+    #[cfg(not(windows))]
+    {
+        result.unwrap();
+        assert_eq!(read(target.join("..\\..\\evil.txt")).unwrap(), b"evil");
+    }
+
+    #[cfg(windows)]
+    {
+        let target = temp_dir.path().join("windows_names");
+        decompress_file(resources.join("windows_names.7z"), &target).unwrap();
+        assert_eq!(read(target.join("drive.txt")).unwrap(), b"drive");
+        assert_eq!(read(target.join("unc.txt")).unwrap(), b"unc");
+        assert_eq!(read(target.join("_CON")).unwrap(), b"con");
+        assert_eq!(read(target.join("_nul.txt")).unwrap(), b"nul");
+        assert_eq!(read(target.join("trailing")).unwrap(), b"trailing");
+    }
+}
+
+#[test]
+fn test_external_file_properties() {
+    // The names and modification times are stored in additional streams instead of the header.
+    let mut reader =
+        ArchiveReader::open("tests/resources/external_properties.7z", Password::empty()).unwrap();
+    let archive = reader.archive();
+    assert_eq!(archive.additional_streams.as_ref().unwrap().len(), 2);
+    let names: Vec<_> = archive.files.iter().map(|file| file.name()).collect();
+    assert_eq!(names, ["hello.txt", "dir/world.txt"]);
+    let mtimes: Vec<u64> = archive
+        .files
+        .iter()
+        .map(|file| file.last_modified_date().into())
+        .collect();
+    assert_eq!(mtimes, [133_000_000_000_000_000, 133_000_000_010_000_000]);
+    assert!(
+        archive
+            .files
+            .iter()
+            .all(|file| file.windows_attributes() == 0x20)
+    );
+    assert_eq!(reader.read_file("hello.txt").unwrap(), b"hello");
+    assert_eq!(reader.read_file("dir/world.txt").unwrap(), b"world");
+}
+
+#[cfg(feature = "codec")]
+#[test]
+fn test_decode_raw_stream() {
+    // The block parameters are taken from the archive, as a recovery tool would guess them.
+    let path = "tests/resources/single_file_with_content_lzma.7z";
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    let expected = reader.read_file("file.txt").unwrap();
+    let archive = reader.archive();
+    let coder = &archive.blocks[0].coders[0];
+    let (offset, length) = archive.pack_stream_ranges()[0];
+
+    let mut file = File::open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    let mut decoder = sevenz_rust2::codec::decode_raw_stream(
+        file.take(length),
+        coder.encoder_method_id(),
+        coder.properties(),
+        Some(expected.len() as u64),
+        None,
+    )
+    .unwrap();
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data).unwrap();
+    assert_eq!(data, expected);
+
+    // The LZMA2 and BCJ coders of a block are chained manually, without knowing the sizes.
+    let path = "tests/resources/decompress_example_lzma2_bcj_x86.7z";
+    let mut reader = ArchiveReader::open(path, Password::empty()).unwrap();
+    let expected = reader.read_file("decompress.exe").unwrap();
+    let archive = reader.archive();
+    let block = &archive.blocks[0];
+    let (offset, length) = archive.pack_stream_ranges()[0];
+
+    let mut file = File::open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    let mut decoder: Box<dyn Read> = Box::new(file.take(length));
+    for coder in block.ordered_coder_iter().map(|(_, coder)| coder) {
+        decoder = Box::new(
+            sevenz_rust2::codec::decode_raw_stream(
+                decoder,
+                coder.encoder_method_id(),
+                coder.properties(),
+                None,
+                None,
+            )
+            .unwrap(),
+        );
+    }
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data).unwrap();
+    assert_eq!(data, expected);
+
+    let unknown = sevenz_rust2::codec::decode_raw_stream(Cursor::new([]), &[0x7F], &[], None, None);
+    assert!(matches!(unknown, Err(Error::UnsupportedCoder { .. })));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialized_archive_round_trips_through_from_archive() {
+    fn read_entries(reader: &mut ArchiveReader<File>) -> Vec<(String, Vec<u8>)> {
+        let mut entries = Vec::new();
+        reader
+            .for_each_entries(|entry, data| {
+                let mut content = Vec::new();
+                data.read_to_end(&mut content)?;
+                entries.push((entry.name().to_string(), content));
+                Ok(true)
+            })
+            .unwrap();
+        entries
+    }
+
+    for name in [
+        "7za433_7zip_lzma2_bcj2.7z",
+        "interleaved_empty_entries.7z",
+        "non_solid.7z",
+        "solid.7z",
+    ] {
+        let path = format!("{}/tests/resources/{name}", env!("CARGO_MANIFEST_DIR"));
+        let mut file = File::open(&path).unwrap();
+        let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+        let json = serde_json::to_string(&archive).unwrap();
+        let cached: Archive = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&cached).unwrap(), json, "{name}");
+
+        let mut expected =
+            ArchiveReader::new(File::open(&path).unwrap(), Password::empty()).unwrap();
+        let mut reader = ArchiveReader::from_archive(cached, file, Password::empty());
+        assert_eq!(
+            read_entries(&mut reader),
+            read_entries(&mut expected),
+            "{name}"
+        );
+    }
+
+    // Caches of other format versions are rejected.
+    let mut file = File::open(format!(
+        "{}/tests/resources/solid.7z",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+    let mut json: serde_json::Value = serde_json::to_value(&archive).unwrap();
+    assert_eq!(json["format_version"], 1);
+    json["format_version"] = 0.into();
+    let error = serde_json::from_value::<Archive>(json.clone()).unwrap_err();
+    assert!(error.to_string().contains("format version 0"), "{error}");
+    json.as_object_mut().unwrap().remove("format_version");
+    assert!(serde_json::from_value::<Archive>(json).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialized_archive_is_validated() {
+    let mut file = File::open(format!(
+        "{}/tests/resources/solid.7z",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    let archive = Archive::read(&mut file, &Password::empty()).unwrap();
+    let json: serde_json::Value = serde_json::to_value(&archive).unwrap();
+    let corrupt = |corrupt: &dyn Fn(&mut serde_json::Value)| {
+        let mut json = json.clone();
+        corrupt(&mut json);
+        serde_json::from_value::<Archive>(json)
+            .unwrap_err()
+            .to_string()
+    };
+
+    // The stream map is rebuilt, not trusted.
+    let mut stream_map = json.clone();
+    stream_map["stream_map"]["file_block_index"] = serde_json::json!([]);
+    let cached: Archive = serde_json::from_value(stream_map).unwrap();
+    assert_eq!(cached.stream_map.file_block_index.len(), cached.files.len());
+
+    let error = corrupt(&|json| json["blocks"][0]["coders"] = serde_json::json!([]));
+    assert!(error.contains("Block 0"), "{error}");
+    let error = corrupt(&|json| json["blocks"][0]["num_unpack_sub_streams"] = 1000.into());
+    assert!(error.contains("too few files"), "{error}");
+    let error = corrupt(&|json| {
+        json["pack_crcs_defined"] = serde_json::json!({ "values": [64], "bit_count": 1 })
+    });
+    assert!(error.contains("out of range"), "{error}");
+    let error = corrupt(&|json| {
+        json["pack_crcs_defined"] = serde_json::json!({ "values": [], "bit_count": u64::MAX })
+    });
+    assert!(error.contains("bit set"), "{error}");
+}
@@ -89,3 +89,35 @@ fn compress_multi_files_mix_solid_and_non_solid() {
         assert_eq!(&std::fs::read_to_string(&decompress_file).unwrap(), content);
     }
 }
+
+#[cfg(feature = "compress")]
+#[test]
+fn compress_solid_block_file_count_limit() {
+    use std::io::Cursor;
+
+    let contents: Vec<String> = (0..150).map(|i| format!("file{i} with content")).collect();
+    let entries = (0..150)
+        .map(|i| ArchiveEntry::new_file(&format!("file{i}.txt")))
+        .collect();
+    let readers = contents
+        .iter()
+        .map(|content| SourceReader::new(content.as_bytes()))
+        .collect();
+
+    let mut writer = ArchiveWriter::new(Cursor::new(Vec::new())).unwrap();
+    writer.set_solid_block_file_count_limit(50);
+    writer.push_archive_entries(entries, readers).unwrap();
+    writer.flush_solid_block().unwrap();
+    let archive = writer.finish_into_bytes().unwrap();
+
+    let mut reader = ArchiveReader::new(Cursor::new(archive), Password::empty()).unwrap();
+    assert_eq!(reader.archive().blocks.len(), 3);
+    assert_eq!(
+        reader.archive().stream_map.block_first_file_index,
+        [0, 50, 100]
+    );
+    for (i, content) in contents.iter().enumerate() {
+        let data = reader.read_file(&format!("file{i}.txt")).unwrap();
+        assert_eq!(data, content.as_bytes());
+    }
+}